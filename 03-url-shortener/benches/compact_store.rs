@@ -0,0 +1,174 @@
+//! Память на ссылку и задержка чтения у хранилищ в памяти.
+//!
+//! `cargo bench --bench compact_store -- [число ссылок]`, по умолчанию миллион.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::{
+        Arc,
+        atomic::{AtomicIsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use rust_url_shortener::{
+    adapters::{compact_repository::CompactRepository, in_memory_repository::InMemoryRepository},
+    app::{
+        command::create_short_url::CreateShortUrlRepository,
+        event::{EventOutbox, LinkEvent},
+        link::Link,
+        query::get_full_url::GetFullUrlRepository,
+    },
+};
+
+/// аллокатор, считающий занятые байты
+struct Counting;
+
+static ALLOCATED: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size() as isize, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size() as isize, Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(
+            new_size as isize - layout.size() as isize,
+            Ordering::Relaxed,
+        );
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const LOOKUPS: usize = 1_000_000;
+
+/// коды как у nanoid и цели на тысяче хостов
+fn dataset(count: usize) -> Vec<(String, String)> {
+    const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_-";
+    (0..count)
+        .map(|i| {
+            let mut n = (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            let code: String = (0..12)
+                .map(|_| {
+                    let c = ALPHABET[(n % 64) as usize] as char;
+                    n /= 64;
+                    c
+                })
+                .collect();
+            let url = format!(
+                "https://shop{}.example.com/products/{i}?utm_source=newsletter",
+                i % 1000
+            );
+            (code, url)
+        })
+        .collect()
+}
+
+/// Байты на ссылку после заполнения, пока события лежат в очереди,
+/// и после их публикации, и средняя задержка чтения
+fn measure<S>(
+    name: &str,
+    data: &[(String, String)],
+    build: impl FnOnce() -> S,
+    insert: impl Fn(&S, &str, &str),
+    relay: impl Fn(&S),
+    lookup: impl Fn(&S, &str) -> bool,
+) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let store = build();
+    for (code, url) in data {
+        insert(&store, code, url);
+    }
+    let queued = ALLOCATED.load(Ordering::Relaxed) - before;
+    relay(&store);
+    let bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+
+    let mut found = 0;
+    let mut index = 0usize;
+    let started = Instant::now();
+    for _ in 0..LOOKUPS {
+        index = (index + 7919) % data.len();
+        found += lookup(&store, black_box(&data[index].0)) as usize;
+    }
+    let elapsed = started.elapsed();
+    assert_eq!(found, LOOKUPS);
+
+    println!(
+        "{name:<32} {:>8.1} bytes/link queued {:>8.1} bytes/link relayed {:>8.0} ns/lookup",
+        queued as f64 / data.len() as f64,
+        bytes as f64 / data.len() as f64,
+        per_lookup(elapsed),
+    );
+}
+
+fn per_lookup(elapsed: Duration) -> f64 {
+    elapsed.as_nanos() as f64 / LOOKUPS as f64
+}
+
+fn main() {
+    let count = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(1_000_000);
+    let data = dataset(count);
+    println!("{count} links, {LOOKUPS} lookups");
+
+    measure(
+        "DashMap<String, String>",
+        &data,
+        DashMap::<String, String>::new,
+        |store, code, url| {
+            store.insert(code.to_owned(), url.to_owned());
+        },
+        |_| {},
+        |store, code| store.get(code).is_some(),
+    );
+    measure(
+        "InMemoryRepository",
+        &data,
+        || InMemoryRepository::new(Arc::new(DashMap::new())),
+        save,
+        relay,
+        |store, code| store.get(code).is_ok(),
+    );
+    measure(
+        "CompactRepository",
+        &data,
+        CompactRepository::new,
+        save,
+        relay,
+        |store, code| store.get(code).is_ok(),
+    );
+}
+
+fn save<S: CreateShortUrlRepository>(store: &S, code: &str, url: &str) {
+    store
+        .save(
+            Link::new(url),
+            code.to_owned(),
+            LinkEvent::created(code, url),
+        )
+        .unwrap();
+}
+
+/// публикация всех событий, как её сделал бы ретранслятор очереди
+fn relay<S: EventOutbox>(store: &S) {
+    let ids: Vec<String> = store
+        .pending(usize::MAX)
+        .unwrap()
+        .into_iter()
+        .map(|event| event.id)
+        .collect();
+    store.acknowledge(&ids).unwrap();
+}
@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // protoc берём из вендоренного пакета, чтобы сборка не зависела от системы
+    let protoc = protoc_bin_vendored::protoc_bin_path()?;
+    // SAFETY: build-скрипт выполняется в одном потоке
+    unsafe { std::env::set_var("PROTOC", protoc) };
+
+    tonic_prost_build::compile_protos("proto/shortener.proto")?;
+    Ok(())
+}
@@ -0,0 +1,419 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use lru::LruCache;
+
+use crate::app::{
+    command::{
+        create_short_url::CreateShortUrlRepository, delete_short_url::DeleteShortUrlRepository,
+        record_click::RecordClickRepository, update_short_url::UpdateShortUrlRepository,
+    },
+    error::AppError,
+    event::{EventOutbox, LinkEvent},
+    health::HealthCheck,
+    link::Link,
+    query::{
+        get_full_url::GetFullUrlRepository,
+        get_stats::{GetStatsRepository, LinkStats},
+        list_short_urls::{LinkFilter, ListShortUrlsRepository, ShortUrlEntry},
+    },
+};
+
+/// настройки кэша
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// максимальное число записей
+    pub capacity: NonZeroUsize,
+    /// время жизни найденной ссылки
+    pub ttl: Duration,
+    /// время жизни промаха
+    pub negative_ttl: Duration,
+}
+
+/// счётчики попаданий и промахов кэша
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+struct CacheEntry {
+    /// `None` для закэшированного промаха
+    link: Option<Link>,
+    expires_at: Instant,
+}
+
+/// Кэширующий декоратор над любым репозиторием.
+///
+/// Чтения идут через ограниченный LRU кэш с TTL, промахи тоже кэшируются,
+/// чтобы перебор коротких ссылок не доходил до хранилища.
+/// Записи через этот же декоратор сбрасывают запись в кэше,
+/// изменения в обход него становятся видны по истечении TTL.
+#[derive(Clone)]
+pub struct CachedRepository<R> {
+    inner: R,
+    config: CacheConfig,
+    cache: Arc<Mutex<LruCache<String, CacheEntry>>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+    /// число сбросов, чтение из хранилища, начатое до сброса, в кэш не кладётся
+    invalidations: Arc<AtomicU64>,
+}
+
+impl<R> CachedRepository<R> {
+    pub fn new(inner: R, config: CacheConfig) -> Self {
+        Self {
+            inner,
+            config,
+            cache: Arc::new(Mutex::new(LruCache::new(config.capacity))),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+            invalidations: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// текущие счётчики кэша
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// вызывается после изменения в хранилище, иначе чтение между сбросом
+    /// и изменением вернуло бы в кэш старую ссылку
+    fn invalidate(&self, short_url: &str) {
+        let mut cache = self.cache.lock().unwrap();
+        self.invalidations.fetch_add(1, Ordering::SeqCst);
+        cache.pop(short_url);
+    }
+}
+
+impl<R> GetFullUrlRepository for CachedRepository<R>
+where
+    R: GetFullUrlRepository,
+{
+    fn get(&self, short_url: &str) -> Result<Link, AppError> {
+        if let Some(entry) = self.cache.lock().unwrap().get(short_url)
+            && entry.expires_at > Instant::now()
+        {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return entry.link.clone().ok_or(AppError::NotFound);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let invalidations = self.invalidations.load(Ordering::SeqCst);
+        let res = self.inner.get(short_url);
+        let entry = match &res {
+            Ok(link) => CacheEntry {
+                link: Some(link.clone()),
+                expires_at: Instant::now() + self.config.ttl,
+            },
+            Err(AppError::NotFound) => CacheEntry {
+                link: None,
+                expires_at: Instant::now() + self.config.negative_ttl,
+            },
+            Err(_) => return res,
+        };
+        let mut cache = self.cache.lock().unwrap();
+        // ссылку могли изменить, пока шло чтение
+        if self.invalidations.load(Ordering::SeqCst) == invalidations {
+            cache.put(short_url.to_owned(), entry);
+        }
+
+        res
+    }
+}
+
+/// кэш не заменяет хранилище, поэтому готовность определяется им
+impl<R> HealthCheck for CachedRepository<R>
+where
+    R: HealthCheck,
+{
+    fn check_health(&self) -> Result<(), AppError> {
+        self.inner.check_health()
+    }
+}
+
+impl<R> CreateShortUrlRepository for CachedRepository<R>
+where
+    R: CreateShortUrlRepository,
+{
+    fn save(&self, link: Link, short_url: String, event: LinkEvent) -> Result<(), AppError> {
+        let res = self.inner.save(link, short_url.clone(), event);
+        self.invalidate(&short_url);
+        res
+    }
+
+    fn count(&self) -> Result<usize, AppError> {
+        self.inner.count()
+    }
+}
+
+impl<R> DeleteShortUrlRepository for CachedRepository<R>
+where
+    R: DeleteShortUrlRepository,
+{
+    fn delete_if(
+        &self,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        let res = self.inner.delete_if(short_url, check, event);
+        self.invalidate(short_url);
+        res
+    }
+}
+
+impl<R> UpdateShortUrlRepository for CachedRepository<R>
+where
+    R: UpdateShortUrlRepository,
+{
+    fn update(
+        &self,
+        short_url: &str,
+        change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        let res = self.inner.update(short_url, change, event);
+        self.invalidate(short_url);
+        res
+    }
+}
+
+impl<R> RecordClickRepository for CachedRepository<R>
+where
+    R: RecordClickRepository,
+{
+    fn record_click(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        let res = self.inner.record_click(short_url, target, event);
+        // у ссылки с ограничением переходов в кэше остался бы старый счёт переходов
+        let limited = self
+            .cache
+            .lock()
+            .unwrap()
+            .peek(short_url)
+            .and_then(|entry| entry.link.as_ref())
+            .is_some_and(|link| link.max_clicks.is_some());
+        if limited {
+            self.invalidate(short_url);
+        }
+        res
+    }
+}
+
+impl<R> GetStatsRepository for CachedRepository<R>
+where
+    R: GetStatsRepository,
+{
+    fn get_stats(&self, short_url: &str) -> Result<LinkStats, AppError> {
+        self.inner.get_stats(short_url)
+    }
+}
+
+impl<R> ListShortUrlsRepository for CachedRepository<R>
+where
+    R: ListShortUrlsRepository,
+{
+    fn list(&self) -> Result<Vec<ShortUrlEntry>, AppError> {
+        self.inner.list()
+    }
+
+    fn search(&self, filter: &LinkFilter) -> Result<Vec<ShortUrlEntry>, AppError> {
+        self.inner.search(filter)
+    }
+}
+
+impl<R> EventOutbox for CachedRepository<R>
+where
+    R: EventOutbox,
+{
+    fn pending(&self, limit: usize) -> Result<Vec<LinkEvent>, AppError> {
+        self.inner.pending(limit)
+    }
+
+    fn acknowledge(&self, event_ids: &[String]) -> Result<(), AppError> {
+        self.inner.acknowledge(event_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use crate::adapters::in_memory_repository::InMemoryRepository;
+
+    use super::*;
+
+    fn config(capacity: usize, ttl: Duration) -> CacheConfig {
+        CacheConfig {
+            capacity: NonZeroUsize::new(capacity).unwrap(),
+            ttl,
+            negative_ttl: ttl,
+        }
+    }
+
+    #[test]
+    fn second_get_is_served_from_cache() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let repo = CachedRepository::new(
+            InMemoryRepository::new(store.clone()),
+            config(10, Duration::from_secs(60)),
+        );
+
+        // when
+        repo.get("123").unwrap();
+        store.insert("123".to_owned(), Link::new("https://github.com"));
+        let result = repo.get("123");
+
+        // then
+        assert_eq!(result, Ok(Link::new("https://google.com")));
+        assert_eq!(repo.stats(), CacheStats { hits: 1, misses: 1 });
+    }
+
+    #[test]
+    fn misses_are_cached_and_invalidated_on_save() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let repo = CachedRepository::new(
+            InMemoryRepository::new(store),
+            config(10, Duration::from_secs(60)),
+        );
+
+        // when
+        let first = repo.get("123");
+        let second = repo.get("123");
+        repo.save(
+            Link::new("https://google.com"),
+            "123".to_owned(),
+            LinkEvent::created("123", "https://google.com"),
+        )
+        .unwrap();
+        let third = repo.get("123");
+
+        // then
+        assert_eq!(first, Err(AppError::NotFound));
+        assert_eq!(second, Err(AppError::NotFound));
+        assert_eq!(third, Ok(Link::new("https://google.com")));
+        assert_eq!(repo.stats(), CacheStats { hits: 1, misses: 2 });
+    }
+
+    /// хранилище, первое чтение из которого ждёт, пока тест его отпустит
+    struct SlowFirstRead {
+        inner: InMemoryRepository,
+        read: std::sync::Barrier,
+        release: std::sync::Barrier,
+        first: std::sync::atomic::AtomicBool,
+    }
+
+    impl GetFullUrlRepository for &SlowFirstRead {
+        fn get(&self, short_url: &str) -> Result<Link, AppError> {
+            let res = self.inner.get(short_url);
+            if self.first.swap(false, Ordering::SeqCst) {
+                self.read.wait();
+                self.release.wait();
+            }
+            res
+        }
+    }
+
+    #[test]
+    fn read_started_before_save_is_not_cached() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let inner = SlowFirstRead {
+            inner: InMemoryRepository::new(store.clone()),
+            read: std::sync::Barrier::new(2),
+            release: std::sync::Barrier::new(2),
+            first: true.into(),
+        };
+        let repo = CachedRepository::new(&inner, config(10, Duration::from_secs(60)));
+
+        // when
+        let stale = std::thread::scope(|scope| {
+            let reader = scope.spawn(|| repo.get("123"));
+            inner.read.wait();
+            store.insert("123".to_owned(), Link::new("https://google.com"));
+            repo.invalidate("123");
+            inner.release.wait();
+            reader.join().unwrap()
+        });
+        let fresh = repo.get("123");
+
+        // then
+        assert_eq!(stale, Err(AppError::NotFound));
+        assert_eq!(fresh, Ok(Link::new("https://google.com")));
+    }
+
+    #[test]
+    fn expired_entry_is_reloaded() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let repo = CachedRepository::new(
+            InMemoryRepository::new(store.clone()),
+            config(10, Duration::ZERO),
+        );
+
+        // when
+        repo.get("123").unwrap();
+        store.insert("123".to_owned(), Link::new("https://github.com"));
+        let result = repo.get("123");
+
+        // then
+        assert_eq!(result, Ok(Link::new("https://github.com")));
+        assert_eq!(repo.stats(), CacheStats { hits: 0, misses: 2 });
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("1".to_owned(), Link::new("https://google.com"));
+        store.insert("2".to_owned(), Link::new("https://github.com"));
+        let repo = CachedRepository::new(
+            InMemoryRepository::new(store),
+            config(1, Duration::from_secs(60)),
+        );
+
+        // when
+        repo.get("1").unwrap();
+        repo.get("2").unwrap();
+        repo.get("1").unwrap();
+
+        // then
+        assert_eq!(repo.stats(), CacheStats { hits: 0, misses: 3 });
+    }
+
+    #[test]
+    fn delete_invalidates_cached_url() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let repo = CachedRepository::new(
+            InMemoryRepository::new(store),
+            config(10, Duration::from_secs(60)),
+        );
+        repo.get("123").unwrap();
+
+        // when
+        repo.delete("123", LinkEvent::deleted("123")).unwrap();
+
+        // then
+        assert_eq!(repo.get("123"), Err(AppError::NotFound));
+    }
+}
@@ -0,0 +1,1181 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, RandomState},
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::{
+    adapters::hash_ring::HashRing,
+    app::{
+        command::{
+            create_short_url::CreateShortUrlRepository, delete_short_url::DeleteShortUrlRepository,
+            record_click::RecordClickRepository, update_short_url::UpdateShortUrlRepository,
+        },
+        error::AppError,
+        event::{EventOutbox, LinkEvent, now_millis},
+        health::HealthCheck,
+        link::Link,
+        link_health::CheckScope,
+        query::{
+            get_full_url::GetFullUrlRepository,
+            get_stats::{GetStatsRepository, LinkStats},
+            list_short_urls::{ListShortUrlsRepository, ShortUrlEntry},
+        },
+    },
+};
+
+/// хранилище узла, принимающее копии ссылок, за которые отвечает другой владелец
+pub trait ReplicaStore {
+    /// запись копии без события, счётчики переходов существующей копии не сбрасываются
+    fn put_replica(&self, short_url: &str, link: Link) -> Result<(), AppError>;
+    /// удаление копии без события, отсутствие копии не ошибка
+    fn remove_replica(&self, short_url: &str) -> Result<(), AppError>;
+    /// Версия ключа для сверки копий, `None` если узел о ключе не знает.
+    /// После удаления ссылки версия остаётся надгробием, и старая копия
+    /// с меньшей версией не вернёт ссылку.
+    fn version(&self, short_url: &str) -> Result<Option<u64>, AppError>;
+    fn set_version(&self, short_url: &str, version: u64) -> Result<(), AppError>;
+}
+
+/// локальное хранилище узла кластера
+pub trait ShardStore:
+    CreateShortUrlRepository
+    + UpdateShortUrlRepository
+    + DeleteShortUrlRepository
+    + RecordClickRepository
+    + GetFullUrlRepository
+    + GetStatsRepository
+    + ListShortUrlsRepository
+    + ReplicaStore
+{
+}
+
+impl<T> ShardStore for T where
+    T: CreateShortUrlRepository
+        + UpdateShortUrlRepository
+        + DeleteShortUrlRepository
+        + RecordClickRepository
+        + GetFullUrlRepository
+        + GetStatsRepository
+        + ListShortUrlsRepository
+        + ReplicaStore
+{
+}
+
+/// Запрос узла к хранилищу другого узла. Изменения несут версию, которую
+/// получит ключ, а `expected` — версию, от которой изменение отталкивалось.
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum PeerRequest {
+    Get {
+        key: String,
+    },
+    Save {
+        key: String,
+        link: Link,
+        event: LinkEvent,
+        version: u64,
+    },
+    Replace {
+        key: String,
+        link: Link,
+        event: LinkEvent,
+        expected: u64,
+        version: u64,
+    },
+    Delete {
+        key: String,
+        event: LinkEvent,
+        expected: u64,
+        version: u64,
+    },
+    Click {
+        key: String,
+        target: Option<String>,
+        event: LinkEvent,
+    },
+    Stats {
+        key: String,
+    },
+    List,
+    PutReplica {
+        key: String,
+        link: Link,
+        version: u64,
+    },
+    RemoveReplica {
+        key: String,
+        version: u64,
+    },
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum PeerReply {
+    Done,
+    Record(Box<Record>),
+    Stats(LinkStats),
+    List(Vec<ShortUrlEntry>),
+}
+
+/// копия ключа на узле: ссылка или, если `link` нет, надгробие удалённой ссылки
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct Record {
+    version: u64,
+    link: Option<Link>,
+}
+
+/// версия следующего изменения: больше прошлой и, пока часы узлов
+/// примерно совпадают, больше версий, выданных другими узлами раньше
+fn next_version(after: u64) -> u64 {
+    (after + 1).max(now_millis())
+}
+
+fn unexpected(reply: PeerReply) -> AppError {
+    AppError::Internal(format!("unexpected peer reply {reply:?}"))
+}
+
+/// Сетевой вызов из синхронного метода хранилища. В многопоточном рантайме
+/// tokio поток на время вызова отдаёт свои задачи другим потокам.
+fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+/// подпись вызова узла общим секретом кластера
+fn proof(secret: &[u8], challenge: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts any key");
+    mac.update(challenge.as_bytes());
+    mac
+}
+
+/// соединение с узлом; буфер чтения живёт столько же, сколько соединение,
+/// чтобы не потерять прочитанное наперёд
+type Connection = BufReader<TcpStream>;
+
+fn read_line(connection: &mut Connection) -> io::Result<String> {
+    let mut line = String::new();
+    if connection.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line)
+}
+
+fn write_line(connection: &Connection, value: &impl serde::Serialize) -> io::Result<()> {
+    let mut bytes = serde_json::to_vec(value).map_err(io::Error::other)?;
+    bytes.push(b'\n');
+    connection.get_ref().write_all(&bytes)
+}
+
+/// Локальное хранилище узла. Изменения одного ключа идут под его блокировкой,
+/// чтобы версия менялась вместе со ссылкой.
+struct LocalShard<S> {
+    store: S,
+    locks: Box<[Mutex<()>]>,
+    hasher: RandomState,
+}
+
+impl<S: ShardStore> LocalShard<S> {
+    const LOCKS: usize = 64;
+
+    fn new(store: S) -> Self {
+        Self {
+            store,
+            locks: (0..Self::LOCKS).map(|_| Mutex::new(())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn lock(&self, key: &str) -> MutexGuard<'_, ()> {
+        self.locks[self.hasher.hash_one(key) as usize % Self::LOCKS]
+            .lock()
+            .unwrap()
+    }
+
+    fn record(&self, key: &str) -> Result<Record, AppError> {
+        let link = match self.store.get(key) {
+            Ok(link) => Some(link),
+            Err(AppError::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+        match (self.store.version(key)?, link) {
+            (None, None) => Err(AppError::NotFound),
+            (version, link) => Ok(Record {
+                version: version.unwrap_or_default(),
+                link,
+            }),
+        }
+    }
+
+    /// `Conflict`, если ключ изменили после того, как инициатор его прочитал
+    fn check_expected(&self, key: &str, expected: u64) -> Result<(), AppError> {
+        match self.store.version(key)? {
+            Some(version) if version > expected => Err(AppError::Conflict),
+            _ => Ok(()),
+        }
+    }
+
+    /// копия не новее той, что уже есть на узле, отбрасывается
+    fn is_stale(&self, key: &str, version: u64) -> Result<bool, AppError> {
+        Ok(self
+            .store
+            .version(key)?
+            .is_some_and(|known| known >= version))
+    }
+
+    fn handle(&self, request: PeerRequest) -> Result<PeerReply, AppError> {
+        match request {
+            PeerRequest::Get { key } => self.record(&key).map(|r| PeerReply::Record(Box::new(r))),
+            PeerRequest::Save {
+                key,
+                link,
+                event,
+                version,
+            } => {
+                let _lock = self.lock(&key);
+                self.store.save(link, key.clone(), event)?;
+                self.store.set_version(&key, version)?;
+                Ok(PeerReply::Done)
+            }
+            PeerRequest::Replace {
+                key,
+                link,
+                event,
+                expected,
+                version,
+            } => {
+                let _lock = self.lock(&key);
+                self.check_expected(&key, expected)?;
+                // владелец, пропустивший ссылку, сначала получает её копию
+                if self.store.version(&key)? != Some(expected) {
+                    self.store.put_replica(&key, link.clone())?;
+                }
+                let replace = |stored: &mut Link| {
+                    *stored = link.clone();
+                    Ok(())
+                };
+                self.store.update(&key, &replace, event)?;
+                self.store.set_version(&key, version)?;
+                Ok(PeerReply::Done)
+            }
+            PeerRequest::Delete {
+                key,
+                event,
+                expected,
+                version,
+            } => {
+                let _lock = self.lock(&key);
+                self.check_expected(&key, expected)?;
+                self.store.delete(&key, event)?;
+                self.store.set_version(&key, version)?;
+                Ok(PeerReply::Done)
+            }
+            PeerRequest::Click { key, target, event } => {
+                let _lock = self.lock(&key);
+                self.store.record_click(&key, target.as_deref(), event)?;
+                // списанный переход ограниченной ссылки — тоже изменение,
+                // иначе параллельная правка вернула бы старый счёт
+                if self.store.get(&key)?.max_clicks.is_some() {
+                    let version = self.store.version(&key)?.unwrap_or_default();
+                    self.store.set_version(&key, next_version(version))?;
+                }
+                Ok(PeerReply::Done)
+            }
+            PeerRequest::Stats { key } => self.store.get_stats(&key).map(PeerReply::Stats),
+            PeerRequest::List => self.store.list().map(PeerReply::List),
+            PeerRequest::PutReplica { key, link, version } => {
+                let _lock = self.lock(&key);
+                if !self.is_stale(&key, version)? {
+                    self.store.put_replica(&key, link)?;
+                    self.store.set_version(&key, version)?;
+                }
+                Ok(PeerReply::Done)
+            }
+            PeerRequest::RemoveReplica { key, version } => {
+                let _lock = self.lock(&key);
+                if !self.is_stale(&key, version)? {
+                    self.store.remove_replica(&key)?;
+                    self.store.set_version(&key, version)?;
+                }
+                Ok(PeerReply::Done)
+            }
+        }
+    }
+}
+
+/// записи ключа у владельцев, которые ответили
+struct Read<'a> {
+    /// самая новая запись
+    latest: Record,
+    /// владельцы со старой записью или без неё
+    stale: Vec<&'a str>,
+}
+
+/// Хранилище ссылок, распределённое по узлам кластера.
+///
+/// Ключ хранят `replicas` узлов, выбранных консистентным хэшированием.
+/// Каждое изменение ключа получает версию: изменение с событием уходит
+/// первому доступному владельцу, остальным — копия без события, так что
+/// событие публикует только один узел. Изменение отталкивается от
+/// прочитанной версии, и первый владелец отклоняет его, если ключ успели
+/// изменить, тогда оно повторяется. Удаление оставляет надгробие с версией,
+/// поэтому проходит и без части владельцев, а пропущенная копия не вернётся.
+/// Надгробия не удаляются и занимают по ключу с числом на узле.
+///
+/// Чтение параллельно спрашивает всех владельцев, берёт самую новую запись
+/// и дописывает её тем, у кого запись старее (read-repair). Переходы считает
+/// первый доступный владелец; пока он недоступен, ограниченную ссылку
+/// списывает следующий по своей копии, и переходов может пройти больше лимита.
+///
+/// Узлы говорят json по строке на запрос и ответ поверх tcp. Соединение
+/// начинается с вызова: принимающий узел присылает случайную строку, а
+/// подключившийся отвечает её hmac на общем секрете кластера. Трафик не
+/// шифруется, порт должен быть доступен только узлам кластера.
+pub struct ClusterRepository<S> {
+    node: Arc<str>,
+    ring: Arc<HashRing>,
+    replicas: usize,
+    secret: Arc<[u8]>,
+    local: Arc<LocalShard<S>>,
+    /// открытые соединения с узлами, готовые к следующему запросу
+    pool: Arc<Mutex<HashMap<String, Vec<Connection>>>>,
+}
+
+impl<S> Clone for ClusterRepository<S> {
+    fn clone(&self) -> Self {
+        Self {
+            node: self.node.clone(),
+            ring: self.ring.clone(),
+            replicas: self.replicas,
+            secret: self.secret.clone(),
+            local: self.local.clone(),
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+impl<S> ClusterRepository<S>
+where
+    S: ShardStore + Send + Sync + 'static,
+{
+    /// задержка установки соединения с узлом, после неё узел считается недоступным
+    const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+    const IO_TIMEOUT: Duration = Duration::from_secs(2);
+    /// сколько простаивающее соединение ждёт следующего запроса
+    const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+    /// открытых соединений с одним узлом в пуле
+    const POOL_PER_NODE: usize = 8;
+    /// одновременных соединений от других узлов
+    const MAX_PEER_CONNECTIONS: usize = 64;
+    /// попыток изменения, если ключ менялся одновременно
+    const ATTEMPTS: usize = 5;
+
+    /// `node` — адрес этого узла, `peers` — адреса остальных узлов кластера
+    pub fn new(
+        node: impl Into<String>,
+        peers: impl IntoIterator<Item = String>,
+        replicas: usize,
+        secret: &str,
+        local: S,
+    ) -> Self {
+        let node: String = node.into();
+        let ring = HashRing::new(peers.into_iter().chain([node.clone()]));
+        Self {
+            node: node.into(),
+            ring: Arc::new(ring),
+            replicas: replicas.max(1),
+            secret: secret.as_bytes().into(),
+            local: Arc::new(LocalShard::new(local)),
+            pool: Arc::default(),
+        }
+    }
+
+    /// Приём запросов других узлов к локальному хранилищу, каждое
+    /// соединение обслуживает свой поток. Соединения сверх
+    /// [`Self::MAX_PEER_CONNECTIONS`] и без верного ответа на вызов закрываются.
+    pub fn serve(&self, listener: TcpListener) {
+        let open = Arc::new(AtomicUsize::new(0));
+        let this = self.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                if open.fetch_add(1, Ordering::SeqCst) >= Self::MAX_PEER_CONNECTIONS {
+                    open.fetch_sub(1, Ordering::SeqCst);
+                    continue;
+                }
+                let (this, open) = (this.clone(), open.clone());
+                std::thread::spawn(move || {
+                    if let Err(e) = this.serve_connection(stream) {
+                        eprintln!("peer connection failed: {e}");
+                    }
+                    open.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+    }
+
+    fn serve_connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Self::IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(Self::IO_TIMEOUT))?;
+        let mut connection = BufReader::new(stream);
+        let challenge = nanoid::nanoid!(32);
+        write_line(&connection, &challenge)?;
+        let answer: String =
+            serde_json::from_str(&read_line(&mut connection)?).map_err(io::Error::other)?;
+        let answer = hex::decode(answer.trim()).map_err(io::Error::other)?;
+        if proof(&self.secret, &challenge)
+            .verify_slice(&answer)
+            .is_err()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "peer failed authentication",
+            ));
+        }
+
+        connection
+            .get_ref()
+            .set_read_timeout(Some(Self::IDLE_TIMEOUT))?;
+        loop {
+            let line = match read_line(&mut connection) {
+                Ok(line) => line,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+            let response = match serde_json::from_str(&line) {
+                Ok(request) => self.local.handle(request),
+                Err(e) => Err(AppError::InvalidInput(e.to_string())),
+            };
+            write_line(&connection, &response)?;
+        }
+    }
+
+    fn owners(&self, key: &str) -> Vec<&str> {
+        self.ring.owners(key, self.replicas)
+    }
+
+    fn call(&self, node: &str, request: PeerRequest) -> Result<PeerReply, AppError> {
+        if node == &*self.node {
+            return self.local.handle(request);
+        }
+        self.send(node, &request)
+            .map_err(|e| AppError::Internal(format!("peer {node}: {e}")))
+            .flatten()
+    }
+
+    /// Запрос по соединению из пула или по новому. Соединение из пула могло
+    /// закрыться, пока лежало без дела; если узел закрыл его, не ответив,
+    /// запрос до него не дошёл и повторяется по новому соединению.
+    fn send(&self, node: &str, request: &PeerRequest) -> io::Result<Result<PeerReply, AppError>> {
+        let pooled = self.pool.lock().unwrap().get_mut(node).and_then(Vec::pop);
+        if let Some(connection) = pooled {
+            match self.exchange(node, connection, request) {
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::UnexpectedEof
+                            | io::ErrorKind::BrokenPipe
+                            | io::ErrorKind::ConnectionReset
+                    ) => {}
+                result => return result,
+            }
+        }
+        let connection = self.connect(node)?;
+        self.exchange(node, connection, request)
+    }
+
+    fn connect(&self, node: &str) -> io::Result<Connection> {
+        let addr = node
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "address not resolved"))?;
+        let stream = TcpStream::connect_timeout(&addr, Self::CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(Self::IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(Self::IO_TIMEOUT))?;
+        let mut connection = BufReader::new(stream);
+        let challenge: String =
+            serde_json::from_str(&read_line(&mut connection)?).map_err(io::Error::other)?;
+        let answer = hex::encode(proof(&self.secret, &challenge).finalize().into_bytes());
+        write_line(&connection, &answer)?;
+        Ok(connection)
+    }
+
+    fn exchange(
+        &self,
+        node: &str,
+        mut connection: Connection,
+        request: &PeerRequest,
+    ) -> io::Result<Result<PeerReply, AppError>> {
+        write_line(&connection, request)?;
+        let reply = serde_json::from_str(&read_line(&mut connection)?).map_err(io::Error::other)?;
+        let mut pool = self.pool.lock().unwrap();
+        let idle = pool.entry(node.to_owned()).or_default();
+        if idle.len() < Self::POOL_PER_NODE {
+            idle.push(connection);
+        }
+        Ok(reply)
+    }
+
+    /// записи ключа у всех владельцев, другие узлы опрашиваются параллельно
+    fn read(&self, key: &str) -> Result<Read<'_>, AppError> {
+        let owners = self.owners(key);
+        let request = || PeerRequest::Get {
+            key: key.to_owned(),
+        };
+        let replies: Vec<Result<PeerReply, AppError>> = std::thread::scope(|scope| {
+            let remote: Vec<_> = owners
+                .iter()
+                .map(|node| {
+                    (*node != &*self.node).then(|| scope.spawn(move || self.call(node, request())))
+                })
+                .collect();
+            remote
+                .into_iter()
+                .map(|handle| match handle {
+                    Some(handle) => handle
+                        .join()
+                        .unwrap_or_else(|_| Err(AppError::Internal("peer call panicked".into()))),
+                    None => self.local.handle(request()),
+                })
+                .collect()
+        });
+
+        let mut records = Vec::new();
+        let mut missing = Vec::new();
+        let mut last = AppError::Internal(format!("no owners for {key}"));
+        for (node, reply) in owners.iter().zip(replies) {
+            match reply {
+                Ok(PeerReply::Record(record)) => records.push((*node, *record)),
+                Ok(reply) => last = unexpected(reply),
+                Err(AppError::NotFound) => missing.push(*node),
+                Err(e) => last = e,
+            }
+        }
+        let Some(latest) = records.iter().map(|(_, r)| r).max_by_key(|r| r.version) else {
+            return Err(if missing.is_empty() {
+                last
+            } else {
+                AppError::NotFound
+            });
+        };
+        let latest = latest.clone();
+        let mut stale = missing;
+        stale.extend(
+            records
+                .iter()
+                .filter(|(_, record)| record.version < latest.version)
+                .map(|(node, _)| *node),
+        );
+        Ok(Read { latest, stale })
+    }
+
+    /// Ответ первого владельца, который смог его дать: `Internal` означает
+    /// недоступный узел или сбой его хранилища, и тогда спрашивается следующий.
+    fn first_owner(
+        &self,
+        key: &str,
+        request: impl Fn() -> PeerRequest,
+    ) -> Result<PeerReply, AppError> {
+        let mut last = AppError::Internal(format!("no owners for {key}"));
+        for node in self.owners(key) {
+            match self.call(node, request()) {
+                Err(e @ AppError::Internal(_)) => last = e,
+                result => return result,
+            }
+        }
+        Err(last)
+    }
+
+    /// Запрос с событием первому доступному владельцу и копия остальным.
+    /// Владельцы перебираются в одном порядке на всех узлах, поэтому
+    /// одновременные изменения сверяют версию на одном и том же узле.
+    /// Владелец без ссылки пропускается и получает копию после того,
+    /// как запрос примет следующий.
+    fn replicate(
+        &self,
+        key: &str,
+        primary: impl Fn() -> PeerRequest,
+        replica: impl Fn() -> PeerRequest,
+    ) -> Result<(), AppError> {
+        let mut accepted = false;
+        let mut skipped = Vec::new();
+        let mut last = AppError::Internal(format!("no owners for {key}"));
+        for node in self.owners(key) {
+            let request = if accepted { replica() } else { primary() };
+            match self.call(node, request) {
+                Ok(_) if !accepted => {
+                    accepted = true;
+                    for node in skipped.drain(..) {
+                        if let Err(e) = self.call(node, replica()) {
+                            eprintln!("replica {node} missed {key}: {e}");
+                        }
+                    }
+                }
+                Ok(_) => {}
+                Err(AppError::NotFound) if !accepted => {
+                    skipped.push(node);
+                    last = AppError::NotFound;
+                }
+                Err(e @ AppError::Internal(_)) => {
+                    if accepted {
+                        eprintln!("replica {node} missed {key}: {e}");
+                    }
+                    last = e;
+                }
+                Err(e) if !accepted => return Err(e),
+                Err(e) => eprintln!("replica {node} rejected {key}: {e}"),
+            }
+        }
+        if accepted { Ok(()) } else { Err(last) }
+    }
+
+    /// изменение от последней версии ключа, повторяемое, пока ключ меняют одновременно
+    fn modify(
+        &self,
+        key: &str,
+        write: impl Fn(&Read<'_>) -> Result<(), AppError>,
+    ) -> Result<(), AppError> {
+        for _ in 0..Self::ATTEMPTS {
+            match write(&self.read(key)?) {
+                Err(AppError::Conflict) => continue,
+                result => return result,
+            }
+        }
+        Err(AppError::Conflict)
+    }
+
+    /// дописать последнюю запись владельцам, у которых она старее
+    fn repair(&self, key: &str, read: &Read<'_>) {
+        let version = read.latest.version;
+        for node in &read.stale {
+            let request = match &read.latest.link {
+                Some(link) => PeerRequest::PutReplica {
+                    key: key.to_owned(),
+                    link: link.clone(),
+                    version,
+                },
+                None => PeerRequest::RemoveReplica {
+                    key: key.to_owned(),
+                    version,
+                },
+            };
+            if let Err(e) = self.call(node, request) {
+                eprintln!("read-repair of {key} on {node} failed: {e}");
+            }
+        }
+    }
+}
+
+impl<S: HealthCheck> HealthCheck for ClusterRepository<S> {
+    fn check_health(&self) -> Result<(), AppError> {
+        self.local.store.check_health()
+    }
+}
+
+/// узел публикует события, записанные в его локальное хранилище
+/// Ссылку проверяет первый владелец по кольцу, остальные узлы её пропускают.
+/// Пока первый владелец недоступен, его ссылки не проверяются.
+impl<S: ShardStore + Send + Sync + 'static> CheckScope for ClusterRepository<S> {
+    fn covers(&self, key: &str) -> bool {
+        self.owners(key).first() == Some(&&*self.node)
+    }
+}
+
+impl<S: EventOutbox> EventOutbox for ClusterRepository<S> {
+    fn pending(&self, limit: usize) -> Result<Vec<LinkEvent>, AppError> {
+        self.local.store.pending(limit)
+    }
+
+    fn acknowledge(&self, event_ids: &[String]) -> Result<(), AppError> {
+        self.local.store.acknowledge(event_ids)
+    }
+}
+
+impl<S: ShardStore + Send + Sync + 'static> CreateShortUrlRepository for ClusterRepository<S> {
+    fn save(&self, link: Link, short_url: String, event: LinkEvent) -> Result<(), AppError> {
+        let version = next_version(0);
+        blocking(|| {
+            self.replicate(
+                &short_url,
+                || PeerRequest::Save {
+                    key: short_url.clone(),
+                    link: link.clone(),
+                    event: event.clone(),
+                    version,
+                },
+                || PeerRequest::PutReplica {
+                    key: short_url.clone(),
+                    link: link.clone(),
+                    version,
+                },
+            )
+        })
+    }
+
+    /// ссылки всего кластера, копии считаются один раз
+    fn count(&self) -> Result<usize, AppError> {
+        self.list().map(|entries| entries.len())
+    }
+}
+
+impl<S: ShardStore + Send + Sync + 'static> UpdateShortUrlRepository for ClusterRepository<S> {
+    /// Изменение нельзя передать по сети, поэтому узлам уходит готовая ссылка.
+    /// Владелец примет её, только если ключ не менялся после чтения.
+    fn update(
+        &self,
+        short_url: &str,
+        change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        blocking(|| {
+            self.modify(short_url, |read| {
+                let mut link = read.latest.link.clone().ok_or(AppError::NotFound)?;
+                change(&mut link)?;
+                let version = next_version(read.latest.version);
+                self.replicate(
+                    short_url,
+                    || PeerRequest::Replace {
+                        key: short_url.to_owned(),
+                        link: link.clone(),
+                        event: event.clone(),
+                        expected: read.latest.version,
+                        version,
+                    },
+                    || PeerRequest::PutReplica {
+                        key: short_url.to_owned(),
+                        link: link.clone(),
+                        version,
+                    },
+                )
+            })
+        })
+    }
+}
+
+impl<S: ShardStore + Send + Sync + 'static> DeleteShortUrlRepository for ClusterRepository<S> {
+    /// Проверка идёт по последней версии ссылки, как и изменение в `update`.
+    /// Недоступный владелец получит надгробие при чтении после возвращения.
+    fn delete_if(
+        &self,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        blocking(|| {
+            self.modify(short_url, |read| {
+                check(read.latest.link.as_ref().ok_or(AppError::NotFound)?)?;
+                let version = next_version(read.latest.version);
+                self.replicate(
+                    short_url,
+                    || PeerRequest::Delete {
+                        key: short_url.to_owned(),
+                        event: event.clone(),
+                        expected: read.latest.version,
+                        version,
+                    },
+                    || PeerRequest::RemoveReplica {
+                        key: short_url.to_owned(),
+                        version,
+                    },
+                )
+            })
+        })
+    }
+}
+
+impl<S: ShardStore + Send + Sync + 'static> RecordClickRepository for ClusterRepository<S> {
+    fn record_click(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        blocking(|| {
+            self.first_owner(short_url, || PeerRequest::Click {
+                key: short_url.to_owned(),
+                target: target.map(str::to_owned),
+                event: event.clone(),
+            })
+            .map(|_| ())
+        })
+    }
+}
+
+impl<S: ShardStore + Send + Sync + 'static> GetFullUrlRepository for ClusterRepository<S> {
+    fn get(&self, short_url: &str) -> Result<Link, AppError> {
+        blocking(|| {
+            let read = self.read(short_url)?;
+            self.repair(short_url, &read);
+            read.latest.link.ok_or(AppError::NotFound)
+        })
+    }
+}
+
+impl<S: ShardStore + Send + Sync + 'static> GetStatsRepository for ClusterRepository<S> {
+    fn get_stats(&self, short_url: &str) -> Result<LinkStats, AppError> {
+        blocking(|| {
+            match self.first_owner(short_url, || PeerRequest::Stats {
+                key: short_url.to_owned(),
+            })? {
+                PeerReply::Stats(stats) => Ok(stats),
+                reply => Err(unexpected(reply)),
+            }
+        })
+    }
+}
+
+impl<S: ShardStore + Send + Sync + 'static> ListShortUrlsRepository for ClusterRepository<S> {
+    /// Ссылки со всех доступных узлов; из копий берётся запись ближайшего
+    /// к началу списка владельцев, где ведётся счёт переходов.
+    fn list(&self) -> Result<Vec<ShortUrlEntry>, AppError> {
+        blocking(|| {
+            let mut merged: BTreeMap<String, (usize, ShortUrlEntry)> = BTreeMap::new();
+            let mut answered = false;
+            let mut last = None;
+            for node in self.ring.nodes() {
+                let entries = match self.call(node, PeerRequest::List) {
+                    Ok(PeerReply::List(entries)) => entries,
+                    Ok(reply) => {
+                        last = Some(unexpected(reply));
+                        continue;
+                    }
+                    Err(e) => {
+                        last = Some(e);
+                        continue;
+                    }
+                };
+                answered = true;
+                for entry in entries {
+                    let rank = self
+                        .owners(&entry.id)
+                        .iter()
+                        .position(|owner| owner == node)
+                        .unwrap_or(usize::MAX);
+                    match merged.get(&entry.id) {
+                        Some((best, _)) if *best <= rank => {}
+                        _ => {
+                            merged.insert(entry.id.clone(), (rank, entry));
+                        }
+                    }
+                }
+            }
+
+            match (answered, last) {
+                (false, Some(e)) => Err(e),
+                _ => Ok(merged.into_values().map(|(_, entry)| entry).collect()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use crate::adapters::in_memory_repository::InMemoryRepository;
+
+    use super::*;
+
+    struct Node {
+        cluster: ClusterRepository<InMemoryRepository>,
+        local: InMemoryRepository,
+    }
+
+    const SECRET: &str = "s3cret";
+
+    /// свободные порты localhost для узлов кластера
+    fn addresses(count: usize) -> (Vec<TcpListener>, Vec<String>) {
+        let listeners: Vec<TcpListener> = (0..count)
+            .map(|_| TcpListener::bind("127.0.0.1:0").unwrap())
+            .collect();
+        let addrs = listeners
+            .iter()
+            .map(|l| l.local_addr().unwrap().to_string())
+            .collect();
+        (listeners, addrs)
+    }
+
+    fn join(
+        listener: TcpListener,
+        addr: &str,
+        addrs: &[String],
+        local: InMemoryRepository,
+    ) -> Node {
+        let peers = addrs.iter().filter(|peer| *peer != addr).cloned();
+        let cluster = ClusterRepository::new(addr, peers, 2, SECRET, local.clone());
+        cluster.serve(listener);
+        Node { cluster, local }
+    }
+
+    fn store() -> InMemoryRepository {
+        InMemoryRepository::new(Arc::new(DashMap::new()))
+    }
+
+    /// узлы на портах localhost, последние `down` узлов не запущены
+    fn start(count: usize, down: usize) -> Vec<Node> {
+        let (listeners, addrs) = addresses(count);
+        listeners
+            .into_iter()
+            .take(count - down)
+            .zip(&addrs)
+            .map(|(listener, addr)| join(listener, addr, &addrs, store()))
+            .collect()
+    }
+
+    fn save(repo: &impl CreateShortUrlRepository, key: &str) {
+        let url = format!("https://example.com/{key}");
+        repo.save(
+            Link::new(&url),
+            key.to_owned(),
+            LinkEvent::created(key, &url),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn link_saved_on_one_node_resolves_on_every_node() {
+        // given
+        let nodes = start(3, 0);
+        let keys: Vec<String> = (0..20).map(|i| format!("key{i}")).collect();
+
+        // when
+        for key in &keys {
+            save(&nodes[0].cluster, key);
+        }
+        nodes[1]
+            .cluster
+            .record_click("key0", None, LinkEvent::clicked("key0"))
+            .unwrap();
+
+        // then
+        for key in &keys {
+            for node in &nodes {
+                assert_eq!(
+                    node.cluster.get(key).map(|link| link.url),
+                    Ok(format!("https://example.com/{key}"))
+                );
+            }
+            let copies = nodes.iter().filter(|n| n.local.get(key).is_ok()).count();
+            assert_eq!(copies, 2, "copies of {key}");
+        }
+        assert_eq!(nodes[2].cluster.get_stats("key0").unwrap().clicks, 1);
+        assert_eq!(nodes[2].cluster.count(), Ok(20));
+        let events: usize = nodes
+            .iter()
+            .map(|n| n.local.pending(100).unwrap().len())
+            .sum();
+        assert_eq!(events, 21, "one event per change, not per copy");
+    }
+
+    #[test]
+    fn every_link_is_checked_by_one_node() {
+        // given
+        let nodes = start(3, 0);
+        let keys: Vec<String> = (0..50).map(|i| format!("team-a/key{i}")).collect();
+
+        // when
+        let checkers: Vec<usize> = keys
+            .iter()
+            .map(|key| nodes.iter().filter(|n| n.cluster.covers(key)).count())
+            .collect();
+
+        // then
+        assert!(checkers.iter().all(|count| *count == 1));
+        assert!(
+            nodes
+                .iter()
+                .all(|n| keys.iter().any(|key| n.cluster.covers(key)))
+        );
+    }
+
+    #[test]
+    fn one_node_down_keeps_links_available() {
+        // given
+        let nodes = start(3, 1);
+        let keys: Vec<String> = (0..20).map(|i| format!("key{i}")).collect();
+
+        // when
+        for key in &keys {
+            save(&nodes[0].cluster, key);
+        }
+
+        // then
+        for key in &keys {
+            assert!(nodes[1].cluster.get(key).is_ok(), "{key} lost");
+        }
+        assert_eq!(nodes[1].cluster.list().unwrap().len(), 20);
+    }
+
+    #[test]
+    fn deleted_link_stays_deleted_when_owner_returns() {
+        // given
+        let (mut listeners, addrs) = addresses(3);
+        drop(listeners.pop());
+        let nodes: Vec<Node> = listeners
+            .into_iter()
+            .zip(&addrs)
+            .map(|(listener, addr)| join(listener, addr, &addrs, store()))
+            .collect();
+        let keys: Vec<String> = (0..20).map(|i| format!("key{i}")).collect();
+        // выключенный узел успел получить свои копии до удаления
+        let returning = store();
+        for key in &keys {
+            save(&nodes[0].cluster, key);
+            if nodes[0].cluster.owners(key).contains(&addrs[2].as_str()) {
+                let read = nodes[0].cluster.read(key).unwrap();
+                let link = read.latest.link.unwrap();
+                returning.put_replica(key, link).unwrap();
+                returning.set_version(key, read.latest.version).unwrap();
+            }
+        }
+        assert!(!returning.list().unwrap().is_empty());
+
+        // when
+        for key in &keys {
+            nodes[0]
+                .cluster
+                .delete(key, LinkEvent::deleted(key))
+                .unwrap();
+        }
+        let listener = TcpListener::bind(&addrs[2]).unwrap();
+        let returned = join(listener, &addrs[2], &addrs, returning);
+
+        // then
+        for key in &keys {
+            for node in nodes.iter().chain([&returned]) {
+                assert_eq!(node.cluster.get(key), Err(AppError::NotFound), "{key}");
+            }
+            assert_eq!(returned.local.get(key), Err(AppError::NotFound), "{key}");
+        }
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        // given
+        let nodes = start(2, 0);
+        nodes[0]
+            .cluster
+            .save(
+                Link::new("https://example.com/0"),
+                "abc".to_owned(),
+                LinkEvent::created("abc", "https://example.com/0"),
+            )
+            .unwrap();
+        let increment = |link: &mut Link| {
+            let n: u32 = link.url.rsplit('/').next().unwrap().parse().unwrap();
+            link.url = format!("https://example.com/{}", n + 1);
+            Ok(())
+        };
+
+        // when
+        let applied: usize = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|i| {
+                    let cluster = &nodes[i % 2].cluster;
+                    scope.spawn(move || {
+                        (0..10)
+                            .filter(|_| {
+                                cluster
+                                    .update("abc", &increment, LinkEvent::updated("abc", ""))
+                                    .is_ok()
+                            })
+                            .count()
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).sum()
+        });
+
+        // then
+        assert!(applied > 0);
+        for node in &nodes {
+            assert_eq!(
+                node.cluster.get("abc").unwrap().url,
+                format!("https://example.com/{applied}")
+            );
+        }
+    }
+
+    #[test]
+    fn peer_with_wrong_secret_is_rejected() {
+        // given
+        let nodes = start(2, 0);
+        save(&nodes[0].cluster, "abc");
+        let addr = nodes[0].cluster.node.to_string();
+        let intruder = ClusterRepository::new("127.0.0.1:1", [addr.clone()], 1, "guess", store());
+
+        // when
+        let reply = intruder.call(&addr, PeerRequest::List);
+
+        // then
+        assert!(matches!(reply, Err(AppError::Internal(_))), "{reply:?}");
+        assert_eq!(nodes[1].cluster.count(), Ok(1));
+    }
+
+    #[test]
+    fn read_repairs_missing_copy() {
+        // given
+        let nodes = start(2, 0);
+        save(&nodes[0].local, "abc");
+
+        // when
+        let link = nodes[1].cluster.get("abc");
+
+        // then
+        assert_eq!(link, Ok(Link::new("https://example.com/abc")));
+        assert_eq!(nodes[1].local.get("abc"), link);
+        assert_eq!(nodes[1].local.pending(10), Ok(vec![]));
+    }
+
+    #[test]
+    fn update_keeps_clicks_and_reaches_copies() {
+        // given
+        let nodes = start(2, 0);
+        save(&nodes[0].cluster, "abc");
+        nodes[0]
+            .cluster
+            .record_click("abc", None, LinkEvent::clicked("abc"))
+            .unwrap();
+
+        // when
+        nodes[1]
+            .cluster
+            .update(
+                "abc",
+                &|link| {
+                    link.url = "https://example.org".to_owned();
+                    Ok(())
+                },
+                LinkEvent::updated("abc", "https://example.org"),
+            )
+            .unwrap();
+
+        // then
+        for node in &nodes {
+            assert_eq!(node.local.get("abc").unwrap().url, "https://example.org");
+        }
+        assert_eq!(nodes[1].cluster.get_stats("abc").unwrap().clicks, 1);
+    }
+}
@@ -0,0 +1,702 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, RandomState},
+    sync::{Arc, RwLock},
+};
+
+use dashmap::DashMap;
+
+use crate::{
+    adapters::{cluster_repository::ReplicaStore, in_memory_repository::ShardedOutbox},
+    app::{
+        command::{
+            create_short_url::CreateShortUrlRepository, delete_short_url::DeleteShortUrlRepository,
+            record_click::RecordClickRepository, update_short_url::UpdateShortUrlRepository,
+        },
+        error::AppError,
+        event::{EventOutbox, LinkEvent},
+        health::HealthCheck,
+        link::Link,
+        query::{
+            get_full_url::GetFullUrlRepository,
+            get_stats::{GetStatsRepository, LinkStats},
+            list_short_urls::{LinkFilter, ListShortUrlsRepository, ShortUrlEntry, TrashFilter},
+        },
+    },
+};
+
+/// Код ссылки, лежащий прямо в записи таблицы без отдельной аллокации.
+/// Короткие коды дополняются нулями, поэтому коды с нулём не помещаются.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Code([u8; Code::CAPACITY]);
+
+impl Code {
+    const CAPACITY: usize = 16;
+
+    fn new(key: &str) -> Option<Self> {
+        if key.len() > Self::CAPACITY || key.contains('\0') {
+            return None;
+        }
+        let mut bytes = [0; Self::CAPACITY];
+        bytes[..key.len()].copy_from_slice(key.as_bytes());
+        Some(Self(bytes))
+    }
+
+    fn as_str(&self) -> &str {
+        let len = self
+            .0
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(Self::CAPACITY);
+        // байты скопированы из &str целиком, поэтому это корректный utf-8
+        std::str::from_utf8(&self.0[..len]).unwrap()
+    }
+}
+
+/// Цель простой ссылки: интернированный хост и остаток url в арене шарда.
+/// Ссылка, набравшая больше `u32::MAX` переходов, переезжает в полную таблицу.
+#[derive(Clone, Copy)]
+struct Slot {
+    host: u32,
+    offset: u32,
+    len: u32,
+    clicks: u32,
+}
+
+/// Остатки url подряд, без разделителей, в кусках постоянного размера:
+/// в отличие от удваивающегося `Vec` арена не держит про запас до половины
+/// занятой памяти.
+#[derive(Default)]
+struct Arena {
+    chunks: Vec<Vec<u8>>,
+}
+
+impl Arena {
+    /// самый длинный остаток url, который помещается в арену
+    const CHUNK: usize = 64 * 1024;
+
+    fn push(&mut self, bytes: &[u8]) -> Result<u32, AppError> {
+        if self
+            .chunks
+            .last()
+            .is_none_or(|chunk| chunk.len() + bytes.len() > Self::CHUNK)
+        {
+            if (self.chunks.len() + 1) * Self::CHUNK > u32::MAX as usize {
+                return Err(AppError::Internal("compact store shard is full".to_owned()));
+            }
+            self.chunks.push(Vec::new());
+        }
+        let index = self.chunks.len() - 1;
+        let chunk = &mut self.chunks[index];
+        let offset = index * Self::CHUNK + chunk.len();
+        chunk.extend_from_slice(bytes);
+        Ok(offset as u32)
+    }
+
+    fn get(&self, offset: u32, len: u32) -> &[u8] {
+        let (index, start) = (offset as usize / Self::CHUNK, offset as usize % Self::CHUNK);
+        &self.chunks[index][start..start + len as usize]
+    }
+
+    /// занятые байты
+    fn len(&self) -> usize {
+        self.chunks.iter().map(Vec::len).sum()
+    }
+}
+
+/// часть таблицы под своей блокировкой
+#[derive(Default)]
+struct Shard {
+    slots: HashMap<Code, Slot>,
+    arena: Arena,
+    /// байты арены, на которые больше не ссылается ни одна запись
+    garbage: usize,
+}
+
+impl Shard {
+    /// меньше этого мусора арена не уплотняется
+    const COMPACT_MIN: usize = 64 * 1024;
+
+    fn rest(&self, slot: &Slot) -> &str {
+        std::str::from_utf8(self.arena.get(slot.offset, slot.len)).unwrap()
+    }
+
+    fn insert(&mut self, code: Code, host: u32, rest: &str, clicks: u32) -> Result<(), AppError> {
+        let slot = Slot {
+            host,
+            offset: self.arena.push(rest.as_bytes())?,
+            len: rest.len() as u32,
+            clicks,
+        };
+        if let Some(old) = self.slots.insert(code, slot) {
+            self.garbage += old.len as usize;
+        }
+        self.compact_if_needed();
+        Ok(())
+    }
+
+    fn remove(&mut self, code: &Code) -> Option<Slot> {
+        let slot = self.slots.remove(code)?;
+        self.garbage += slot.len as usize;
+        self.compact_if_needed();
+        Some(slot)
+    }
+
+    /// переписывание арены без мусора, когда мусора больше половины
+    fn compact_if_needed(&mut self) {
+        if self.garbage < Self::COMPACT_MIN || self.garbage * 2 < self.arena.len() {
+            return;
+        }
+        let mut arena = Arena::default();
+        for slot in self.slots.values_mut() {
+            // новая арена меньше старой, поэтому места в ней хватит
+            slot.offset = arena.push(self.arena.get(slot.offset, slot.len)).unwrap();
+        }
+        self.arena = arena;
+        self.garbage = 0;
+    }
+}
+
+/// хосты целей, общие для всех шардов
+#[derive(Default)]
+struct Hosts {
+    ids: HashMap<Arc<str>, u32>,
+    names: Vec<Arc<str>>,
+}
+
+/// ссылка с настройками сверх цели и счётчики по её целям
+struct RichLink {
+    link: Link,
+    clicks: u64,
+    targets: BTreeMap<String, u64>,
+}
+
+/// Плотное хранилище ссылок в памяти для сотен миллионов ссылок.
+///
+/// Простые ссылки, у которых есть только цель, лежат в шардах под своими
+/// `RwLock`: код хранится в записи массивом байт, начало url до пути
+/// (`scheme://host`) интернируется на всё хранилище, а остаток пишется
+/// в арену шарда. Память на ссылку в сравнении с
+/// [`InMemoryRepository`](crate::adapters::in_memory_repository::InMemoryRepository),
+/// отдельно с неопубликованными событиями и после их публикации,
+/// меряет `cargo bench --bench compact_store`.
+/// Ссылки с паролем, описанием, правилами или длинным кодом хранятся
+/// целиком в отдельной таблице.
+#[derive(Clone)]
+pub struct CompactRepository {
+    shards: Arc<[RwLock<Shard>]>,
+    hasher: RandomState,
+    hosts: Arc<RwLock<Hosts>>,
+    rich: Arc<DashMap<String, RichLink>>,
+    outbox: Arc<ShardedOutbox>,
+    /// версии ключей узла кластера, вне кластера пусто
+    versions: Arc<DashMap<String, u64>>,
+}
+
+impl Default for CompactRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompactRepository {
+    const SHARDS: usize = 64;
+
+    pub fn new() -> Self {
+        Self {
+            shards: (0..Self::SHARDS)
+                .map(|_| RwLock::new(Shard::default()))
+                .collect(),
+            hasher: RandomState::new(),
+            hosts: Arc::new(RwLock::new(Hosts::default())),
+            rich: Arc::new(DashMap::new()),
+            outbox: Arc::default(),
+            versions: Arc::new(DashMap::new()),
+        }
+    }
+
+    fn shard(&self, code: &Code) -> &RwLock<Shard> {
+        &self.shards[self.hasher.hash_one(code) as usize % Self::SHARDS]
+    }
+
+    fn intern(&self, host: &str) -> u32 {
+        if let Some(id) = self.hosts.read().unwrap().ids.get(host) {
+            return *id;
+        }
+        let mut hosts = self.hosts.write().unwrap();
+        if let Some(id) = hosts.ids.get(host) {
+            return *id;
+        }
+        let id = hosts.names.len() as u32;
+        let name: Arc<str> = host.into();
+        hosts.names.push(name.clone());
+        hosts.ids.insert(name, id);
+        id
+    }
+
+    fn url(&self, shard: &Shard, slot: &Slot) -> String {
+        let hosts = self.hosts.read().unwrap();
+        let host = &hosts.names[slot.host as usize];
+        let rest = shard.rest(slot);
+        let mut url = String::with_capacity(host.len() + rest.len());
+        url.push_str(host);
+        url.push_str(rest);
+        url
+    }
+
+    /// ссылка вместе со счётчиками из любой из таблиц
+    fn load(&self, short_url: &str) -> Option<RichLink> {
+        if let Some(code) = Code::new(short_url) {
+            let shard = self.shard(&code).read().unwrap();
+            if let Some(slot) = shard.slots.get(&code) {
+                return Some(RichLink {
+                    link: Link::new(self.url(&shard, slot)),
+                    clicks: slot.clicks.into(),
+                    targets: BTreeMap::new(),
+                });
+            }
+        }
+        self.rich.get(short_url).map(|stored| RichLink {
+            link: stored.link.clone(),
+            clicks: stored.clicks,
+            targets: stored.targets.clone(),
+        })
+    }
+
+    /// Запись в плотную таблицу, если ссылка простая, иначе в таблицу целиком.
+    /// Копия из другой таблицы удаляется после записи, чтобы читатели
+    /// не застали ссылку отсутствующей. Вызывается под блокировкой части
+    /// очереди этой ссылки.
+    fn store(&self, short_url: &str, stored: RichLink) -> Result<(), AppError> {
+        let code = Code::new(short_url);
+        let (host, rest) = split_host(&stored.link.url);
+        let plain = stored.targets.is_empty()
+            && rest.len() <= Arena::CHUNK
+            && stored.link == Link::new(stored.link.url.clone());
+        match (code, u32::try_from(stored.clicks)) {
+            (Some(code), Ok(clicks)) if plain => {
+                let host = self.intern(host);
+                self.shard(&code)
+                    .write()
+                    .unwrap()
+                    .insert(code, host, rest, clicks)?;
+                self.rich.remove(short_url);
+            }
+            (code, _) => {
+                self.rich.insert(short_url.to_owned(), stored);
+                if let Some(code) = code {
+                    self.shard(&code).write().unwrap().remove(&code);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// удаление из обеих таблиц, `false` если ссылки не было
+    fn remove(&self, short_url: &str) -> bool {
+        let plain = Code::new(short_url)
+            .is_some_and(|code| self.shard(&code).write().unwrap().remove(&code).is_some());
+        let rich = self.rich.remove(short_url).is_some();
+        plain || rich
+    }
+}
+
+/// `scheme://host[:port]` и остаток url, начиная с пути
+fn split_host(url: &str) -> (&str, &str) {
+    let authority = url.find("://").map(|i| i + 3).unwrap_or(0);
+    let end = url[authority..]
+        .find(['/', '?', '#'])
+        .map(|i| authority + i)
+        .unwrap_or(url.len());
+    url.split_at(end)
+}
+
+/// память процесса всегда доступна
+impl HealthCheck for CompactRepository {
+    fn check_health(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+impl CreateShortUrlRepository for CompactRepository {
+    fn save(&self, link: Link, short_url: String, event: LinkEvent) -> Result<(), AppError> {
+        self.outbox.with_event(&short_url, event, || {
+            self.store(
+                &short_url,
+                RichLink {
+                    link,
+                    clicks: 0,
+                    targets: BTreeMap::new(),
+                },
+            )
+        })
+    }
+
+    fn count(&self) -> Result<usize, AppError> {
+        let plain: usize = self
+            .shards
+            .iter()
+            .map(|shard| shard.read().unwrap().slots.len())
+            .sum();
+        Ok(plain + self.rich.len())
+    }
+}
+
+impl UpdateShortUrlRepository for CompactRepository {
+    fn update(
+        &self,
+        short_url: &str,
+        change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.outbox.with_event(short_url, event, || {
+            // после изменения ссылка может переехать в другую таблицу
+            let mut stored = self.load(short_url).ok_or(AppError::NotFound)?;
+            change(&mut stored.link)?;
+            self.store(short_url, stored)
+        })
+    }
+}
+
+impl DeleteShortUrlRepository for CompactRepository {
+    fn delete_if(
+        &self,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        // записи ссылки идут под блокировкой её части очереди,
+        // ссылка не поменяется между проверкой и удалением
+        self.outbox.with_event(short_url, event, || {
+            let stored = self.load(short_url).ok_or(AppError::NotFound)?;
+            check(&stored.link)?;
+            self.remove(short_url);
+            Ok(())
+        })
+    }
+}
+
+impl RecordClickRepository for CompactRepository {
+    fn record_click(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.outbox.with_event(short_url, event, || {
+            if let Some(code) = Code::new(short_url) {
+                let mut shard = self.shard(&code).write().unwrap();
+                if let Some(slot) = shard.slots.get_mut(&code) {
+                    if let Some(clicks) = slot.clicks.checked_add(1) {
+                        slot.clicks = clicks;
+                        return Ok(());
+                    }
+                    drop(shard);
+                    // счётчик больше не помещается в запись
+                    let mut stored = self.load(short_url).ok_or(AppError::NotFound)?;
+                    stored.clicks += 1;
+                    return self.store(short_url, stored);
+                }
+            }
+            // блокировка записи в ссылку делает списание атомарным
+            let mut stored = self.rich.get_mut(short_url).ok_or(AppError::NotFound)?;
+            stored.link.take_click()?;
+            stored.clicks += 1;
+            if let Some(target) = target {
+                *stored.targets.entry(target.to_owned()).or_default() += 1;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl GetFullUrlRepository for CompactRepository {
+    fn get(&self, short_url: &str) -> Result<Link, AppError> {
+        if let Some(code) = Code::new(short_url) {
+            let shard = self.shard(&code).read().unwrap();
+            if let Some(slot) = shard.slots.get(&code) {
+                return Ok(Link::new(self.url(&shard, slot)));
+            }
+        }
+        self.rich
+            .get(short_url)
+            .map(|stored| stored.link.clone())
+            .ok_or(AppError::NotFound)
+    }
+}
+
+impl GetStatsRepository for CompactRepository {
+    fn get_stats(&self, short_url: &str) -> Result<LinkStats, AppError> {
+        if let Some(code) = Code::new(short_url)
+            && let Some(slot) = self.shard(&code).read().unwrap().slots.get(&code)
+        {
+            return Ok(LinkStats {
+                clicks: slot.clicks.into(),
+                targets: BTreeMap::new(),
+            });
+        }
+        self.rich
+            .get(short_url)
+            .map(|stored| LinkStats {
+                clicks: stored.clicks,
+                targets: stored.targets.clone(),
+            })
+            .ok_or(AppError::NotFound)
+    }
+}
+
+impl ListShortUrlsRepository for CompactRepository {
+    fn list(&self) -> Result<Vec<ShortUrlEntry>, AppError> {
+        self.search(&LinkFilter {
+            trash: TrashFilter::Include,
+            ..Default::default()
+        })
+    }
+
+    /// Простые ссылки без описания и пометок подходят под фильтр все разом
+    /// или ни одна, поэтому поиск по тегу или тексту их не перебирает.
+    fn search(&self, filter: &LinkFilter) -> Result<Vec<ShortUrlEntry>, AppError> {
+        let mut entries = Vec::new();
+        let plain = filter.matches_link(&Link::new(String::new()));
+        for shard in self.shards.iter().filter(|_| plain) {
+            let shard = shard.read().unwrap();
+            entries.extend(shard.slots.iter().map(|(code, slot)| ShortUrlEntry {
+                id: code.as_str().to_owned(),
+                url: self.url(&shard, slot),
+                clicks: slot.clicks.into(),
+                meta: Default::default(),
+                trashed_at: None,
+                failing_since: None,
+                disabled_at: None,
+            }));
+        }
+        entries.extend(
+            self.rich
+                .iter()
+                .filter(|item| filter.matches_link(&item.link))
+                .map(|item| ShortUrlEntry {
+                    id: item.key().clone(),
+                    url: item.link.url.clone(),
+                    clicks: item.clicks,
+                    meta: item.link.meta.clone(),
+                    trashed_at: item.link.trashed_at,
+                    failing_since: item.link.failing_since,
+                    disabled_at: item.link.disabled_at,
+                }),
+        );
+        Ok(entries)
+    }
+}
+
+impl ReplicaStore for CompactRepository {
+    fn put_replica(&self, short_url: &str, link: Link) -> Result<(), AppError> {
+        self.outbox.without_event(short_url, || {
+            let (clicks, targets) = self
+                .load(short_url)
+                .map(|stored| (stored.clicks, stored.targets))
+                .unwrap_or_default();
+            self.store(
+                short_url,
+                RichLink {
+                    link,
+                    clicks,
+                    targets,
+                },
+            )
+        })
+    }
+
+    fn remove_replica(&self, short_url: &str) -> Result<(), AppError> {
+        self.outbox
+            .without_event(short_url, || self.remove(short_url));
+        Ok(())
+    }
+
+    fn version(&self, short_url: &str) -> Result<Option<u64>, AppError> {
+        Ok(self.versions.get(short_url).map(|version| *version))
+    }
+
+    fn set_version(&self, short_url: &str, version: u64) -> Result<(), AppError> {
+        self.versions.insert(short_url.to_owned(), version);
+        Ok(())
+    }
+}
+
+impl EventOutbox for CompactRepository {
+    fn pending(&self, limit: usize) -> Result<Vec<LinkEvent>, AppError> {
+        Ok(self.outbox.pending(limit))
+    }
+
+    fn acknowledge(&self, event_ids: &[String]) -> Result<(), AppError> {
+        self.outbox.acknowledge(event_ids);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::link::LinkMeta;
+
+    use super::*;
+
+    fn save(repo: &CompactRepository, key: &str, link: Link) {
+        let url = link.url.clone();
+        repo.save(link, key.to_owned(), LinkEvent::created(key, &url))
+            .unwrap();
+    }
+
+    #[test]
+    fn plain_links_share_hosts() {
+        // given
+        let repo = CompactRepository::new();
+
+        // when
+        for i in 0..1000 {
+            save(
+                &repo,
+                &format!("code{i}"),
+                Link::new(format!("https://shop.example/items/{i}?ref=mail")),
+            );
+        }
+        save(&repo, "other", Link::new("http://other.example"));
+
+        // then
+        assert_eq!(
+            repo.get("code42"),
+            Ok(Link::new("https://shop.example/items/42?ref=mail"))
+        );
+        assert_eq!(repo.get("other"), Ok(Link::new("http://other.example")));
+        assert_eq!(repo.hosts.read().unwrap().names.len(), 2);
+        assert!(repo.rich.is_empty());
+        assert_eq!(repo.count(), Ok(1001));
+    }
+
+    #[test]
+    fn link_moves_between_tables_with_its_clicks() {
+        // given
+        let repo = CompactRepository::new();
+        save(&repo, "abc", Link::new("https://a.example/x"));
+        repo.record_click("abc", None, LinkEvent::clicked("abc"))
+            .unwrap();
+        let tagged = LinkMeta::new(None, None, vec!["promo".to_owned()]).unwrap();
+
+        // when
+        repo.update(
+            "abc",
+            &|link| {
+                link.meta = tagged.clone();
+                Ok(())
+            },
+            LinkEvent::updated("abc", "https://a.example/x"),
+        )
+        .unwrap();
+        let rich = repo.rich.len();
+        repo.update(
+            "abc",
+            &|link| {
+                link.meta = LinkMeta::default();
+                Ok(())
+            },
+            LinkEvent::updated("abc", "https://a.example/x"),
+        )
+        .unwrap();
+
+        // then
+        assert_eq!(rich, 1);
+        assert!(repo.rich.is_empty());
+        assert_eq!(repo.get("abc"), Ok(Link::new("https://a.example/x")));
+        assert_eq!(repo.get_stats("abc").unwrap().clicks, 1);
+        assert_eq!(repo.pending(10).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn search_by_tag_skips_plain_links() {
+        // given
+        let repo = CompactRepository::new();
+        save(&repo, "plain", Link::new("https://a.example"));
+        let tagged = LinkMeta::new(None, None, vec!["promo".to_owned()]).unwrap();
+        save(
+            &repo,
+            "tagged",
+            Link::new("https://b.example").with_meta(tagged),
+        );
+
+        // when
+        let by_tag = repo
+            .search(&LinkFilter {
+                tag: Some("promo".to_owned()),
+                ..Default::default()
+            })
+            .unwrap();
+        let all = repo.search(&LinkFilter::default()).unwrap();
+
+        // then
+        let ids: Vec<&str> = by_tag.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["tagged"]);
+        assert_eq!(all.len(), 2);
+    }
+
+    #[test]
+    fn long_code_is_stored_whole() {
+        // given
+        let repo = CompactRepository::new();
+        let key = "tenant/abcdefghijkl";
+
+        // when
+        save(&repo, key, Link::new("https://a.example"));
+
+        // then
+        assert_eq!(repo.get(key), Ok(Link::new("https://a.example")));
+        assert_eq!(repo.list().unwrap()[0].id, key);
+        assert_eq!(
+            repo.delete(key, LinkEvent::deleted(key)),
+            Ok(()),
+            "long code deleted"
+        );
+        assert_eq!(repo.get(key), Err(AppError::NotFound));
+    }
+
+    #[test]
+    fn rewritten_targets_do_not_grow_arena() {
+        // given
+        let repo = CompactRepository::new();
+        let path = "p".repeat(1000);
+        save(&repo, "abc", Link::new(format!("https://a.example/{path}")));
+
+        // when
+        for i in 0..500 {
+            let url = format!("https://a.example/{path}/{i}");
+            repo.update(
+                "abc",
+                &|link| {
+                    link.url = url.clone();
+                    Ok(())
+                },
+                LinkEvent::updated("abc", &url),
+            )
+            .unwrap();
+        }
+
+        // then
+        let code = Code::new("abc").unwrap();
+        let arena = repo.shard(&code).read().unwrap().arena.len();
+        assert!(arena < 2 * Shard::COMPACT_MIN + 2000, "arena {arena} bytes");
+        assert_eq!(
+            repo.get("abc").unwrap().url,
+            format!("https://a.example/{path}/499")
+        );
+    }
+
+    #[test]
+    fn split_host_keeps_port_and_query() {
+        assert_eq!(
+            split_host("https://a.example:8443/x?y=1"),
+            ("https://a.example:8443", "/x?y=1")
+        );
+        assert_eq!(
+            split_host("https://a.example?y=1"),
+            ("https://a.example", "?y=1")
+        );
+        assert_eq!(split_host("https://a.example"), ("https://a.example", ""));
+    }
+}
@@ -0,0 +1,965 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    adapters::{
+        cluster_repository::ReplicaStore,
+        file_repository::check_file,
+        in_memory_repository::{InMemoryRepository, StoredLink},
+    },
+    app::{
+        command::{
+            create_short_url::CreateShortUrlRepository, delete_short_url::DeleteShortUrlRepository,
+            record_click::RecordClickRepository, update_short_url::UpdateShortUrlRepository,
+        },
+        error::AppError,
+        event::{EventOutbox, LinkEvent},
+        health::HealthCheck,
+        link::Link,
+        query::{
+            get_full_url::GetFullUrlRepository,
+            get_stats::{GetStatsRepository, LinkStats},
+            list_short_urls::{LinkFilter, ListShortUrlsRepository, ShortUrlEntry},
+        },
+    },
+};
+
+/// когда журнал сбрасывается на диск
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsyncPolicy {
+    /// после каждой записи, подтверждённое изменение не теряется
+    Always,
+    /// фоновой задачей раз в интервал, при сбое теряются изменения последнего интервала
+    Interval(Duration),
+    /// сброс остаётся за операционной системой
+    Never,
+}
+
+/// `always`, `never` или интервал в миллисекундах
+impl FromStr for FsyncPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(FsyncPolicy::Always),
+            "never" => Ok(FsyncPolicy::Never),
+            millis => millis
+                .parse()
+                .map(|millis| FsyncPolicy::Interval(Duration::from_millis(millis)))
+                .map_err(|_| format!("expected always, never or milliseconds, got {millis}")),
+        }
+    }
+}
+
+/// настройки журнала и снимков хранилища в памяти
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DurabilityConfig {
+    /// каталог со снимком и журналом
+    pub dir: PathBuf,
+    pub fsync: FsyncPolicy,
+    /// как часто снимок заменяет накопившийся журнал
+    pub snapshot_interval: Duration,
+}
+
+/// изменение хранилища в журнале
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum WalOp {
+    Save {
+        key: String,
+        link: Link,
+        event: LinkEvent,
+    },
+    /// изменённая ссылка целиком, потому что само изменение не сериализуется
+    Update {
+        key: String,
+        link: Link,
+        event: LinkEvent,
+    },
+    Delete {
+        key: String,
+        event: LinkEvent,
+    },
+    Click {
+        key: String,
+        target: Option<String>,
+        event: LinkEvent,
+    },
+    Acknowledge {
+        ids: Vec<String>,
+    },
+    PutReplica {
+        key: String,
+        link: Link,
+    },
+    RemoveReplica {
+        key: String,
+    },
+    SetVersion {
+        key: String,
+        version: u64,
+    },
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct WalRecord {
+    seq: u64,
+    op: WalOp,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Snapshot {
+    /// последняя запись журнала, вошедшая в снимок
+    seq: u64,
+    links: Vec<StoredLink>,
+    outbox: Vec<LinkEvent>,
+    /// версии ключей узла кластера
+    #[serde(default)]
+    versions: BTreeMap<String, u64>,
+}
+
+const SNAPSHOT: &str = "snapshot.bin";
+const WAL: &str = "wal.log";
+/// журнал, который пишется в снимок прямо сейчас
+const WAL_OLD: &str = "wal.old";
+const SNAPSHOT_MAGIC: &[u8; 8] = b"SHRTSNP1";
+/// заголовок записи журнала: длина и crc32 тела
+const HEADER: usize = 8;
+/// запись длиннее считается повреждённой, а не выделяет гигабайты
+const MAX_RECORD: usize = 16 * 1024 * 1024;
+
+fn io_error(path: &Path, e: impl Display) -> AppError {
+    AppError::Internal(format!("{}: {e}", path.display()))
+}
+
+/// запись журнала в виде `длина, crc32, cbor`
+fn encode(record: &WalRecord) -> Result<Vec<u8>, AppError> {
+    let mut body = Vec::new();
+    ciborium::into_writer(record, &mut body).map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut bytes = Vec::with_capacity(HEADER + body.len());
+    bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&body).to_le_bytes());
+    bytes.extend_from_slice(&body);
+    Ok(bytes)
+}
+
+/// Целые записи журнала и длина их префикса. Разбор останавливается на
+/// первой оборванной или испорченной записи: это хвост, который не успел
+/// дописаться до сбоя.
+fn decode(bytes: &[u8]) -> (Vec<WalRecord>, usize) {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = bytes.get(offset..offset + HEADER) {
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let Some(body) = bytes.get(offset + HEADER..offset + HEADER + len) else {
+            break;
+        };
+        if len > MAX_RECORD || crc32fast::hash(body) != crc {
+            break;
+        }
+        let Ok(record) = ciborium::from_reader(body) else {
+            break;
+        };
+        records.push(record);
+        offset += HEADER + len;
+    }
+    (records, offset)
+}
+
+/// Изменение из журнала. Записи проверяются до того, как попасть в журнал,
+/// поэтому на том же состоянии они удаются и при восстановлении.
+fn apply(repo: &InMemoryRepository, op: WalOp) -> Result<(), AppError> {
+    match op {
+        WalOp::Save { key, link, event } => repo.save(link, key, event),
+        WalOp::Update { key, link, event } => {
+            let replace = |stored: &mut Link| {
+                *stored = link.clone();
+                Ok(())
+            };
+            repo.update(&key, &replace, event)
+        }
+        WalOp::Delete { key, event } => repo.delete(&key, event),
+        WalOp::Click { key, target, event } => repo.record_click(&key, target.as_deref(), event),
+        WalOp::Acknowledge { ids } => repo.acknowledge(&ids),
+        WalOp::PutReplica { key, link } => repo.put_replica(&key, link),
+        WalOp::RemoveReplica { key } => repo.remove_replica(&key),
+        WalOp::SetVersion { key, version } => repo.set_version(&key, version),
+    }
+}
+
+/// состояние, собранное из снимка и журналов
+struct Recovered {
+    inner: InMemoryRepository,
+    /// последняя применённая запись
+    seq: u64,
+    /// длина целых записей последнего журнала
+    valid_len: u64,
+}
+
+/// Снимок и повтор журналов `wals` по порядку. Записи, уже вошедшие
+/// в снимок, пропускаются; пропуски в номерах и отброшенные хвосты
+/// пишутся в лог, потому что это потерянные изменения.
+fn recover(dir: &Path, wals: &[&str]) -> Result<Recovered, AppError> {
+    let (inner, mut seq) = match read_snapshot(&dir.join(SNAPSHOT))? {
+        Some(snapshot) => {
+            let inner = InMemoryRepository::restore(snapshot.links, snapshot.outbox);
+            for (key, version) in snapshot.versions {
+                inner.set_version(&key, version)?;
+            }
+            (inner, snapshot.seq)
+        }
+        None => (InMemoryRepository::new(Default::default()), 0),
+    };
+    let mut valid_len = 0;
+    for name in wals {
+        let path = dir.join(name);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(io_error(&path, e)),
+        };
+        let (records, len) = decode(&bytes);
+        for record in records {
+            if record.seq <= seq {
+                continue;
+            }
+            if record.seq != seq + 1 {
+                eprintln!(
+                    "{}: wal records {}..{} are missing",
+                    path.display(),
+                    seq + 1,
+                    record.seq - 1
+                );
+            }
+            seq = record.seq;
+            if let Err(e) = apply(&inner, record.op) {
+                eprintln!("{}: wal record {seq} not applied: {e}", path.display());
+            }
+        }
+        if len < bytes.len() {
+            eprintln!(
+                "{}: dropped {} bytes of torn or corrupt records after record {seq}",
+                path.display(),
+                bytes.len() - len
+            );
+        }
+        valid_len = len as u64;
+    }
+    Ok(Recovered {
+        inner,
+        seq,
+        valid_len,
+    })
+}
+
+fn read_snapshot(path: &Path) -> Result<Option<Snapshot>, AppError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(io_error(path, e)),
+    };
+    // снимок заменяется переименованием, поэтому порча — это сбой диска,
+    // и молча начать с пустого хранилища нельзя
+    let body = bytes
+        .strip_prefix(SNAPSHOT_MAGIC)
+        .filter(|rest| rest.len() >= 4)
+        .ok_or_else(|| io_error(path, "not a snapshot"))?;
+    let (crc, body) = body.split_at(4);
+    if crc32fast::hash(body) != u32::from_le_bytes(crc.try_into().unwrap()) {
+        return Err(io_error(path, "snapshot checksum mismatch"));
+    }
+    ciborium::from_reader(body)
+        .map(Some)
+        .map_err(|e| io_error(path, e))
+}
+
+/// запись через временный файл, чтобы на диске всегда был целый снимок
+fn write_snapshot(dir: &Path, snapshot: &Snapshot) -> Result<(), AppError> {
+    let path = dir.join(SNAPSHOT);
+    let mut body = Vec::new();
+    ciborium::into_writer(snapshot, &mut body).map_err(|e| io_error(&path, e))?;
+    let tmp = path.with_extension("tmp");
+    let mut file = File::create(&tmp).map_err(|e| io_error(&tmp, e))?;
+    file.write_all(SNAPSHOT_MAGIC)
+        .and_then(|_| file.write_all(&crc32fast::hash(&body).to_le_bytes()))
+        .and_then(|_| file.write_all(&body))
+        .and_then(|_| file.sync_all())
+        .map_err(|e| io_error(&tmp, e))?;
+    fs::rename(&tmp, &path).map_err(|e| io_error(&path, e))?;
+    sync_dir(dir)
+}
+
+/// переименования в каталоге переживают сбой только после его fsync
+fn sync_dir(dir: &Path) -> Result<(), AppError> {
+    #[cfg(unix)]
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|e| io_error(dir, e))?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// открытый журнал и номер последней записи
+struct Wal {
+    dir: PathBuf,
+    file: File,
+    /// длина целых записей, до неё файл обрезается после неудачной записи
+    len: u64,
+    seq: u64,
+    fsync: FsyncPolicy,
+    /// есть записи, ещё не сброшенные на диск
+    dirty: bool,
+}
+
+impl Wal {
+    fn path(&self) -> PathBuf {
+        self.dir.join(WAL)
+    }
+
+    /// дописывает запись и возвращает изменение обратно для применения
+    fn append(&mut self, op: WalOp) -> Result<WalOp, AppError> {
+        let record = WalRecord {
+            seq: self.seq + 1,
+            op,
+        };
+        let bytes = encode(&record)?;
+        if let Err(e) = self.file.write_all(&bytes) {
+            // не оставлять в середине журнала оборванную запись
+            let _ = self.file.set_len(self.len);
+            return Err(io_error(&self.path(), e));
+        }
+        self.len += bytes.len() as u64;
+        self.seq = record.seq;
+
+        if self.fsync == FsyncPolicy::Always {
+            self.sync()?;
+        } else {
+            self.dirty = true;
+        }
+        Ok(record.op)
+    }
+
+    fn sync(&mut self) -> Result<(), AppError> {
+        self.file
+            .sync_data()
+            .map_err(|e| io_error(&self.path(), e))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Журнал уходит в `wal.old` под снимок, записи продолжаются в новый.
+    /// Если прошлый снимок не удался и `wal.old` остался, журнал не
+    /// переносится: его записи, уже вошедшие в снимок, отбросятся по номеру.
+    fn rotate(&mut self) -> Result<(), AppError> {
+        self.sync()?;
+        let old = self.dir.join(WAL_OLD);
+        if old.exists() {
+            return Ok(());
+        }
+        let path = self.path();
+        fs::rename(&path, &old).map_err(|e| io_error(&path, e))?;
+        self.file = open_wal(&path)?;
+        self.len = 0;
+        sync_dir(&self.dir)
+    }
+}
+
+fn open_wal(path: &Path) -> Result<File, AppError> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| io_error(path, e))
+}
+
+/// Хранилище в памяти с журналом изменений и периодическими снимками.
+///
+/// Каждое изменение сначала проверяется на текущем состоянии, затем
+/// дописывается в журнал и только после этого применяется в памяти, так что
+/// чтение никогда не видит изменений, которых нет в журнале. Снимок в cbor
+/// заменяет накопленный журнал, а при запуске загружается снимок
+/// и повторяется хвост журнала после него.
+#[derive(Clone)]
+pub struct DurableRepository {
+    inner: InMemoryRepository,
+    wal: Arc<Mutex<Wal>>,
+    /// снимки снимаются по одному
+    checkpointing: Arc<Mutex<()>>,
+}
+
+impl DurableRepository {
+    /// восстановление из каталога, пустой каталог — пустое хранилище
+    pub fn open(config: &DurabilityConfig) -> Result<Self, AppError> {
+        let dir = config.dir.clone();
+        fs::create_dir_all(&dir).map_err(|e| io_error(&dir, e))?;
+
+        let Recovered {
+            inner,
+            seq,
+            valid_len,
+        } = recover(&dir, &[WAL_OLD, WAL])?;
+        // оборванная последняя запись отрезается, чтобы новые шли за целыми
+        let path = dir.join(WAL);
+        let file = open_wal(&path)?;
+        file.set_len(valid_len).map_err(|e| io_error(&path, e))?;
+        let repo = Self {
+            inner,
+            wal: Arc::new(Mutex::new(Wal {
+                dir,
+                file,
+                len: valid_len,
+                seq,
+                fsync: config.fsync,
+                dirty: false,
+            })),
+            checkpointing: Arc::new(Mutex::new(())),
+        };
+        // снимок сразу после восстановления, чтобы не повторять журнал снова
+        repo.checkpoint()?;
+        Ok(repo)
+    }
+
+    /// Снимок на место журнала. Под блокировкой журнала он только уходит
+    /// в `wal.old`; снимок собирается из прошлого снимка и этого журнала
+    /// в отдельной копии хранилища, пока изменения идут дальше.
+    pub fn checkpoint(&self) -> Result<(), AppError> {
+        let _checkpointing = self.checkpointing.lock().unwrap();
+        let dir = {
+            let mut wal = self.wal.lock().unwrap();
+            wal.rotate()?;
+            wal.dir.clone()
+        };
+        let Recovered { inner, seq, .. } = recover(&dir, &[WAL_OLD])?;
+        let (links, outbox) = inner.dump();
+        let snapshot = Snapshot {
+            seq,
+            links,
+            outbox,
+            versions: inner.versions(),
+        };
+        write_snapshot(&dir, &snapshot)?;
+        let old = dir.join(WAL_OLD);
+        match fs::remove_file(&old) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(io_error(&old, e)),
+        }
+    }
+
+    /// Сброс дописанных записей на диск. Сам fsync идёт через копию
+    /// дескриптора, чтобы не держать журнал, пока диск отвечает.
+    pub fn flush(&self) -> Result<(), AppError> {
+        let (file, path) = {
+            let mut wal = self.wal.lock().unwrap();
+            if !wal.dirty {
+                return Ok(());
+            }
+            let file = wal.file.try_clone().map_err(|e| io_error(&wal.path(), e))?;
+            wal.dirty = false;
+            (file, wal.path())
+        };
+        file.sync_data().map_err(|e| {
+            self.wal.lock().unwrap().dirty = true;
+            io_error(&path, e)
+        })
+    }
+
+    /// сброс журнала раз в интервал для [`FsyncPolicy::Interval`]
+    pub async fn run_flusher(self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            let repo = self.clone();
+            match tokio::task::spawn_blocking(move || repo.flush()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("wal flush failed: {e}"),
+                Err(e) => eprintln!("wal flush failed: {e}"),
+            }
+        }
+    }
+
+    /// снимки по расписанию, пока процесс жив
+    pub async fn run_snapshots(self, interval: Duration) {
+        loop {
+            tokio::time::sleep(interval).await;
+            let repo = self.clone();
+            match tokio::task::spawn_blocking(move || repo.checkpoint()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("snapshot failed: {e}"),
+                Err(e) => eprintln!("snapshot failed: {e}"),
+            }
+        }
+    }
+
+    /// Проверка изменения, запись в журнал и применение в памяти под
+    /// блокировкой журнала: порядок записей совпадает с порядком изменений,
+    /// и проверенное состояние не меняется до применения.
+    fn logged(
+        &self,
+        plan: impl FnOnce(&InMemoryRepository) -> Result<WalOp, AppError>,
+    ) -> Result<(), AppError> {
+        let mut wal = self.wal.lock().unwrap();
+        let op = plan(&self.inner)?;
+        let op = wal.append(op)?;
+        apply(&self.inner, op)
+    }
+}
+
+impl HealthCheck for DurableRepository {
+    fn check_health(&self) -> Result<(), AppError> {
+        let path = self.wal.lock().unwrap().path();
+        check_file(&path)
+    }
+}
+
+impl CreateShortUrlRepository for DurableRepository {
+    fn save(&self, link: Link, short_url: String, event: LinkEvent) -> Result<(), AppError> {
+        self.logged(|_| {
+            Ok(WalOp::Save {
+                key: short_url,
+                link,
+                event,
+            })
+        })
+    }
+
+    fn count(&self) -> Result<usize, AppError> {
+        self.inner.count()
+    }
+}
+
+impl UpdateShortUrlRepository for DurableRepository {
+    fn update(
+        &self,
+        short_url: &str,
+        change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.logged(|inner| {
+            let mut link = inner.get(short_url)?;
+            change(&mut link)?;
+            Ok(WalOp::Update {
+                key: short_url.to_owned(),
+                link,
+                event,
+            })
+        })
+    }
+}
+
+impl DeleteShortUrlRepository for DurableRepository {
+    fn delete_if(
+        &self,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.logged(|inner| {
+            check(&inner.get(short_url)?)?;
+            Ok(WalOp::Delete {
+                key: short_url.to_owned(),
+                event,
+            })
+        })
+    }
+}
+
+impl RecordClickRepository for DurableRepository {
+    fn record_click(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.logged(|inner| {
+            inner.get(short_url)?.take_click()?;
+            Ok(WalOp::Click {
+                key: short_url.to_owned(),
+                target: target.map(str::to_owned),
+                event,
+            })
+        })
+    }
+}
+
+impl GetFullUrlRepository for DurableRepository {
+    fn get(&self, short_url: &str) -> Result<Link, AppError> {
+        self.inner.get(short_url)
+    }
+}
+
+impl GetStatsRepository for DurableRepository {
+    fn get_stats(&self, short_url: &str) -> Result<LinkStats, AppError> {
+        self.inner.get_stats(short_url)
+    }
+}
+
+impl ListShortUrlsRepository for DurableRepository {
+    fn list(&self) -> Result<Vec<ShortUrlEntry>, AppError> {
+        self.inner.list()
+    }
+
+    fn search(&self, filter: &LinkFilter) -> Result<Vec<ShortUrlEntry>, AppError> {
+        self.inner.search(filter)
+    }
+}
+
+impl ReplicaStore for DurableRepository {
+    fn put_replica(&self, short_url: &str, link: Link) -> Result<(), AppError> {
+        self.logged(|_| {
+            Ok(WalOp::PutReplica {
+                key: short_url.to_owned(),
+                link,
+            })
+        })
+    }
+
+    fn remove_replica(&self, short_url: &str) -> Result<(), AppError> {
+        self.logged(|_| {
+            Ok(WalOp::RemoveReplica {
+                key: short_url.to_owned(),
+            })
+        })
+    }
+
+    fn version(&self, short_url: &str) -> Result<Option<u64>, AppError> {
+        self.inner.version(short_url)
+    }
+
+    fn set_version(&self, short_url: &str, version: u64) -> Result<(), AppError> {
+        self.logged(|_| {
+            Ok(WalOp::SetVersion {
+                key: short_url.to_owned(),
+                version,
+            })
+        })
+    }
+}
+
+/// подтверждения тоже пишутся в журнал, иначе после перезапуска
+/// все события с последнего снимка ушли бы подписчикам повторно
+impl EventOutbox for DurableRepository {
+    fn pending(&self, limit: usize) -> Result<Vec<LinkEvent>, AppError> {
+        self.inner.pending(limit)
+    }
+
+    fn acknowledge(&self, event_ids: &[String]) -> Result<(), AppError> {
+        self.logged(|_| {
+            Ok(WalOp::Acknowledge {
+                ids: event_ids.to_vec(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::link::{ClickLimit, LinkOptions};
+
+    use super::*;
+
+    fn config(dir: &Path) -> DurabilityConfig {
+        DurabilityConfig {
+            dir: dir.to_path_buf(),
+            fsync: FsyncPolicy::Never,
+            snapshot_interval: Duration::from_secs(60),
+        }
+    }
+
+    /// всё видимое состояние хранилища
+    type State = (Vec<StoredLink>, Vec<LinkEvent>);
+
+    fn state(repo: &DurableRepository) -> State {
+        let (mut links, outbox) = repo.inner.dump();
+        links.sort_by(|a, b| a.short_url.cmp(&b.short_url));
+        (links, outbox)
+    }
+
+    /// изменения всех видов, после каждого — состояние и длина журнала
+    fn write_history(repo: &DurableRepository) -> Vec<(State, u64)> {
+        let wal_len = || repo.wal.lock().unwrap().len;
+        let limited = Link::new("https://b.example").with_options(LinkOptions {
+            max_clicks: ClickLimit::Set(2),
+            ..Default::default()
+        });
+        let steps: Vec<Box<dyn Fn() + '_>> = vec![
+            Box::new(|| {
+                repo.save(
+                    Link::new("https://a.example"),
+                    "a".to_owned(),
+                    LinkEvent::created("a", "https://a.example"),
+                )
+                .unwrap()
+            }),
+            Box::new(|| {
+                repo.save(
+                    limited.clone(),
+                    "b".to_owned(),
+                    LinkEvent::created("b", "https://b.example"),
+                )
+                .unwrap()
+            }),
+            Box::new(|| {
+                repo.record_click("b", None, LinkEvent::clicked("b"))
+                    .unwrap()
+            }),
+            Box::new(|| {
+                repo.update(
+                    "a",
+                    &|link| {
+                        link.url = "https://a.example/new".to_owned();
+                        Ok(())
+                    },
+                    LinkEvent::updated("a", "https://a.example/new"),
+                )
+                .unwrap()
+            }),
+            Box::new(|| {
+                let ids: Vec<String> = repo.pending(2).unwrap().into_iter().map(|e| e.id).collect();
+                repo.acknowledge(&ids).unwrap()
+            }),
+            Box::new(|| {
+                repo.record_click("b", None, LinkEvent::clicked("b"))
+                    .unwrap()
+            }),
+            Box::new(|| repo.delete("a", LinkEvent::deleted("a")).unwrap()),
+        ];
+
+        let mut history = vec![(state(repo), wal_len())];
+        for step in steps {
+            step();
+            history.push((state(repo), wal_len()));
+        }
+        history
+    }
+
+    #[test]
+    fn state_survives_restart() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = DurableRepository::open(&config(dir.path())).unwrap();
+        let history = write_history(&repo);
+        drop(repo);
+
+        // when
+        let reopened = DurableRepository::open(&config(dir.path())).unwrap();
+
+        // then
+        assert_eq!(state(&reopened), history.last().unwrap().0);
+        assert_eq!(
+            reopened.record_click("b", None, LinkEvent::clicked("b")),
+            Err(AppError::Gone(None))
+        );
+    }
+
+    #[test]
+    fn recovers_from_wal_cut_at_any_offset() {
+        // given
+        let source = tempfile::tempdir().unwrap();
+        let repo = DurableRepository::open(&config(source.path())).unwrap();
+        let history = write_history(&repo);
+        drop(repo);
+        let wal = fs::read(source.path().join(WAL)).unwrap();
+        let snapshot = fs::read(source.path().join(SNAPSHOT)).unwrap();
+
+        for cut in 0..=wal.len() {
+            let dir = tempfile::tempdir().unwrap();
+            fs::write(dir.path().join(SNAPSHOT), &snapshot).unwrap();
+            fs::write(dir.path().join(WAL), &wal[..cut]).unwrap();
+
+            // when
+            let reopened = DurableRepository::open(&config(dir.path())).unwrap();
+
+            // then
+            let (expected, _) = history
+                .iter()
+                .rev()
+                .find(|(_, len)| *len as usize <= cut)
+                .unwrap();
+            assert_eq!(&state(&reopened), expected, "wal cut at {cut}");
+        }
+    }
+
+    #[test]
+    fn garbage_tail_is_dropped_and_log_continues() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = DurableRepository::open(&config(dir.path())).unwrap();
+        let history = write_history(&repo);
+        drop(repo);
+        let mut wal = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(WAL))
+            .unwrap();
+        wal.write_all(&[0x20, 0, 0, 0, 0xde, 0xad, 0xbe, 0xef, 1, 2, 3])
+            .unwrap();
+        drop(wal);
+
+        // when
+        let reopened = DurableRepository::open(&config(dir.path())).unwrap();
+        reopened
+            .save(
+                Link::new("https://c.example"),
+                "c".to_owned(),
+                LinkEvent::created("c", "https://c.example"),
+            )
+            .unwrap();
+        drop(reopened);
+        let again = DurableRepository::open(&config(dir.path())).unwrap();
+
+        // then
+        let (links, _) = state(&again);
+        let (expected, _) = &history.last().unwrap().0;
+        assert_eq!(links.len(), expected.len() + 1);
+        assert_eq!(again.get("c"), Ok(Link::new("https://c.example")));
+    }
+
+    #[test]
+    fn interrupted_checkpoint_does_not_replay_twice() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = DurableRepository::open(&config(dir.path())).unwrap();
+        repo.save(
+            Link::new("https://a.example"),
+            "a".to_owned(),
+            LinkEvent::created("a", "https://a.example"),
+        )
+        .unwrap();
+        repo.record_click("a", None, LinkEvent::clicked("a"))
+            .unwrap();
+        // снимок записан, но журнал, вошедший в него, ещё не удалён
+        let wal = fs::read(dir.path().join(WAL)).unwrap();
+        repo.checkpoint().unwrap();
+        fs::write(dir.path().join(WAL_OLD), wal).unwrap();
+        drop(repo);
+
+        // when
+        let reopened = DurableRepository::open(&config(dir.path())).unwrap();
+
+        // then
+        assert_eq!(reopened.get_stats("a").unwrap().clicks, 1);
+        assert_eq!(reopened.pending(10).unwrap().len(), 2);
+        assert!(!dir.path().join(WAL_OLD).exists());
+    }
+
+    #[test]
+    fn failed_wal_write_is_not_applied() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = DurableRepository::open(&config(dir.path())).unwrap();
+        // дескриптор только для чтения, запись в журнал не удаётся
+        repo.wal.lock().unwrap().file = File::open(dir.path().join(WAL)).unwrap();
+
+        // when
+        let result = repo.save(
+            Link::new("https://a.example"),
+            "a".to_owned(),
+            LinkEvent::created("a", "https://a.example"),
+        );
+
+        // then
+        assert!(result.is_err());
+        assert_eq!(repo.get("a"), Err(AppError::NotFound));
+        assert_eq!(repo.pending(10), Ok(vec![]));
+    }
+
+    #[test]
+    fn interval_policy_is_flushed_by_flusher() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = DurableRepository::open(&DurabilityConfig {
+            fsync: FsyncPolicy::Interval(Duration::from_millis(100)),
+            ..config(dir.path())
+        })
+        .unwrap();
+        repo.save(
+            Link::new("https://a.example"),
+            "a".to_owned(),
+            LinkEvent::created("a", "https://a.example"),
+        )
+        .unwrap();
+        assert!(repo.wal.lock().unwrap().dirty);
+
+        // when
+        repo.flush().unwrap();
+
+        // then
+        assert!(!repo.wal.lock().unwrap().dirty);
+    }
+
+    #[test]
+    fn checkpoint_keeps_changes_after_rotation() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = DurableRepository::open(&config(dir.path())).unwrap();
+        repo.save(
+            Link::new("https://a.example"),
+            "a".to_owned(),
+            LinkEvent::created("a", "https://a.example"),
+        )
+        .unwrap();
+        repo.record_click("a", None, LinkEvent::clicked("a"))
+            .unwrap();
+
+        // when
+        repo.checkpoint().unwrap();
+        repo.record_click("a", None, LinkEvent::clicked("a"))
+            .unwrap();
+        let expected = state(&repo);
+        drop(repo);
+        let reopened = DurableRepository::open(&config(dir.path())).unwrap();
+
+        // then
+        assert_eq!(state(&reopened), expected);
+        assert_eq!(reopened.get_stats("a").unwrap().clicks, 2);
+    }
+
+    #[test]
+    fn recovery_continues_past_missing_records() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let mut wal = Vec::new();
+        for (seq, key) in [(1, "a"), (3, "c")] {
+            let url = format!("https://{key}.example");
+            wal.extend(
+                encode(&WalRecord {
+                    seq,
+                    op: WalOp::Save {
+                        key: key.to_owned(),
+                        link: Link::new(&url),
+                        event: LinkEvent::created(key, &url),
+                    },
+                })
+                .unwrap(),
+            );
+        }
+        fs::write(dir.path().join(WAL), wal).unwrap();
+
+        // when
+        let recovered = recover(dir.path(), &[WAL]).unwrap();
+
+        // then
+        assert_eq!(recovered.seq, 3);
+        assert_eq!(recovered.inner.count(), Ok(2));
+    }
+
+    #[test]
+    fn fsync_policy_parses() {
+        assert_eq!("always".parse(), Ok(FsyncPolicy::Always));
+        assert_eq!("never".parse(), Ok(FsyncPolicy::Never));
+        assert_eq!(
+            "250".parse(),
+            Ok(FsyncPolicy::Interval(Duration::from_millis(250)))
+        );
+        assert!("sometimes".parse::<FsyncPolicy>().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{
+    adapters::file_repository::check_file,
+    app::{
+        audit::{AuditEntry, AuditRecord, AuditTrail},
+        error::AppError,
+        health::HealthCheck,
+    },
+};
+
+/// Журнал аудита в файле jsonl: одна запись на строку, файл только дописывается.
+///
+/// Последняя запись читается из файла при первом дописывании и дальше
+/// хранится в памяти, поэтому писать в один файл должен один процесс.
+/// Строка без перевода строки в конце считается оборванной при сбое:
+/// при чтении она пропускается, а перед первым дописыванием отрезается.
+pub struct FileAuditTrail {
+    path: PathBuf,
+    /// последняя запись, `None` пока файл не прочитан
+    last: Mutex<Option<Option<AuditEntry>>>,
+}
+
+impl FileAuditTrail {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            last: Mutex::new(None),
+        }
+    }
+
+    fn read(&self) -> Result<String, AppError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(AppError::Internal(format!("{}: {e}", self.path.display()))),
+        }
+    }
+
+    /// отрезает оборванную последнюю строку, чтобы следующая запись начиналась с новой строки
+    fn truncate_torn_tail(&self, content: &str) -> Result<(), AppError> {
+        if content.is_empty() || content.ends_with('\n') {
+            return Ok(());
+        }
+        let keep = content.rfind('\n').map_or(0, |i| i + 1);
+        eprintln!(
+            "{}: dropping torn audit tail of {} bytes",
+            self.path.display(),
+            content.len() - keep
+        );
+        OpenOptions::new()
+            .write(true)
+            .open(&self.path)
+            .and_then(|file| file.set_len(keep as u64).and_then(|_| file.sync_data()))
+            .map_err(|e| AppError::Internal(format!("{}: {e}", self.path.display())))
+    }
+}
+
+/// полные строки журнала, оборванный хвост не включается
+fn complete_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .split_inclusive('\n')
+        .filter_map(|line| line.strip_suffix('\n'))
+}
+
+impl HealthCheck for FileAuditTrail {
+    fn check_health(&self) -> Result<(), AppError> {
+        check_file(&self.path)
+    }
+}
+
+impl AuditTrail for FileAuditTrail {
+    fn append(&self, record: AuditRecord) -> Result<AuditEntry, AppError> {
+        let mut last = self.last.lock().unwrap();
+        if last.is_none() {
+            let content = self.read()?;
+            self.truncate_torn_tail(&content)?;
+            *last = Some(parse(&content)?.pop());
+        }
+        let entry = AuditEntry::chain(last.as_ref().and_then(Option::as_ref), record);
+
+        let mut line = serde_json::to_vec(&entry).map_err(|e| AppError::Internal(e.to_string()))?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| AppError::Internal(format!("{}: {e}", self.path.display())))?;
+        file.write_all(&line)
+            .and_then(|_| file.sync_data())
+            .map_err(|e| AppError::Internal(format!("{}: {e}", self.path.display())))?;
+
+        *last = Some(Some(entry.clone()));
+        Ok(entry)
+    }
+
+    fn entries(&self) -> Result<Vec<AuditEntry>, AppError> {
+        parse(&self.read()?)
+    }
+}
+
+fn parse(content: &str) -> Result<Vec<AuditEntry>, AppError> {
+    complete_lines(content)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                AppError::Internal(format!("audit line {} is corrupt: {e}", index + 1))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(action: &str) -> AuditRecord {
+        AuditRecord::new("admin", Some("4f2a".to_owned()), "10.0.0.1", action, "abc")
+    }
+
+    #[test]
+    fn chain_continues_after_reopen() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        FileAuditTrail::new(&path)
+            .append(record("link.create"))
+            .unwrap();
+
+        // when
+        let reopened = FileAuditTrail::new(&path);
+        let entry = reopened.append(record("link.delete")).unwrap();
+
+        // then
+        assert_eq!(entry.seq, 2);
+        assert_eq!(reopened.verify().unwrap().last_hash, entry.hash);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn torn_tail_is_dropped_before_append() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        FileAuditTrail::new(&path)
+            .append(record("link.create"))
+            .unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"seq":2,"at":"#).unwrap();
+
+        // when
+        let reopened = FileAuditTrail::new(&path);
+        let before = reopened.entries().unwrap();
+        let entry = reopened.append(record("link.delete")).unwrap();
+
+        // then
+        assert_eq!(before.len(), 1);
+        assert_eq!(entry.seq, 2);
+        assert_eq!(reopened.verify().unwrap().entries, 2);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn edited_file_fails_verification() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let trail = FileAuditTrail::new(&path);
+        trail.append(record("link.create")).unwrap();
+        trail.append(record("link.update")).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, content.replacen("10.0.0.1", "10.0.0.2", 1)).unwrap();
+
+        // when
+        let result = FileAuditTrail::new(&path).verify();
+
+        // then
+        assert_eq!(
+            result,
+            Err(AppError::Internal("audit entry 1 was modified".to_owned()))
+        );
+    }
+}
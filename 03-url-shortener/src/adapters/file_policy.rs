@@ -0,0 +1,174 @@
+use std::{fs, io::ErrorKind, path::PathBuf, sync::Mutex, time::SystemTime};
+
+use crate::app::{
+    error::AppError,
+    policy::{HostList, HostPolicy, TargetPolicy},
+};
+
+/// список хостов из файла, перечитываемый при изменении файла
+struct WatchedList {
+    path: PathBuf,
+    /// время изменения файла на момент последнего чтения, `None` если файла нет
+    modified: Option<SystemTime>,
+    loaded: bool,
+    hosts: HostList,
+}
+
+impl WatchedList {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            modified: None,
+            loaded: false,
+            hosts: HostList::default(),
+        }
+    }
+
+    /// перечитать файл, если он изменился; вернёт `true`, если список обновлён
+    fn refresh(&mut self) -> Result<bool, AppError> {
+        let modified = match fs::metadata(&self.path).and_then(|m| m.modified()) {
+            Ok(modified) => Some(modified),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(AppError::Internal(e.to_string())),
+        };
+        if self.loaded && modified == self.modified {
+            return Ok(false);
+        }
+
+        // пропавший файл означает пустой список
+        self.hosts = match modified {
+            Some(_) => HostList::parse(
+                &fs::read_to_string(&self.path).map_err(|e| AppError::Internal(e.to_string()))?,
+            ),
+            None => HostList::default(),
+        };
+        self.modified = modified;
+        self.loaded = true;
+        Ok(true)
+    }
+}
+
+struct State {
+    blocklist: Option<WatchedList>,
+    allowlist: Option<WatchedList>,
+    policy: HostPolicy,
+}
+
+/// Политика по хостам с блок- и allow-листами из файлов.
+///
+/// Файлы проверяются на изменения при каждой проверке цели.
+/// Без файла allow-листа режим allow-листа выключен; если файл задан,
+/// но отсутствует, запрещены все цели.
+pub struct FilePolicy {
+    state: Mutex<State>,
+}
+
+impl FilePolicy {
+    pub fn new(
+        blocklist: Option<PathBuf>,
+        allowlist: Option<PathBuf>,
+        own_domains: &[String],
+    ) -> Self {
+        let mut own = HostList::default();
+        for domain in own_domains {
+            own.add(domain);
+        }
+
+        Self {
+            state: Mutex::new(State {
+                blocklist: blocklist.map(WatchedList::new),
+                allowlist: allowlist.map(WatchedList::new),
+                policy: HostPolicy {
+                    own_domains: own,
+                    ..Default::default()
+                },
+            }),
+        }
+    }
+}
+
+impl TargetPolicy for FilePolicy {
+    fn check(&self, full_url: &str) -> Result<(), AppError> {
+        let mut state = self.state.lock().unwrap();
+        let State {
+            blocklist,
+            allowlist,
+            policy,
+        } = &mut *state;
+
+        if let Some(list) = blocklist
+            && list.refresh()?
+        {
+            policy.blocklist = list.hosts.clone();
+        }
+        if let Some(list) = allowlist
+            && list.refresh()?
+        {
+            policy.allowlist = Some(list.hosts.clone());
+        }
+
+        policy.check(full_url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, time::Duration};
+
+    use super::*;
+
+    fn write(path: &PathBuf, content: &str, modified: SystemTime) {
+        fs::write(path, content).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn blocklist_is_reloaded_on_change() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocklist.txt");
+        let now = SystemTime::now();
+        write(&path, "evil.com\n", now);
+        let policy = FilePolicy::new(Some(path.clone()), None, &[]);
+
+        // when
+        let before = policy.check("https://phish.net");
+        write(&path, "evil.com\nphish.net\n", now + Duration::from_secs(1));
+        let after = policy.check("https://phish.net");
+
+        // then
+        assert_eq!(before, Ok(()));
+        assert_eq!(
+            after,
+            Err(AppError::TargetRejected("phish.net is blocked".to_owned()))
+        );
+    }
+
+    #[test]
+    fn missing_allowlist_rejects_everything() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let policy = FilePolicy::new(None, Some(dir.path().join("allowlist.txt")), &[]);
+
+        // when
+        let result = policy.check("https://google.com");
+
+        // then
+        assert!(matches!(result, Err(AppError::TargetRejected(_))));
+    }
+
+    #[test]
+    fn own_domains_are_rejected() {
+        // given
+        let policy = FilePolicy::new(None, None, &["sho.rt".to_owned()]);
+
+        // then
+        assert!(policy.check("https://sho.rt/abc").is_err());
+        assert_eq!(policy.check("https://google.com"), Ok(()));
+    }
+}
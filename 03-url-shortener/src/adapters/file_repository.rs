@@ -0,0 +1,495 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    adapters::cluster_repository::ReplicaStore,
+    app::{
+        command::{
+            create_short_url::CreateShortUrlRepository, delete_short_url::DeleteShortUrlRepository,
+            record_click::RecordClickRepository, update_short_url::UpdateShortUrlRepository,
+        },
+        error::AppError,
+        event::{EventOutbox, LinkEvent},
+        health::HealthCheck,
+        link::Link,
+        query::{
+            get_full_url::GetFullUrlRepository,
+            get_stats::{GetStatsRepository, LinkStats},
+            list_short_urls::{LinkFilter, ListShortUrlsRepository, ShortUrlEntry, TrashFilter},
+        },
+    },
+};
+
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+struct FileData {
+    links: BTreeMap<String, StoredLink>,
+    /// события, ещё не переданные подписчикам
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    outbox: Vec<LinkEvent>,
+    /// версии ключей узла кластера
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    versions: BTreeMap<String, u64>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct StoredLink {
+    #[serde(flatten)]
+    link: Link,
+    #[serde(default)]
+    clicks: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    target_clicks: BTreeMap<String, u64>,
+}
+
+/// Хранилище ссылок в json файле.
+///
+/// Файл перечитывается при каждой операции, поэтому изменения,
+/// сделанные другим процессом (например, cli), сразу видны серверу.
+/// Изменения разных процессов не теряются: чтение, изменение и запись
+/// идут под блокировкой файла `{path}.lock` в ОС.
+/// События пишутся в тот же файл одной записью с изменением, так что
+/// сервер опубликует и события, порождённые cli.
+#[derive(Clone)]
+pub struct FileRepository {
+    path: Arc<PathBuf>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl FileRepository {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: Arc::new(path.as_ref().to_path_buf()),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn load(&self) -> Result<FileData, AppError> {
+        match fs::read(self.path.as_ref()) {
+            Ok(bytes) => {
+                serde_json::from_slice(&bytes).map_err(|e| AppError::Internal(e.to_string()))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(FileData::default()),
+            Err(e) => Err(AppError::Internal(e.to_string())),
+        }
+    }
+
+    fn store(&self, data: &FileData) -> Result<(), AppError> {
+        let bytes =
+            serde_json::to_vec_pretty(data).map_err(|e| AppError::Internal(e.to_string()))?;
+        write_atomic(&self.path, &bytes)
+    }
+
+    fn read<T>(&self, f: impl FnOnce(&FileData) -> Result<T, AppError>) -> Result<T, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        f(&self.load()?)
+    }
+
+    fn update<T>(
+        &self,
+        f: impl FnOnce(&mut FileData) -> Result<T, AppError>,
+    ) -> Result<T, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        let _file_lock = lock_file(&self.path)?;
+        let mut data = self.load()?;
+        let res = f(&mut data)?;
+        self.store(&data)?;
+        Ok(res)
+    }
+}
+
+/// Исключительная блокировка файла `path` между процессами, держится,
+/// пока жив возвращённый дескриптор. Блокируется отдельный файл `{path}.lock`,
+/// потому что сам файл заменяется при каждой записи.
+pub(crate) fn lock_file(path: &Path) -> Result<fs::File, AppError> {
+    let lock_path = sibling(path, "lock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| AppError::Internal(format!("{}: {e}", lock_path.display())))?;
+    file.lock()
+        .map_err(|e| AppError::Internal(format!("{}: {e}", lock_path.display())))?;
+    Ok(file)
+}
+
+/// Запись через временный файл, чтобы не оставить файл недописанным.
+/// Имя временного файла своё у каждой записи, чтобы параллельные записи
+/// не писали в один временный файл.
+pub(crate) fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), AppError> {
+    let tmp = sibling(
+        path,
+        &format!("{}.{}.tmp", std::process::id(), nanoid::nanoid!(8)),
+    );
+    fs::write(&tmp, bytes).map_err(|e| AppError::Internal(e.to_string()))?;
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        AppError::Internal(e.to_string())
+    })
+}
+
+/// файл рядом с `path` с добавленным к имени суффиксом
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Файл хранилища доступен: существующий файл открывается на чтение,
+/// а для ещё не созданного есть каталог, доступный на запись.
+pub(crate) fn check_file(path: &Path) -> Result<(), AppError> {
+    match fs::File::open(path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            let meta = fs::metadata(dir)
+                .map_err(|e| AppError::Internal(format!("{}: {e}", dir.display())))?;
+            if !meta.is_dir() || meta.permissions().readonly() {
+                return Err(AppError::Internal(format!(
+                    "{} is not writable",
+                    dir.display()
+                )));
+            }
+            Ok(())
+        }
+        Err(e) => Err(AppError::Internal(format!("{}: {e}", path.display()))),
+    }
+}
+
+impl HealthCheck for FileRepository {
+    fn check_health(&self) -> Result<(), AppError> {
+        check_file(&self.path)
+    }
+}
+
+impl CreateShortUrlRepository for FileRepository {
+    fn save(&self, link: Link, short_url: String, event: LinkEvent) -> Result<(), AppError> {
+        self.update(|data| {
+            data.links.insert(
+                short_url,
+                StoredLink {
+                    link,
+                    clicks: 0,
+                    target_clicks: BTreeMap::new(),
+                },
+            );
+            data.outbox.push(event);
+            Ok(())
+        })
+    }
+
+    fn count(&self) -> Result<usize, AppError> {
+        self.read(|data| Ok(data.links.len()))
+    }
+}
+
+impl DeleteShortUrlRepository for FileRepository {
+    fn delete_if(
+        &self,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.update(|data| {
+            let stored = data.links.get(short_url).ok_or(AppError::NotFound)?;
+            check(&stored.link)?;
+            data.links.remove(short_url);
+            data.outbox.push(event);
+            Ok(())
+        })
+    }
+}
+
+impl UpdateShortUrlRepository for FileRepository {
+    fn update(
+        &self,
+        short_url: &str,
+        change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.update(|data| {
+            let stored = data.links.get_mut(short_url).ok_or(AppError::NotFound)?;
+            change(&mut stored.link)?;
+            data.outbox.push(event);
+            Ok(())
+        })
+    }
+}
+
+impl RecordClickRepository for FileRepository {
+    fn record_click(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.update(|data| {
+            let link = data.links.get_mut(short_url).ok_or(AppError::NotFound)?;
+            link.link.take_click()?;
+            link.clicks += 1;
+            if let Some(target) = target {
+                *link.target_clicks.entry(target.to_owned()).or_default() += 1;
+            }
+            data.outbox.push(event);
+            Ok(())
+        })
+    }
+}
+
+impl GetFullUrlRepository for FileRepository {
+    fn get(&self, short_url: &str) -> Result<Link, AppError> {
+        self.read(|data| {
+            data.links
+                .get(short_url)
+                .map(|stored| stored.link.clone())
+                .ok_or(AppError::NotFound)
+        })
+    }
+}
+
+impl GetStatsRepository for FileRepository {
+    fn get_stats(&self, short_url: &str) -> Result<LinkStats, AppError> {
+        self.read(|data| {
+            data.links
+                .get(short_url)
+                .map(|link| LinkStats {
+                    clicks: link.clicks,
+                    targets: link.target_clicks.clone(),
+                })
+                .ok_or(AppError::NotFound)
+        })
+    }
+}
+
+impl ListShortUrlsRepository for FileRepository {
+    fn list(&self) -> Result<Vec<ShortUrlEntry>, AppError> {
+        self.search(&LinkFilter {
+            trash: TrashFilter::Include,
+            ..Default::default()
+        })
+    }
+
+    fn search(&self, filter: &LinkFilter) -> Result<Vec<ShortUrlEntry>, AppError> {
+        self.read(|data| {
+            let entries = data
+                .links
+                .iter()
+                .filter(|(_, link)| filter.matches_link(&link.link))
+                .map(|(id, link)| ShortUrlEntry {
+                    id: id.clone(),
+                    url: link.link.url.clone(),
+                    clicks: link.clicks,
+                    meta: link.link.meta.clone(),
+                    trashed_at: link.link.trashed_at,
+                    failing_since: link.link.failing_since,
+                    disabled_at: link.link.disabled_at,
+                })
+                .collect();
+            Ok(entries)
+        })
+    }
+}
+
+impl ReplicaStore for FileRepository {
+    fn put_replica(&self, short_url: &str, link: Link) -> Result<(), AppError> {
+        self.update(|data| {
+            match data.links.get_mut(short_url) {
+                Some(stored) => stored.link = link,
+                None => {
+                    data.links.insert(
+                        short_url.to_owned(),
+                        StoredLink {
+                            link,
+                            clicks: 0,
+                            target_clicks: BTreeMap::new(),
+                        },
+                    );
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn remove_replica(&self, short_url: &str) -> Result<(), AppError> {
+        self.update(|data| {
+            data.links.remove(short_url);
+            Ok(())
+        })
+    }
+
+    fn version(&self, short_url: &str) -> Result<Option<u64>, AppError> {
+        self.read(|data| Ok(data.versions.get(short_url).copied()))
+    }
+
+    fn set_version(&self, short_url: &str, version: u64) -> Result<(), AppError> {
+        self.update(|data| {
+            data.versions.insert(short_url.to_owned(), version);
+            Ok(())
+        })
+    }
+}
+
+impl EventOutbox for FileRepository {
+    fn pending(&self, limit: usize) -> Result<Vec<LinkEvent>, AppError> {
+        self.read(|data| Ok(data.outbox.iter().take(limit).cloned().collect()))
+    }
+
+    fn acknowledge(&self, event_ids: &[String]) -> Result<(), AppError> {
+        self.update(|data| {
+            data.outbox.retain(|event| !event_ids.contains(&event.id));
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clicks_from_two_processes_are_not_lost() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("links.json");
+        // у каждого экземпляра свой мьютекс, как у отдельных процессов
+        let server = FileRepository::new(&path);
+        let cli = FileRepository::new(&path);
+        server
+            .save(
+                Link::new("https://google.com"),
+                "123".to_owned(),
+                LinkEvent::created("123", "https://google.com"),
+            )
+            .unwrap();
+
+        // when
+        std::thread::scope(|scope| {
+            for repo in [&server, &cli] {
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        repo.record_click("123", None, LinkEvent::clicked("123"))
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        // then
+        assert_eq!(server.get_stats("123").unwrap().clicks, 100);
+        let files = fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(files, 2, "only the data and the lock file are left");
+    }
+
+    #[test]
+    fn saved_url_survives_reopen() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("links.json");
+        let repo = FileRepository::new(&path);
+
+        // when
+        repo.save(
+            Link::new("https://google.com"),
+            "123".to_owned(),
+            LinkEvent::created("123", "https://google.com"),
+        )
+        .unwrap();
+        repo.record_click("123", None, LinkEvent::clicked("123"))
+            .unwrap();
+        let reopened = FileRepository::new(&path);
+
+        // then
+        assert_eq!(reopened.get("123"), Ok(Link::new("https://google.com")));
+        assert_eq!(
+            reopened.get_stats("123"),
+            Ok(LinkStats {
+                clicks: 1,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn missing_file_is_empty_store() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = FileRepository::new(dir.path().join("links.json"));
+
+        // when
+        let result = repo.get("123");
+
+        // then
+        assert_eq!(result, Err(AppError::NotFound));
+        assert_eq!(repo.list(), Ok(vec![]));
+    }
+
+    #[test]
+    fn delete_removes_url_from_file() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = FileRepository::new(dir.path().join("links.json"));
+        repo.save(
+            Link::new("https://google.com"),
+            "123".to_owned(),
+            LinkEvent::created("123", "https://google.com"),
+        )
+        .unwrap();
+
+        // when
+        repo.delete("123", LinkEvent::deleted("123")).unwrap();
+
+        // then
+        assert_eq!(repo.get("123"), Err(AppError::NotFound));
+        assert_eq!(
+            repo.delete("123", LinkEvent::deleted("123")),
+            Err(AppError::NotFound)
+        );
+    }
+
+    #[test]
+    fn events_are_written_with_changes() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("links.json");
+        let repo = FileRepository::new(&path);
+        let created = LinkEvent::created("123", "https://google.com");
+
+        // when
+        repo.save(
+            Link::new("https://google.com"),
+            "123".to_owned(),
+            created.clone(),
+        )
+        .unwrap();
+        let failed = repo.delete("456", LinkEvent::deleted("456"));
+        let reopened = FileRepository::new(&path);
+
+        // then
+        assert_eq!(failed, Err(AppError::NotFound));
+        assert_eq!(reopened.pending(10), Ok(vec![created.clone()]));
+        reopened.acknowledge(&[created.id]).unwrap();
+        assert_eq!(reopened.pending(10), Ok(vec![]));
+        assert_eq!(reopened.get("123"), Ok(Link::new("https://google.com")));
+    }
+
+    #[test]
+    fn health_requires_existing_directory() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let fresh = FileRepository::new(dir.path().join("links.json"));
+        let orphan = FileRepository::new(dir.path().join("missing").join("links.json"));
+
+        // then
+        assert_eq!(fresh.check_health(), Ok(()));
+        assert!(orphan.check_health().is_err());
+    }
+}
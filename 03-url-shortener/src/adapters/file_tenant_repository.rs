@@ -0,0 +1,119 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    adapters::file_repository::check_file,
+    app::{
+        error::AppError,
+        health::HealthCheck,
+        tenant::{Tenant, TenantRepository},
+    },
+};
+
+/// хранилище арендаторов в json файле
+#[derive(Clone)]
+pub struct FileTenantRepository {
+    path: Arc<PathBuf>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl FileTenantRepository {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: Arc::new(path.as_ref().to_path_buf()),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, Tenant>, AppError> {
+        match fs::read(self.path.as_ref()) {
+            Ok(bytes) => {
+                serde_json::from_slice(&bytes).map_err(|e| AppError::Internal(e.to_string()))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(AppError::Internal(e.to_string())),
+        }
+    }
+
+    fn update(
+        &self,
+        f: impl FnOnce(&mut BTreeMap<String, Tenant>) -> Result<(), AppError>,
+    ) -> Result<(), AppError> {
+        let _guard = self.lock.lock().unwrap();
+        let mut tenants = self.load()?;
+        f(&mut tenants)?;
+
+        let bytes =
+            serde_json::to_vec_pretty(&tenants).map_err(|e| AppError::Internal(e.to_string()))?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, bytes).map_err(|e| AppError::Internal(e.to_string()))?;
+        fs::rename(&tmp, self.path.as_ref()).map_err(|e| AppError::Internal(e.to_string()))
+    }
+}
+
+impl HealthCheck for FileTenantRepository {
+    fn check_health(&self) -> Result<(), AppError> {
+        check_file(&self.path)
+    }
+}
+
+impl TenantRepository for FileTenantRepository {
+    fn list(&self) -> Result<Vec<Tenant>, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(self.load()?.into_values().collect())
+    }
+
+    fn get(&self, id: &str) -> Result<Tenant, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        self.load()?.remove(id).ok_or(AppError::NotFound)
+    }
+
+    fn save(&self, tenant: Tenant) -> Result<(), AppError> {
+        self.update(|tenants| {
+            tenants.insert(tenant.id.clone(), tenant);
+            Ok(())
+        })
+    }
+
+    fn delete(&self, id: &str) -> Result<(), AppError> {
+        self.update(|tenants| tenants.remove(id).map(|_| ()).ok_or(AppError::NotFound))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::id_provider::IdStrategy;
+
+    use super::*;
+
+    #[test]
+    fn tenants_survive_reopen() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tenants.json");
+        let tenant = Tenant {
+            id: "team-a".to_owned(),
+            domains: vec!["go.team-a.example".to_owned()],
+            id_strategy: IdStrategy::Alphanumeric { length: 6 },
+            blocklist: vec![],
+            allowlist: None,
+            max_links: Some(100),
+        };
+
+        // when
+        FileTenantRepository::new(&path)
+            .save(tenant.clone())
+            .unwrap();
+        let reopened = FileTenantRepository::new(&path);
+
+        // then
+        assert_eq!(reopened.get("team-a"), Ok(tenant.clone()));
+        assert_eq!(reopened.list(), Ok(vec![tenant]));
+        assert_eq!(reopened.delete("team-b"), Err(AppError::NotFound));
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{
+    adapters::{
+        file_repository::{check_file, lock_file, write_atomic},
+        in_memory_webhook_store::WebhookData,
+    },
+    app::{
+        error::AppError,
+        health::HealthCheck,
+        webhook::{Delivery, Subscription, WebhookStore},
+    },
+};
+
+/// Хранилище вебхуков в json файле.
+///
+/// Как и у [`FileRepository`](super::file_repository::FileRepository), файл
+/// перечитывается при каждой операции и меняется под блокировкой в ОС,
+/// так что события, поставленные в очередь cli, доставит диспетчер сервера.
+pub struct FileWebhookStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileWebhookStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn load(&self) -> Result<WebhookData, AppError> {
+        match fs::read(&self.path) {
+            Ok(bytes) => {
+                serde_json::from_slice(&bytes).map_err(|e| AppError::Internal(e.to_string()))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(WebhookData::default()),
+            Err(e) => Err(AppError::Internal(e.to_string())),
+        }
+    }
+
+    fn read<T>(&self, f: impl FnOnce(&WebhookData) -> T) -> Result<T, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        Ok(f(&self.load()?))
+    }
+
+    fn update<T>(
+        &self,
+        f: impl FnOnce(&mut WebhookData) -> Result<T, AppError>,
+    ) -> Result<T, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        let _file_lock = lock_file(&self.path)?;
+        let mut data = self.load()?;
+        let res = f(&mut data)?;
+
+        let bytes =
+            serde_json::to_vec_pretty(&data).map_err(|e| AppError::Internal(e.to_string()))?;
+        write_atomic(&self.path, &bytes)?;
+        Ok(res)
+    }
+}
+
+impl HealthCheck for FileWebhookStore {
+    fn check_health(&self) -> Result<(), AppError> {
+        check_file(&self.path)
+    }
+}
+
+impl WebhookStore for FileWebhookStore {
+    fn subscriptions(&self) -> Result<Vec<Subscription>, AppError> {
+        self.read(|data| data.subscriptions())
+    }
+
+    fn save_subscription(&self, subscription: Subscription) -> Result<(), AppError> {
+        self.update(|data| {
+            data.save_subscription(subscription);
+            Ok(())
+        })
+    }
+
+    fn delete_subscription(&self, id: &str) -> Result<(), AppError> {
+        self.update(|data| data.delete_subscription(id))
+    }
+
+    fn enqueue(&self, deliveries: Vec<Delivery>) -> Result<(), AppError> {
+        self.update(|data| {
+            data.enqueue(deliveries);
+            Ok(())
+        })
+    }
+
+    fn due(&self, now: u64, limit: usize) -> Result<Vec<Delivery>, AppError> {
+        self.read(|data| data.due(now, limit))
+    }
+
+    fn lease(&self, now: u64, until: u64, limit: usize) -> Result<Vec<Delivery>, AppError> {
+        self.update(|data| Ok(data.lease(now, until, limit)))
+    }
+
+    fn complete(&self, delivery_id: &str) -> Result<(), AppError> {
+        self.update(|data| {
+            data.complete(delivery_id);
+            Ok(())
+        })
+    }
+
+    fn reschedule(&self, delivery: Delivery) -> Result<(), AppError> {
+        self.update(|data| {
+            data.reschedule(delivery);
+            Ok(())
+        })
+    }
+
+    fn dead_letter(&self, delivery: Delivery) -> Result<(), AppError> {
+        self.update(|data| {
+            data.dead_letter(delivery);
+            Ok(())
+        })
+    }
+
+    fn dead_letters(&self) -> Result<Vec<Delivery>, AppError> {
+        self.read(|data| data.dead_letters())
+    }
+
+    fn requeue(&self, delivery_id: &str, now: u64) -> Result<(), AppError> {
+        self.update(|data| data.requeue(delivery_id, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::event::{LinkEvent, LinkEventKind};
+
+    use super::*;
+
+    #[test]
+    fn outbox_survives_reopen() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("webhooks.json");
+        let delivery = Delivery {
+            id: "d1".to_owned(),
+            subscription_id: "s1".to_owned(),
+            url: "http://a.example/hook".to_owned(),
+            event: LinkEvent::new(LinkEventKind::Deleted {
+                short_url: "abc".to_owned(),
+            }),
+            attempts: 0,
+            next_attempt_at: 10,
+            last_error: None,
+        };
+
+        // when
+        FileWebhookStore::new(&path)
+            .enqueue(vec![delivery.clone()])
+            .unwrap();
+        let reopened = FileWebhookStore::new(&path);
+
+        // then
+        assert_eq!(reopened.due(5, 10), Ok(vec![]));
+        assert_eq!(reopened.due(10, 10), Ok(vec![delivery.clone()]));
+
+        reopened.dead_letter(delivery).unwrap();
+        assert_eq!(reopened.due(10, 10), Ok(vec![]));
+        reopened.requeue("d1", 20).unwrap();
+        assert_eq!(reopened.due(20, 10).unwrap()[0].next_attempt_at, 20);
+        assert_eq!(reopened.dead_letters(), Ok(vec![]));
+    }
+
+    #[test]
+    fn leased_delivery_is_given_to_one_dispatcher() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("webhooks.json");
+        let delivery = Delivery {
+            id: "d1".to_owned(),
+            subscription_id: "s1".to_owned(),
+            url: "http://a.example/hook".to_owned(),
+            event: LinkEvent::new(LinkEventKind::Deleted {
+                short_url: "abc".to_owned(),
+            }),
+            attempts: 0,
+            next_attempt_at: 10,
+            last_error: None,
+        };
+        let server = FileWebhookStore::new(&path);
+        let cli = FileWebhookStore::new(&path);
+        server.enqueue(vec![delivery]).unwrap();
+
+        // when
+        let first = server.lease(10, 100, 10).unwrap();
+        let second = cli.lease(50, 150, 10).unwrap();
+        let expired = cli.lease(100, 200, 10).unwrap();
+
+        // then
+        assert_eq!(first.len(), 1);
+        assert_eq!(second, vec![]);
+        assert_eq!(expired.len(), 1);
+    }
+}
@@ -0,0 +1,106 @@
+use sha2::{Digest, Sha256};
+
+/// Кольцо консистентного хэширования.
+///
+/// Каждый узел занимает на кольце несколько виртуальных точек, ключ
+/// принадлежит узлам, чьи точки первыми встречаются по часовой стрелке
+/// от хэша ключа. При добавлении или удалении узла переезжает только
+/// его доля ключей.
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    nodes: Vec<String>,
+    /// точки кольца по возрастанию: хэш и индекс узла в `nodes`
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    /// виртуальных точек на узел, сглаживают неравномерность долей
+    const VNODES: usize = 64;
+
+    pub fn new(nodes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut nodes: Vec<String> = nodes.into_iter().map(Into::into).collect();
+        nodes.sort();
+        nodes.dedup();
+        let mut points: Vec<(u64, usize)> = nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(index, node)| {
+                (0..Self::VNODES).map(move |vnode| (hash(&format!("{node}#{vnode}")), index))
+            })
+            .collect();
+        points.sort_unstable();
+        Self { nodes, points }
+    }
+
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    /// до `n` разных узлов, хранящих ключ, первый из них основной
+    pub fn owners(&self, key: &str, n: usize) -> Vec<&str> {
+        let n = n.min(self.nodes.len());
+        let mut owners: Vec<&str> = Vec::with_capacity(n);
+        if n == 0 {
+            return owners;
+        }
+        let start = self.points.partition_point(|(point, _)| *point < hash(key));
+        for (_, index) in self.points[start..].iter().chain(&self.points[..start]) {
+            let node = self.nodes[*index].as_str();
+            if !owners.contains(&node) {
+                owners.push(node);
+                if owners.len() == n {
+                    break;
+                }
+            }
+        }
+        owners
+    }
+}
+
+fn hash(value: &str) -> u64 {
+    let digest = Sha256::digest(value.as_bytes());
+    u64::from_be_bytes(digest[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owners_are_distinct_and_stable() {
+        // given
+        let ring = HashRing::new(["a:1", "b:1", "c:1"]);
+        let reordered = HashRing::new(["c:1", "a:1", "b:1"]);
+
+        // when
+        let owners = ring.owners("abc123", 2);
+
+        // then
+        assert_eq!(owners.len(), 2);
+        assert_ne!(owners[0], owners[1]);
+        assert_eq!(owners, reordered.owners("abc123", 2));
+        assert_eq!(ring.owners("abc123", 5).len(), 3);
+    }
+
+    #[test]
+    fn new_node_takes_only_its_share() {
+        // given
+        let before = HashRing::new(["a:1", "b:1", "c:1"]);
+        let after = HashRing::new(["a:1", "b:1", "c:1", "d:1"]);
+        let keys: Vec<String> = (0..4000).map(|i| format!("key{i}")).collect();
+
+        // when
+        let moved = keys
+            .iter()
+            .filter(|key| before.owners(key, 1) != after.owners(key, 1))
+            .count();
+        let to_new = keys
+            .iter()
+            .filter(|key| after.owners(key, 1) == ["d:1"])
+            .count();
+
+        // then
+        assert_eq!(moved, to_new);
+        assert!((600..1400).contains(&moved), "moved {moved} of 4000");
+    }
+}
@@ -0,0 +1,487 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::{
+    Method,
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect,
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+use url::{Host, Url};
+
+use crate::app::link_health::{Probe, TargetProber};
+
+/// настройки фоновой проверки целей ссылок
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkCheckConfig {
+    /// пауза между проходами по всем ссылкам
+    pub interval: Duration,
+    /// сколько запросов идёт одновременно
+    pub concurrency: usize,
+    /// пауза между запросами к одному хосту
+    pub host_delay: Duration,
+    pub timeout: Duration,
+    /// отключать ссылки, цель которых не отвечает дольше этого, `None` чтобы не отключать
+    pub disable_after: Option<Duration>,
+    /// проверять цели во внутренних сетях и на самом узле
+    pub allow_private: bool,
+}
+
+/// Проверка целей по http: сначала HEAD, а если он не удался, GET,
+/// потому что часть серверов не умеет HEAD или отвечает на него иначе.
+/// Редиректы проходятся, итог определяет последний ответ.
+///
+/// Цели задают пользователи, поэтому без `allow_private` запросы во внутренние
+/// сети не уходят: адреса имён отбираются после разрешения, а адреса в url
+/// проверяются до запроса и на каждом редиректе.
+#[derive(Clone)]
+pub struct HttpProber {
+    client: reqwest::Client,
+    concurrency: usize,
+    host_delay: Duration,
+    allow_private: bool,
+}
+
+impl HttpProber {
+    /// редиректов на одну проверку, как у reqwest по умолчанию
+    const MAX_REDIRECTS: usize = 10;
+
+    pub fn new(config: &LinkCheckConfig) -> Self {
+        let builder = match config.allow_private {
+            true => {
+                reqwest::Client::builder().redirect(redirect::Policy::limited(Self::MAX_REDIRECTS))
+            }
+            // через прокси имя разрешал бы прокси, а не PublicOnly
+            false => reqwest::Client::builder()
+                .no_proxy()
+                .dns_resolver(PublicOnly)
+                .redirect(redirect::Policy::custom(|attempt| {
+                    if attempt.previous().len() >= Self::MAX_REDIRECTS {
+                        return attempt.error("too many redirects");
+                    }
+                    match check_public(attempt.url()) {
+                        Ok(()) => attempt.follow(),
+                        Err(e) => attempt.error(e),
+                    }
+                })),
+        };
+        let client = builder
+            .timeout(config.timeout)
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION"),
+                " link checker"
+            ))
+            .build()
+            .expect("http client");
+
+        Self {
+            client,
+            concurrency: config.concurrency.max(1),
+            host_delay: config.host_delay,
+            allow_private: config.allow_private,
+        }
+    }
+
+    /// Проверка одной цели. Между HEAD и запасным GET тоже выдерживается
+    /// `host_delay`, место в `permits` на время паузы освобождается.
+    async fn probe(&self, permits: &Semaphore, url: &str) -> Probe {
+        if !self.allow_private
+            && let Err(e) = Url::parse(url)
+                .map_err(|e| e.to_string())
+                .and_then(|url| check_public(&url))
+        {
+            return Probe::Failed(e);
+        }
+        let head = {
+            let _permit = permits.acquire().await.expect("semaphore is never closed");
+            request(&self.client, Method::HEAD, url).await
+        };
+        if let Probe::Status(status) = head
+            && status < 400
+        {
+            return head;
+        }
+        tokio::time::sleep(self.host_delay).await;
+        let _permit = permits.acquire().await.expect("semaphore is never closed");
+        request(&self.client, Method::GET, url).await
+    }
+}
+
+async fn request(client: &reqwest::Client, method: Method, url: &str) -> Probe {
+    // тело ответа не читается, соединение закрывается вместе с ответом
+    match client.request(method, url).send().await {
+        Ok(response) => Probe::Status(response.status().as_u16()),
+        Err(e) => Probe::Failed(e.to_string()),
+    }
+}
+
+impl TargetProber for HttpProber {
+    /// Хосты проверяются параллельно, но запросов сразу не больше `concurrency`.
+    /// Запросы к одному хосту идут по очереди с паузой `host_delay`,
+    /// чтобы не нагружать сайт, на который ведёт много ссылок.
+    async fn probe_all(&self, urls: Vec<String>) -> HashMap<String, Probe> {
+        let mut by_host: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for url in urls {
+            let host = url::Url::parse(&url)
+                .ok()
+                .and_then(|parsed| parsed.host_str().map(str::to_owned))
+                .unwrap_or_default();
+            let queue = by_host.entry(host).or_default();
+            if !queue.contains(&url) {
+                queue.push(url);
+            }
+        }
+
+        let permits = Arc::new(Semaphore::new(self.concurrency));
+        let prober = Arc::new(self.clone());
+        let mut tasks = JoinSet::new();
+        for urls in by_host.into_values() {
+            let prober = prober.clone();
+            let permits = permits.clone();
+            tasks.spawn(async move {
+                let mut probes = Vec::with_capacity(urls.len());
+                for (i, url) in urls.into_iter().enumerate() {
+                    if i > 0 {
+                        tokio::time::sleep(prober.host_delay).await;
+                    }
+                    let result = prober.probe(&permits, &url).await;
+                    probes.push((url, result));
+                }
+                probes
+            });
+        }
+
+        let mut results = HashMap::new();
+        while let Some(probes) = tasks.join_next().await {
+            match probes {
+                Ok(probes) => results.extend(probes),
+                Err(e) => eprintln!("link probe task failed: {e}"),
+            }
+        }
+        results
+    }
+}
+
+/// разрешение имён, отбрасывающее адреса внутренних сетей
+struct PublicOnly;
+
+impl Resolve for PublicOnly {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{host} has no public address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// адрес в url, если он указан явно, не должен вести во внутреннюю сеть
+fn check_public(url: &Url) -> Result<(), String> {
+    let ip = match url.host() {
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+        // имена проверяются после разрешения в PublicOnly
+        _ => return Ok(()),
+    };
+    match is_public(ip) {
+        true => Ok(()),
+        false => Err(format!("{ip} is not a public address")),
+    }
+}
+
+/// Адрес доступен из интернета: не локальный, не из частных и служебных сетей.
+/// Замена нестабильному `IpAddr::is_global`.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8, 100.64.0.0/10, 192.0.0.0/24, 198.18.0.0/15, 240.0.0.0/4
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        // 64:ff9b:1::/48, 100::/64, 2001:db8::/32
+        || (first == 0x64 && ip.segments()[1] == 0xff9b && ip.segments()[2] == 1)
+        || (first == 0x100 && ip.segments()[1..4] == [0, 0, 0])
+        || (first == 0x2001 && ip.segments()[1] == 0xdb8))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        sync::{
+            Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Instant,
+    };
+
+    use axum::{
+        Router,
+        extract::{Path, State},
+        http::{HeaderMap, Method, StatusCode, header},
+        routing::any,
+    };
+
+    use super::*;
+
+    /// запрос к подставному серверу
+    struct Seen {
+        method: Method,
+        host: String,
+        path: String,
+        at: Instant,
+    }
+
+    #[derive(Default)]
+    struct Stand {
+        requests: Mutex<Vec<Seen>>,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    /// Сервер с заранее заданными ответами: `/status/{code}` отвечает `code`,
+    /// `/no-head/{code}` отвечает 405 на HEAD и `code` на GET.
+    async fn serve(stand: Arc<Stand>) -> SocketAddr {
+        async fn scripted(
+            State(stand): State<Arc<Stand>>,
+            method: Method,
+            headers: HeaderMap,
+            Path((kind, code)): Path<(String, u16)>,
+        ) -> StatusCode {
+            let now = stand.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            stand.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            stand.requests.lock().unwrap().push(Seen {
+                method: method.clone(),
+                host: headers[header::HOST].to_str().unwrap().to_owned(),
+                path: format!("/{kind}/{code}"),
+                at: Instant::now(),
+            });
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            stand.in_flight.fetch_sub(1, Ordering::SeqCst);
+            match (kind.as_str(), method) {
+                ("no-head", Method::HEAD) => StatusCode::METHOD_NOT_ALLOWED,
+                _ => StatusCode::from_u16(code).unwrap(),
+            }
+        }
+
+        let router = Router::new()
+            .route("/{kind}/{code}", any(scripted))
+            .with_state(stand);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        addr
+    }
+
+    fn config(concurrency: usize, host_delay: Duration) -> LinkCheckConfig {
+        LinkCheckConfig {
+            interval: Duration::from_secs(60),
+            concurrency,
+            host_delay,
+            timeout: Duration::from_secs(5),
+            disable_after: None,
+            // подставной сервер слушает localhost
+            allow_private: true,
+        }
+    }
+
+    fn prober(concurrency: usize, host_delay: Duration) -> HttpProber {
+        HttpProber::new(&config(concurrency, host_delay))
+    }
+
+    #[tokio::test]
+    async fn head_falls_back_to_get() {
+        // given
+        let stand = Arc::new(Stand::default());
+        let addr = serve(stand.clone()).await;
+        let url = |path: &str| format!("http://{addr}{path}");
+        let urls = vec![
+            url("/status/200"),
+            url("/status/404"),
+            url("/no-head/200"),
+            url("/status/200"),
+            "http://127.0.0.1:1/closed".to_owned(),
+        ];
+
+        // when
+        let probes = prober(4, Duration::ZERO).probe_all(urls).await;
+
+        // then
+        assert_eq!(probes.len(), 4);
+        assert_eq!(probes[&url("/status/200")], Probe::Status(200));
+        assert_eq!(probes[&url("/status/404")], Probe::Status(404));
+        assert_eq!(probes[&url("/no-head/200")], Probe::Status(200));
+        assert!(matches!(
+            probes["http://127.0.0.1:1/closed"],
+            Probe::Failed(_)
+        ));
+        let mut requests: Vec<String> = stand
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|seen| format!("{} {}", seen.method, seen.path))
+            .collect();
+        requests.sort();
+        assert_eq!(
+            requests,
+            vec![
+                "GET /no-head/200",
+                "GET /status/404",
+                "HEAD /no-head/200",
+                "HEAD /status/200",
+                "HEAD /status/404",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_are_spaced_per_host_and_limited() {
+        // given
+        let stand = Arc::new(Stand::default());
+        let addr = serve(stand.clone()).await;
+        // один сервер под двумя именами, для проверки это два разных хоста
+        let hosts = [
+            format!("127.0.0.1:{}", addr.port()),
+            format!("localhost:{}", addr.port()),
+        ];
+        let urls: Vec<String> = hosts
+            .iter()
+            .flat_map(|host| (200..204).map(move |code| format!("http://{host}/status/{code}")))
+            .collect();
+        let delay = Duration::from_millis(50);
+
+        // when
+        let probes = prober(1, delay).probe_all(urls).await;
+
+        // then
+        assert_eq!(probes.len(), 8);
+        assert!(probes.values().all(|probe| !probe.is_failure()));
+        let requests = stand.requests.lock().unwrap();
+        for host in &hosts {
+            let times: Vec<Instant> = requests
+                .iter()
+                .filter(|seen| seen.host == *host)
+                .map(|seen| seen.at)
+                .collect();
+            assert_eq!(times.len(), 4);
+            assert!(times.windows(2).all(|pair| pair[1] - pair[0] >= delay));
+        }
+        assert_eq!(stand.max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn get_after_head_waits_for_host_delay() {
+        // given
+        let stand = Arc::new(Stand::default());
+        let addr = serve(stand.clone()).await;
+        let delay = Duration::from_millis(50);
+
+        // when
+        let probes = prober(1, delay)
+            .probe_all(vec![format!("http://{addr}/no-head/200")])
+            .await;
+
+        // then
+        assert_eq!(probes.values().next(), Some(&Probe::Status(200)));
+        let requests = stand.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].at - requests[0].at >= delay);
+    }
+
+    #[tokio::test]
+    async fn private_targets_are_not_requested_by_default() {
+        // given
+        let stand = Arc::new(Stand::default());
+        let addr = serve(stand.clone()).await;
+        let prober = HttpProber::new(&LinkCheckConfig {
+            allow_private: false,
+            ..config(1, Duration::ZERO)
+        });
+        let urls = vec![
+            format!("http://{addr}/status/200"),
+            format!("http://localhost:{}/status/200", addr.port()),
+            format!("http://[::ffff:127.0.0.1]:{}/status/200", addr.port()),
+        ];
+
+        // when
+        let probes = prober.probe_all(urls).await;
+
+        // then
+        assert_eq!(probes.len(), 3);
+        assert!(
+            probes
+                .values()
+                .all(|probe| matches!(probe, Probe::Failed(_)))
+        );
+        assert!(stand.requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_global_addresses_are_public() {
+        // given
+        let private = [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ];
+        let public = ["93.184.216.34", "1.1.1.1", "2606:4700:4700::1111"];
+
+        // then
+        for ip in private {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in public {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        assert!(check_public(&Url::parse("http://169.254.169.254/latest").unwrap()).is_err());
+        assert!(check_public(&Url::parse("http://example.com/").unwrap()).is_ok());
+    }
+}
@@ -0,0 +1,32 @@
+use std::sync::Mutex;
+
+use crate::app::{
+    audit::{AuditEntry, AuditRecord, AuditTrail},
+    error::AppError,
+    health::HealthCheck,
+};
+
+/// журнал аудита в памяти процесса, пропадает при перезапуске
+#[derive(Default)]
+pub struct InMemoryAuditTrail {
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl HealthCheck for InMemoryAuditTrail {
+    fn check_health(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+impl AuditTrail for InMemoryAuditTrail {
+    fn append(&self, record: AuditRecord) -> Result<AuditEntry, AppError> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = AuditEntry::chain(entries.last(), record);
+        entries.push(entry.clone());
+        Ok(entry)
+    }
+
+    fn entries(&self) -> Result<Vec<AuditEntry>, AppError> {
+        Ok(self.entries.lock().unwrap().clone())
+    }
+}
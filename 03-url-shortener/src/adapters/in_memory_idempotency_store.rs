@@ -0,0 +1,134 @@
+use std::{num::NonZeroUsize, sync::Mutex};
+
+use lru::LruCache;
+
+use crate::app::{
+    error::AppError,
+    idempotency::{IdempotencyStore, Reservation, StoredResponse},
+};
+
+struct Entry {
+    fingerprint: String,
+    expires_at: u64,
+    /// `None`, пока запрос выполняется
+    response: Option<StoredResponse>,
+}
+
+/// Ключи идемпотентности в памяти процесса.
+///
+/// Ключей не больше `capacity`: новый ключ вытесняет самый давно занятый.
+/// Ключи истекают в порядке занятия, поэтому истёкшие выбрасываются
+/// с начала очереди при занятии нового ключа.
+pub struct InMemoryIdempotencyStore {
+    keys: Mutex<LruCache<String, Entry>>,
+}
+
+impl InMemoryIdempotencyStore {
+    pub const DEFAULT_CAPACITY: usize = 10_000;
+
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            keys: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+}
+
+impl Default for InMemoryIdempotencyStore {
+    fn default() -> Self {
+        Self::new(NonZeroUsize::new(Self::DEFAULT_CAPACITY).unwrap())
+    }
+}
+
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        now: u64,
+        expires_at: u64,
+    ) -> Result<Reservation, AppError> {
+        let mut keys = self.keys.lock().unwrap();
+        while keys
+            .peek_lru()
+            .is_some_and(|(_, entry)| entry.expires_at <= now)
+        {
+            keys.pop_lru();
+        }
+        // peek не меняет очередь: повтор не продлевает жизнь ключа
+        if let Some(entry) = keys.peek(key)
+            && entry.expires_at > now
+        {
+            return Ok(if entry.fingerprint != fingerprint {
+                Reservation::Mismatch
+            } else {
+                match &entry.response {
+                    Some(response) => Reservation::Completed(response.clone()),
+                    None => Reservation::InProgress,
+                }
+            });
+        }
+        keys.put(
+            key.to_owned(),
+            Entry {
+                fingerprint: fingerprint.to_owned(),
+                expires_at,
+                response: None,
+            },
+        );
+        Ok(Reservation::Started)
+    }
+
+    fn complete(&self, key: &str, response: StoredResponse) -> Result<(), AppError> {
+        if let Some(entry) = self.keys.lock().unwrap().peek_mut(key) {
+            entry.response = Some(response);
+        }
+        Ok(())
+    }
+
+    fn abandon(&self, key: &str) -> Result<(), AppError> {
+        self.keys.lock().unwrap().pop(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_expires() {
+        // given
+        let store = InMemoryIdempotencyStore::default();
+        store.begin("key", "a", 0, 100).unwrap();
+
+        // when
+        let before = store.begin("key", "b", 99, 199).unwrap();
+        let after = store.begin("key", "b", 100, 200).unwrap();
+
+        // then
+        assert_eq!(before, Reservation::Mismatch);
+        assert_eq!(after, Reservation::Started);
+    }
+
+    #[test]
+    fn oldest_key_is_evicted_when_full() {
+        // given
+        let store = InMemoryIdempotencyStore::new(NonZeroUsize::new(2).unwrap());
+        store.begin("first", "a", 0, 100).unwrap();
+        store.begin("second", "a", 1, 101).unwrap();
+
+        // when
+        store.begin("first", "a", 2, 102).unwrap();
+        store.begin("third", "a", 3, 103).unwrap();
+
+        // then
+        assert_eq!(
+            store.begin("second", "a", 4, 104).unwrap(),
+            Reservation::InProgress
+        );
+        assert_eq!(
+            store.begin("first", "b", 5, 105).unwrap(),
+            Reservation::Started
+        );
+    }
+}
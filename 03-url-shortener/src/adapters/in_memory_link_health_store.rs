@@ -0,0 +1,109 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
+
+use dashmap::DashMap;
+
+use crate::app::{
+    error::AppError,
+    link_health::{LinkHealth, LinkHealthStore},
+};
+
+/// Итоги проверок в памяти узла.
+///
+/// После перезапуска итоги собираются заново, поэтому отсчёт времени
+/// до отключения ссылки тоже начинается заново. Представления арендаторов
+/// делят одну таблицу, ключи в ней как у
+/// [`NamespacedRepository`](crate::adapters::namespaced_repository::NamespacedRepository).
+#[derive(Clone, Default)]
+pub struct InMemoryLinkHealthStore {
+    links: Arc<DashMap<String, BTreeMap<String, LinkHealth>>>,
+    prefix: Arc<str>,
+}
+
+impl InMemoryLinkHealthStore {
+    /// представление той же таблицы для пространства имён арендатора,
+    /// пустое пространство имён — ссылки без арендатора
+    pub fn scoped(&self, namespace: &str) -> Self {
+        let prefix = match namespace {
+            "" => String::new(),
+            ns => format!("{ns}/"),
+        };
+        Self {
+            links: self.links.clone(),
+            prefix: prefix.into(),
+        }
+    }
+
+    fn key(&self, short_url: &str) -> String {
+        format!("{}{short_url}", self.prefix)
+    }
+
+    /// код ссылки из этого пространства имён
+    fn code<'a>(&self, key: &'a str) -> Option<&'a str> {
+        key.strip_prefix(self.prefix.as_ref())
+            .filter(|code| !code.contains('/'))
+    }
+}
+
+impl LinkHealthStore for InMemoryLinkHealthStore {
+    fn get(&self, short_url: &str) -> Result<BTreeMap<String, LinkHealth>, AppError> {
+        Ok(self
+            .links
+            .get(&self.key(short_url))
+            .map(|health| health.clone())
+            .unwrap_or_default())
+    }
+
+    fn put(&self, short_url: &str, health: BTreeMap<String, LinkHealth>) -> Result<(), AppError> {
+        if health.is_empty() {
+            self.links.remove(&self.key(short_url));
+        } else {
+            self.links.insert(self.key(short_url), health);
+        }
+        Ok(())
+    }
+
+    fn retain(&self, short_urls: &HashSet<String>) -> Result<(), AppError> {
+        self.links
+            .retain(|key, _| self.code(key).is_none_or(|code| short_urls.contains(code)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(now: u64) -> BTreeMap<String, LinkHealth> {
+        BTreeMap::from([(
+            "https://a.example".to_owned(),
+            LinkHealth {
+                last_status: Some(200),
+                last_error: None,
+                last_checked: now,
+                consecutive_failures: 0,
+                failing_since: None,
+            },
+        )])
+    }
+
+    #[test]
+    fn namespaces_do_not_see_each_other() {
+        // given
+        let store = InMemoryLinkHealthStore::default();
+        let team_a = store.scoped("team-a");
+        store.put("abc", health(1)).unwrap();
+        team_a.put("abc", health(2)).unwrap();
+        team_a.put("old", health(3)).unwrap();
+
+        // when
+        team_a.retain(&HashSet::from(["abc".to_owned()])).unwrap();
+
+        // then
+        assert_eq!(store.get("abc").unwrap(), health(1));
+        assert_eq!(team_a.get("abc").unwrap(), health(2));
+        assert!(team_a.get("old").unwrap().is_empty());
+    }
+}
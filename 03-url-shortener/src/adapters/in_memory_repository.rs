@@ -1,36 +1,423 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashSet},
+    hash::{BuildHasher, BuildHasherDefault, DefaultHasher},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
 
 use dashmap::DashMap;
 
-use crate::app::{
-    command::create_short_url::CreateShortUrlRepository, query::get_full_url::GetFullUrlRepository,
+use crate::{
+    adapters::cluster_repository::ReplicaStore,
+    app::{
+        command::{
+            create_short_url::CreateShortUrlRepository, delete_short_url::DeleteShortUrlRepository,
+            record_click::RecordClickRepository, update_short_url::UpdateShortUrlRepository,
+        },
+        error::AppError,
+        event::{EventOutbox, LinkEvent},
+        health::HealthCheck,
+        link::Link,
+        query::{
+            get_full_url::GetFullUrlRepository,
+            get_stats::{GetStatsRepository, LinkStats},
+            list_short_urls::{LinkFilter, ListShortUrlsRepository, ShortUrlEntry},
+        },
+    },
 };
 
+/// ссылка вместе со счётчиками, как она попадает в снимок хранилища
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct StoredLink {
+    pub short_url: String,
+    pub link: Link,
+    pub clicks: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub targets: BTreeMap<String, u64>,
+}
+
+/// Очередь неопубликованных событий, разбитая на части по ключу ссылки.
+///
+/// Изменение и запись его события идут под блокировкой одной части, так что
+/// события одной ссылки лежат в порядке изменений, а записи в разные ссылки
+/// не ждут друг друга. Общий номер восстанавливает порядок при чтении.
+pub struct ShardedOutbox {
+    shards: Vec<Mutex<Vec<(u64, LinkEvent)>>>,
+    next: AtomicU64,
+}
+
+impl Default for ShardedOutbox {
+    fn default() -> Self {
+        Self {
+            shards: (0..Self::SHARDS).map(|_| Mutex::default()).collect(),
+            next: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ShardedOutbox {
+    const SHARDS: usize = 16;
+    /// ёмкость части, которую не стоит отдавать обратно
+    const KEPT_CAPACITY: usize = 1024;
+
+    fn shard(&self, key: &str) -> &Mutex<Vec<(u64, LinkEvent)>> {
+        let hash = BuildHasherDefault::<DefaultHasher>::default().hash_one(key);
+        &self.shards[hash as usize % Self::SHARDS]
+    }
+
+    /// изменение ссылки `key` и запись события, если изменение удалось
+    pub fn with_event(
+        &self,
+        key: &str,
+        event: LinkEvent,
+        change: impl FnOnce() -> Result<(), AppError>,
+    ) -> Result<(), AppError> {
+        let mut shard = self.shard(key).lock().unwrap();
+        change()?;
+        shard.push((self.next.fetch_add(1, Ordering::Relaxed), event));
+        Ok(())
+    }
+
+    /// изменение ссылки `key` без события, но в том же порядке с изменениями с событиями
+    pub fn without_event<T>(&self, key: &str, change: impl FnOnce() -> T) -> T {
+        let _shard = self.shard(key).lock().unwrap();
+        change()
+    }
+
+    /// Результат `read` и все события, пока изменения стоят: нужен снимкам,
+    /// где ссылки и очередь должны быть согласованы
+    pub fn freeze<T>(&self, read: impl FnOnce() -> T) -> (T, Vec<LinkEvent>) {
+        let shards: Vec<_> = self.shards.iter().map(|s| s.lock().unwrap()).collect();
+        let value = read();
+        let mut events: Vec<_> = shards.iter().flat_map(|s| s.iter()).collect();
+        events.sort_by_key(|(seq, _)| *seq);
+        (value, events.into_iter().map(|(_, e)| e.clone()).collect())
+    }
+
+    /// события из снимка, порядок сохраняется
+    pub fn restore(&self, events: Vec<LinkEvent>) {
+        for event in events {
+            let key = event.kind.short_url().to_owned();
+            let _ = self.with_event(&key, event, || Ok(()));
+        }
+    }
+
+    pub fn pending(&self, limit: usize) -> Vec<LinkEvent> {
+        // первые `limit` каждой части содержат первые `limit` всей очереди
+        let mut events: Vec<(u64, LinkEvent)> = self
+            .shards
+            .iter()
+            .flat_map(|s| {
+                s.lock()
+                    .unwrap()
+                    .iter()
+                    .take(limit)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        events.sort_by_key(|(seq, _)| *seq);
+        events.into_iter().take(limit).map(|(_, e)| e).collect()
+    }
+
+    pub fn acknowledge(&self, event_ids: &[String]) {
+        let ids: HashSet<&str> = event_ids.iter().map(String::as_str).collect();
+        for shard in &self.shards {
+            let mut shard = shard.lock().unwrap();
+            shard.retain(|(_, event)| !ids.contains(event.id.as_str()));
+            // после всплеска событий очередь не держит память под пустые места
+            if shard.capacity() > Self::KEPT_CAPACITY && shard.len() < shard.capacity() / 4 {
+                let kept = Self::KEPT_CAPACITY.max(shard.len() * 2);
+                shard.shrink_to(kept);
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct InMemoryRepository {
-    store: Arc<DashMap<String, String>>,
+    store: Arc<DashMap<String, Link>>,
+    clicks: Arc<DashMap<String, u64>>,
+    target_clicks: Arc<DashMap<String, BTreeMap<String, u64>>>,
+    outbox: Arc<ShardedOutbox>,
+    /// версии ключей узла кластера
+    versions: Arc<DashMap<String, u64>>,
 }
 
 impl InMemoryRepository {
-    pub fn new(store: Arc<DashMap<String, String>>) -> Self {
-        Self { store }
+    pub fn new(store: Arc<DashMap<String, Link>>) -> Self {
+        Self {
+            store,
+            clicks: Arc::new(DashMap::new()),
+            target_clicks: Arc::new(DashMap::new()),
+            outbox: Arc::default(),
+            versions: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// хранилище, восстановленное из снимка
+    pub fn restore(links: Vec<StoredLink>, outbox: Vec<LinkEvent>) -> Self {
+        let repo = Self::new(Arc::new(DashMap::new()));
+        for stored in links {
+            if stored.clicks > 0 {
+                repo.clicks.insert(stored.short_url.clone(), stored.clicks);
+            }
+            if !stored.targets.is_empty() {
+                repo.target_clicks
+                    .insert(stored.short_url.clone(), stored.targets);
+            }
+            repo.store.insert(stored.short_url, stored.link);
+        }
+        repo.outbox.restore(outbox);
+        repo
+    }
+
+    /// все ссылки со счётчиками и неопубликованные события, согласованные
+    /// между собой: изменения ждут, пока снимок не будет снят
+    pub fn dump(&self) -> (Vec<StoredLink>, Vec<LinkEvent>) {
+        self.outbox.freeze(|| {
+            self.store
+                .iter()
+                .map(|item| StoredLink {
+                    short_url: item.key().clone(),
+                    link: item.value().clone(),
+                    clicks: self.clicks.get(item.key()).map(|c| *c).unwrap_or_default(),
+                    targets: self
+                        .target_clicks
+                        .get(item.key())
+                        .map(|t| t.clone())
+                        .unwrap_or_default(),
+                })
+                .collect()
+        })
+    }
+
+    /// версии ключей узла кластера для снимка
+    pub fn versions(&self) -> BTreeMap<String, u64> {
+        self.versions
+            .iter()
+            .map(|item| (item.key().clone(), *item.value()))
+            .collect()
+    }
+}
+
+/// память процесса всегда доступна
+impl HealthCheck for InMemoryRepository {
+    fn check_health(&self) -> Result<(), AppError> {
+        Ok(())
     }
 }
 
 impl CreateShortUrlRepository for InMemoryRepository {
-    fn save(&self, full_url: String, short_url: String) -> Result<(), String> {
-        self.store.insert(short_url, full_url);
+    fn save(&self, link: Link, short_url: String, event: LinkEvent) -> Result<(), AppError> {
+        self.outbox.with_event(&short_url, event, || {
+            self.store.insert(short_url.clone(), link);
+            Ok(())
+        })
+    }
 
-        Ok(())
+    fn count(&self) -> Result<usize, AppError> {
+        Ok(self.store.len())
+    }
+}
+
+impl UpdateShortUrlRepository for InMemoryRepository {
+    fn update(
+        &self,
+        short_url: &str,
+        change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.outbox.with_event(short_url, event, || {
+            let mut link = self.store.get_mut(short_url).ok_or(AppError::NotFound)?;
+            change(&mut link)
+        })
     }
 }
 
 impl GetFullUrlRepository for InMemoryRepository {
-    fn get(&self, short_url: &str) -> Result<String, String> {
+    fn get(&self, short_url: &str) -> Result<Link, AppError> {
         let res = self.store.get(short_url);
         match res {
-            Some(full_url) => Ok(full_url.clone()),
-            None => Err("Not Found".to_owned()),
+            Some(link) => Ok(link.clone()),
+            None => Err(AppError::NotFound),
         }
     }
 }
+
+impl RecordClickRepository for InMemoryRepository {
+    fn record_click(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.outbox.with_event(short_url, event, || {
+            // блокировка записи в ссылку делает списание атомарным
+            let mut link = self.store.get_mut(short_url).ok_or(AppError::NotFound)?;
+            link.take_click()?;
+            drop(link);
+            *self.clicks.entry(short_url.to_owned()).or_default() += 1;
+            if let Some(target) = target {
+                *self
+                    .target_clicks
+                    .entry(short_url.to_owned())
+                    .or_default()
+                    .entry(target.to_owned())
+                    .or_default() += 1;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl GetStatsRepository for InMemoryRepository {
+    fn get_stats(&self, short_url: &str) -> Result<LinkStats, AppError> {
+        if !self.store.contains_key(short_url) {
+            return Err(AppError::NotFound);
+        }
+        let clicks = self.clicks.get(short_url).map(|c| *c).unwrap_or_default();
+        let targets = self
+            .target_clicks
+            .get(short_url)
+            .map(|t| t.clone())
+            .unwrap_or_default();
+
+        Ok(LinkStats { clicks, targets })
+    }
+}
+
+impl DeleteShortUrlRepository for InMemoryRepository {
+    fn delete_if(
+        &self,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.outbox.with_event(short_url, event, || {
+            // проверка и удаление под одной блокировкой записи
+            let mut check_result = Err(AppError::NotFound);
+            self.store.remove_if(short_url, |_, link| {
+                check_result = check(link);
+                check_result.is_ok()
+            });
+            check_result?;
+            self.clicks.remove(short_url);
+            self.target_clicks.remove(short_url);
+            Ok(())
+        })
+    }
+}
+
+impl InMemoryRepository {
+    fn entry(&self, id: &str, link: &Link) -> ShortUrlEntry {
+        ShortUrlEntry {
+            id: id.to_owned(),
+            url: link.url.clone(),
+            meta: link.meta.clone(),
+            clicks: self.clicks.get(id).map(|c| *c).unwrap_or_default(),
+            trashed_at: link.trashed_at,
+            failing_since: link.failing_since,
+            disabled_at: link.disabled_at,
+        }
+    }
+}
+
+impl ListShortUrlsRepository for InMemoryRepository {
+    fn list(&self) -> Result<Vec<ShortUrlEntry>, AppError> {
+        let entries = self
+            .store
+            .iter()
+            .map(|item| self.entry(item.key(), item.value()))
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn search(&self, filter: &LinkFilter) -> Result<Vec<ShortUrlEntry>, AppError> {
+        let entries = self
+            .store
+            .iter()
+            .filter(|item| filter.matches_link(item.value()))
+            .map(|item| self.entry(item.key(), item.value()))
+            .collect();
+
+        Ok(entries)
+    }
+}
+
+impl ReplicaStore for InMemoryRepository {
+    fn put_replica(&self, short_url: &str, link: Link) -> Result<(), AppError> {
+        self.store.insert(short_url.to_owned(), link);
+        Ok(())
+    }
+
+    fn remove_replica(&self, short_url: &str) -> Result<(), AppError> {
+        self.store.remove(short_url);
+        self.clicks.remove(short_url);
+        self.target_clicks.remove(short_url);
+        Ok(())
+    }
+
+    fn version(&self, short_url: &str) -> Result<Option<u64>, AppError> {
+        Ok(self.versions.get(short_url).map(|version| *version))
+    }
+
+    fn set_version(&self, short_url: &str, version: u64) -> Result<(), AppError> {
+        self.versions.insert(short_url.to_owned(), version);
+        Ok(())
+    }
+}
+
+impl EventOutbox for InMemoryRepository {
+    fn pending(&self, limit: usize) -> Result<Vec<LinkEvent>, AppError> {
+        Ok(self.outbox.pending(limit))
+    }
+
+    fn acknowledge(&self, event_ids: &[String]) -> Result<(), AppError> {
+        self.outbox.acknowledge(event_ids);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outbox_keeps_order_across_shards() {
+        // given
+        let outbox = ShardedOutbox::default();
+        let events: Vec<LinkEvent> = (0..40)
+            .map(|i| LinkEvent::clicked(&format!("link-{}", i % 7)))
+            .collect();
+        for event in &events {
+            let key = event.kind.short_url().to_owned();
+            outbox.with_event(&key, event.clone(), || Ok(())).unwrap();
+        }
+
+        // when
+        let first = outbox.pending(10);
+        outbox.acknowledge(&first.iter().map(|e| e.id.clone()).collect::<Vec<_>>());
+        let rest = outbox.pending(100);
+
+        // then
+        assert_eq!(first, events[..10]);
+        assert_eq!(rest, events[10..]);
+    }
+
+    #[test]
+    fn failed_change_records_no_event() {
+        // given
+        let outbox = ShardedOutbox::default();
+
+        // when
+        let result = outbox.with_event("a", LinkEvent::clicked("a"), || Err(AppError::NotFound));
+
+        // then
+        assert_eq!(result, Err(AppError::NotFound));
+        assert_eq!(outbox.pending(10), vec![]);
+    }
+}
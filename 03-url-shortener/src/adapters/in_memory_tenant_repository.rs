@@ -0,0 +1,51 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+
+use crate::app::{
+    error::AppError,
+    health::HealthCheck,
+    tenant::{Tenant, TenantRepository},
+};
+
+#[derive(Clone, Default)]
+pub struct InMemoryTenantRepository {
+    store: Arc<DashMap<String, Tenant>>,
+}
+
+impl InMemoryTenantRepository {
+    pub fn new(store: Arc<DashMap<String, Tenant>>) -> Self {
+        Self { store }
+    }
+}
+
+impl HealthCheck for InMemoryTenantRepository {
+    fn check_health(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+impl TenantRepository for InMemoryTenantRepository {
+    fn list(&self) -> Result<Vec<Tenant>, AppError> {
+        let mut tenants: Vec<Tenant> = self.store.iter().map(|t| t.value().clone()).collect();
+        tenants.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(tenants)
+    }
+
+    fn get(&self, id: &str) -> Result<Tenant, AppError> {
+        self.store
+            .get(id)
+            .map(|t| t.value().clone())
+            .ok_or(AppError::NotFound)
+    }
+
+    fn save(&self, tenant: Tenant) -> Result<(), AppError> {
+        self.store.insert(tenant.id.clone(), tenant);
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), AppError> {
+        self.store.remove(id).ok_or(AppError::NotFound)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use crate::app::{
+    error::AppError,
+    health::HealthCheck,
+    webhook::{Delivery, Subscription, WebhookStore},
+};
+
+/// подписки, очередь доставок и недоставленные события
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+pub(crate) struct WebhookData {
+    #[serde(default)]
+    subscriptions: BTreeMap<String, Subscription>,
+    #[serde(default)]
+    outbox: BTreeMap<String, Delivery>,
+    #[serde(default)]
+    dead_letters: BTreeMap<String, Delivery>,
+}
+
+impl WebhookData {
+    pub(crate) fn subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions.values().cloned().collect()
+    }
+
+    pub(crate) fn save_subscription(&mut self, subscription: Subscription) {
+        self.subscriptions
+            .insert(subscription.id.clone(), subscription);
+    }
+
+    pub(crate) fn delete_subscription(&mut self, id: &str) -> Result<(), AppError> {
+        self.subscriptions.remove(id).ok_or(AppError::NotFound)?;
+        Ok(())
+    }
+
+    pub(crate) fn enqueue(&mut self, deliveries: Vec<Delivery>) {
+        for delivery in deliveries {
+            self.outbox.insert(delivery.id.clone(), delivery);
+        }
+    }
+
+    pub(crate) fn due(&self, now: u64, limit: usize) -> Vec<Delivery> {
+        let mut due: Vec<Delivery> = self
+            .outbox
+            .values()
+            .filter(|d| d.next_attempt_at <= now)
+            .cloned()
+            .collect();
+        due.sort_by_key(|d| d.next_attempt_at);
+        due.truncate(limit);
+        due
+    }
+
+    pub(crate) fn lease(&mut self, now: u64, until: u64, limit: usize) -> Vec<Delivery> {
+        let due = self.due(now, limit);
+        for delivery in &due {
+            if let Some(queued) = self.outbox.get_mut(&delivery.id) {
+                queued.next_attempt_at = until;
+            }
+        }
+        due
+    }
+
+    pub(crate) fn complete(&mut self, delivery_id: &str) {
+        self.outbox.remove(delivery_id);
+    }
+
+    /// Доставка, удалённая из очереди за время отправки (например,
+    /// повтором из недоставленных в другом процессе), не возвращается.
+    pub(crate) fn reschedule(&mut self, delivery: Delivery) {
+        if let Some(queued) = self.outbox.get_mut(&delivery.id) {
+            *queued = delivery;
+        }
+    }
+
+    pub(crate) fn dead_letter(&mut self, delivery: Delivery) {
+        self.outbox.remove(&delivery.id);
+        self.dead_letters.insert(delivery.id.clone(), delivery);
+    }
+
+    pub(crate) fn dead_letters(&self) -> Vec<Delivery> {
+        self.dead_letters.values().cloned().collect()
+    }
+
+    pub(crate) fn requeue(&mut self, delivery_id: &str, now: u64) -> Result<(), AppError> {
+        let mut delivery = self
+            .dead_letters
+            .remove(delivery_id)
+            .ok_or(AppError::NotFound)?;
+        delivery.attempts = 0;
+        delivery.next_attempt_at = now;
+        self.outbox.insert(delivery.id.clone(), delivery);
+        Ok(())
+    }
+}
+
+/// хранилище вебхуков в памяти процесса, очередь теряется при перезапуске
+#[derive(Default)]
+pub struct InMemoryWebhookStore {
+    data: Mutex<WebhookData>,
+}
+
+impl HealthCheck for InMemoryWebhookStore {
+    fn check_health(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+impl WebhookStore for InMemoryWebhookStore {
+    fn subscriptions(&self) -> Result<Vec<Subscription>, AppError> {
+        Ok(self.data.lock().unwrap().subscriptions())
+    }
+
+    fn save_subscription(&self, subscription: Subscription) -> Result<(), AppError> {
+        self.data.lock().unwrap().save_subscription(subscription);
+        Ok(())
+    }
+
+    fn delete_subscription(&self, id: &str) -> Result<(), AppError> {
+        self.data.lock().unwrap().delete_subscription(id)
+    }
+
+    fn enqueue(&self, deliveries: Vec<Delivery>) -> Result<(), AppError> {
+        self.data.lock().unwrap().enqueue(deliveries);
+        Ok(())
+    }
+
+    fn due(&self, now: u64, limit: usize) -> Result<Vec<Delivery>, AppError> {
+        Ok(self.data.lock().unwrap().due(now, limit))
+    }
+
+    fn lease(&self, now: u64, until: u64, limit: usize) -> Result<Vec<Delivery>, AppError> {
+        Ok(self.data.lock().unwrap().lease(now, until, limit))
+    }
+
+    fn complete(&self, delivery_id: &str) -> Result<(), AppError> {
+        self.data.lock().unwrap().complete(delivery_id);
+        Ok(())
+    }
+
+    fn reschedule(&self, delivery: Delivery) -> Result<(), AppError> {
+        self.data.lock().unwrap().reschedule(delivery);
+        Ok(())
+    }
+
+    fn dead_letter(&self, delivery: Delivery) -> Result<(), AppError> {
+        self.data.lock().unwrap().dead_letter(delivery);
+        Ok(())
+    }
+
+    fn dead_letters(&self) -> Result<Vec<Delivery>, AppError> {
+        Ok(self.data.lock().unwrap().dead_letters())
+    }
+
+    fn requeue(&self, delivery_id: &str, now: u64) -> Result<(), AppError> {
+        self.data.lock().unwrap().requeue(delivery_id, now)
+    }
+}
@@ -1 +1,19 @@
+pub mod cached_repository;
+pub mod cluster_repository;
+pub mod compact_repository;
+pub mod durable_repository;
+pub mod file_audit_trail;
+pub mod file_policy;
+pub mod file_repository;
+pub mod file_tenant_repository;
+pub mod file_webhook_store;
+pub mod hash_ring;
+pub mod http_prober;
+pub mod in_memory_audit_trail;
+pub mod in_memory_idempotency_store;
+pub mod in_memory_link_health_store;
 pub mod in_memory_repository;
+pub mod in_memory_tenant_repository;
+pub mod in_memory_webhook_store;
+pub mod namespaced_repository;
+pub mod webhook_dispatcher;
@@ -0,0 +1,352 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    app::{
+        command::{
+            create_short_url::CreateShortUrlRepository, delete_short_url::DeleteShortUrlRepository,
+            record_click::RecordClickRepository, update_short_url::UpdateShortUrlRepository,
+        },
+        error::AppError,
+        event::LinkEvent,
+        link::Link,
+        query::{
+            get_full_url::GetFullUrlRepository,
+            get_stats::{GetStatsRepository, LinkStats},
+            list_short_urls::{LinkFilter, ListShortUrlsRepository, ShortUrlEntry},
+        },
+    },
+    id_provider::is_reserved,
+};
+
+/// Представление общего хранилища, ограниченное пространством имён арендатора.
+///
+/// Ключи хранятся как `{namespace}/{код}`; пустое пространство имён
+/// соответствует ссылкам без арендатора. Коды с `/` отклоняются в любом
+/// пространстве имён, иначе через пустое можно было бы достать чужую ссылку.
+/// Коды из [`RESERVED_CODES`](crate::id_provider::RESERVED_CODES) заняты
+/// страницами сервиса и не сохраняются. События ссылок помечаются арендатором.
+#[derive(Clone)]
+pub struct NamespacedRepository<R> {
+    inner: R,
+    prefix: Arc<str>,
+    count: Arc<LinkCount>,
+}
+
+/// Число ссылок пространства имён для квоты.
+///
+/// Полный пересчёт идёт через `list` не чаще раза в [`LinkCount::RECOUNT_EVERY`],
+/// между пересчётами число меняется вместе с созданием и удалением ссылок
+/// через это представление.
+#[derive(Default)]
+struct LinkCount {
+    counted: Mutex<Option<(usize, Instant)>>,
+}
+
+impl LinkCount {
+    /// как долго число верно без пересчёта, чтобы учесть изменения других процессов
+    const RECOUNT_EVERY: Duration = Duration::from_secs(60);
+
+    fn get(&self, recount: impl FnOnce() -> Result<usize, AppError>) -> Result<usize, AppError> {
+        if let Some((count, at)) = *self.counted.lock().unwrap()
+            && at.elapsed() < Self::RECOUNT_EVERY
+        {
+            return Ok(count);
+        }
+        let count = recount()?;
+        *self.counted.lock().unwrap() = Some((count, Instant::now()));
+        Ok(count)
+    }
+
+    fn adjust(&self, change: impl FnOnce(usize) -> usize) {
+        if let Some((count, _)) = self.counted.lock().unwrap().as_mut() {
+            *count = change(*count);
+        }
+    }
+}
+
+impl<R> NamespacedRepository<R> {
+    pub fn new(inner: R, namespace: &str) -> Self {
+        let prefix = match namespace {
+            "" => String::new(),
+            ns => format!("{ns}/"),
+        };
+
+        Self {
+            inner,
+            prefix: prefix.into(),
+            count: Arc::default(),
+        }
+    }
+
+    /// событие с арендатором, короткая ссылка в нём остаётся без префикса
+    fn tagged(&self, mut event: LinkEvent) -> LinkEvent {
+        if let Some(tenant) = self.prefix.strip_suffix('/') {
+            event.tenant = Some(tenant.to_owned());
+        }
+        event
+    }
+
+    /// ключ ссылки в общем хранилище, код с `/` ссылкой не бывает
+    fn scoped(&self, short_url: &str) -> Result<String, AppError> {
+        if short_url.contains('/') {
+            return Err(AppError::NotFound);
+        }
+        Ok(format!("{}{short_url}", self.prefix))
+    }
+
+    /// код внутри пространства имён, `None` для чужих ключей
+    fn unscoped<'a>(&self, key: &'a str) -> Option<&'a str> {
+        let code = key.strip_prefix(self.prefix.as_ref())?;
+        (!code.contains('/')).then_some(code)
+    }
+
+    /// записи своего пространства имён с кодами без префикса
+    fn own(&self, entries: Vec<ShortUrlEntry>) -> Vec<ShortUrlEntry> {
+        entries
+            .into_iter()
+            .filter_map(|entry| {
+                let id = self.unscoped(&entry.id)?.to_owned();
+                Some(ShortUrlEntry { id, ..entry })
+            })
+            .collect()
+    }
+}
+
+impl<R> CreateShortUrlRepository for NamespacedRepository<R>
+where
+    R: CreateShortUrlRepository + ListShortUrlsRepository,
+{
+    fn save(&self, link: Link, short_url: String, event: LinkEvent) -> Result<(), AppError> {
+        if is_reserved(&short_url) {
+            return Err(AppError::InvalidInput(format!(
+                "short code {short_url} is reserved"
+            )));
+        }
+        let key = self.scoped(&short_url).map_err(|_| {
+            AppError::InvalidInput(format!("short code {short_url} must not contain '/'"))
+        })?;
+        self.inner.save(link, key, self.tagged(event))?;
+        self.count.adjust(|count| count + 1);
+        Ok(())
+    }
+
+    fn count(&self) -> Result<usize, AppError> {
+        self.count.get(|| Ok(self.list()?.len()))
+    }
+}
+
+impl<R> DeleteShortUrlRepository for NamespacedRepository<R>
+where
+    R: DeleteShortUrlRepository,
+{
+    fn delete_if(
+        &self,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.inner
+            .delete_if(&self.scoped(short_url)?, check, self.tagged(event))?;
+        self.count.adjust(|count| count.saturating_sub(1));
+        Ok(())
+    }
+}
+
+impl<R> UpdateShortUrlRepository for NamespacedRepository<R>
+where
+    R: UpdateShortUrlRepository,
+{
+    fn update(
+        &self,
+        short_url: &str,
+        change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.inner
+            .update(&self.scoped(short_url)?, change, self.tagged(event))
+    }
+}
+
+impl<R> RecordClickRepository for NamespacedRepository<R>
+where
+    R: RecordClickRepository,
+{
+    fn record_click(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+        event: LinkEvent,
+    ) -> Result<(), AppError> {
+        self.inner
+            .record_click(&self.scoped(short_url)?, target, self.tagged(event))
+    }
+}
+
+impl<R> GetFullUrlRepository for NamespacedRepository<R>
+where
+    R: GetFullUrlRepository,
+{
+    fn get(&self, short_url: &str) -> Result<Link, AppError> {
+        self.inner.get(&self.scoped(short_url)?)
+    }
+}
+
+impl<R> GetStatsRepository for NamespacedRepository<R>
+where
+    R: GetStatsRepository,
+{
+    fn get_stats(&self, short_url: &str) -> Result<LinkStats, AppError> {
+        self.inner.get_stats(&self.scoped(short_url)?)
+    }
+}
+
+impl<R> ListShortUrlsRepository for NamespacedRepository<R>
+where
+    R: ListShortUrlsRepository,
+{
+    fn list(&self) -> Result<Vec<ShortUrlEntry>, AppError> {
+        Ok(self.own(self.inner.list()?))
+    }
+
+    fn search(&self, filter: &LinkFilter) -> Result<Vec<ShortUrlEntry>, AppError> {
+        Ok(self.own(self.inner.search(filter)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use crate::{adapters::in_memory_repository::InMemoryRepository, app::event::EventOutbox};
+
+    use super::*;
+
+    #[test]
+    fn same_code_in_different_namespaces() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let shared = InMemoryRepository::new(store.clone());
+        let team_a = NamespacedRepository::new(shared.clone(), "team-a");
+        let team_b = NamespacedRepository::new(shared.clone(), "team-b");
+
+        // when
+        team_a
+            .save(
+                Link::new("https://a.example/docs"),
+                "docs".to_owned(),
+                LinkEvent::created("docs", "https://a.example/docs"),
+            )
+            .unwrap();
+        team_b
+            .save(
+                Link::new("https://b.example/docs"),
+                "docs".to_owned(),
+                LinkEvent::created("docs", "https://b.example/docs"),
+            )
+            .unwrap();
+
+        // then
+        assert_eq!(team_a.get("docs").unwrap().url, "https://a.example/docs");
+        assert_eq!(team_b.get("docs").unwrap().url, "https://b.example/docs");
+        assert!(store.contains_key("team-a/docs"));
+        let tenants: Vec<Option<String>> = shared
+            .pending(10)
+            .unwrap()
+            .into_iter()
+            .map(|event| event.tenant)
+            .collect();
+        assert_eq!(
+            tenants,
+            vec![Some("team-a".to_owned()), Some("team-b".to_owned())]
+        );
+    }
+
+    #[test]
+    fn reserved_codes_are_rejected() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let default = NamespacedRepository::new(InMemoryRepository::new(store.clone()), "");
+
+        // when
+        let result = default.save(
+            Link::new("https://a.example"),
+            "ui".to_owned(),
+            LinkEvent::created("ui", "https://a.example"),
+        );
+
+        // then
+        assert_eq!(
+            result,
+            Err(AppError::InvalidInput(
+                "short code ui is reserved".to_owned()
+            ))
+        );
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn list_and_count_only_own_namespace() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("root".to_owned(), Link::new("https://google.com"));
+        store.insert("team-a/docs".to_owned(), Link::new("https://a.example"));
+        let shared = InMemoryRepository::new(store);
+        let default = NamespacedRepository::new(shared.clone(), "");
+        let team_a = NamespacedRepository::new(shared, "team-a");
+
+        // then
+        let ids = |repo: &NamespacedRepository<InMemoryRepository>| -> Vec<String> {
+            repo.list().unwrap().into_iter().map(|e| e.id).collect()
+        };
+        assert_eq!(ids(&default), vec!["root"]);
+        assert_eq!(ids(&team_a), vec!["docs"]);
+        assert_eq!(team_a.count(), Ok(1));
+        assert_eq!(team_a.get("root"), Err(AppError::NotFound));
+    }
+
+    #[test]
+    fn default_namespace_does_not_reach_tenant_links() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("team-a/docs".to_owned(), Link::new("https://a.example"));
+        let default = NamespacedRepository::new(InMemoryRepository::new(store), "");
+
+        // when
+        let read = default.get("team-a/docs");
+        let saved = default.save(
+            Link::new("https://evil.example"),
+            "team-a/docs".to_owned(),
+            LinkEvent::created("team-a/docs", "https://evil.example"),
+        );
+
+        // then
+        assert_eq!(read, Err(AppError::NotFound));
+        assert!(matches!(saved, Err(AppError::InvalidInput(_))));
+        assert_eq!(default.get_stats("team-a/docs"), Err(AppError::NotFound));
+    }
+
+    #[test]
+    fn count_follows_saves_without_listing() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let team_a = NamespacedRepository::new(InMemoryRepository::new(store.clone()), "team-a");
+        assert_eq!(team_a.count(), Ok(0));
+
+        // when
+        team_a
+            .save(
+                Link::new("https://a.example"),
+                "docs".to_owned(),
+                LinkEvent::created("docs", "https://a.example"),
+            )
+            .unwrap();
+        // ссылка, добавленная в обход представления, видна только после пересчёта
+        store.insert("team-a/other".to_owned(), Link::new("https://a.example"));
+
+        // then
+        assert_eq!(team_a.count(), Ok(1));
+    }
+}
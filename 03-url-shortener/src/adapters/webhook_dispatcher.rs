@@ -0,0 +1,338 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::app::{
+    error::AppError,
+    event::now_millis,
+    webhook::{Delivery, RetryPolicy, WebhookStore},
+};
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const ID_HEADER: &str = "x-webhook-id";
+
+/// сколько доставок отправляется за один проход
+const BATCH: usize = 100;
+/// предел ожидания ответа получателя
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Подпись тела запроса: `sha256=` и hex от HMAC-SHA256 строки `{timestamp}.{body}`.
+///
+/// Метка времени входит в подпись, чтобы получатель мог отбрасывать
+/// перехваченные и повторно отправленные запросы.
+pub fn sign(secret: &str, timestamp: u64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Фоновый диспетчер, отправляющий события из очереди по http.
+///
+/// Ответ 2xx считается доставкой, любой другой ответ или ошибка сети
+/// откладывает повтор по [`RetryPolicy`]. Доставки берутся в аренду на время,
+/// за которое точно успеет уйти вся пачка, поэтому несколько диспетчеров
+/// над одним хранилищем не отправят одно событие дважды. Ключ подписи берётся
+/// из подписки при отправке, доставки подписок, которых уже нет, отбрасываются.
+pub struct WebhookDispatcher {
+    store: Arc<dyn WebhookStore>,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    poll_interval: Duration,
+}
+
+impl WebhookDispatcher {
+    pub fn new(store: Arc<dyn WebhookStore>, retry: RetryPolicy) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .build()
+            .expect("http client");
+
+        Self {
+            store,
+            client,
+            retry,
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// как часто проверять очередь
+    pub fn with_poll_interval(self, poll_interval: Duration) -> Self {
+        Self {
+            poll_interval,
+            ..self
+        }
+    }
+
+    pub async fn run(self) {
+        loop {
+            if let Err(e) = self.dispatch_due().await {
+                eprintln!("webhook dispatch failed: {e}");
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// одна попытка для каждой наступившей доставки, возвращает число успешных
+    pub async fn dispatch_due(&self) -> Result<usize, AppError> {
+        let now = now_millis();
+        let lease = (SEND_TIMEOUT * BATCH as u32).as_millis() as u64;
+        let leased = self.store.lease(now, now + lease, BATCH)?;
+        if leased.is_empty() {
+            return Ok(0);
+        }
+        let secrets: HashMap<String, String> = self
+            .store
+            .subscriptions()?
+            .into_iter()
+            .map(|s| (s.id, s.secret))
+            .collect();
+        let mut delivered = 0;
+        for mut delivery in leased {
+            let Some(secret) = secrets.get(&delivery.subscription_id) else {
+                // от событий отписались, пока они ждали в очереди
+                self.store.complete(&delivery.id)?;
+                continue;
+            };
+            match self.send(&delivery, secret).await {
+                Ok(()) => {
+                    self.store.complete(&delivery.id)?;
+                    delivered += 1;
+                }
+                Err(error) => {
+                    delivery.attempts += 1;
+                    delivery.last_error = Some(error);
+                    if delivery.attempts >= self.retry.max_attempts {
+                        self.store.dead_letter(delivery)?;
+                    } else {
+                        let delay = self.retry.delay(delivery.attempts).as_millis() as u64;
+                        delivery.next_attempt_at = now_millis() + delay;
+                        self.store.reschedule(delivery)?;
+                    }
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    async fn send(&self, delivery: &Delivery, secret: &str) -> Result<(), String> {
+        let body = serde_json::to_vec(&delivery.event).map_err(|e| e.to_string())?;
+        let timestamp = now_millis() / 1000;
+
+        let response = self
+            .client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(ID_HEADER, &delivery.event.id)
+            .header(EVENT_HEADER, delivery.event.kind.name())
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, sign(secret, timestamp, &body))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(format!("receiver responded with {status}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::{Router, body::Bytes, extract::State, http::HeaderMap, routing::post};
+    use reqwest::StatusCode;
+
+    use crate::{
+        adapters::in_memory_webhook_store::InMemoryWebhookStore,
+        app::{
+            event::{EventSubscriber, LinkEvent},
+            webhook::Webhooks,
+        },
+    };
+
+    use super::*;
+
+    /// получатель, отвечающий заданными статусами и запоминающий запросы
+    #[derive(Clone, Default)]
+    struct Receiver {
+        statuses: Arc<Mutex<Vec<StatusCode>>>,
+        received: Arc<Mutex<Vec<(HeaderMap, Bytes)>>>,
+    }
+
+    async fn receive(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> StatusCode {
+        receiver.received.lock().unwrap().push((headers, body));
+        let mut statuses = receiver.statuses.lock().unwrap();
+        if statuses.is_empty() {
+            StatusCode::OK
+        } else {
+            statuses.remove(0)
+        }
+    }
+
+    async fn start_receiver(statuses: Vec<StatusCode>) -> (String, Receiver) {
+        let receiver = Receiver {
+            statuses: Arc::new(Mutex::new(statuses)),
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(receiver.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, receiver)
+    }
+
+    fn setup(
+        url: String,
+        max_attempts: u32,
+    ) -> (Webhooks, WebhookDispatcher, Arc<InMemoryWebhookStore>) {
+        let store = Arc::new(InMemoryWebhookStore::default());
+        let webhooks = Webhooks::new(store.clone());
+        webhooks
+            .subscribe(url, vec![], Some("secret".to_owned()))
+            .unwrap();
+        let retry = RetryPolicy {
+            max_attempts,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        };
+        (
+            webhooks,
+            WebhookDispatcher::new(store.clone(), retry),
+            store,
+        )
+    }
+
+    fn created() -> LinkEvent {
+        LinkEvent::created("abc", "https://google.com")
+    }
+
+    #[tokio::test]
+    async fn delivery_is_signed() {
+        // given
+        let (url, receiver) = start_receiver(vec![]).await;
+        let (webhooks, dispatcher, _) = setup(url, 3);
+        let event = created();
+        webhooks.handle(&event).unwrap();
+
+        // when
+        let delivered = dispatcher.dispatch_due().await.unwrap();
+
+        // then
+        assert_eq!(delivered, 1);
+        let received = receiver.received.lock().unwrap();
+        let (headers, body) = &received[0];
+        let timestamp: u64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(headers[SIGNATURE_HEADER], sign("secret", timestamp, body));
+        assert_eq!(headers[EVENT_HEADER], "link.created");
+        assert_eq!(serde_json::from_slice::<LinkEvent>(body).unwrap(), event);
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_retried() {
+        // given
+        let (url, receiver) = start_receiver(vec![StatusCode::INTERNAL_SERVER_ERROR]).await;
+        let (webhooks, dispatcher, store) = setup(url, 3);
+        webhooks.handle(&created()).unwrap();
+
+        // when
+        let first = dispatcher.dispatch_due().await.unwrap();
+        let queued = store.due(u64::MAX, 10).unwrap();
+        let second = dispatcher.dispatch_due().await.unwrap();
+
+        // then
+        assert_eq!((first, second), (0, 1));
+        assert_eq!(queued[0].attempts, 1);
+        assert_eq!(
+            queued[0].last_error.as_deref(),
+            Some("receiver responded with 500 Internal Server Error")
+        );
+        assert_eq!(receiver.received.lock().unwrap().len(), 2);
+        assert_eq!(store.due(u64::MAX, 10), Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn exhausted_delivery_goes_to_dead_letters() {
+        // given
+        let (url, _receiver) =
+            start_receiver(vec![StatusCode::BAD_GATEWAY, StatusCode::BAD_GATEWAY]).await;
+        let (webhooks, dispatcher, store) = setup(url, 2);
+        webhooks.handle(&created()).unwrap();
+
+        // when
+        dispatcher.dispatch_due().await.unwrap();
+        dispatcher.dispatch_due().await.unwrap();
+
+        // then
+        assert_eq!(store.due(u64::MAX, 10), Ok(vec![]));
+        let dead = webhooks.dead_letters().unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, 2);
+
+        // when
+        webhooks.retry_dead_letter(&dead[0].id).unwrap();
+        let delivered = dispatcher.dispatch_due().await.unwrap();
+
+        // then
+        assert_eq!(delivered, 1);
+        assert_eq!(webhooks.dead_letters(), Ok(vec![]));
+    }
+
+    #[tokio::test]
+    async fn backoff_postpones_next_attempt() {
+        // given
+        let (url, receiver) = start_receiver(vec![StatusCode::SERVICE_UNAVAILABLE]).await;
+        let store = Arc::new(InMemoryWebhookStore::default());
+        let webhooks = Webhooks::new(store.clone());
+        webhooks.subscribe(url, vec![], None).unwrap();
+        let dispatcher = WebhookDispatcher::new(store.clone(), RetryPolicy::default());
+        webhooks.handle(&created()).unwrap();
+
+        // when
+        dispatcher.dispatch_due().await.unwrap();
+        let second = dispatcher.dispatch_due().await.unwrap();
+
+        // then
+        assert_eq!(second, 0);
+        assert_eq!(receiver.received.lock().unwrap().len(), 1);
+        let queued = store.due(u64::MAX, 10).unwrap();
+        assert!(queued[0].next_attempt_at >= now_millis() + 500);
+    }
+
+    #[tokio::test]
+    async fn leased_deliveries_are_sent_once_and_dropped_after_unsubscribe() {
+        // given
+        let (url, receiver) = start_receiver(vec![]).await;
+        let (webhooks, dispatcher, store) = setup(url, 3);
+        let other = WebhookDispatcher::new(store.clone(), RetryPolicy::default());
+        webhooks.handle(&created()).unwrap();
+        let leased = store.lease(now_millis(), u64::MAX, 10).unwrap();
+
+        // when
+        let while_leased = other.dispatch_due().await.unwrap();
+        store.reschedule(leased[0].clone()).unwrap();
+        let subscription = webhooks.subscriptions().unwrap().remove(0);
+        webhooks.unsubscribe(&subscription.id).unwrap();
+        let after_unsubscribe = dispatcher.dispatch_due().await.unwrap();
+
+        // then
+        assert_eq!((while_leased, after_unsubscribe), (0, 0));
+        assert!(receiver.received.lock().unwrap().is_empty());
+        assert_eq!(store.due(u64::MAX, 10), Ok(vec![]));
+        assert_eq!(webhooks.dead_letters(), Ok(vec![]));
+    }
+}
@@ -0,0 +1,396 @@
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::app::{
+    error::AppError,
+    event::{EventSubscriber, LinkEvent, RecentIds, now_millis},
+    health::HealthCheck,
+    link::Link,
+};
+
+/// хэш, на который ссылается первая запись журнала
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Кто, откуда и что сделал с объектом, значения до и после изменения.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// время в миллисекундах unix
+    pub at: u64,
+    pub actor: String,
+    /// отпечаток ключа api, сам ключ в журнал не попадает
+    #[serde(default)]
+    pub api_key: Option<String>,
+    pub ip: String,
+    /// действие вида `link.update`
+    pub action: String,
+    /// вид объекта: `link`, `tenant`, `webhook`
+    pub resource: String,
+    /// id объекта
+    pub id: String,
+    #[serde(default)]
+    pub before: Option<Value>,
+    #[serde(default)]
+    pub after: Option<Value>,
+    /// событие, вместе с которым запись попала в очередь хранилища
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(actor: &str, api_key: Option<String>, ip: &str, action: &str, id: &str) -> Self {
+        let resource = action.split('.').next().unwrap_or_default();
+        Self {
+            at: now_millis(),
+            actor: actor.to_owned(),
+            api_key,
+            ip: ip.to_owned(),
+            action: action.to_owned(),
+            resource: resource.to_owned(),
+            id: id.to_owned(),
+            before: None,
+            after: None,
+            event: None,
+        }
+    }
+
+    pub fn with_before(mut self, before: &impl Serialize) -> Self {
+        self.before = serde_json::to_value(before).ok();
+        self
+    }
+
+    pub fn with_after(mut self, after: &impl Serialize) -> Self {
+        self.after = serde_json::to_value(after).ok();
+        self
+    }
+}
+
+/// Кто выполняет изменение: имя, отпечаток ключа api и адрес.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    pub name: String,
+    pub api_key: Option<String>,
+    pub ip: String,
+}
+
+impl Actor {
+    pub fn new(name: impl Into<String>, api_key: Option<String>, ip: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            api_key,
+            ip: ip.into(),
+        }
+    }
+
+    /// фоновые задачи самого сервиса
+    pub fn system() -> Self {
+        Self::new("system", None, "local")
+    }
+
+    /// заготовка записи о действии `action` над объектом `id`
+    pub fn record(&self, action: &str, id: &str) -> AuditRecord {
+        AuditRecord::new(&self.name, self.api_key.clone(), &self.ip, action, id)
+    }
+
+    /// запись об изменении ссылки со значениями до и после
+    pub fn link_record(
+        &self,
+        action: &str,
+        id: &str,
+        before: Option<&Link>,
+        after: Option<&Link>,
+    ) -> AuditRecord {
+        let mut record = self.record(action, id);
+        record.before = before.map(|link| link_view(id, link));
+        record.after = after.map(|link| link_view(id, link));
+        record
+    }
+}
+
+/// ссылка для журнала: все настройки, но вместо хэша пароля только его наличие
+fn link_view(id: &str, link: &Link) -> Value {
+    let mut view = serde_json::to_value(link).unwrap_or_default();
+    if let Value::Object(fields) = &mut view {
+        fields.remove("password_hash");
+        fields.insert("id".to_owned(), id.into());
+        fields.insert("protected".to_owned(), link.is_protected().into());
+    }
+    view
+}
+
+/// Запись журнала, сцепленная с предыдущей.
+///
+/// `hash` считается от номера, хэша предыдущей записи и самой записи,
+/// поэтому правка, вставка или удаление записи в середине журнала
+/// ломают цепочку во всех последующих записях.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// номер записи, начиная с 1
+    pub seq: u64,
+    pub prev_hash: String,
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// запись, следующая за `prev`
+    pub fn chain(prev: Option<&AuditEntry>, record: AuditRecord) -> Self {
+        let (seq, prev_hash) = match prev {
+            Some(prev) => (prev.seq + 1, prev.hash.clone()),
+            None => (1, GENESIS_HASH.to_owned()),
+        };
+        let hash = chain_hash(seq, &prev_hash, &record);
+        Self {
+            seq,
+            prev_hash,
+            record,
+            hash,
+        }
+    }
+}
+
+fn chain_hash(seq: u64, prev_hash: &str, record: &AuditRecord) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(seq.to_be_bytes());
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(serde_json::to_vec(record).unwrap_or_default());
+    hex::encode(hasher.finalize())
+}
+
+/// отбор записей для админского api
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    /// записи об этой короткой ссылке
+    #[serde(default)]
+    pub link: Option<String>,
+    #[serde(default)]
+    pub actor: Option<String>,
+    /// не больше стольких последних записей
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        let record = &entry.record;
+        self.link
+            .as_ref()
+            .is_none_or(|link| record.resource == "link" && record.id == *link)
+            && self
+                .actor
+                .as_ref()
+                .is_none_or(|actor| record.actor == *actor)
+    }
+}
+
+/// итог проверки цепочки
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChainReport {
+    pub entries: u64,
+    /// хэш последней записи, его стоит хранить отдельно от журнала:
+    /// по нему видно, если кто-то отрезал конец
+    pub last_hash: String,
+}
+
+/// Проверка номеров, ссылок на предыдущие записи и хэшей.
+/// Ошибка называет первую запись, где цепочка нарушена.
+pub fn verify_chain(entries: &[AuditEntry]) -> Result<ChainReport, AppError> {
+    let mut prev_hash = GENESIS_HASH;
+    for (index, entry) in entries.iter().enumerate() {
+        let expected = index as u64 + 1;
+        let broken = if entry.seq != expected {
+            Some("out of sequence")
+        } else if entry.prev_hash != prev_hash {
+            Some("does not follow the previous entry")
+        } else if entry.hash != chain_hash(entry.seq, &entry.prev_hash, &entry.record) {
+            Some("was modified")
+        } else {
+            None
+        };
+        if let Some(reason) = broken {
+            return Err(AppError::Internal(format!(
+                "audit entry {expected} {reason}"
+            )));
+        }
+        prev_hash = &entry.hash;
+    }
+    Ok(ChainReport {
+        entries: entries.len() as u64,
+        last_hash: prev_hash.to_owned(),
+    })
+}
+
+/// Порт журнала аудита: только дописывание и чтение.
+pub trait AuditTrail: HealthCheck + Send + Sync {
+    /// дописать запись в конец цепочки
+    fn append(&self, record: AuditRecord) -> Result<AuditEntry, AppError>;
+    /// все записи по порядку
+    fn entries(&self) -> Result<Vec<AuditEntry>, AppError>;
+
+    /// подходящие записи, новые в конце
+    fn query(&self, filter: &AuditFilter) -> Result<Vec<AuditEntry>, AppError> {
+        let mut found: Vec<AuditEntry> = self
+            .entries()?
+            .into_iter()
+            .filter(|entry| filter.matches(entry))
+            .collect();
+        if let Some(limit) = filter.limit {
+            found.drain(..found.len().saturating_sub(limit));
+        }
+        Ok(found)
+    }
+
+    fn verify(&self) -> Result<ChainReport, AppError> {
+        verify_chain(&self.entries()?)
+    }
+}
+
+/// Подписчик шины событий, переносящий в журнал записи аудита из событий.
+///
+/// Запись об изменении ссылки лежит в событии и сохраняется в очереди
+/// хранилища вместе с изменением, поэтому изменение без записи не случится,
+/// а журнал её не потеряет. Повторы одного события отбрасываются по id среди
+/// последних [`AuditRecorder::REMEMBERED`] записей журнала.
+pub struct AuditRecorder {
+    trail: Arc<dyn AuditTrail>,
+    /// id последних перенесённых событий, `None` пока журнал не прочитан
+    seen: Mutex<Option<RecentIds>>,
+}
+
+impl AuditRecorder {
+    /// больше, чем событий в одной пачке ретранслятора очереди
+    const REMEMBERED: usize = 1024;
+
+    pub fn new(trail: Arc<dyn AuditTrail>) -> Self {
+        Self {
+            trail,
+            seen: Mutex::new(None),
+        }
+    }
+}
+
+impl EventSubscriber for AuditRecorder {
+    fn handle(&self, event: &LinkEvent) -> Result<(), AppError> {
+        let Some(record) = &event.audit else {
+            return Ok(());
+        };
+        let mut seen = self.seen.lock().unwrap();
+        if seen.is_none() {
+            let entries = self.trail.entries()?;
+            let mut recent = RecentIds::new(Self::REMEMBERED);
+            let start = entries.len().saturating_sub(Self::REMEMBERED);
+            for id in entries[start..]
+                .iter()
+                .filter_map(|e| e.record.event.clone())
+            {
+                recent.insert(id);
+            }
+            *seen = Some(recent);
+        }
+        let seen = seen.as_mut().expect("loaded above");
+        if seen.contains(&event.id) {
+            return Ok(());
+        }
+        let mut record = (**record).clone();
+        record.event = Some(event.id.clone());
+        self.trail.append(record)?;
+        seen.insert(event.id.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapters::in_memory_audit_trail::InMemoryAuditTrail;
+
+    use super::*;
+
+    fn chain(actions: &[&str]) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> = Vec::new();
+        for action in actions {
+            let record = AuditRecord::new("admin", None, "127.0.0.1", action, "abc")
+                .with_after(&serde_json::json!({"url": "https://a.example"}));
+            entries.push(AuditEntry::chain(entries.last(), record));
+        }
+        entries
+    }
+
+    #[test]
+    fn intact_chain_is_verified() {
+        // given
+        let entries = chain(&["link.create", "link.update", "link.delete"]);
+
+        // when
+        let report = verify_chain(&entries).unwrap();
+
+        // then
+        assert_eq!(report.entries, 3);
+        assert_eq!(report.last_hash, entries[2].hash);
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[0].record.resource, "link");
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        // given
+        let entries = chain(&["link.create", "link.update", "link.delete"]);
+        let mut edited = entries.clone();
+        edited[1].record.actor = "someone-else".to_owned();
+        let mut removed = entries.clone();
+        removed.remove(1);
+        let mut rehashed = entries.clone();
+        rehashed[1] = AuditEntry::chain(
+            Some(&entries[0]),
+            AuditRecord::new("admin", None, "127.0.0.1", "link.update", "xyz"),
+        );
+
+        // when
+        let results = [edited, removed, rehashed].map(|entries| verify_chain(&entries));
+
+        // then
+        assert_eq!(
+            results,
+            [
+                Err(AppError::Internal("audit entry 2 was modified".to_owned())),
+                Err(AppError::Internal(
+                    "audit entry 2 out of sequence".to_owned()
+                )),
+                Err(AppError::Internal(
+                    "audit entry 3 does not follow the previous entry".to_owned()
+                )),
+            ]
+        );
+    }
+
+    #[test]
+    fn recorder_skips_events_without_record_and_repeats() {
+        // given
+        let trail = Arc::new(InMemoryAuditTrail::default());
+        let actor = Actor::new("alice", None, "10.0.0.1");
+        let link = Link::protected("https://a.example", "secret").unwrap();
+        let event = LinkEvent::created("abc", "https://a.example").with_audit(actor.link_record(
+            "link.create",
+            "abc",
+            None,
+            Some(&link),
+        ));
+        let recorder = AuditRecorder::new(trail.clone());
+        recorder.handle(&event).unwrap();
+
+        // when
+        AuditRecorder::new(trail.clone()).handle(&event).unwrap();
+        recorder.handle(&LinkEvent::clicked("abc")).unwrap();
+
+        // then
+        let entries = trail.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].record.event, Some(event.id));
+        assert_eq!(entries[0].record.actor, "alice");
+        let after = entries[0].record.after.as_ref().unwrap();
+        assert_eq!(after["protected"], true);
+        assert_eq!(after.get("password_hash"), None);
+    }
+}
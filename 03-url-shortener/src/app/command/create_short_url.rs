@@ -1,7 +1,21 @@
-use crate::id_provider::IDProvider;
+use std::sync::Arc;
+
+use crate::{
+    app::{
+        audit::Actor,
+        error::AppError,
+        event::LinkEvent,
+        link::{Link, LinkOptions},
+        policy::{AllowAll, TargetPolicy},
+    },
+    id_provider::IDProvider,
+};
 
 pub trait CreateShortUrlRepository {
-    fn save(&self, full_url: String, short_url: String) -> Result<(), String>;
+    /// сохранение ссылки вместе с записью события о создании
+    fn save(&self, link: Link, short_url: String, event: LinkEvent) -> Result<(), AppError>;
+    /// число сохранённых ссылок
+    fn count(&self) -> Result<usize, AppError>;
 }
 
 pub struct CreateShortUrlCommand<I, R>
@@ -11,6 +25,8 @@ where
 {
     id_provider: I,
     repo: R,
+    policy: Arc<dyn TargetPolicy>,
+    max_links: Option<usize>,
 }
 
 impl<I, R> CreateShortUrlCommand<I, R>
@@ -19,12 +35,86 @@ where
     R: CreateShortUrlRepository,
 {
     pub fn new(id_provider: I, repo: R) -> Self {
-        Self { id_provider, repo }
+        Self {
+            id_provider,
+            repo,
+            policy: Arc::new(AllowAll),
+            max_links: None,
+        }
     }
 
-    pub async fn execute(&self, full_url: String) -> Result<String, String> {
+    /// проверять цели ссылок заданной политикой
+    pub fn with_policy(self, policy: Arc<dyn TargetPolicy>) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Ограничить число ссылок в хранилище.
+    ///
+    /// Квота мягкая: параллельные создания могут превысить её на несколько ссылок.
+    pub fn with_quota(self, max_links: Option<usize>) -> Self {
+        Self { max_links, ..self }
+    }
+
+    pub async fn execute(&self, full_url: String, actor: &Actor) -> Result<String, AppError> {
+        self.execute_with_options(full_url, None, LinkOptions::default(), actor)
+            .await
+    }
+
+    /// создание ссылки, защищённой паролем
+    pub async fn execute_protected(
+        &self,
+        full_url: String,
+        password: String,
+        actor: &Actor,
+    ) -> Result<String, AppError> {
+        self.execute_with_options(full_url, Some(password), LinkOptions::default(), actor)
+            .await
+    }
+
+    /// создание ссылки с настройками и, если задан пароль, защищённой им
+    pub async fn execute_with_options(
+        &self,
+        full_url: String,
+        password: Option<String>,
+        mut options: LinkOptions,
+        actor: &Actor,
+    ) -> Result<String, AppError> {
+        self.admit(&full_url, &mut options)?;
+        let link = match password {
+            Some(password) => {
+                tokio::task::spawn_blocking(move || Link::protected(full_url, &password))
+                    .await
+                    .map_err(|e| AppError::Internal(e.to_string()))??
+            }
+            None => Link::new(full_url),
+        };
+        self.save(link.with_options(options), actor)
+    }
+
+    /// проверка целей, политики и квоты до создания ссылки
+    fn admit(&self, full_url: &str, options: &mut LinkOptions) -> Result<(), AppError> {
+        options.validate()?;
+        self.policy.check(full_url)?;
+        for destination in options.destinations() {
+            self.policy.check(destination)?;
+        }
+        if let Some(max_links) = self.max_links
+            && self.repo.count()? >= max_links
+        {
+            return Err(AppError::QuotaExceeded);
+        }
+        Ok(())
+    }
+
+    fn save(&self, link: Link, actor: &Actor) -> Result<String, AppError> {
         let id = self.id_provider.provide();
-        self.repo.save(full_url, id.clone())?;
+        let event = LinkEvent::created(&id, &link.url).with_audit(actor.link_record(
+            "link.create",
+            &id,
+            None,
+            Some(&link),
+        ));
+        self.repo.save(link, id.clone(), event)?;
         Ok(id)
     }
 }
@@ -37,6 +127,7 @@ mod tests {
 
     use crate::{
         adapters::in_memory_repository::InMemoryRepository,
+        app::policy::{HostList, HostPolicy},
         id_provider::{FakeIDProvider, NanoIdProvider},
     };
 
@@ -46,12 +137,12 @@ mod tests {
     async fn get_short_url() {
         // Given
         let idp = FakeIDProvider::new("123".to_owned());
-        let store: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
         let repo = InMemoryRepository::new(store);
         let command = CreateShortUrlCommand::new(idp, repo);
 
         // when
-        let result = command.execute("test".to_owned()).await;
+        let result = command.execute("test".to_owned(), &Actor::system()).await;
 
         // then
         assert_ne!(result, Ok("".to_owned()))
@@ -61,13 +152,13 @@ mod tests {
     async fn get_two_diferent_short_url() {
         // Given
         let idp = NanoIdProvider;
-        let store: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
         let repo = InMemoryRepository::new(store);
         let command = CreateShortUrlCommand::new(idp, repo);
 
         // when
-        let result1 = command.execute("test".to_owned()).await;
-        let result2 = command.execute("test".to_owned()).await;
+        let result1 = command.execute("test".to_owned(), &Actor::system()).await;
+        let result2 = command.execute("test".to_owned(), &Actor::system()).await;
 
         // then
         assert_ne!(result1, result2)
@@ -76,16 +167,84 @@ mod tests {
     #[tokio::test]
     async fn after_save_store_should_have_one_item() {
         let idp = NanoIdProvider;
-        let store: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let repo = InMemoryRepository::new(store.clone());
+        let command = CreateShortUrlCommand::new(idp, repo);
+
+        // when
+        let short_url = command
+            .execute("test".to_owned(), &Actor::system())
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(store.len(), 1);
+        let link = store.get(&short_url).unwrap();
+        assert_eq!(link.url, "test");
+    }
+
+    #[tokio::test]
+    async fn protected_url_is_saved_with_password_hash() {
+        let idp = FakeIDProvider::new("123".to_owned());
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
         let repo = InMemoryRepository::new(store.clone());
         let command = CreateShortUrlCommand::new(idp, repo);
 
         // when
-        let short_url = command.execute("test".to_owned()).await.unwrap();
+        command
+            .execute_protected("test".to_owned(), "secret".to_owned(), &Actor::system())
+            .await
+            .unwrap();
+
+        // then
+        let link = store.get("123").unwrap();
+        assert_eq!(link.url, "test");
+        assert_eq!(link.verify_password("secret"), Ok(true));
+    }
+
+    #[tokio::test]
+    async fn rejected_target_is_not_saved() {
+        // given
+        let idp = FakeIDProvider::new("123".to_owned());
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let repo = InMemoryRepository::new(store.clone());
+        let policy = HostPolicy {
+            blocklist: HostList::parse("evil.com"),
+            ..Default::default()
+        };
+        let command = CreateShortUrlCommand::new(idp, repo).with_policy(Arc::new(policy));
+
+        // when
+        let result = command
+            .execute("https://evil.com".to_owned(), &Actor::system())
+            .await;
+
+        // then
+        assert_eq!(
+            result,
+            Err(AppError::TargetRejected("evil.com is blocked".to_owned()))
+        );
+        assert!(store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn quota_limits_number_of_links() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let repo = InMemoryRepository::new(store.clone());
+        let command = CreateShortUrlCommand::new(NanoIdProvider, repo).with_quota(Some(1));
+
+        // when
+        let first = command
+            .execute("https://google.com".to_owned(), &Actor::system())
+            .await;
+        let second = command
+            .execute("https://github.com".to_owned(), &Actor::system())
+            .await;
 
         // then
+        assert!(first.is_ok());
+        assert_eq!(second, Err(AppError::QuotaExceeded));
         assert_eq!(store.len(), 1);
-        let full_url = store.get(&short_url).unwrap();
-        assert_eq!(full_url.value(), "test");
     }
 }
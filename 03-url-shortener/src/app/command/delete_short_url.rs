@@ -0,0 +1,217 @@
+use crate::app::{
+    audit::Actor,
+    command::update_short_url::{UpdateShortUrlRepository, update_audited},
+    error::AppError,
+    event::{LinkEvent, now_millis},
+    link::Link,
+    query::get_full_url::GetFullUrlRepository,
+};
+
+pub trait DeleteShortUrlRepository {
+    /// Удаление ссылки вместе с записью события, если `check` её пропускает.
+    /// `NotFound` если ссылки нет, ошибка `check` возвращается как есть.
+    fn delete_if(
+        &self,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError>;
+
+    fn delete(&self, short_url: &str, event: LinkEvent) -> Result<(), AppError> {
+        self.delete_if(short_url, &|_| Ok(()), event)
+    }
+}
+
+/// Удаление короткой ссылки в корзину, восстановление и окончательное удаление.
+///
+/// Ссылка в корзине хранится со всеми настройками и переходами,
+/// но переход по ней отвечает 410, а изменить её нельзя.
+pub struct DeleteShortUrlCommand<R>
+where
+    R: DeleteShortUrlRepository + UpdateShortUrlRepository + GetFullUrlRepository,
+{
+    repo: R,
+}
+
+impl<R> DeleteShortUrlCommand<R>
+where
+    R: DeleteShortUrlRepository + UpdateShortUrlRepository + GetFullUrlRepository,
+{
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    /// перенос в корзину, `NotFound` если ссылки нет или она уже там
+    pub async fn execute(&self, short_url: &str, actor: &Actor) -> Result<(), AppError> {
+        let now = now_millis();
+        let change = |link: &mut Link| {
+            if link.is_trashed() {
+                return Err(AppError::NotFound);
+            }
+            link.trashed_at = Some(now);
+            Ok(())
+        };
+        update_audited(&self.repo, actor, "link.delete", short_url, &change, |_| {
+            LinkEvent::trashed(short_url)
+        })
+    }
+
+    /// возврат ссылки из корзины
+    pub async fn restore(&self, short_url: &str, actor: &Actor) -> Result<(), AppError> {
+        let change = |link: &mut Link| {
+            check_trashed(link)?;
+            link.trashed_at = None;
+            Ok(())
+        };
+        update_audited(
+            &self.repo,
+            actor,
+            "link.restore",
+            short_url,
+            &change,
+            |_| LinkEvent::restored(short_url),
+        )
+    }
+
+    /// окончательное удаление ссылки из корзины
+    pub async fn purge(&self, short_url: &str, actor: &Actor) -> Result<(), AppError> {
+        self.delete_audited(actor, short_url, &check_trashed)
+    }
+
+    /// Окончательное удаление, если ссылка лежит в корзине с `before` (мс unix)
+    /// или раньше. Ссылку, которую успели восстановить, проверка не пропустит.
+    pub async fn purge_trashed_before(&self, short_url: &str, before: u64) -> Result<(), AppError> {
+        let check = |link: &Link| match link.trashed_at {
+            Some(at) if at <= before => Ok(()),
+            _ => Err(AppError::InvalidInput(format!(
+                "link was not in trash before {before}"
+            ))),
+        };
+        self.delete_audited(&Actor::system(), short_url, &check)
+    }
+
+    /// удаление с записью аудита в том же событии, как [`update_audited`]
+    fn delete_audited(
+        &self,
+        actor: &Actor,
+        short_url: &str,
+        check: &dyn Fn(&Link) -> Result<(), AppError>,
+    ) -> Result<(), AppError> {
+        for _ in 0..ATTEMPTS {
+            let before = self.repo.get(short_url)?;
+            check(&before)?;
+            let record = actor.link_record("link.purge", short_url, Some(&before), None);
+            let unchanged = |link: &Link| {
+                if *link != before {
+                    return Err(AppError::Conflict);
+                }
+                check(link)
+            };
+            let event = LinkEvent::deleted(short_url).with_audit(record);
+            match self.repo.delete_if(short_url, &unchanged, event) {
+                Err(AppError::Conflict) => continue,
+                result => return result,
+            }
+        }
+        Err(AppError::Conflict)
+    }
+}
+
+/// попыток удаления, если ссылку меняют одновременно
+const ATTEMPTS: usize = 5;
+
+fn check_trashed(link: &Link) -> Result<(), AppError> {
+    match link.is_trashed() {
+        true => Ok(()),
+        false => Err(AppError::InvalidInput("link is not in trash".to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use dashmap::DashMap;
+
+    use crate::adapters::in_memory_repository::InMemoryRepository;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn delete_moves_url_to_trash() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let repo = InMemoryRepository::new(store.clone());
+        let command = DeleteShortUrlCommand::new(repo);
+
+        // when
+        let result = command.execute("123", &Actor::system()).await;
+        let again = command.execute("123", &Actor::system()).await;
+
+        // then
+        assert_eq!(result, Ok(()));
+        assert_eq!(again, Err(AppError::NotFound));
+        let link = store.get("123").unwrap();
+        assert!(link.is_trashed());
+        assert_eq!(link.check_available(), Err(AppError::Gone(None)));
+    }
+
+    #[tokio::test]
+    async fn delete_unknown_url() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let repo = InMemoryRepository::new(store);
+        let command = DeleteShortUrlCommand::new(repo);
+
+        // when
+        let result = command.execute("123", &Actor::system()).await;
+
+        // then
+        assert_eq!(result, Err(AppError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn restore_and_purge_need_a_trashed_url() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        store.insert("456".to_owned(), Link::new("https://github.com"));
+        let command = DeleteShortUrlCommand::new(InMemoryRepository::new(store.clone()));
+        command.execute("123", &Actor::system()).await.unwrap();
+        command.execute("456", &Actor::system()).await.unwrap();
+
+        // when
+        let restored = command.restore("123", &Actor::system()).await;
+        let restored_again = command.restore("123", &Actor::system()).await;
+        let purged_active = command.purge("123", &Actor::system()).await;
+        let purged = command.purge("456", &Actor::system()).await;
+
+        // then
+        assert_eq!(restored, Ok(()));
+        assert!(matches!(restored_again, Err(AppError::InvalidInput(_))));
+        assert!(matches!(purged_active, Err(AppError::InvalidInput(_))));
+        assert_eq!(purged, Ok(()));
+        assert!(!store.get("123").unwrap().is_trashed());
+        assert!(!store.contains_key("456"));
+    }
+
+    #[tokio::test]
+    async fn purge_keeps_recently_trashed_urls() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let command = DeleteShortUrlCommand::new(InMemoryRepository::new(store.clone()));
+        command.execute("123", &Actor::system()).await.unwrap();
+        let trashed_at = store.get("123").unwrap().trashed_at.unwrap();
+
+        // when
+        let too_early = command.purge_trashed_before("123", trashed_at - 1).await;
+        let purged = command.purge_trashed_before("123", trashed_at).await;
+
+        // then
+        assert!(matches!(too_early, Err(AppError::InvalidInput(_))));
+        assert_eq!(purged, Ok(()));
+        assert!(store.is_empty());
+    }
+}
@@ -1 +1,5 @@
 pub mod create_short_url;
+pub mod delete_short_url;
+pub mod record_click;
+pub mod record_probe;
+pub mod update_short_url;
@@ -0,0 +1,132 @@
+use crate::app::{error::AppError, event::LinkEvent};
+
+pub trait RecordClickRepository {
+    /// учёт перехода, `target` — цель сплит-ссылки, по которой ушёл посетитель
+    fn record_click(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+        event: LinkEvent,
+    ) -> Result<(), AppError>;
+}
+
+/// учёт перехода по короткой ссылке
+pub struct RecordClickCommand<R>
+where
+    R: RecordClickRepository,
+{
+    repo: R,
+}
+
+impl<R> RecordClickCommand<R>
+where
+    R: RecordClickRepository,
+{
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    pub async fn execute(&self, short_url: &str) -> Result<(), AppError> {
+        self.execute_for_target(short_url, None).await
+    }
+
+    /// учёт перехода вместе с выбранной целью сплит-ссылки
+    pub async fn execute_for_target(
+        &self,
+        short_url: &str,
+        target: Option<&str>,
+    ) -> Result<(), AppError> {
+        self.repo
+            .record_click(short_url, target, LinkEvent::clicked(short_url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use dashmap::DashMap;
+
+    use crate::{
+        adapters::{
+            compact_repository::CompactRepository, file_repository::FileRepository,
+            in_memory_repository::InMemoryRepository,
+        },
+        app::{
+            command::create_short_url::CreateShortUrlRepository,
+            link::{ClickLimit, Link, LinkOptions},
+        },
+    };
+
+    use super::*;
+
+    fn one_time() -> Link {
+        Link::new("https://a.example").with_options(LinkOptions {
+            max_clicks: ClickLimit::Set(1),
+            exhausted_message: Some(Some("invite already used".to_owned())),
+            ..Default::default()
+        })
+    }
+
+    /// число успешных переходов из нескольких одновременных
+    fn concurrent_clicks<R>(repo: R) -> usize
+    where
+        R: RecordClickRepository + Clone + Send + 'static,
+    {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let repo = repo.clone();
+                thread::spawn(move || repo.record_click("123", None, LinkEvent::clicked("123")))
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert!(results.iter().all(|r| match r {
+            Ok(()) => true,
+            Err(e) => *e == AppError::Gone(Some("invite already used".to_owned())),
+        }));
+        results.iter().filter(|r| r.is_ok()).count()
+    }
+
+    #[test]
+    fn one_time_link_is_used_once_in_memory() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), one_time());
+
+        // then
+        assert_eq!(concurrent_clicks(InMemoryRepository::new(store.clone())), 1);
+        assert_eq!(store.get("123").unwrap().clicks_used, 1);
+    }
+
+    #[test]
+    fn one_time_link_is_used_once_in_file() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let repo = FileRepository::new(dir.path().join("links.json"));
+        repo.save(
+            one_time(),
+            "123".to_owned(),
+            LinkEvent::created("123", "https://a.example"),
+        )
+        .unwrap();
+
+        // then
+        assert_eq!(concurrent_clicks(repo), 1);
+    }
+
+    #[test]
+    fn one_time_link_is_used_once_in_compact_store() {
+        // given
+        let repo = CompactRepository::new();
+        repo.save(
+            one_time(),
+            "123".to_owned(),
+            LinkEvent::created("123", "https://a.example"),
+        )
+        .unwrap();
+
+        // then
+        assert_eq!(concurrent_clicks(repo), 1);
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::app::{
+    command::update_short_url::UpdateShortUrlRepository,
+    error::AppError,
+    event::{LinkEvent, now_millis},
+    link::Link,
+    link_health::{LinkHealth, LinkHealthStore, Probe},
+    query::get_full_url::GetFullUrlRepository,
+};
+
+/// запись итогов проверки целей короткой ссылки
+pub struct RecordProbeCommand<R>
+where
+    R: UpdateShortUrlRepository + GetFullUrlRepository,
+{
+    repo: R,
+    health: Arc<dyn LinkHealthStore>,
+}
+
+impl<R> RecordProbeCommand<R>
+where
+    R: UpdateShortUrlRepository + GetFullUrlRepository,
+{
+    pub fn new(repo: R, health: Arc<dyn LinkHealthStore>) -> Self {
+        Self { repo, health }
+    }
+
+    /// хранить итоги в `health`
+    pub fn with_store(self, health: Arc<dyn LinkHealthStore>) -> Self {
+        Self { health, ..self }
+    }
+
+    /// Запись итогов проверки целей ссылки из `probes`. Итоги пишутся
+    /// в отдельное хранилище, а сама ссылка меняется только на переходах:
+    /// цель перестала отвечать или все снова ответили (`link.checked`),
+    /// все цели не отвечают с `disable_before` (мс unix) или раньше
+    /// (`link.disabled`). Ответившая цель снова включает ссылку. Если ссылку
+    /// за время проверки изменили, переход отбрасывается с `InvalidInput`.
+    pub async fn execute(
+        &self,
+        short_url: &str,
+        probes: &HashMap<String, Probe>,
+        disable_before: Option<u64>,
+    ) -> Result<(), AppError> {
+        let link = self.repo.get(short_url)?;
+        if link.is_trashed() {
+            return Err(AppError::NotFound);
+        }
+        let now = now_millis();
+        let targets = link.target_urls();
+        let mut previous = self.health.get(short_url)?;
+        let health: BTreeMap<String, LinkHealth> = targets
+            .iter()
+            .filter_map(|target| {
+                let before = previous.remove(*target);
+                let after = match probes.get(*target) {
+                    Some(probe) => LinkHealth::after(before.as_ref(), probe, now),
+                    // цель добавили после того, как собрали список на проверку
+                    None => before?,
+                };
+                Some((target.to_string(), after))
+            })
+            .collect();
+        self.health.put(short_url, health.clone())?;
+
+        let failing_since = health
+            .values()
+            .filter_map(|health| health.failing_since)
+            .min()
+            .map(|since| link.failing_since.unwrap_or(since));
+        let all_failing = health.len() == targets.len()
+            && health
+                .values()
+                .all(|health| health.consecutive_failures > 0);
+        let disabled_at = match link.disabled_at {
+            _ if !all_failing => None,
+            Some(at) => Some(at),
+            None => disable_before
+                .filter(|before| health.values().all(|health| health.failing_before(*before)))
+                .map(|_| now),
+        };
+        if failing_since == link.failing_since && disabled_at == link.disabled_at {
+            return Ok(());
+        }
+
+        let event = if link.disabled_at.is_none() && disabled_at.is_some() {
+            LinkEvent::disabled(short_url)
+        } else {
+            let status = health.get(&link.url).and_then(|health| health.last_status);
+            LinkEvent::checked(short_url, status)
+        };
+        let change = |stored: &mut Link| {
+            check_unchanged(stored, &link, &targets)?;
+            stored.failing_since = failing_since;
+            stored.disabled_at = disabled_at;
+            Ok(())
+        };
+        self.repo.update(short_url, &change, event)
+    }
+
+    /// удаление итогов ссылок, которых больше нет
+    pub fn forget_others(&self, short_urls: &HashSet<String>) -> Result<(), AppError> {
+        self.health.retain(short_urls)
+    }
+}
+
+/// переход считался по `checked`, ссылка не должна была измениться с тех пор
+fn check_unchanged(link: &Link, checked: &Link, targets: &BTreeSet<&str>) -> Result<(), AppError> {
+    if link.is_trashed() {
+        return Err(AppError::NotFound);
+    }
+    if link.target_urls() != *targets
+        || link.failing_since != checked.failing_since
+        || link.disabled_at != checked.disabled_at
+    {
+        return Err(AppError::InvalidInput(
+            "link changed during the check".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use crate::{
+        adapters::{
+            in_memory_link_health_store::InMemoryLinkHealthStore,
+            in_memory_repository::InMemoryRepository,
+        },
+        app::{
+            event::{EventOutbox, LinkEventKind},
+            link::LinkOptions,
+            split::WeightedTarget,
+        },
+    };
+
+    use super::*;
+
+    fn probes(results: &[(&str, Probe)]) -> HashMap<String, Probe> {
+        results
+            .iter()
+            .map(|(url, probe)| (url.to_string(), probe.clone()))
+            .collect()
+    }
+
+    fn events(repo: &InMemoryRepository) -> Vec<&'static str> {
+        let events = repo.pending(100).unwrap();
+        let ids: Vec<String> = events.iter().map(|e| e.id.clone()).collect();
+        repo.acknowledge(&ids).unwrap();
+        events.iter().map(|e| e.kind.name()).collect()
+    }
+
+    #[tokio::test]
+    async fn long_failing_link_is_disabled_until_target_answers() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://a.example"));
+        let repo = InMemoryRepository::new(store.clone());
+        let health = Arc::new(InMemoryLinkHealthStore::default());
+        let command = RecordProbeCommand::new(repo.clone(), health.clone());
+        let not_found = probes(&[("https://a.example", Probe::Status(404))]);
+
+        // when
+        command.execute("123", &not_found, Some(0)).await.unwrap();
+        let after_first = store.get("123").unwrap().clone();
+        let first_events = events(&repo);
+        command.execute("123", &not_found, None).await.unwrap();
+        let unchanged_events = events(&repo);
+        command
+            .execute("123", &not_found, Some(now_millis()))
+            .await
+            .unwrap();
+        let after_disable = store.get("123").unwrap().clone();
+        let ok = probes(&[("https://a.example", Probe::Status(200))]);
+        command
+            .execute("123", &ok, Some(now_millis()))
+            .await
+            .unwrap();
+        let after_recovery = store.get("123").unwrap().clone();
+
+        // then
+        assert!(after_first.failing_since.is_some());
+        assert_eq!(after_first.disabled_at, None);
+        assert_eq!(first_events, ["link.checked"]);
+        assert!(unchanged_events.is_empty());
+        assert!(after_disable.disabled_at.is_some());
+        assert!(matches!(
+            after_disable.check_available(),
+            Err(AppError::Gone(Some(_)))
+        ));
+        assert_eq!(after_recovery.failing_since, None);
+        assert_eq!(after_recovery.disabled_at, None);
+        assert_eq!(events(&repo), ["link.disabled", "link.checked"]);
+        let recorded = health.get("123").unwrap();
+        assert_eq!(recorded["https://a.example"].last_status, Some(200));
+    }
+
+    #[tokio::test]
+    async fn split_link_is_disabled_only_when_every_target_fails() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let split = Link::new("https://a.example").with_options(LinkOptions {
+            targets: Some(vec![
+                WeightedTarget {
+                    url: "https://a.example".to_owned(),
+                    weight: 1,
+                },
+                WeightedTarget {
+                    url: "https://b.example".to_owned(),
+                    weight: 1,
+                },
+            ]),
+            ..Default::default()
+        });
+        store.insert("123".to_owned(), split);
+        let repo = InMemoryRepository::new(store.clone());
+        let health = Arc::new(InMemoryLinkHealthStore::default());
+        let command = RecordProbeCommand::new(repo.clone(), health.clone());
+
+        // when
+        let one_dead = probes(&[
+            ("https://a.example", Probe::Status(200)),
+            ("https://b.example", Probe::Status(404)),
+        ]);
+        command
+            .execute("123", &one_dead, Some(u64::MAX))
+            .await
+            .unwrap();
+        let after_one = store.get("123").unwrap().clone();
+        let all_dead = probes(&[
+            ("https://a.example", Probe::Status(503)),
+            ("https://b.example", Probe::Status(404)),
+        ]);
+        command
+            .execute("123", &all_dead, Some(u64::MAX))
+            .await
+            .unwrap();
+        let after_all = store.get("123").unwrap().clone();
+
+        // then
+        assert!(after_one.failing_since.is_some());
+        assert_eq!(after_one.disabled_at, None);
+        assert!(after_all.disabled_at.is_some());
+        assert_eq!(health.get("123").unwrap().len(), 2);
+        assert!(matches!(
+            repo.pending(10).unwrap()[1].kind,
+            LinkEventKind::Disabled { .. }
+        ));
+    }
+}
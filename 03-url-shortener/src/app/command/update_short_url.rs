@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use crate::app::{
+    audit::Actor,
+    error::AppError,
+    event::LinkEvent,
+    link::{Link, LinkOptions},
+    policy::{AllowAll, TargetPolicy},
+    query::get_full_url::GetFullUrlRepository,
+};
+
+pub trait UpdateShortUrlRepository {
+    /// Изменение сохранённой ссылки вместе с записью события, `NotFound` если её нет.
+    /// Ошибка `change` отменяет изменение и возвращается как есть, поэтому
+    /// `change` не должна менять ссылку до того, как решит вернуть ошибку.
+    fn update(
+        &self,
+        short_url: &str,
+        change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+        event: LinkEvent,
+    ) -> Result<(), AppError>;
+}
+
+/// смена цели существующей короткой ссылки
+pub struct UpdateShortUrlCommand<R>
+where
+    R: UpdateShortUrlRepository + GetFullUrlRepository,
+{
+    repo: R,
+    policy: Arc<dyn TargetPolicy>,
+}
+
+impl<R> UpdateShortUrlCommand<R>
+where
+    R: UpdateShortUrlRepository + GetFullUrlRepository,
+{
+    pub fn new(repo: R) -> Self {
+        Self {
+            repo,
+            policy: Arc::new(AllowAll),
+        }
+    }
+
+    /// проверять новые цели заданной политикой
+    pub fn with_policy(self, policy: Arc<dyn TargetPolicy>) -> Self {
+        Self { policy, ..self }
+    }
+
+    pub async fn execute(
+        &self,
+        short_url: &str,
+        full_url: String,
+        actor: &Actor,
+    ) -> Result<(), AppError> {
+        self.policy.check(&full_url)?;
+        let change = |link: &mut Link| {
+            check_active(link)?;
+            link.url = full_url.clone();
+            reset_health(link);
+            Ok(())
+        };
+        update_audited(&self.repo, actor, "link.update", short_url, &change, |_| {
+            LinkEvent::updated(short_url, &full_url)
+        })
+    }
+
+    /// Смена настроек ссылки, кроме пароля. Меняются только цель,
+    /// если она задана, и настройки, заданные в `options`.
+    pub async fn execute_with_options(
+        &self,
+        short_url: &str,
+        full_url: Option<String>,
+        mut options: LinkOptions,
+        actor: &Actor,
+    ) -> Result<(), AppError> {
+        options.validate()?;
+        for destination in full_url
+            .iter()
+            .map(String::as_str)
+            .chain(options.destinations())
+        {
+            self.policy.check(destination)?;
+        }
+        let change = |link: &mut Link| {
+            check_active(link)?;
+            if let Some(full_url) = &full_url {
+                link.url = full_url.clone();
+            }
+            options.apply(link);
+            reset_health(link);
+            Ok(())
+        };
+        update_audited(
+            &self.repo,
+            actor,
+            "link.update",
+            short_url,
+            &change,
+            |link| LinkEvent::updated(short_url, &link.url),
+        )
+    }
+}
+
+/// попыток изменения, если ссылку меняют одновременно
+const ATTEMPTS: usize = 5;
+
+/// Изменение ссылки с записью аудита в том же событии. Значения до и после
+/// считаются по прочитанной ссылке, и изменение проходит, только если
+/// ссылка с тех пор не менялась, иначе чтение повторяется.
+pub(crate) fn update_audited<R>(
+    repo: &R,
+    actor: &Actor,
+    action: &str,
+    short_url: &str,
+    change: &dyn Fn(&mut Link) -> Result<(), AppError>,
+    event: impl Fn(&Link) -> LinkEvent,
+) -> Result<(), AppError>
+where
+    R: UpdateShortUrlRepository + GetFullUrlRepository,
+{
+    for _ in 0..ATTEMPTS {
+        let before = repo.get(short_url)?;
+        let mut after = before.clone();
+        change(&mut after)?;
+        let record = actor.link_record(action, short_url, Some(&before), Some(&after));
+        let unchanged = |link: &mut Link| {
+            if *link != before {
+                return Err(AppError::Conflict);
+            }
+            change(link)
+        };
+        match repo.update(short_url, &unchanged, event(&after).with_audit(record)) {
+            Err(AppError::Conflict) => continue,
+            result => return result,
+        }
+    }
+    Err(AppError::Conflict)
+}
+
+/// ссылку в корзине нельзя менять, пока её не восстановят
+fn check_active(link: &Link) -> Result<(), AppError> {
+    match link.is_trashed() {
+        true => Err(AppError::NotFound),
+        false => Ok(()),
+    }
+}
+
+/// Изменённую ссылку проверяют заново, и отключённая ссылка снова работает:
+/// если цель всё ещё не отвечает, проверки отключат её ещё раз.
+fn reset_health(link: &mut Link) {
+    link.failing_since = None;
+    link.disabled_at = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+
+    use crate::{
+        adapters::in_memory_repository::InMemoryRepository,
+        app::policy::{HostList, HostPolicy},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn update_keeps_password() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let link = Link::protected("https://google.com", "secret").unwrap();
+        store.insert("123".to_owned(), link);
+        let command = UpdateShortUrlCommand::new(InMemoryRepository::new(store.clone()));
+
+        // when
+        let result = command
+            .execute("123", "https://github.com".to_owned(), &Actor::system())
+            .await;
+
+        // then
+        assert_eq!(result, Ok(()));
+        let link = store.get("123").unwrap();
+        assert_eq!(link.url, "https://github.com");
+        assert_eq!(link.verify_password("secret"), Ok(true));
+    }
+
+    #[tokio::test]
+    async fn update_checks_policy_and_existence() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let policy = HostPolicy {
+            blocklist: HostList::parse("evil.com"),
+            ..Default::default()
+        };
+        let command = UpdateShortUrlCommand::new(InMemoryRepository::new(store.clone()))
+            .with_policy(Arc::new(policy));
+
+        // when
+        let rejected = command
+            .execute("123", "https://evil.com".to_owned(), &Actor::system())
+            .await;
+        let missing = command
+            .execute("456", "https://github.com".to_owned(), &Actor::system())
+            .await;
+
+        // then
+        assert!(matches!(rejected, Err(AppError::TargetRejected(_))));
+        assert_eq!(missing, Err(AppError::NotFound));
+        assert_eq!(store.get("123").unwrap().url, "https://google.com");
+    }
+}
@@ -0,0 +1,62 @@
+use std::fmt::Display;
+
+/// ошибки слоя приложения
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum AppError {
+    /// короткая ссылка не найдена
+    NotFound,
+    /// ссылка защищена, а пароль не передан
+    PasswordRequired,
+    /// неверный пароль
+    InvalidPassword,
+    /// слишком много неудачных попыток ввода пароля
+    TooManyAttempts,
+    /// полный url не разбирается или не http(s)
+    InvalidUrl(String),
+    /// цель ссылки запрещена политикой
+    TargetRejected(String),
+    /// исчерпана квота на число ссылок
+    QuotaExceeded,
+    /// переходы по ссылке закончились, с сообщением владельца ссылки
+    Gone(Option<String>),
+    /// некорректные входные данные
+    InvalidInput(String),
+    /// нет прав на операцию
+    Unauthorized,
+    /// ключ идемпотентности уже использован с другим запросом
+    IdempotencyKeyReused,
+    /// запрос с этим ключом идемпотентности ещё выполняется
+    RequestInProgress,
+    /// ссылку одновременно изменил другой запрос
+    Conflict,
+    /// ошибка хранилища или другой инфраструктуры
+    Internal(String),
+}
+
+impl Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::NotFound => write!(f, "Not Found"),
+            AppError::PasswordRequired => write!(f, "Password required"),
+            AppError::InvalidPassword => write!(f, "Invalid password"),
+            AppError::TooManyAttempts => write!(f, "Too many attempts, try again later"),
+            AppError::InvalidUrl(msg) => write!(f, "Invalid url: {msg}"),
+            AppError::TargetRejected(msg) => write!(f, "Target rejected: {msg}"),
+            AppError::QuotaExceeded => write!(f, "Link quota exceeded"),
+            AppError::Gone(Some(msg)) => write!(f, "{msg}"),
+            AppError::Gone(None) => write!(f, "Link is no longer available"),
+            AppError::InvalidInput(msg) => write!(f, "Invalid input: {msg}"),
+            AppError::Unauthorized => write!(f, "Unauthorized"),
+            AppError::IdempotencyKeyReused => {
+                write!(f, "Idempotency key was used with a different request")
+            }
+            AppError::RequestInProgress => {
+                write!(f, "Request with this idempotency key is still in progress")
+            }
+            AppError::Conflict => write!(f, "Link was changed concurrently, try again"),
+            AppError::Internal(msg) => write!(f, "Internal error: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
@@ -0,0 +1,442 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{audit::AuditRecord, error::AppError};
+
+/// Событие жизненного цикла короткой ссылки.
+///
+/// В json тип события лежит в поле `type`, а данные в `data`:
+/// `{"id": "...", "occurred_at": 1700000000000, "type": "link.created", "data": {...}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkEvent {
+    /// уникальный id события, по нему получатели отбрасывают повторы
+    pub id: String,
+    /// время события в миллисекундах unix
+    pub occurred_at: u64,
+    /// арендатор ссылки, `None` для ссылок без арендатора
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    #[serde(flatten)]
+    pub kind: LinkEventKind,
+    /// Запись аудита о том, кто сделал изменение. Сохраняется вместе
+    /// с событием и уходит в журнал, но не получателям вебхуков.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<Box<AuditRecord>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum LinkEventKind {
+    #[serde(rename = "link.created")]
+    Created { short_url: String, full_url: String },
+    #[serde(rename = "link.updated")]
+    Updated { short_url: String, full_url: String },
+    #[serde(rename = "link.trashed")]
+    Trashed { short_url: String },
+    #[serde(rename = "link.restored")]
+    Restored { short_url: String },
+    #[serde(rename = "link.deleted")]
+    Deleted { short_url: String },
+    #[serde(rename = "link.clicked")]
+    Clicked { short_url: String },
+    #[serde(rename = "link.checked")]
+    Checked {
+        short_url: String,
+        status: Option<u16>,
+    },
+    #[serde(rename = "link.disabled")]
+    Disabled { short_url: String },
+}
+
+impl LinkEventKind {
+    /// все типы событий
+    pub const TYPES: [&'static str; 8] = [
+        "link.created",
+        "link.updated",
+        "link.trashed",
+        "link.restored",
+        "link.deleted",
+        "link.clicked",
+        "link.checked",
+        "link.disabled",
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LinkEventKind::Created { .. } => "link.created",
+            LinkEventKind::Updated { .. } => "link.updated",
+            LinkEventKind::Trashed { .. } => "link.trashed",
+            LinkEventKind::Restored { .. } => "link.restored",
+            LinkEventKind::Deleted { .. } => "link.deleted",
+            LinkEventKind::Clicked { .. } => "link.clicked",
+            LinkEventKind::Checked { .. } => "link.checked",
+            LinkEventKind::Disabled { .. } => "link.disabled",
+        }
+    }
+
+    /// ссылка, к которой относится событие
+    pub fn short_url(&self) -> &str {
+        match self {
+            LinkEventKind::Created { short_url, .. }
+            | LinkEventKind::Updated { short_url, .. }
+            | LinkEventKind::Trashed { short_url }
+            | LinkEventKind::Restored { short_url }
+            | LinkEventKind::Deleted { short_url }
+            | LinkEventKind::Clicked { short_url }
+            | LinkEventKind::Checked { short_url, .. }
+            | LinkEventKind::Disabled { short_url } => short_url,
+        }
+    }
+}
+
+impl LinkEvent {
+    pub fn new(kind: LinkEventKind) -> Self {
+        Self {
+            id: nanoid::nanoid!(),
+            occurred_at: now_millis(),
+            tenant: None,
+            kind,
+            audit: None,
+        }
+    }
+
+    pub fn with_audit(self, record: AuditRecord) -> Self {
+        Self {
+            audit: Some(Box::new(record)),
+            ..self
+        }
+    }
+
+    pub fn created(short_url: &str, full_url: &str) -> Self {
+        Self::new(LinkEventKind::Created {
+            short_url: short_url.to_owned(),
+            full_url: full_url.to_owned(),
+        })
+    }
+
+    pub fn updated(short_url: &str, full_url: &str) -> Self {
+        Self::new(LinkEventKind::Updated {
+            short_url: short_url.to_owned(),
+            full_url: full_url.to_owned(),
+        })
+    }
+
+    pub fn trashed(short_url: &str) -> Self {
+        Self::new(LinkEventKind::Trashed {
+            short_url: short_url.to_owned(),
+        })
+    }
+
+    pub fn restored(short_url: &str) -> Self {
+        Self::new(LinkEventKind::Restored {
+            short_url: short_url.to_owned(),
+        })
+    }
+
+    pub fn deleted(short_url: &str) -> Self {
+        Self::new(LinkEventKind::Deleted {
+            short_url: short_url.to_owned(),
+        })
+    }
+
+    pub fn clicked(short_url: &str) -> Self {
+        Self::new(LinkEventKind::Clicked {
+            short_url: short_url.to_owned(),
+        })
+    }
+
+    /// проверка цели, `status` нет, если цель не ответила
+    pub fn checked(short_url: &str, status: Option<u16>) -> Self {
+        Self::new(LinkEventKind::Checked {
+            short_url: short_url.to_owned(),
+            status,
+        })
+    }
+
+    pub fn disabled(short_url: &str) -> Self {
+        Self::new(LinkEventKind::Disabled {
+            short_url: short_url.to_owned(),
+        })
+    }
+}
+
+/// Очередь событий (transactional outbox) в хранилище ссылок.
+///
+/// Команды передают событие в тот же вызов репозитория, что и изменение,
+/// а хранилище сохраняет их одной записью. Отсюда события забирает
+/// [`OutboxRelay`] и удаляет после публикации.
+pub trait EventOutbox: Send + Sync {
+    /// первые неопубликованные события в порядке записи
+    fn pending(&self, limit: usize) -> Result<Vec<LinkEvent>, AppError>;
+    /// удаление опубликованных событий
+    fn acknowledge(&self, event_ids: &[String]) -> Result<(), AppError>;
+}
+
+/// порт публикации событий
+pub trait EventPublisher: Send + Sync {
+    fn publish(&self, event: LinkEvent) -> Result<(), AppError>;
+}
+
+/// обработчик событий внутри процесса
+pub trait EventSubscriber: Send + Sync {
+    fn handle(&self, event: &LinkEvent) -> Result<(), AppError>;
+}
+
+/// id последних обработанных событий, по ним подписчик отбрасывает повторы
+pub struct RecentIds {
+    capacity: usize,
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl RecentIds {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// запоминает id, вытесняя самый старый
+    pub fn insert(&mut self, id: String) {
+        if self.order.len() == self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.ids.remove(&oldest);
+        }
+        if self.ids.insert(id.clone()) {
+            self.order.push_back(id);
+        }
+    }
+}
+
+/// Рассылка событий подписчикам внутри процесса.
+///
+/// Событие получают все подписчики; если хотя бы один вернул ошибку,
+/// событие будет опубликовано повторно, поэтому подписчики должны
+/// отбрасывать повторы по id события.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: RwLock<Vec<Arc<dyn EventSubscriber>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self, subscriber: Arc<dyn EventSubscriber>) {
+        self.subscribers.write().unwrap().push(subscriber);
+    }
+}
+
+impl EventPublisher for EventBus {
+    fn publish(&self, event: LinkEvent) -> Result<(), AppError> {
+        let subscribers = self.subscribers.read().unwrap().clone();
+        let mut result = Ok(());
+        for subscriber in subscribers {
+            if let Err(e) = subscriber.handle(&event) {
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+/// Перенос событий из очереди хранилища в издателя.
+///
+/// Доставка «хотя бы один раз»: событие удаляется из очереди только после
+/// успешной публикации.
+pub struct OutboxRelay {
+    outbox: Arc<dyn EventOutbox>,
+    publisher: Arc<dyn EventPublisher>,
+    poll_interval: Duration,
+}
+
+impl OutboxRelay {
+    /// сколько событий забирается за один проход
+    const BATCH: usize = 100;
+
+    pub fn new(outbox: Arc<dyn EventOutbox>, publisher: Arc<dyn EventPublisher>) -> Self {
+        Self {
+            outbox,
+            publisher,
+            poll_interval: Duration::from_millis(200),
+        }
+    }
+
+    /// Подписчики пишут в файлы и сеть, поэтому проход идёт вне рабочих потоков рантайма
+    pub async fn run(self) {
+        let this = Arc::new(self);
+        loop {
+            let relay = this.clone();
+            match tokio::task::spawn_blocking(move || relay.relay_pending()).await {
+                Ok(Ok(Self::BATCH)) => continue,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("event relay failed: {e}"),
+                Err(e) => eprintln!("event relay panicked: {e}"),
+            }
+            tokio::time::sleep(this.poll_interval).await;
+        }
+    }
+
+    /// Публикация накопившихся событий по порядку, возвращает число опубликованных.
+    /// На первой ошибке проход останавливается, чтобы не нарушать порядок.
+    pub fn relay_pending(&self) -> Result<usize, AppError> {
+        let mut published = Vec::new();
+        let mut result = Ok(());
+        for event in self.outbox.pending(Self::BATCH)? {
+            let id = event.id.clone();
+            if let Err(e) = self.publisher.publish(event) {
+                result = Err(e);
+                break;
+            }
+            published.push(id);
+        }
+        if !published.is_empty() {
+            self.outbox.acknowledge(&published)?;
+        }
+        result.map(|_| published.len())
+    }
+}
+
+/// текущее время в миллисекундах unix
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use dashmap::DashMap;
+
+    use crate::{
+        adapters::in_memory_repository::InMemoryRepository,
+        app::{
+            audit::Actor,
+            command::{
+                create_short_url::{CreateShortUrlCommand, CreateShortUrlRepository},
+                delete_short_url::DeleteShortUrlCommand,
+            },
+            link::Link,
+        },
+        id_provider::FakeIDProvider,
+    };
+
+    use super::*;
+
+    /// подписчик, запоминающий типы событий и падающий, пока `fail` выставлен
+    #[derive(Default)]
+    struct Recorder {
+        seen: Mutex<Vec<&'static str>>,
+        fail: Mutex<bool>,
+    }
+
+    impl EventSubscriber for Recorder {
+        fn handle(&self, event: &LinkEvent) -> Result<(), AppError> {
+            if *self.fail.lock().unwrap() {
+                return Err(AppError::Internal("subscriber is down".to_owned()));
+            }
+            self.seen.lock().unwrap().push(event.kind.name());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn command_events_reach_subscribers_in_order() {
+        // given
+        let repo = InMemoryRepository::new(Arc::new(DashMap::new()));
+        let create =
+            CreateShortUrlCommand::new(FakeIDProvider::new("abc".to_owned()), repo.clone());
+        let delete = DeleteShortUrlCommand::new(repo.clone());
+        let recorder = Arc::new(Recorder::default());
+        let bus = EventBus::default();
+        bus.subscribe(recorder.clone());
+        let relay = OutboxRelay::new(Arc::new(repo.clone()), Arc::new(bus));
+
+        // when
+        create
+            .execute("https://google.com".to_owned(), &Actor::system())
+            .await
+            .unwrap();
+        delete.execute("abc", &Actor::system()).await.unwrap();
+        delete.purge("abc", &Actor::system()).await.unwrap();
+        let relayed = relay.relay_pending();
+
+        // then
+        assert_eq!(relayed, Ok(3));
+        assert_eq!(
+            *recorder.seen.lock().unwrap(),
+            vec!["link.created", "link.trashed", "link.deleted"]
+        );
+        assert_eq!(repo.pending(10), Ok(vec![]));
+    }
+
+    #[test]
+    fn failed_publication_stays_in_outbox() {
+        // given
+        let repo = InMemoryRepository::new(Arc::new(DashMap::new()));
+        let recorder = Arc::new(Recorder::default());
+        *recorder.fail.lock().unwrap() = true;
+        let bus = EventBus::default();
+        bus.subscribe(recorder.clone());
+        let relay = OutboxRelay::new(Arc::new(repo.clone()), Arc::new(bus));
+        repo.save(
+            Link::new("https://google.com"),
+            "abc".to_owned(),
+            LinkEvent::created("abc", "https://google.com"),
+        )
+        .unwrap();
+
+        // when
+        let failed = relay.relay_pending();
+        *recorder.fail.lock().unwrap() = false;
+        let retried = relay.relay_pending();
+
+        // then
+        assert!(failed.is_err());
+        assert_eq!(retried, Ok(1));
+        assert_eq!(*recorder.seen.lock().unwrap(), vec!["link.created"]);
+    }
+
+    #[test]
+    fn event_json_has_type_and_data() {
+        // given
+        let event = LinkEvent {
+            id: "ev1".to_owned(),
+            occurred_at: 42,
+            tenant: Some("team-a".to_owned()),
+            kind: LinkEventKind::Created {
+                short_url: "abc".to_owned(),
+                full_url: "https://google.com".to_owned(),
+            },
+            audit: None,
+        };
+
+        // when
+        let json = serde_json::to_value(&event).unwrap();
+
+        // then
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "id": "ev1",
+                "occurred_at": 42,
+                "tenant": "team-a",
+                "type": "link.created",
+                "data": {"short_url": "abc", "full_url": "https://google.com"},
+            })
+        );
+        assert_eq!(serde_json::from_value::<LinkEvent>(json).unwrap(), event);
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Instant,
+};
+
+use serde::Serialize;
+
+use crate::app::error::AppError;
+
+/// проверка работоспособности адаптера
+pub trait HealthCheck: Send + Sync {
+    /// `Ok` если адаптер может обслуживать запросы
+    fn check_health(&self) -> Result<(), AppError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+/// результат проверки одного компонента
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// отчёт о готовности
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    /// причина неготовности, не связанная с компонентами
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub components: Vec<ComponentHealth>,
+}
+
+/// Готовность приложения: проверки зарегистрированных компонентов
+/// и признак начавшегося завершения работы.
+#[derive(Default)]
+pub struct Readiness {
+    components: Vec<(String, Arc<dyn HealthCheck>)>,
+    shutting_down: AtomicBool,
+}
+
+impl Readiness {
+    /// добавить компонент, без которого приложение не готово
+    pub fn with_component(mut self, name: &str, check: Arc<dyn HealthCheck>) -> Self {
+        self.components.push((name.to_owned(), check));
+        self
+    }
+
+    /// С этого момента приложение не готово принимать новый трафик,
+    /// но продолжает обслуживать запросы, пока его не уберут из балансировки.
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    /// Проверка всех компонентов. Проверки синхронные и могут обращаться
+    /// к диску, поэтому вызывать лучше вне асинхронного потока.
+    pub fn report(&self) -> HealthReport {
+        let components: Vec<ComponentHealth> = self
+            .components
+            .iter()
+            .map(|(name, check)| {
+                let started = Instant::now();
+                let result = check.check_health();
+                ComponentHealth {
+                    name: name.clone(),
+                    status: match result {
+                        Ok(()) => HealthStatus::Up,
+                        Err(_) => HealthStatus::Down,
+                    },
+                    latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+                    error: result.err().map(|e| e.to_string()),
+                }
+            })
+            .collect();
+
+        let reason = self
+            .shutting_down
+            .load(Ordering::SeqCst)
+            .then(|| "shutting down".to_owned());
+        let healthy = components.iter().all(|c| c.status == HealthStatus::Up);
+        let status = match (healthy, &reason) {
+            (true, None) => HealthStatus::Up,
+            _ => HealthStatus::Down,
+        };
+
+        HealthReport {
+            status,
+            reason,
+            components,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Broken;
+
+    impl HealthCheck for Broken {
+        fn check_health(&self) -> Result<(), AppError> {
+            Err(AppError::Internal("disk is gone".to_owned()))
+        }
+    }
+
+    struct Healthy;
+
+    impl HealthCheck for Healthy {
+        fn check_health(&self) -> Result<(), AppError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failing_component_makes_app_not_ready() {
+        // given
+        let readiness = Readiness::default()
+            .with_component("links", Arc::new(Healthy))
+            .with_component("tenants", Arc::new(Broken));
+
+        // when
+        let report = readiness.report();
+
+        // then
+        assert_eq!(report.status, HealthStatus::Down);
+        assert_eq!(report.components[0].status, HealthStatus::Up);
+        assert_eq!(
+            report.components[1].error.as_deref(),
+            Some("Internal error: disk is gone")
+        );
+    }
+
+    #[test]
+    fn shutdown_makes_app_not_ready() {
+        // given
+        let readiness = Readiness::default().with_component("links", Arc::new(Healthy));
+        assert_eq!(readiness.report().status, HealthStatus::Up);
+
+        // when
+        readiness.begin_shutdown();
+
+        // then
+        let report = readiness.report();
+        assert_eq!(report.status, HealthStatus::Down);
+        assert_eq!(report.reason.as_deref(), Some("shutting down"));
+    }
+}
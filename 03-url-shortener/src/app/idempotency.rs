@@ -0,0 +1,217 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::{error::AppError, event::now_millis};
+
+/// ответ на запрос, повторяемый по ключу идемпотентности
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// чем закончилась попытка занять ключ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reservation {
+    /// ключ свободен и теперь занят этим запросом
+    Started,
+    /// запрос с этим ключом и телом уже выполнен
+    Completed(StoredResponse),
+    /// запрос с этим ключом и телом ещё выполняется
+    InProgress,
+    /// ключ уже использован с другим телом
+    Mismatch,
+}
+
+/// Порт хранилища ключей идемпотентности.
+///
+/// `begin` должен атомарно проверять и занимать ключ, иначе два одновременных
+/// повтора оба получат [`Reservation::Started`].
+pub trait IdempotencyStore: Send + Sync {
+    /// занять ключ до `expires_at` (мс unix) или узнать, что с ним уже было
+    fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        now: u64,
+        expires_at: u64,
+    ) -> Result<Reservation, AppError>;
+    /// запомнить ответ занятого ключа
+    fn complete(&self, key: &str, response: StoredResponse) -> Result<(), AppError>;
+    /// освободить ключ, чтобы запрос можно было повторить
+    fn abandon(&self, key: &str) -> Result<(), AppError>;
+}
+
+/// Выполнение запроса не больше одного раза на ключ в течение `ttl`.
+///
+/// Повтор с тем же телом получает сохранённый ответ, повтор во время
+/// выполнения ждёт его окончания. Ответы с ошибкой сервера не сохраняются:
+/// такой запрос можно повторить с тем же ключом.
+pub struct Idempotency {
+    store: Arc<dyn IdempotencyStore>,
+    ttl: Duration,
+    /// сколько повтор ждёт окончания исходного запроса
+    wait: Duration,
+}
+
+impl Idempotency {
+    const POLL_INTERVAL: Duration = Duration::from_millis(25);
+
+    pub fn new(store: Arc<dyn IdempotencyStore>, ttl: Duration) -> Self {
+        Self {
+            store,
+            ttl,
+            wait: Duration::from_secs(10),
+        }
+    }
+
+    pub async fn run<F>(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        execute: F,
+    ) -> Result<Outcome, AppError>
+    where
+        F: Future<Output = StoredResponse>,
+    {
+        let deadline = tokio::time::Instant::now() + self.wait;
+        loop {
+            let now = now_millis();
+            let expires_at = now + self.ttl.as_millis() as u64;
+            match self.store.begin(key, fingerprint, now, expires_at)? {
+                Reservation::Started => break,
+                Reservation::Completed(response) => return Ok(Outcome::Replayed(response)),
+                Reservation::Mismatch => {
+                    return Err(AppError::IdempotencyKeyReused);
+                }
+                Reservation::InProgress if tokio::time::Instant::now() >= deadline => {
+                    return Err(AppError::RequestInProgress);
+                }
+                Reservation::InProgress => tokio::time::sleep(Self::POLL_INTERVAL).await,
+            }
+        }
+
+        // если запрос прервут, ключ останется занятым до истечения срока,
+        // поэтому освобождение делает guard
+        let mut guard = Release {
+            store: &*self.store,
+            key,
+            done: false,
+        };
+        let response = execute.await;
+        guard.done = true;
+        if response.status >= 500 {
+            self.store.abandon(key)?;
+        } else {
+            self.store.complete(key, response.clone())?;
+        }
+        Ok(Outcome::Executed(response))
+    }
+}
+
+/// ответ и был ли он выполнен сейчас или взят из хранилища
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Executed(StoredResponse),
+    Replayed(StoredResponse),
+}
+
+struct Release<'a> {
+    store: &'a dyn IdempotencyStore,
+    key: &'a str,
+    done: bool,
+}
+
+impl Drop for Release<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = self.store.abandon(self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::adapters::in_memory_idempotency_store::InMemoryIdempotencyStore;
+
+    use super::*;
+
+    fn created(body: &str) -> StoredResponse {
+        StoredResponse {
+            status: 200,
+            content_type: Some("application/json".to_owned()),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_duplicates_execute_once() {
+        // given
+        let idempotency = Idempotency::new(
+            Arc::new(InMemoryIdempotencyStore::default()),
+            Duration::from_secs(60),
+        );
+        let executed = AtomicUsize::new(0);
+        let execute = || async {
+            let n = executed.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            created(&format!("link-{n}"))
+        };
+
+        // when
+        let (first, second) = tokio::join!(
+            idempotency.run("key", "body", execute()),
+            idempotency.run("key", "body", execute()),
+        );
+        let other_body = idempotency.run("key", "other", execute()).await;
+
+        // then
+        assert_eq!(executed.load(Ordering::SeqCst), 1);
+        let mut outcomes = [first.unwrap(), second.unwrap()];
+        outcomes.sort_by_key(|outcome| matches!(outcome, Outcome::Replayed(_)));
+        assert_eq!(
+            outcomes,
+            [
+                Outcome::Executed(created("link-0")),
+                Outcome::Replayed(created("link-0")),
+            ]
+        );
+        assert_eq!(other_body, Err(AppError::IdempotencyKeyReused));
+    }
+
+    #[tokio::test]
+    async fn server_errors_and_cancelled_requests_free_the_key() {
+        // given
+        let idempotency = Idempotency::new(
+            Arc::new(InMemoryIdempotencyStore::default()),
+            Duration::from_secs(60),
+        );
+        let failed = StoredResponse {
+            status: 503,
+            content_type: None,
+            body: Vec::new(),
+        };
+
+        // when
+        idempotency
+            .run("key", "body", async { failed.clone() })
+            .await
+            .unwrap();
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(10),
+            idempotency.run("key", "body", std::future::pending()),
+        )
+        .await;
+        let retried = idempotency
+            .run("key", "body", async { created("link") })
+            .await;
+
+        // then
+        assert!(cancelled.is_err());
+        assert_eq!(retried, Ok(Outcome::Executed(created("link"))));
+    }
+}
@@ -0,0 +1,443 @@
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
+};
+
+use std::collections::BTreeSet;
+
+use crate::app::{
+    error::AppError,
+    redirect::{Passthrough, RedirectRules, UtmTemplate},
+    routing::{self, RouteRule},
+    split::{self, WeightedTarget},
+};
+
+/// запись о короткой ссылке
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Link {
+    pub url: String,
+    /// argon2 хэш пароля, если ссылка защищена
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    #[serde(flatten)]
+    pub meta: LinkMeta,
+    #[serde(flatten)]
+    pub redirect: RedirectRules,
+    /// цели, между которыми делится трафик, пустой список означает только `url`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<WeightedTarget>,
+    /// правила выбора цели по посетителю, проверяются раньше `targets`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RouteRule>,
+    /// число переходов, после которого ссылка перестаёт работать
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_clicks: Option<u64>,
+    /// сколько переходов списано в счёт `max_clicks`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub clicks_used: u64,
+    /// сообщение для переходов после исчерпания
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exhausted_message: Option<String>,
+    /// когда ссылку удалили в корзину, мс unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trashed_at: Option<u64>,
+    /// С какого времени не отвечает хотя бы одна цель, мс unix. Меняется
+    /// только когда цели перестают или снова начинают отвечать, итоги
+    /// каждой проверки лежат в [`LinkHealthStore`](crate::app::link_health::LinkHealthStore).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failing_since: Option<u64>,
+    /// когда ссылку отключили из-за неотвечающей цели, мс unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_at: Option<u64>,
+}
+
+impl Link {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            password_hash: None,
+            meta: LinkMeta::default(),
+            redirect: RedirectRules::default(),
+            targets: Vec::new(),
+            rules: Vec::new(),
+            max_clicks: None,
+            clicks_used: 0,
+            exhausted_message: None,
+            trashed_at: None,
+            failing_since: None,
+            disabled_at: None,
+        }
+    }
+
+    /// ссылка, открыть которую можно только зная пароль
+    pub fn protected(url: impl Into<String>, password: &str) -> Result<Self, AppError> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        Ok(Self {
+            url: url.into(),
+            password_hash: Some(hash.to_string()),
+            meta: LinkMeta::default(),
+            redirect: RedirectRules::default(),
+            targets: Vec::new(),
+            rules: Vec::new(),
+            max_clicks: None,
+            clicks_used: 0,
+            exhausted_message: None,
+            trashed_at: None,
+            failing_since: None,
+            disabled_at: None,
+        })
+    }
+
+    pub fn with_meta(self, meta: LinkMeta) -> Self {
+        Self { meta, ..self }
+    }
+
+    pub fn with_options(mut self, options: LinkOptions) -> Self {
+        options.apply(&mut self);
+        self
+    }
+
+    /// `Gone`, если ссылка в корзине, отключена или переходы по ней закончились
+    pub fn check_available(&self) -> Result<(), AppError> {
+        if self.is_trashed() {
+            return Err(AppError::Gone(None));
+        }
+        if self.disabled_at.is_some() {
+            return Err(AppError::Gone(Some(
+                "The link target is no longer responding".to_owned(),
+            )));
+        }
+        match self.max_clicks {
+            Some(max) if self.clicks_used >= max => {
+                Err(AppError::Gone(self.exhausted_message.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Списание перехода у ссылки с ограничением. Хранилища вызывают его
+    /// под той же блокировкой, что и запись, чтобы последний переход
+    /// не достался двум запросам.
+    pub fn take_click(&mut self) -> Result<(), AppError> {
+        self.check_available()?;
+        if self.max_clicks.is_some() {
+            self.clicks_used += 1;
+        }
+        Ok(())
+    }
+
+    /// все url, на которые может вести ссылка: основной, цели сплита и цели правил
+    pub fn target_urls(&self) -> BTreeSet<&str> {
+        std::iter::once(self.url.as_str())
+            .chain(self.targets.iter().map(|target| target.url.as_str()))
+            .chain(self.rules.iter().map(|rule| rule.target.as_str()))
+            .collect()
+    }
+
+    pub fn is_trashed(&self) -> bool {
+        self.trashed_at.is_some()
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// проверка пароля, для незащищённой ссылки подходит любой
+    pub fn verify_password(&self, password: &str) -> Result<bool, AppError> {
+        let Some(hash) = &self.password_hash else {
+            return Ok(true);
+        };
+        let hash = PasswordHash::new(hash).map_err(|e| AppError::Internal(e.to_string()))?;
+
+        Ok(Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok())
+    }
+}
+
+/// Настройки ссылки, задаваемые при создании и изменении. Незаданная
+/// настройка остаётся как есть, у новой ссылки это значение по умолчанию.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkOptions {
+    pub meta: MetaChange,
+    pub passthrough: Option<Passthrough>,
+    /// `Some(None)` убирает utm метки
+    pub utm: Option<Option<UtmTemplate>>,
+    pub targets: Option<Vec<WeightedTarget>>,
+    pub rules: Option<Vec<RouteRule>>,
+    pub max_clicks: ClickLimit,
+    /// `Some(None)` возвращает стандартный ответ 410
+    pub exhausted_message: Option<Option<String>>,
+}
+
+/// Что сделать с ограничением переходов. Изменение ссылки без нового
+/// ограничения оставляет и его, и уже списанные переходы.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClickLimit {
+    #[default]
+    Keep,
+    Remove,
+    /// после стольких переходов ссылка перестаёт работать, списанные переходы сохраняются
+    Set(u64),
+}
+
+impl ClickLimit {
+    pub fn apply(self, link: &mut Link) {
+        match self {
+            ClickLimit::Keep => {}
+            ClickLimit::Remove => {
+                link.max_clicks = None;
+                link.clicks_used = 0;
+            }
+            ClickLimit::Set(max) => link.max_clicks = Some(max),
+        }
+    }
+}
+
+/// Изменение описания ссылки. Отсутствующее поле остаётся как есть,
+/// `Some(None)` стирает заголовок или заметку, пустой список — теги.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetaChange {
+    pub title: Option<Option<String>>,
+    pub note: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
+}
+
+impl MetaChange {
+    /// проверка и нормализация заданных полей по правилам [`LinkMeta::new`]
+    fn validate(&mut self) -> Result<(), AppError> {
+        let meta = LinkMeta::new(
+            self.title.clone().flatten(),
+            self.note.clone().flatten(),
+            self.tags.clone().unwrap_or_default(),
+        )?;
+        if self.title.is_some() {
+            self.title = Some(meta.title);
+        }
+        if self.note.is_some() {
+            self.note = Some(meta.note);
+        }
+        if self.tags.is_some() {
+            self.tags = Some(meta.tags.into_iter().collect());
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, meta: &mut LinkMeta) {
+        if let Some(title) = &self.title {
+            meta.title = title.clone();
+        }
+        if let Some(note) = &self.note {
+            meta.note = note.clone();
+        }
+        if let Some(tags) = &self.tags {
+            meta.tags = tags.iter().cloned().collect();
+        }
+    }
+}
+
+impl LinkOptions {
+    pub const MAX_MESSAGE_LEN: usize = 1024;
+
+    /// проверка и нормализация настроек
+    pub fn validate(&mut self) -> Result<(), AppError> {
+        self.meta.validate()?;
+        if let Some(targets) = &self.targets {
+            split::validate(targets)?;
+        }
+        if let Some(rules) = &mut self.rules {
+            routing::validate(rules)?;
+        }
+        if self.max_clicks == ClickLimit::Set(0) {
+            return Err(AppError::InvalidInput(
+                "max_clicks must be positive".to_owned(),
+            ));
+        }
+        if let Some(message) = &mut self.exhausted_message {
+            *message = non_empty(message.take(), "message", Self::MAX_MESSAGE_LEN)?;
+        }
+        Ok(())
+    }
+
+    /// все заданные адреса, на которые может вести ссылка, кроме основного
+    pub fn destinations(&self) -> impl Iterator<Item = &str> {
+        self.targets
+            .iter()
+            .flatten()
+            .map(|t| t.url.as_str())
+            .chain(self.rules.iter().flatten().map(|r| r.target.as_str()))
+    }
+
+    /// перенос заданных настроек в ссылку
+    pub fn apply(&self, link: &mut Link) {
+        self.meta.apply(&mut link.meta);
+        if let Some(passthrough) = self.passthrough {
+            link.redirect.passthrough = passthrough;
+        }
+        if let Some(utm) = &self.utm {
+            link.redirect.utm = utm.clone();
+        }
+        if let Some(targets) = &self.targets {
+            link.targets = targets.clone();
+        }
+        if let Some(rules) = &self.rules {
+            link.rules = rules.clone();
+        }
+        self.max_clicks.apply(link);
+        if let Some(message) = &self.exhausted_message {
+            link.exhausted_message = message.clone();
+        }
+    }
+}
+
+/// описание ссылки для поиска и группировки
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct LinkMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// произвольная заметка
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+}
+
+impl LinkMeta {
+    pub const MAX_TITLE_LEN: usize = 256;
+    pub const MAX_NOTE_LEN: usize = 4096;
+    pub const MAX_TAGS: usize = 32;
+    pub const MAX_TAG_LEN: usize = 64;
+
+    /// Проверенное описание. Пустые заголовок и заметка отбрасываются,
+    /// теги приводятся к нижнему регистру без пробелов по краям.
+    pub fn new(
+        title: Option<String>,
+        note: Option<String>,
+        tags: Vec<String>,
+    ) -> Result<Self, AppError> {
+        let title = non_empty(title, "title", Self::MAX_TITLE_LEN)?;
+        let note = non_empty(note, "note", Self::MAX_NOTE_LEN)?;
+
+        let mut normalized = BTreeSet::new();
+        for tag in tags {
+            let tag = tag.trim().to_lowercase();
+            if tag.is_empty() || tag.chars().count() > Self::MAX_TAG_LEN {
+                return Err(AppError::InvalidInput(format!(
+                    "tag must be 1 to {} characters long",
+                    Self::MAX_TAG_LEN
+                )));
+            }
+            if tag.contains(char::is_whitespace) || tag.contains(',') {
+                return Err(AppError::InvalidInput(format!(
+                    "tag {tag} must not contain spaces or commas"
+                )));
+            }
+            normalized.insert(tag);
+        }
+        if normalized.len() > Self::MAX_TAGS {
+            return Err(AppError::InvalidInput(format!(
+                "at most {} tags are allowed",
+                Self::MAX_TAGS
+            )));
+        }
+
+        Ok(Self {
+            title,
+            note,
+            tags: normalized,
+        })
+    }
+
+    /// вхождение текста в заголовок или заметку без учёта регистра
+    pub fn mentions(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        [&self.title, &self.note]
+            .into_iter()
+            .flatten()
+            .any(|field| field.to_lowercase().contains(&text))
+    }
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn non_empty(
+    value: Option<String>,
+    field: &str,
+    max_len: usize,
+) -> Result<Option<String>, AppError> {
+    let Some(value) = value.map(|v| v.trim().to_owned()).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+    if value.chars().count() > max_len {
+        return Err(AppError::InvalidInput(format!(
+            "{field} must be at most {max_len} characters long"
+        )));
+    }
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_link_keeps_only_hash() {
+        // when
+        let link = Link::protected("https://google.com", "secret").unwrap();
+
+        // then
+        assert!(link.is_protected());
+        assert!(!link.password_hash.as_ref().unwrap().contains("secret"));
+        assert_eq!(link.verify_password("secret"), Ok(true));
+        assert_eq!(link.verify_password("wrong"), Ok(false));
+    }
+
+    #[test]
+    fn meta_tags_are_normalized() {
+        // when
+        let meta = LinkMeta::new(
+            Some("  Docs ".to_owned()),
+            Some(String::new()),
+            vec!["Rust".to_owned(), "rust ".to_owned(), "web".to_owned()],
+        )
+        .unwrap();
+
+        // then
+        assert_eq!(meta.title.as_deref(), Some("Docs"));
+        assert_eq!(meta.note, None);
+        assert_eq!(
+            meta.tags,
+            BTreeSet::from(["rust".to_owned(), "web".to_owned()])
+        );
+        assert!(meta.mentions("DOC"));
+        assert!(LinkMeta::new(None, None, vec!["two words".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn click_limit_survives_edits() {
+        // given
+        let mut link = Link::new("https://a.example").with_options(LinkOptions {
+            max_clicks: ClickLimit::Set(1),
+            ..Default::default()
+        });
+        link.take_click().unwrap();
+
+        // when
+        ClickLimit::Keep.apply(&mut link);
+        let kept = link.check_available();
+        ClickLimit::Set(3).apply(&mut link);
+        let raised = (link.clicks_used, link.check_available());
+        ClickLimit::Remove.apply(&mut link);
+
+        // then
+        assert_eq!(kept, Err(AppError::Gone(None)));
+        assert_eq!(raised, (1, Ok(())));
+        assert_eq!((link.max_clicks, link.clicks_used), (None, 0));
+    }
+}
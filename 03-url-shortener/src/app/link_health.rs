@@ -0,0 +1,147 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::error::AppError;
+
+/// ответ цели на проверку
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Probe {
+    /// код http ответа
+    Status(u16),
+    /// ответа нет: ошибка сети, tls или таймаут
+    Failed(String),
+}
+
+impl Probe {
+    /// Неудача: цель не ответила, ответила 404, 410 или ошибкой сервера.
+    /// 401, 403 и 429 значат, что страница есть, но проверку к ней не пустили.
+    pub fn is_failure(&self) -> bool {
+        match self {
+            Probe::Status(status) => matches!(status, 404 | 410) || *status >= 500,
+            Probe::Failed(_) => true,
+        }
+    }
+}
+
+/// итоги проверок цели ссылки
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkHealth {
+    /// код последнего ответа, `None` если ответа не было
+    pub last_status: Option<u16>,
+    /// почему не было ответа
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// время последней проверки, мс unix
+    pub last_checked: u64,
+    /// сколько проверок подряд закончились неудачей
+    pub consecutive_failures: u32,
+    /// время первой из этих неудач, мс unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failing_since: Option<u64>,
+}
+
+impl LinkHealth {
+    /// итоги после проверки в момент `now`
+    pub fn after(previous: Option<&LinkHealth>, probe: &Probe, now: u64) -> Self {
+        let (last_status, last_error) = match probe {
+            Probe::Status(status) => (Some(*status), None),
+            Probe::Failed(error) => (None, Some(error.clone())),
+        };
+        let (consecutive_failures, failing_since) = match previous {
+            _ if !probe.is_failure() => (0, None),
+            Some(previous) if previous.consecutive_failures > 0 => (
+                previous.consecutive_failures + 1,
+                previous.failing_since.or(Some(now)),
+            ),
+            _ => (1, Some(now)),
+        };
+
+        Self {
+            last_status,
+            last_error,
+            last_checked: now,
+            consecutive_failures,
+            failing_since,
+        }
+    }
+
+    /// цель не отвечает с `before` (мс unix) или раньше
+    pub fn failing_before(&self, before: u64) -> bool {
+        self.failing_since.is_some_and(|since| since <= before)
+    }
+}
+
+/// Итоги проверок по целям ссылок.
+///
+/// Итоги меняются на каждом проходе проверки, поэтому хранятся отдельно
+/// от ссылок и без событий; в ссылку попадают только переходы между
+/// состояниями: цель перестала отвечать, снова ответила, ссылку отключили.
+pub trait LinkHealthStore: Send + Sync {
+    /// итоги по url целей ссылки, пусто если ссылку не проверяли
+    fn get(&self, short_url: &str) -> Result<BTreeMap<String, LinkHealth>, AppError>;
+    /// итоги ссылки целиком, пустые итоги удаляют запись
+    fn put(&self, short_url: &str, health: BTreeMap<String, LinkHealth>) -> Result<(), AppError>;
+    /// удаление итогов ссылок, которых нет среди `short_urls`
+    fn retain(&self, short_urls: &HashSet<String>) -> Result<(), AppError>;
+}
+
+/// Порт проверки целей.
+///
+/// Каждый url из списка проверяется один раз, в ответе есть итог для каждого.
+pub trait TargetProber: Send + Sync {
+    fn probe_all(&self, urls: Vec<String>) -> impl Future<Output = HashMap<String, Probe>> + Send;
+}
+
+/// Какие ссылки проверяет этот узел.
+///
+/// Ключи — как в общем хранилище, с пространством имён арендатора.
+pub trait CheckScope: Send + Sync {
+    fn covers(&self, key: &str) -> bool;
+}
+
+/// одиночный узел проверяет все ссылки
+pub struct AllLinks;
+
+impl CheckScope for AllLinks {
+    fn covers(&self, _key: &str) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_are_counted_until_target_answers() {
+        // given
+        let probes = [
+            (10, Probe::Status(404)),
+            (20, Probe::Failed("connection refused".to_owned())),
+            (30, Probe::Status(503)),
+            (40, Probe::Status(403)),
+        ];
+
+        // when
+        let mut history: Vec<LinkHealth> = Vec::new();
+        for (now, probe) in &probes {
+            history.push(LinkHealth::after(history.last(), probe, *now));
+        }
+
+        // then
+        let failures: Vec<(u32, Option<u64>)> = history
+            .iter()
+            .map(|health| (health.consecutive_failures, health.failing_since))
+            .collect();
+        assert_eq!(
+            failures,
+            vec![(1, Some(10)), (2, Some(10)), (3, Some(10)), (0, None)]
+        );
+        assert_eq!(history[1].last_status, None);
+        assert_eq!(history[1].last_error.as_deref(), Some("connection refused"));
+        assert!(history[2].failing_before(10));
+        assert!(!history[2].failing_before(9));
+        assert!(!history[3].failing_before(u64::MAX));
+    }
+}
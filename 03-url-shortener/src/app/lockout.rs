@@ -0,0 +1,260 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+
+use crate::app::error::AppError;
+
+/// настройки блокировки после неудачных попыток ввода пароля
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockoutConfig {
+    /// число неудачных попыток до блокировки
+    pub max_failures: u32,
+    /// на сколько блокируется клиент
+    pub lock_for: Duration,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            lock_for: Duration::from_secs(300),
+        }
+    }
+}
+
+struct Attempts {
+    failures: u32,
+    /// проверок пароля, которые идут сейчас и ещё не записаны
+    in_flight: u32,
+    last_failure: Option<Instant>,
+    locked_until: Option<Instant>,
+}
+
+impl Attempts {
+    fn new() -> Self {
+        Self {
+            failures: 0,
+            in_flight: 0,
+            last_failure: None,
+            locked_until: None,
+        }
+    }
+
+    /// запись больше ничего не ограничивает и её можно удалить
+    fn expired(&self, now: Instant, lock_for: Duration) -> bool {
+        self.in_flight == 0
+            && self.locked_until.is_none_or(|until| until <= now)
+            && self
+                .last_failure
+                .is_none_or(|at| now.duration_since(at) >= lock_for)
+    }
+}
+
+/// Учёт неудачных попыток ввода пароля по ключу клиента.
+///
+/// Попытка занимается в [`Lockout::reserve`] до проверки пароля, поэтому
+/// параллельные запросы не проверят больше паролей, чем разрешено.
+/// Записи, которые уже ничего не ограничивают, время от времени удаляются.
+#[derive(Default)]
+pub struct Lockout {
+    config: LockoutConfig,
+    attempts: DashMap<String, Attempts>,
+    /// занятых попыток с последней чистки
+    reserved: AtomicUsize,
+}
+
+impl Lockout {
+    /// как часто удалять записи, которые уже ничего не ограничивают
+    const EVICT_EVERY: usize = 1024;
+
+    pub fn new(config: LockoutConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Занять попытку ввода пароля, ошибка если клиент заблокирован
+    /// или все его попытки уже заняты идущими проверками.
+    pub fn reserve(&self, key: &str) -> Result<Attempt<'_>, AppError> {
+        if self.reserved.fetch_add(1, Ordering::Relaxed) % Self::EVICT_EVERY
+            == Self::EVICT_EVERY - 1
+        {
+            self.evict_expired();
+        }
+        let now = Instant::now();
+        let mut attempts = self
+            .attempts
+            .entry(key.to_owned())
+            .or_insert_with(Attempts::new);
+        if attempts.expired(now, self.config.lock_for) {
+            // блокировка истекла или давно не ошибались, считаем попытки заново
+            *attempts = Attempts::new();
+        }
+        if attempts.locked_until.is_some_and(|until| until > now)
+            || attempts.failures + attempts.in_flight >= self.config.max_failures
+        {
+            return Err(AppError::TooManyAttempts);
+        }
+        attempts.in_flight += 1;
+        Ok(Attempt {
+            lockout: self,
+            key: key.to_owned(),
+            released: false,
+        })
+    }
+
+    fn release(&self, key: &str, failed: bool) {
+        let now = Instant::now();
+        if let Some(mut attempts) = self.attempts.get_mut(key) {
+            attempts.in_flight = attempts.in_flight.saturating_sub(1);
+            if failed {
+                attempts.failures += 1;
+                attempts.last_failure = Some(now);
+            } else {
+                attempts.failures = 0;
+                attempts.last_failure = None;
+            }
+            // идущая блокировка не продлевается и не сбрасывается
+            if attempts.failures >= self.config.max_failures && attempts.locked_until.is_none() {
+                attempts.locked_until = Some(now + self.config.lock_for);
+            }
+        }
+        self.attempts.remove_if(key, |_, attempts| {
+            attempts.expired(now, self.config.lock_for)
+        });
+    }
+
+    fn evict_expired(&self) {
+        let now = Instant::now();
+        self.attempts
+            .retain(|_, attempts| !attempts.expired(now, self.config.lock_for));
+    }
+}
+
+/// Занятая попытка ввода пароля. Если её не отметить, она освобождается
+/// без записи неудачи, например когда ссылки не оказалось.
+pub struct Attempt<'a> {
+    lockout: &'a Lockout,
+    key: String,
+    released: bool,
+}
+
+impl Attempt<'_> {
+    /// неверный пароль
+    pub fn failed(mut self) {
+        self.release(true);
+    }
+
+    /// верный пароль, счётчик неудач клиента сбрасывается
+    pub fn succeeded(mut self) {
+        self.release(false);
+    }
+
+    fn release(&mut self, failed: bool) {
+        if !self.released {
+            self.released = true;
+            self.lockout.release(&self.key, failed);
+        }
+    }
+}
+
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        if !self.released {
+            // без пароля неудачей не считается, но и счётчик не сбрасывается
+            self.released = true;
+            if let Some(mut attempts) = self.lockout.attempts.get_mut(&self.key) {
+                attempts.in_flight = attempts.in_flight.saturating_sub(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockout(max_failures: u32, lock_for: Duration) -> Lockout {
+        Lockout::new(LockoutConfig {
+            max_failures,
+            lock_for,
+        })
+    }
+
+    #[test]
+    fn locks_after_max_failures() {
+        // given
+        let lockout = lockout(2, Duration::from_secs(60));
+
+        // when
+        lockout.reserve("client").unwrap().failed();
+        let after_one = lockout.reserve("client").map(drop);
+        lockout.reserve("client").unwrap().failed();
+        let after_two = lockout.reserve("client").map(drop);
+
+        // then
+        assert_eq!(after_one, Ok(()));
+        assert_eq!(after_two, Err(AppError::TooManyAttempts));
+        assert_eq!(lockout.reserve("other").map(drop), Ok(()));
+    }
+
+    #[test]
+    fn parallel_attempts_count_against_the_limit() {
+        // given
+        let lockout = lockout(2, Duration::from_secs(60));
+
+        // when
+        let first = lockout.reserve("client").unwrap();
+        let second = lockout.reserve("client").unwrap();
+        let third = lockout.reserve("client").map(drop);
+        drop(first);
+        let after_release = lockout.reserve("client").map(drop);
+        second.failed();
+
+        // then
+        assert_eq!(third, Err(AppError::TooManyAttempts));
+        assert_eq!(after_release, Ok(()));
+        assert_eq!(lockout.attempts.get("client").unwrap().failures, 1);
+    }
+
+    #[test]
+    fn parallel_failures_lock_once() {
+        // given
+        let lockout = lockout(2, Duration::from_secs(60));
+        let first = lockout.reserve("client").unwrap();
+        let second = lockout.reserve("client").unwrap();
+
+        // when
+        first.failed();
+        second.failed();
+
+        // then
+        assert_eq!(
+            lockout.reserve("client").map(drop),
+            Err(AppError::TooManyAttempts)
+        );
+        let attempts = lockout.attempts.get("client").unwrap();
+        assert_eq!((attempts.failures, attempts.in_flight), (2, 0));
+        assert!(attempts.locked_until.is_some());
+    }
+
+    #[test]
+    fn lock_expires_and_is_evicted() {
+        // given
+        let lockout = lockout(1, Duration::ZERO);
+
+        // when
+        lockout.reserve("client").unwrap().failed();
+        let after = lockout.reserve("client").map(drop);
+        lockout.reserve("other").unwrap().succeeded();
+        lockout.evict_expired();
+
+        // then
+        assert_eq!(after, Ok(()));
+        assert!(lockout.attempts.is_empty());
+    }
+}
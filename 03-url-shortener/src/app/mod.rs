@@ -1,5 +1,19 @@
+pub mod audit;
 pub mod command;
+pub mod error;
+pub mod event;
+pub mod health;
+pub mod idempotency;
+pub mod link;
+pub mod link_health;
+pub mod lockout;
+pub mod policy;
 pub mod query;
+pub mod redirect;
+pub mod routing;
+pub mod split;
+pub mod tenant;
+pub mod webhook;
 
 #[cfg(test)]
 mod tests {
@@ -9,7 +23,8 @@ mod tests {
     use crate::{
         adapters::in_memory_repository::InMemoryRepository,
         app::{
-            command::create_short_url::CreateShortUrlCommand, query::get_full_url::GetFullUrlQuery,
+            audit::Actor, command::create_short_url::CreateShortUrlCommand, link::Link,
+            query::get_full_url::GetFullUrlQuery,
         },
         id_provider::NanoIdProvider,
     };
@@ -18,7 +33,7 @@ mod tests {
     async fn create_and_get_short_url() {
         // given
         let idp = NanoIdProvider;
-        let store: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
         let repo = InMemoryRepository::new(store.clone());
 
         let create_command = CreateShortUrlCommand::new(idp, repo.clone());
@@ -26,7 +41,7 @@ mod tests {
 
         // when
         let res = create_command
-            .execute("https://google.com".to_owned())
+            .execute("https://google.com".to_owned(), &Actor::system())
             .await
             .unwrap();
         let res2 = get_query.execute(&res).await.unwrap();
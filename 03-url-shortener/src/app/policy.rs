@@ -0,0 +1,189 @@
+use std::{collections::HashSet, sync::Arc};
+
+use url::Url;
+
+use crate::app::error::AppError;
+
+/// политика допустимых целей для коротких ссылок
+pub trait TargetPolicy: Send + Sync {
+    fn check(&self, full_url: &str) -> Result<(), AppError>;
+}
+
+/// политика, пропускающая любые цели
+pub struct AllowAll;
+
+impl TargetPolicy for AllowAll {
+    fn check(&self, _full_url: &str) -> Result<(), AppError> {
+        Ok(())
+    }
+}
+
+/// политика, которой должны удовлетворять все вложенные политики
+pub struct AllOf(pub Vec<Arc<dyn TargetPolicy>>);
+
+impl TargetPolicy for AllOf {
+    fn check(&self, full_url: &str) -> Result<(), AppError> {
+        self.0.iter().try_for_each(|policy| policy.check(full_url))
+    }
+}
+
+/// Набор хостов.
+///
+/// Строка `example.com` совпадает только с этим хостом,
+/// `*.example.com` — с ним самим и со всеми поддоменами.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostList {
+    exact: HashSet<String>,
+    suffixes: Vec<String>,
+}
+
+impl HostList {
+    /// разбор списка по одному хосту на строку, `#` начинает комментарий
+    pub fn parse(text: &str) -> Self {
+        let mut list = HostList::default();
+        for line in text.lines() {
+            let entry = line.split('#').next().unwrap_or_default().trim();
+            if !entry.is_empty() {
+                list.add(entry);
+            }
+        }
+        list
+    }
+
+    pub fn add(&mut self, entry: &str) {
+        let entry = entry.trim_end_matches('.').to_lowercase();
+        match entry.strip_prefix("*.") {
+            Some(suffix) => self.suffixes.push(suffix.to_owned()),
+            None => {
+                self.exact.insert(entry);
+            }
+        }
+    }
+
+    pub fn contains(&self, host: &str) -> bool {
+        self.exact.contains(host)
+            || self.suffixes.iter().any(|suffix| {
+                host == suffix
+                    || host
+                        .strip_suffix(suffix.as_str())
+                        .is_some_and(|rest| rest.ends_with('.'))
+            })
+    }
+}
+
+/// Политика по хостам целей.
+///
+/// Цель должна быть http(s) url с хостом. Запрещены хосты из блок-листа
+/// и собственные короткие домены, чтобы нельзя было собрать цикл редиректов.
+/// Если задан allow-лист, разрешены только хосты из него.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostPolicy {
+    pub blocklist: HostList,
+    pub allowlist: Option<HostList>,
+    pub own_domains: HostList,
+}
+
+impl TargetPolicy for HostPolicy {
+    fn check(&self, full_url: &str) -> Result<(), AppError> {
+        let url = Url::parse(full_url).map_err(|e| AppError::InvalidUrl(e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AppError::InvalidUrl(format!(
+                "unsupported scheme {}",
+                url.scheme()
+            )));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| AppError::InvalidUrl("url has no host".to_owned()))?
+            .trim_end_matches('.');
+
+        if self.own_domains.contains(host) {
+            return Err(AppError::TargetRejected(format!(
+                "{host} is our own short domain"
+            )));
+        }
+        if self.blocklist.contains(host) {
+            return Err(AppError::TargetRejected(format!("{host} is blocked")));
+        }
+        if let Some(allowlist) = &self.allowlist
+            && !allowlist.contains(host)
+        {
+            return Err(AppError::TargetRejected(format!("{host} is not allowed")));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_exact_hosts_and_suffixes() {
+        // given
+        let policy = HostPolicy {
+            blocklist: HostList::parse("evil.com\n*.phish.net # spam\n"),
+            ..Default::default()
+        };
+
+        // then
+        assert!(policy.check("https://evil.com/login").is_err());
+        assert!(policy.check("https://EVIL.com./login").is_err());
+        assert!(policy.check("https://a.b.phish.net").is_err());
+        assert!(policy.check("https://phish.net").is_err());
+        assert_eq!(policy.check("https://sub.evil.com"), Ok(()));
+        assert_eq!(policy.check("https://notphish.net"), Ok(()));
+    }
+
+    #[test]
+    fn allowlist_only_mode() {
+        // given
+        let policy = HostPolicy {
+            allowlist: Some(HostList::parse("*.corp.example")),
+            ..Default::default()
+        };
+
+        // then
+        assert_eq!(policy.check("https://wiki.corp.example/page"), Ok(()));
+        assert_eq!(
+            policy.check("https://google.com"),
+            Err(AppError::TargetRejected(
+                "google.com is not allowed".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_own_short_domain() {
+        // given
+        let policy = HostPolicy {
+            own_domains: HostList::parse("sho.rt"),
+            ..Default::default()
+        };
+
+        // when
+        let result = policy.check("https://sho.rt/abc");
+
+        // then
+        assert_eq!(
+            result,
+            Err(AppError::TargetRejected(
+                "sho.rt is our own short domain".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_urls() {
+        // given
+        let policy = HostPolicy::default();
+
+        // then
+        assert!(matches!(policy.check("test"), Err(AppError::InvalidUrl(_))));
+        assert!(matches!(
+            policy.check("javascript:alert(1)"),
+            Err(AppError::InvalidUrl(_))
+        ));
+    }
+}
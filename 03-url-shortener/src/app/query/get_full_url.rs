@@ -1,11 +1,65 @@
+use std::sync::Arc;
+
+use crate::{
+    app::{
+        error::AppError,
+        event::now_millis,
+        link::Link,
+        lockout::{Lockout, LockoutConfig},
+        routing::{self, RouteRequest},
+        split,
+    },
+    id_provider::IDProvider,
+};
+
+/// сведения о переходе, от которых зависит его адрес
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Visit {
+    /// query string запроса
+    pub query: String,
+    /// устойчивый признак посетителя, например его ip и user agent
+    pub visitor: String,
+    /// ключ цели сплит-ссылки, выбранной посетителю при прошлом переходе
+    pub variant: Option<String>,
+    pub user_agent: String,
+    pub accept_language: String,
+    /// момент перехода в секундах unix, по умолчанию текущий
+    pub at: Option<u64>,
+}
+
+/// адрес перехода по ссылке
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    /// код, под которым ссылка сохранена, он может отличаться от введённого
+    pub short_url: String,
+    pub url: String,
+    /// номер сработавшего правила маршрутизации
+    pub rule: Option<usize>,
+    /// выбранная цель, если ссылка делит трафик и ни одно правило не сработало
+    pub variant: Option<Variant>,
+}
+
+/// цель сплит-ссылки, выбранная для перехода
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub index: usize,
+    /// ключ цели для cookie, см. [`split::key`]
+    pub key: String,
+    /// адрес цели без добавленных параметров
+    pub url: String,
+}
+
 pub trait GetFullUrlRepository {
-    fn get(&self, short_url: &str) -> Result<String, String>;
+    fn get(&self, short_url: &str) -> Result<Link, AppError>;
 }
 pub struct GetFullUrlQuery<R>
 where
     R: GetFullUrlRepository,
 {
     repo: R,
+    lockout: Lockout,
+    /// формат кодов для приведения введённого кода, `None` чтобы искать как есть
+    codes: Option<Arc<dyn IDProvider + Send + Sync>>,
 }
 
 impl<R> GetFullUrlQuery<R>
@@ -13,22 +67,154 @@ where
     R: GetFullUrlRepository,
 {
     pub fn new(repo: R) -> Self {
-        Self { repo }
+        Self::with_lockout(repo, LockoutConfig::default())
+    }
+
+    pub fn with_lockout(repo: R, lockout: LockoutConfig) -> Self {
+        Self {
+            repo,
+            lockout: Lockout::new(lockout),
+            codes: None,
+        }
+    }
+
+    /// приводить введённые коды к формату, в котором их выдаёт `codes`
+    pub fn with_codes(self, codes: Arc<dyn IDProvider + Send + Sync>) -> Self {
+        Self {
+            codes: Some(codes),
+            ..self
+        }
+    }
+
+    /// Ссылка и код, под которым она сохранена. Опечатки, которые формат кода
+    /// умеет исправлять, исправляются, а код с неисправимой опечаткой
+    /// отклоняется без обращения к хранилищу. Код, не похожий на коды формата,
+    /// ищется как есть: ссылки, созданные до смены формата, остаются доступны.
+    fn lookup(&self, short_url: &str) -> Result<(String, Link), AppError> {
+        let code = match &self.codes {
+            Some(codes) => codes.normalize(short_url)?,
+            None => None,
+        }
+        .unwrap_or_else(|| short_url.to_owned());
+        self.repo.get(&code).map(|link| (code, link))
     }
 
-    pub async fn execute(&self, short_url: &str) -> Result<String, String> {
-        self.repo.get(short_url)
+    pub async fn execute(&self, short_url: &str) -> Result<String, AppError> {
+        Ok(self.resolve(short_url, &Visit::default()).await?.url)
+    }
+
+    /// Адрес перехода: цель, выбранная посетителю, с utm метками ссылки и,
+    /// если ссылка это разрешает, параметрами из query string запроса.
+    pub async fn resolve(&self, short_url: &str, visit: &Visit) -> Result<Resolved, AppError> {
+        let (short_url, link) = self.lookup(short_url)?;
+        link.check_available()?;
+        if link.is_protected() {
+            return Err(AppError::PasswordRequired);
+        }
+        Ok(resolve_link(&short_url, &link, visit))
+    }
+
+    /// сохранённая ссылка со всеми настройками, для администрирования
+    pub async fn details(&self, short_url: &str) -> Result<Link, AppError> {
+        self.lookup(short_url).map(|(_, link)| link)
+    }
+
+    /// Адрес перехода без проверки пароля и учёта перехода,
+    /// для проверки настроек ссылки.
+    pub async fn preview(&self, short_url: &str, visit: &Visit) -> Result<Resolved, AppError> {
+        let (short_url, link) = self.lookup(short_url)?;
+        Ok(resolve_link(&short_url, &link, visit))
+    }
+
+    /// Получение полного url защищённой ссылки.
+    ///
+    /// После нескольких неверных паролей клиент на время блокируется
+    /// для этой ссылки, даже если следующий пароль верный.
+    pub async fn execute_with_password(
+        &self,
+        short_url: &str,
+        password: String,
+        client: &str,
+        visit: &Visit,
+    ) -> Result<Resolved, AppError> {
+        let (short_url, link) = self.lookup(short_url)?;
+        let key = format!("{client}/{short_url}");
+        let attempt = self.lockout.reserve(&key)?;
+        link.check_available()?;
+        let (link, verified) = tokio::task::spawn_blocking(move || {
+            let verified = link.verify_password(&password);
+            (link, verified)
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+
+        if !verified? {
+            attempt.failed();
+            return Err(AppError::InvalidPassword);
+        }
+        attempt.succeeded();
+        Ok(resolve_link(&short_url, &link, visit))
+    }
+}
+
+/// Выбор цели и построение адреса: первое подходящее правило, иначе цель
+/// сплит-ссылки, иначе основной url. Прошлый выбор цели сплит-ссылки
+/// сохраняется, пока такая цель есть у ссылки.
+fn resolve_link(short_url: &str, link: &Link, visit: &Visit) -> Resolved {
+    let request = RouteRequest {
+        user_agent: &visit.user_agent,
+        accept_language: &visit.accept_language,
+        query: &visit.query,
+        at: visit.at.unwrap_or_else(|| now_millis() / 1000),
+    };
+    if let Some((index, rule)) = routing::route(&link.rules, &request) {
+        return Resolved {
+            short_url: short_url.to_owned(),
+            url: link.redirect.apply(&rule.target, &visit.query),
+            rule: Some(index),
+            variant: None,
+        };
+    }
+
+    let variant = match link.targets.as_slice() {
+        [] => None,
+        targets => {
+            let index = visit
+                .variant
+                .as_deref()
+                .and_then(|key| split::find(targets, key))
+                .unwrap_or_else(|| split::pick(targets, short_url, &visit.visitor));
+            let url = targets[index].url.clone();
+            Some(Variant {
+                index,
+                key: split::key(&url),
+                url,
+            })
+        }
+    };
+    let target = variant
+        .as_ref()
+        .map_or(link.url.as_str(), |v| v.url.as_str());
+
+    Resolved {
+        short_url: short_url.to_owned(),
+        url: link.redirect.apply(target, &visit.query),
+        rule: None,
+        variant,
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::sync::Arc;
+    use std::{sync::Arc, time::Duration};
 
     use dashmap::DashMap;
     use tokio::join;
 
-    use crate::adapters::in_memory_repository::InMemoryRepository;
+    use crate::{
+        adapters::in_memory_repository::InMemoryRepository, app::split::WeightedTarget,
+        id_provider::IdStrategy,
+    };
 
     use super::*;
 
@@ -37,8 +223,8 @@ mod test {
         // given
         struct FakeRepository;
         impl GetFullUrlRepository for FakeRepository {
-            fn get(&self, _short_url: &str) -> Result<String, String> {
-                Ok("123".to_owned())
+            fn get(&self, _short_url: &str) -> Result<Link, AppError> {
+                Ok(Link::new("123"))
             }
         }
         let repo = FakeRepository;
@@ -54,8 +240,8 @@ mod test {
     #[tokio::test]
     async fn get_full_url_grom_inmemory_repo() {
         // given
-        let store: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
-        store.insert("123".to_owned(), "https://google.com".to_owned());
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
 
         let repo = InMemoryRepository::new(store);
         let query = GetFullUrlQuery::new(repo);
@@ -70,9 +256,9 @@ mod test {
     #[tokio::test]
     async fn get_two_diferent_full_url() {
         // given
-        let store: Arc<DashMap<String, String>> = Arc::new(DashMap::new());
-        store.insert("123".to_owned(), "https://google.com".to_owned());
-        store.insert("456".to_owned(), "https://github.com".to_owned());
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        store.insert("456".to_owned(), Link::new("https://github.com"));
 
         let repo = InMemoryRepository::new(store);
         let query = GetFullUrlQuery::new(repo);
@@ -87,4 +273,159 @@ mod test {
         assert_eq!(result1, Ok("https://google.com".to_owned()));
         assert_eq!(result2, Ok("https://github.com".to_owned()));
     }
+
+    #[tokio::test]
+    async fn protected_url_needs_password() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert(
+            "123".to_owned(),
+            Link::protected("https://google.com", "secret").unwrap(),
+        );
+        let query = GetFullUrlQuery::new(InMemoryRepository::new(store));
+
+        // when
+        let without_password = query.execute("123").await;
+        let with_password = query
+            .execute_with_password("123", "secret".to_owned(), "client", &Visit::default())
+            .await;
+
+        // then
+        assert_eq!(without_password, Err(AppError::PasswordRequired));
+        assert_eq!(with_password.unwrap().url, "https://google.com");
+    }
+
+    #[tokio::test]
+    async fn client_is_locked_after_wrong_passwords() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert(
+            "123".to_owned(),
+            Link::protected("https://google.com", "secret").unwrap(),
+        );
+        let query = GetFullUrlQuery::with_lockout(
+            InMemoryRepository::new(store),
+            LockoutConfig {
+                max_failures: 2,
+                lock_for: Duration::from_secs(60),
+            },
+        );
+
+        // when
+        let first = query
+            .execute_with_password("123", "wrong".to_owned(), "client", &Visit::default())
+            .await;
+        query
+            .execute_with_password("123", "wrong".to_owned(), "client", &Visit::default())
+            .await
+            .unwrap_err();
+        let locked = query
+            .execute_with_password("123", "secret".to_owned(), "client", &Visit::default())
+            .await;
+        let other_client = query
+            .execute_with_password("123", "secret".to_owned(), "other", &Visit::default())
+            .await;
+
+        // then
+        assert_eq!(first, Err(AppError::InvalidPassword));
+        assert_eq!(locked, Err(AppError::TooManyAttempts));
+        assert_eq!(other_client.unwrap().url, "https://google.com");
+    }
+
+    #[tokio::test]
+    async fn split_link_keeps_visitor_on_chosen_target() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let mut link = Link::new("https://a.example");
+        link.targets = ["https://a.example", "https://b.example"]
+            .map(|url| WeightedTarget {
+                url: url.to_owned(),
+                weight: 1,
+            })
+            .to_vec();
+        store.insert("123".to_owned(), link);
+        let query = GetFullUrlQuery::new(InMemoryRepository::new(store));
+        let visit = |visitor: &str, variant: Option<&str>| Visit {
+            visitor: visitor.to_owned(),
+            variant: variant.map(str::to_owned),
+            ..Default::default()
+        };
+
+        // when
+        let first = query.resolve("123", &visit("ip ua", None)).await.unwrap();
+        let again = query.resolve("123", &visit("ip ua", None)).await.unwrap();
+        let from_cookie = query
+            .resolve(
+                "123",
+                &visit("other", Some(&split::key("https://b.example"))),
+            )
+            .await
+            .unwrap();
+        let stale_cookie = query
+            .resolve("123", &visit("ip ua", Some("5")))
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(first, again);
+        assert_eq!(first.url, first.variant.as_ref().unwrap().url);
+        assert_eq!(from_cookie.url, "https://b.example");
+        assert_eq!(stale_cookie, first);
+    }
+
+    #[tokio::test]
+    async fn mistyped_code_is_normalized_before_lookup() {
+        // given
+        struct CountingRepository {
+            code: String,
+            lookups: std::sync::Mutex<Vec<String>>,
+        }
+        impl GetFullUrlRepository for &CountingRepository {
+            fn get(&self, short_url: &str) -> Result<Link, AppError> {
+                self.lookups.lock().unwrap().push(short_url.to_owned());
+                match short_url == self.code {
+                    true => Ok(Link::new("https://google.com")),
+                    false => Err(AppError::NotFound),
+                }
+            }
+        }
+        let strategy = IdStrategy::Crockford { length: 6 };
+        let repo = CountingRepository {
+            code: "7K0M1PT".to_owned(),
+            lookups: Default::default(),
+        };
+        let query = GetFullUrlQuery::new(&repo).with_codes(Arc::new(strategy));
+
+        // when
+        let resolved = query.resolve("7k-Om-lpt", &Visit::default()).await;
+        let wrong_check = query.execute("7K0M1PS").await;
+
+        // then
+        assert_eq!(resolved.unwrap().short_url, "7K0M1PT");
+        assert_eq!(
+            wrong_check,
+            Err(AppError::InvalidInput(
+                "7K0M1PS is not a valid short code".to_owned()
+            ))
+        );
+        assert_eq!(*repo.lookups.lock().unwrap(), ["7K0M1PT"]);
+    }
+
+    #[tokio::test]
+    async fn links_created_before_code_format_change_still_resolve() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("abc123".to_owned(), Link::new("https://a.example"));
+        store.insert("promo_24".to_owned(), Link::new("https://b.example"));
+        let query = GetFullUrlQuery::new(InMemoryRepository::new(store))
+            .with_codes(Arc::new(IdStrategy::Crockford { length: 6 }));
+
+        // when
+        let nanoid = query.execute("abc123").await;
+        let alias = query.resolve("promo_24", &Visit::default()).await;
+
+        // then
+        assert_eq!(nanoid, Ok("https://a.example".to_owned()));
+        assert_eq!(alias.unwrap().short_url, "promo_24");
+    }
 }
@@ -0,0 +1,21 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::app::{
+    error::AppError,
+    link_health::{LinkHealth, LinkHealthStore},
+};
+
+/// итоги последних проверок целей ссылки
+pub struct GetLinkHealthQuery {
+    store: Arc<dyn LinkHealthStore>,
+}
+
+impl GetLinkHealthQuery {
+    pub fn new(store: Arc<dyn LinkHealthStore>) -> Self {
+        Self { store }
+    }
+
+    pub async fn execute(&self, short_url: &str) -> Result<BTreeMap<String, LinkHealth>, AppError> {
+        self.store.get(short_url)
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use crate::app::error::AppError;
+
+/// статистика по короткой ссылке
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct LinkStats {
+    pub clicks: u64,
+    /// переходы по целям сплит-ссылки
+    pub targets: BTreeMap<String, u64>,
+}
+
+pub trait GetStatsRepository {
+    fn get_stats(&self, short_url: &str) -> Result<LinkStats, AppError>;
+}
+
+pub struct GetStatsQuery<R>
+where
+    R: GetStatsRepository,
+{
+    repo: R,
+}
+
+impl<R> GetStatsQuery<R>
+where
+    R: GetStatsRepository,
+{
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    pub async fn execute(&self, short_url: &str) -> Result<LinkStats, AppError> {
+        self.repo.get_stats(short_url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use dashmap::DashMap;
+
+    use crate::{
+        adapters::in_memory_repository::InMemoryRepository,
+        app::{command::record_click::RecordClickCommand, link::Link},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn stats_count_recorded_clicks() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let repo = InMemoryRepository::new(store);
+        let record_click = RecordClickCommand::new(repo.clone());
+        let query = GetStatsQuery::new(repo);
+
+        // when
+        record_click.execute("123").await.unwrap();
+        record_click.execute("123").await.unwrap();
+        let result = query.execute("123").await;
+
+        // then
+        assert_eq!(
+            result,
+            Ok(LinkStats {
+                clicks: 2,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn stats_for_unknown_url() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        let repo = InMemoryRepository::new(store);
+        let query = GetStatsQuery::new(repo);
+
+        // when
+        let result = query.execute("123").await;
+
+        // then
+        assert_eq!(result, Err(AppError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn stats_count_clicks_per_target() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://a.example"));
+        let repo = InMemoryRepository::new(store);
+        let record_click = RecordClickCommand::new(repo.clone());
+        let query = GetStatsQuery::new(repo);
+
+        // when
+        for target in [
+            "https://a.example",
+            "https://b.example",
+            "https://b.example",
+        ] {
+            record_click
+                .execute_for_target("123", Some(target))
+                .await
+                .unwrap();
+        }
+        let result = query.execute("123").await.unwrap();
+
+        // then
+        assert_eq!(result.clicks, 3);
+        assert_eq!(
+            result.targets,
+            BTreeMap::from([
+                ("https://a.example".to_owned(), 1),
+                ("https://b.example".to_owned(), 2),
+            ])
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+
+use crate::app::{
+    error::AppError,
+    link::{Link, LinkMeta},
+};
+
+/// короткая ссылка вместе с полным url, числом переходов и описанием
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ShortUrlEntry {
+    pub id: String,
+    pub url: String,
+    pub clicks: u64,
+    #[serde(flatten)]
+    pub meta: LinkMeta,
+    /// когда ссылку удалили в корзину, мс unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trashed_at: Option<u64>,
+    /// с какого момента не отвечает хотя бы одна цель, мс unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failing_since: Option<u64>,
+    /// когда ссылку отключили из-за неотвечающей цели, мс unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled_at: Option<u64>,
+}
+
+/// какие ссылки показывать по отношению к корзине
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashFilter {
+    /// только ссылки вне корзины
+    #[default]
+    Exclude,
+    /// все ссылки
+    Include,
+    /// только ссылки в корзине
+    Only,
+}
+
+/// условия поиска ссылок, пустые условия не ограничивают выборку
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+pub struct LinkFilter {
+    /// ссылка должна быть помечена этим тегом
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// текст в заголовке или заметке
+    #[serde(default)]
+    pub q: Option<String>,
+    /// ссылки из корзины по умолчанию не показываются
+    #[serde(default)]
+    pub trash: TrashFilter,
+    /// только ссылки, цель которых не прошла последнюю проверку
+    #[serde(default)]
+    pub failing: bool,
+    /// только ссылки хотя бы с одним тегом, для подсчёта тегов
+    #[serde(skip)]
+    pub tagged: bool,
+}
+
+impl LinkFilter {
+    pub fn matches(&self, entry: &ShortUrlEntry) -> bool {
+        self.admits(&entry.meta, entry.trashed_at, entry.failing_since)
+    }
+
+    /// то же, что [`LinkFilter::matches`], до сборки записи списка
+    pub fn matches_link(&self, link: &Link) -> bool {
+        self.admits(&link.meta, link.trashed_at, link.failing_since)
+    }
+
+    fn admits(&self, meta: &LinkMeta, trashed_at: Option<u64>, failing_since: Option<u64>) -> bool {
+        let tag = self
+            .tag
+            .as_deref()
+            .map(|tag| tag.trim().to_lowercase())
+            .filter(|tag| !tag.is_empty());
+        let text = self.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+
+        let trash = match self.trash {
+            TrashFilter::Exclude => trashed_at.is_none(),
+            TrashFilter::Include => true,
+            TrashFilter::Only => trashed_at.is_some(),
+        };
+
+        let failing = failing_since.is_some();
+
+        trash
+            && (failing || !self.failing)
+            && (!self.tagged || !meta.tags.is_empty())
+            && tag.is_none_or(|tag| meta.tags.contains(&tag))
+            && text.is_none_or(|text| meta.mentions(text))
+    }
+}
+
+/// число ссылок с тегом
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+pub trait ListShortUrlsRepository {
+    fn list(&self) -> Result<Vec<ShortUrlEntry>, AppError>;
+
+    /// Ссылки под фильтром. Хранилища проверяют фильтр на месте
+    /// и собирают записи только для подошедших ссылок.
+    fn search(&self, filter: &LinkFilter) -> Result<Vec<ShortUrlEntry>, AppError> {
+        let mut entries = self.list()?;
+        entries.retain(|entry| filter.matches(entry));
+        Ok(entries)
+    }
+}
+
+/// список коротких ссылок, отсортированный по id
+pub struct ListShortUrlsQuery<R>
+where
+    R: ListShortUrlsRepository,
+{
+    repo: R,
+}
+
+impl<R> ListShortUrlsQuery<R>
+where
+    R: ListShortUrlsRepository,
+{
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    /// ссылки вне корзины
+    pub async fn execute(&self) -> Result<Vec<ShortUrlEntry>, AppError> {
+        self.search(&LinkFilter::default()).await
+    }
+
+    /// ссылки, подходящие под фильтр, отсортированные по id
+    pub async fn search(&self, filter: &LinkFilter) -> Result<Vec<ShortUrlEntry>, AppError> {
+        let mut entries = self.repo.search(filter)?;
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(entries)
+    }
+
+    /// Теги, начинающиеся с `prefix`, от самых частых к редким,
+    /// для подсказок при вводе.
+    pub async fn tags(&self, prefix: &str) -> Result<Vec<TagCount>, AppError> {
+        let prefix = prefix.trim().to_lowercase();
+        let tagged = LinkFilter {
+            tagged: true,
+            ..Default::default()
+        };
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for entry in self.repo.search(&tagged)? {
+            for tag in entry.meta.tags {
+                if tag.starts_with(&prefix) {
+                    *counts.entry(tag).or_default() += 1;
+                }
+            }
+        }
+
+        let mut tags: Vec<TagCount> = counts
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag, count })
+            .collect();
+        tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use dashmap::DashMap;
+
+    use crate::{adapters::in_memory_repository::InMemoryRepository, app::link::Link};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn list_is_sorted_by_id() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("456".to_owned(), Link::new("https://github.com"));
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let repo = InMemoryRepository::new(store);
+        let query = ListShortUrlsQuery::new(repo);
+
+        // when
+        let result = query.execute().await.unwrap();
+
+        // then
+        let ids: Vec<&str> = result.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["123", "456"]);
+    }
+
+    fn tagged(url: &str, title: &str, tags: &[&str]) -> Link {
+        let tags = tags.iter().map(|t| t.to_string()).collect();
+        Link::new(url).with_meta(LinkMeta::new(Some(title.to_owned()), None, tags).unwrap())
+    }
+
+    #[tokio::test]
+    async fn search_filters_by_tag_and_text() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert(
+            "1".to_owned(),
+            tagged("https://a.com", "Rust book", &["docs", "rust"]),
+        );
+        store.insert(
+            "2".to_owned(),
+            tagged("https://b.com", "Go book", &["docs"]),
+        );
+        store.insert(
+            "3".to_owned(),
+            tagged("https://c.com", "Rust blog", &["blog"]),
+        );
+        let query = ListShortUrlsQuery::new(InMemoryRepository::new(store));
+
+        // when
+        let filter = LinkFilter {
+            tag: Some("Docs".to_owned()),
+            q: Some("rust".to_owned()),
+            ..Default::default()
+        };
+        let found = query.search(&filter).await.unwrap();
+        let tags = query.tags("").await.unwrap();
+
+        // then
+        let ids: Vec<&str> = found.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, vec!["1"]);
+        assert_eq!(
+            tags,
+            vec![
+                TagCount {
+                    tag: "docs".to_owned(),
+                    count: 2
+                },
+                TagCount {
+                    tag: "blog".to_owned(),
+                    count: 1
+                },
+                TagCount {
+                    tag: "rust".to_owned(),
+                    count: 1
+                },
+            ]
+        );
+        assert_eq!(query.tags("ru").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn trashed_links_are_listed_only_on_request() {
+        // given
+        let store: Arc<DashMap<String, Link>> = Arc::new(DashMap::new());
+        store.insert("1".to_owned(), Link::new("https://a.com"));
+        store.insert(
+            "2".to_owned(),
+            Link {
+                trashed_at: Some(1_700_000_000_000),
+                ..Link::new("https://b.com")
+            },
+        );
+        let query = ListShortUrlsQuery::new(InMemoryRepository::new(store));
+
+        let cases = [
+            (TrashFilter::Exclude, vec!["1"]),
+            (TrashFilter::Include, vec!["1", "2"]),
+            (TrashFilter::Only, vec!["2"]),
+        ];
+
+        for (trash, expected) in cases {
+            // when
+            let filter = LinkFilter {
+                trash,
+                ..Default::default()
+            };
+            let found = query.search(&filter).await.unwrap();
+
+            // then
+            let ids: Vec<&str> = found.iter().map(|e| e.id.as_str()).collect();
+            assert_eq!(ids, expected);
+        }
+    }
+}
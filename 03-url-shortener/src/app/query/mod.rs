@@ -1 +1,4 @@
 pub mod get_full_url;
+pub mod get_link_health;
+pub mod get_stats;
+pub mod list_short_urls;
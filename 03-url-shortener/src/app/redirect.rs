@@ -0,0 +1,199 @@
+use url::{Url, form_urlencoded};
+
+use crate::app::error::AppError;
+
+/// что делать с query string входящего запроса при переходе
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Passthrough {
+    /// входящие параметры отбрасываются
+    #[default]
+    Off,
+    /// добавляются только параметры, которых нет в цели и utm метках
+    Merge,
+    /// входящие параметры заменяют одноимённые параметры цели и utm метки
+    Override,
+}
+
+impl Passthrough {
+    pub fn is_off(&self) -> bool {
+        *self == Passthrough::Off
+    }
+}
+
+/// utm метки, добавляемые к цели при переходе
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct UtmTemplate {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub medium: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<String>,
+}
+
+impl UtmTemplate {
+    /// проверенный шаблон, пустые значения отбрасываются
+    pub fn new(
+        source: Option<String>,
+        medium: Option<String>,
+        campaign: Option<String>,
+    ) -> Result<Self, AppError> {
+        let template = Self {
+            source: source
+                .map(|v| v.trim().to_owned())
+                .filter(|v| !v.is_empty()),
+            medium: medium
+                .map(|v| v.trim().to_owned())
+                .filter(|v| !v.is_empty()),
+            campaign: campaign
+                .map(|v| v.trim().to_owned())
+                .filter(|v| !v.is_empty()),
+        };
+        if template.pairs().is_empty() {
+            return Err(AppError::InvalidInput(
+                "utm template needs at least one of source, medium and campaign".to_owned(),
+            ));
+        }
+        Ok(template)
+    }
+
+    fn pairs(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("utm_source", &self.source),
+            ("utm_medium", &self.medium),
+            ("utm_campaign", &self.campaign),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value.as_deref()?)))
+        .collect()
+    }
+}
+
+/// правила построения адреса перехода из цели ссылки
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct RedirectRules {
+    #[serde(default, skip_serializing_if = "Passthrough::is_off")]
+    pub passthrough: Passthrough,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utm: Option<UtmTemplate>,
+}
+
+impl RedirectRules {
+    /// Адрес перехода. Параметры применяются по порядку: параметры цели,
+    /// затем utm метки шаблона, заменяющие одноимённые, затем входящие
+    /// параметры по режиму `passthrough`.
+    ///
+    /// Цель без изменений возвращается как есть, цель, которую не удалось
+    /// разобрать как url, тоже.
+    pub fn apply(&self, target: &str, incoming: &str) -> String {
+        let incoming: Vec<(String, String)> = match self.passthrough {
+            Passthrough::Off => Vec::new(),
+            _ => form_urlencoded::parse(incoming.trim_start_matches('?').as_bytes())
+                .into_owned()
+                .collect(),
+        };
+        let utm = self
+            .utm
+            .as_ref()
+            .map(UtmTemplate::pairs)
+            .unwrap_or_default();
+        if incoming.is_empty() && utm.is_empty() {
+            return target.to_owned();
+        }
+        let Ok(mut url) = Url::parse(target) else {
+            return target.to_owned();
+        };
+
+        let mut params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        for (key, value) in utm {
+            params.retain(|(k, _)| k != key);
+            params.push((key.to_owned(), value.to_owned()));
+        }
+        match self.passthrough {
+            Passthrough::Off => {}
+            Passthrough::Merge => {
+                let present: Vec<String> = params.iter().map(|(k, _)| k.clone()).collect();
+                params.extend(incoming.into_iter().filter(|(k, _)| !present.contains(k)));
+            }
+            Passthrough::Override => {
+                params.retain(|(k, _)| !incoming.iter().any(|(key, _)| key == k));
+                params.extend(incoming);
+            }
+        }
+
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(params);
+        }
+        url.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utm() -> Option<UtmTemplate> {
+        Some(
+            UtmTemplate::new(
+                Some("newsletter".to_owned()),
+                Some("email".to_owned()),
+                None,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn utm_replaces_target_params_and_merge_keeps_them() {
+        // given
+        let rules = RedirectRules {
+            passthrough: Passthrough::Merge,
+            utm: utm(),
+        };
+
+        // when
+        let url = rules.apply(
+            "https://a.example/p?utm_source=old&id=1",
+            "id=2&utm_medium=partner&ref=x",
+        );
+
+        // then
+        assert_eq!(
+            url,
+            "https://a.example/p?id=1&utm_source=newsletter&utm_medium=email&ref=x"
+        );
+    }
+
+    #[test]
+    fn override_lets_incoming_params_win() {
+        // given
+        let rules = RedirectRules {
+            passthrough: Passthrough::Override,
+            utm: utm(),
+        };
+
+        // when
+        let url = rules.apply("https://a.example/p?id=1", "id=2&utm_medium=partner");
+
+        // then
+        assert_eq!(
+            url,
+            "https://a.example/p?utm_source=newsletter&id=2&utm_medium=partner"
+        );
+    }
+
+    #[test]
+    fn target_is_untouched_without_rules() {
+        // given
+        let rules = RedirectRules::default();
+
+        // then
+        assert_eq!(
+            rules.apply("https://a.example/p?q=a b", "ref=x"),
+            "https://a.example/p?q=a b"
+        );
+    }
+}
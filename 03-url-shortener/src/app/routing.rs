@@ -0,0 +1,412 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use url::form_urlencoded;
+
+use crate::app::error::AppError;
+
+/// класс устройства посетителя по user agent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceClass {
+    Ios,
+    Android,
+    /// прочие мобильные устройства
+    Mobile,
+    Desktop,
+    Bot,
+}
+
+impl DeviceClass {
+    pub fn of(user_agent: &str) -> Self {
+        let ua = user_agent.to_lowercase();
+        if ["bot", "crawler", "spider", "slurp"]
+            .iter()
+            .any(|m| ua.contains(m))
+        {
+            DeviceClass::Bot
+        } else if ["iphone", "ipad", "ipod"].iter().any(|m| ua.contains(m)) {
+            DeviceClass::Ios
+        } else if ua.contains("android") {
+            DeviceClass::Android
+        } else if ua.contains("mobile") {
+            DeviceClass::Mobile
+        } else {
+            DeviceClass::Desktop
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+}
+
+/// Интервал времени суток `from..to` в формате `HH:MM`.
+///
+/// Интервал может переходить через полночь (`22:00`–`06:00`), дни недели
+/// относятся к началу интервала. Пустой интервал с `from == to` не допускается.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct TimeWindow {
+    pub from: ClockTime,
+    pub to: ClockTime,
+    /// дни недели, пустой список означает любой день
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+    /// смещение местного времени от UTC в минутах
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+impl TimeWindow {
+    fn validate(&self) -> Result<(), String> {
+        if self.from == self.to {
+            return Err("time window must not be empty".to_owned());
+        }
+        if self.utc_offset_minutes.abs() > 14 * 60 {
+            return Err("utc offset must be within 14 hours".to_owned());
+        }
+        Ok(())
+    }
+
+    /// попадает ли момент `at` (секунды unix) в интервал
+    fn contains(&self, at: u64) -> bool {
+        let (ClockTime(from), ClockTime(to)) = (self.from, self.to);
+        let local = at as i64 / 60 + i64::from(self.utc_offset_minutes);
+        let minute = local.rem_euclid(24 * 60) as u32;
+        let day = local.div_euclid(24 * 60);
+
+        // начало интервала: сегодня, либо вчера для ночного интервала после полуночи
+        let started_on = match from <= to {
+            true if (from..to).contains(&minute) => day,
+            false if minute >= from => day,
+            false if minute < to => day - 1,
+            _ => return false,
+        };
+        // 1 января 1970 года был четвергом
+        let weekday = Weekday::ALL[(started_on + 3).rem_euclid(7) as usize];
+        self.days.is_empty() || self.days.contains(&weekday)
+    }
+}
+
+/// время суток `HH:MM`, разобранное при чтении в минуты от полуночи
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ClockTime(u32);
+
+impl FromStr for ClockTime {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("time {value} must be HH:MM");
+        let (hours, minutes) = value.split_once(':').ok_or_else(invalid)?;
+        let hours: u32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+        Ok(ClockTime(hours * 60 + minutes))
+    }
+}
+
+impl TryFrom<String> for ClockTime {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ClockTime> for String {
+    fn from(ClockTime(minutes): ClockTime) -> Self {
+        format!("{:02}:{:02}", minutes / 60, minutes % 60)
+    }
+}
+
+/// Условия правила. Правило срабатывает, когда выполнены все заданные
+/// условия; внутри списка достаточно совпадения с одним элементом.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct RuleConditions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub device: Vec<DeviceClass>,
+    /// Языки вида `de` или `pt-br`, сравниваются с самым предпочтительным
+    /// языком из `Accept-Language`; `pt` подходит и для `pt-BR`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub language: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<TimeWindow>,
+    /// параметры query string и их точные значения
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub query: BTreeMap<String, String>,
+}
+
+impl RuleConditions {
+    fn is_empty(&self) -> bool {
+        self.device.is_empty()
+            && self.language.is_empty()
+            && self.time.is_none()
+            && self.query.is_empty()
+    }
+}
+
+/// правило маршрутизации: цель для переходов, подходящих под условия
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct RouteRule {
+    #[serde(rename = "when")]
+    pub conditions: RuleConditions,
+    pub target: String,
+}
+
+/// запрос перехода в том виде, в каком его видят правила
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteRequest<'a> {
+    pub user_agent: &'a str,
+    pub accept_language: &'a str,
+    pub query: &'a str,
+    /// момент перехода в секундах unix
+    pub at: u64,
+}
+
+impl RouteRule {
+    pub fn matches(&self, request: &RouteRequest) -> bool {
+        let when = &self.conditions;
+        if !when.device.is_empty() && !when.device.contains(&DeviceClass::of(request.user_agent)) {
+            return false;
+        }
+        if !when.language.is_empty() {
+            let Some(preferred) = preferred_language(request.accept_language) else {
+                return false;
+            };
+            let matches = |lang: &String| {
+                preferred == *lang
+                    || preferred
+                        .strip_prefix(lang.as_str())
+                        .is_some_and(|rest| rest.starts_with('-'))
+            };
+            if !when.language.iter().any(matches) {
+                return false;
+            }
+        }
+        if let Some(time) = &when.time
+            && !time.contains(request.at)
+        {
+            return false;
+        }
+        if !when.query.is_empty() {
+            let params: Vec<(String, String)> = form_urlencoded::parse(request.query.as_bytes())
+                .into_owned()
+                .collect();
+            let has = |(key, value): (&String, &String)| {
+                params.iter().any(|(k, v)| k == key && v == value)
+            };
+            if !when.query.iter().all(has) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// первое подходящее правило и его номер
+pub fn route<'a>(rules: &'a [RouteRule], request: &RouteRequest) -> Option<(usize, &'a RouteRule)> {
+    rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| rule.matches(request))
+}
+
+pub const MAX_RULES: usize = 50;
+
+/// Проверка правил при сохранении ссылки. Языки приводятся к нижнему регистру.
+pub fn validate(rules: &mut [RouteRule]) -> Result<(), AppError> {
+    if rules.len() > MAX_RULES {
+        return Err(AppError::InvalidInput(format!(
+            "at most {MAX_RULES} rules are allowed"
+        )));
+    }
+    for (index, rule) in rules.iter_mut().enumerate() {
+        let invalid = |reason: String| AppError::InvalidInput(format!("rule {index}: {reason}"));
+        if rule.conditions.is_empty() {
+            return Err(invalid("rule needs at least one condition".to_owned()));
+        }
+        for lang in &mut rule.conditions.language {
+            *lang = lang.trim().to_lowercase();
+            let valid = !lang.is_empty()
+                && lang.split('-').all(|part| {
+                    !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric())
+                });
+            if !valid {
+                return Err(invalid(format!("invalid language {lang}")));
+            }
+        }
+        if let Some(time) = &rule.conditions.time {
+            time.validate().map_err(invalid)?;
+        }
+    }
+    Ok(())
+}
+
+/// язык с наибольшим весом из `Accept-Language` в нижнем регистре
+fn preferred_language(accept_language: &str) -> Option<String> {
+    accept_language
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let lang = parts.next()?.trim().to_lowercase();
+            let quality = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (!lang.is_empty() && lang != "*" && quality > 0.0).then_some((lang, quality))
+        })
+        // при равных весах побеждает указанный раньше
+        .fold(
+            None,
+            |best: Option<(String, f32)>, (lang, quality)| match best {
+                Some((_, best_quality)) if best_quality >= quality => best,
+                _ => Some((lang, quality)),
+            },
+        )
+        .map(|(lang, _)| lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPHONE: &str = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) Mobile/15E148";
+    const ANDROID: &str = "Mozilla/5.0 (Linux; Android 14; Pixel 8) Mobile Safari/537.36";
+
+    fn rule(conditions: RuleConditions, target: &str) -> RouteRule {
+        RouteRule {
+            conditions,
+            target: target.to_owned(),
+        }
+    }
+
+    #[test]
+    fn devices_and_languages_pick_first_matching_rule() {
+        // given
+        let rules = vec![
+            rule(
+                RuleConditions {
+                    device: vec![DeviceClass::Ios],
+                    ..Default::default()
+                },
+                "https://apps.apple.com/app",
+            ),
+            rule(
+                RuleConditions {
+                    device: vec![DeviceClass::Android],
+                    ..Default::default()
+                },
+                "https://play.google.com/app",
+            ),
+            rule(
+                RuleConditions {
+                    language: vec!["de".to_owned()],
+                    ..Default::default()
+                },
+                "https://example.de",
+            ),
+        ];
+        let request = |user_agent, accept_language| RouteRequest {
+            user_agent,
+            accept_language,
+            ..Default::default()
+        };
+
+        // then
+        assert_eq!(route(&rules, &request(IPHONE, "de-DE")).unwrap().0, 0);
+        assert_eq!(route(&rules, &request(ANDROID, "")).unwrap().0, 1);
+        assert_eq!(
+            route(&rules, &request("curl", "en;q=0.5, de-AT;q=0.9"))
+                .unwrap()
+                .0,
+            2
+        );
+        assert_eq!(route(&rules, &request("curl", "en, de;q=0.9")), None);
+    }
+
+    #[test]
+    fn time_window_wraps_midnight_and_respects_days() {
+        // given
+        let window = TimeWindow {
+            from: "22:00".parse().unwrap(),
+            to: "06:00".parse().unwrap(),
+            days: vec![Weekday::Fri],
+            utc_offset_minutes: 60,
+        };
+        // 2024-01-05 — пятница
+        let friday_midnight_utc = 1_704_412_800;
+
+        // then
+        // 23:30 местного времени в пятницу
+        assert!(window.contains(friday_midnight_utc + 22 * 3600 + 30 * 60));
+        // 02:00 в субботу, интервал начался в пятницу
+        assert!(window.contains(friday_midnight_utc + 25 * 3600));
+        // 12:00 в пятницу
+        assert!(!window.contains(friday_midnight_utc + 11 * 3600));
+        // 23:30 в четверг
+        assert!(!window.contains(friday_midnight_utc - 30 * 60));
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        // given
+        let mut empty = vec![rule(RuleConditions::default(), "https://a.example")];
+        let bad_time = serde_json::from_str::<TimeWindow>(r#"{"from": "25:00", "to": "06:00"}"#);
+        let mut empty_window = vec![rule(
+            RuleConditions {
+                time: Some(TimeWindow {
+                    from: "06:00".parse().unwrap(),
+                    to: "06:00".parse().unwrap(),
+                    days: vec![],
+                    utc_offset_minutes: 0,
+                }),
+                ..Default::default()
+            },
+            "https://a.example",
+        )];
+        let mut languages = vec![rule(
+            RuleConditions {
+                language: vec![" PT-BR".to_owned()],
+                ..Default::default()
+            },
+            "https://a.example",
+        )];
+
+        // then
+        assert!(validate(&mut empty).is_err());
+        let bad_time = bad_time.unwrap_err().to_string();
+        assert!(
+            bad_time.starts_with("time 25:00 must be HH:MM"),
+            "{bad_time}"
+        );
+        assert_eq!(
+            validate(&mut empty_window),
+            Err(AppError::InvalidInput(
+                "rule 0: time window must not be empty".to_owned()
+            ))
+        );
+        assert_eq!(validate(&mut languages), Ok(()));
+        assert_eq!(languages[0].conditions.language, vec!["pt-br"]);
+    }
+}
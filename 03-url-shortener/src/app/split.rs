@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use sha2::{Digest, Sha256};
+
+use crate::app::error::AppError;
+
+/// одна из целей ссылки, делящей трафик между несколькими адресами
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct WeightedTarget {
+    pub url: String,
+    /// доля трафика относительно суммы весов всех целей
+    pub weight: u32,
+}
+
+/// Проверка целей сплит-ссылки: их либо нет, либо не меньше двух,
+/// адреса целей различны и у каждой положительный вес.
+pub fn validate(targets: &[WeightedTarget]) -> Result<(), AppError> {
+    if targets.len() == 1 {
+        return Err(AppError::InvalidInput(
+            "a split link needs at least two targets".to_owned(),
+        ));
+    }
+    let urls: HashSet<&str> = targets.iter().map(|t| t.url.as_str()).collect();
+    if urls.len() != targets.len() {
+        return Err(AppError::InvalidInput(
+            "split targets must have distinct urls".to_owned(),
+        ));
+    }
+    if targets.iter().any(|t| t.weight == 0) {
+        return Err(AppError::InvalidInput(
+            "target weight must be positive".to_owned(),
+        ));
+    }
+    if targets.iter().map(|t| u64::from(t.weight)).sum::<u64>() > u64::from(u32::MAX) {
+        return Err(AppError::InvalidInput(
+            "target weights are too large".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Ключ цели, не зависящий от её места в списке, чтобы выбор посетителя
+/// переживал перестановку и удаление других целей.
+pub fn key(url: &str) -> String {
+    hex::encode(&Sha256::digest(url.as_bytes())[..8])
+}
+
+/// Номер цели с ключом [`key`], если такая цель ещё есть.
+pub fn find(targets: &[WeightedTarget], key: &str) -> Option<usize> {
+    targets.iter().position(|t| self::key(&t.url) == key)
+}
+
+/// Номер цели для посетителя.
+///
+/// Выбор зависит только от ссылки и признака посетителя, поэтому повторные
+/// переходы того же посетителя попадают в ту же цель, а по всем посетителям
+/// трафик делится пропорционально весам.
+pub fn pick(targets: &[WeightedTarget], short_url: &str, visitor: &str) -> usize {
+    let total: u64 = targets.iter().map(|t| u64::from(t.weight)).sum();
+    if total == 0 {
+        return 0;
+    }
+
+    let digest = Sha256::new()
+        .chain_update(short_url.as_bytes())
+        .chain_update([0])
+        .chain_update(visitor.as_bytes())
+        .finalize();
+    let mut bucket = u64::from_be_bytes(digest[..8].try_into().unwrap()) % total;
+    for (index, target) in targets.iter().enumerate() {
+        let weight = u64::from(target.weight);
+        if bucket < weight {
+            return index;
+        }
+        bucket -= weight;
+    }
+    targets.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(weights: &[u32]) -> Vec<WeightedTarget> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, weight)| WeightedTarget {
+                url: format!("https://{i}.example"),
+                weight: *weight,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn traffic_is_split_by_weight_and_sticky() {
+        // given
+        let targets = targets(&[1, 3]);
+
+        // when
+        let picks: Vec<usize> = (0..4000)
+            .map(|i| pick(&targets, "abc", &format!("10.0.0.{i}")))
+            .collect();
+
+        // then
+        let second = picks.iter().filter(|&&p| p == 1).count();
+        assert!((2800..3200).contains(&second), "{second}");
+        assert_eq!(pick(&targets, "abc", "10.0.0.7"), picks[7]);
+    }
+
+    #[test]
+    fn target_is_found_by_key_after_reorder() {
+        // given
+        let mut targets = targets(&[1, 1, 1]);
+        let chosen = key(&targets[1].url);
+
+        // when
+        targets.remove(0);
+        targets.reverse();
+
+        // then
+        assert_eq!(find(&targets, &chosen), Some(1));
+        assert_eq!(find(&targets, "unknown"), None);
+    }
+
+    #[test]
+    fn duplicate_target_urls_are_rejected() {
+        // given
+        let mut targets = targets(&[1, 1]);
+        targets[1].url = targets[0].url.clone();
+
+        // when
+        let result = validate(&targets);
+
+        // then
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn single_or_weightless_targets_are_rejected() {
+        assert!(validate(&targets(&[1])).is_err());
+        assert!(validate(&targets(&[1, 0])).is_err());
+        assert_eq!(validate(&targets(&[])), Ok(()));
+        assert_eq!(validate(&targets(&[50, 50])), Ok(()));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    app::{
+        error::AppError,
+        health::HealthCheck,
+        policy::{HostList, HostPolicy},
+    },
+    id_provider::IdStrategy,
+};
+
+/// Арендатор со своими короткими доменами и пространством коротких ссылок.
+///
+/// Ссылки арендатора хранятся под ключами `{id}/{код}`, поэтому один и тот же
+/// код на разных доменах ведёт в разные места.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Tenant {
+    pub id: String,
+    /// домены, по заголовку Host которых выбирается арендатор
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub id_strategy: IdStrategy,
+    /// хосты, на которые нельзя ссылаться, в формате [`HostList`]
+    #[serde(default)]
+    pub blocklist: Vec<String>,
+    /// если задан, ссылаться можно только на эти хосты
+    #[serde(default)]
+    pub allowlist: Option<Vec<String>>,
+    /// максимальное число ссылок
+    #[serde(default)]
+    pub max_links: Option<usize>,
+}
+
+impl Tenant {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let valid_id = !self.id.is_empty()
+            && self
+                .id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid_id {
+            return Err(AppError::InvalidInput(
+                "tenant id must consist of a-z, 0-9 and -".to_owned(),
+            ));
+        }
+        if self.domains.is_empty() {
+            return Err(AppError::InvalidInput(
+                "tenant needs at least one domain".to_owned(),
+            ));
+        }
+        if !(4..=64).contains(&self.id_strategy.length()) {
+            return Err(AppError::InvalidInput(
+                "id length must be between 4 and 64".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// политика целей арендатора, свои домены всегда запрещены
+    pub fn policy(&self) -> HostPolicy {
+        let list = |entries: &[String]| {
+            let mut list = HostList::default();
+            entries.iter().for_each(|e| list.add(e));
+            list
+        };
+
+        HostPolicy {
+            blocklist: list(&self.blocklist),
+            allowlist: self.allowlist.as_deref().map(list),
+            own_domains: list(&self.domains),
+        }
+    }
+}
+
+pub trait TenantRepository: HealthCheck + Send + Sync {
+    fn list(&self) -> Result<Vec<Tenant>, AppError>;
+    fn get(&self, id: &str) -> Result<Tenant, AppError>;
+    fn save(&self, tenant: Tenant) -> Result<(), AppError>;
+    fn delete(&self, id: &str) -> Result<(), AppError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::policy::TargetPolicy;
+
+    use super::*;
+
+    fn tenant() -> Tenant {
+        Tenant {
+            id: "team-a".to_owned(),
+            domains: vec!["go.team-a.example".to_owned()],
+            id_strategy: IdStrategy::default(),
+            blocklist: vec!["*.evil.com".to_owned()],
+            allowlist: None,
+            max_links: None,
+        }
+    }
+
+    #[test]
+    fn validate_tenant_id() {
+        // given
+        let mut bad = tenant();
+        bad.id = "Team A/".to_owned();
+
+        // then
+        assert_eq!(tenant().validate(), Ok(()));
+        assert!(matches!(bad.validate(), Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn tenant_policy_blocks_own_domain_and_blocklist() {
+        // given
+        let policy = tenant().policy();
+
+        // then
+        assert!(policy.check("https://go.team-a.example/docs").is_err());
+        assert!(policy.check("https://www.evil.com").is_err());
+        assert_eq!(policy.check("https://google.com"), Ok(()));
+    }
+}
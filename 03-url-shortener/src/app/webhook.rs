@@ -0,0 +1,323 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::app::{
+    error::AppError,
+    event::{EventSubscriber, LinkEvent, LinkEventKind, RecentIds, now_millis},
+    health::HealthCheck,
+};
+
+/// подписка внешнего получателя на события ссылок
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub id: String,
+    /// адрес, на который отправляются события
+    pub url: String,
+    /// ключ для подписи тела запроса HMAC-SHA256
+    pub secret: String,
+    /// типы событий, пустой список означает все
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+impl Subscription {
+    pub fn wants(&self, event: &LinkEvent) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event.kind.name())
+    }
+}
+
+/// доставка одного события одному получателю
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub id: String,
+    pub subscription_id: String,
+    pub url: String,
+    pub event: LinkEvent,
+    /// число неудачных попыток
+    #[serde(default)]
+    pub attempts: u32,
+    /// время следующей попытки в миллисекундах unix
+    pub next_attempt_at: u64,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+/// Хранилище подписок и очереди доставок (outbox).
+///
+/// Доставки лежат в очереди до успешной отправки, поэтому переживают
+/// перезапуск сервера. Исчерпавшие попытки доставки переносятся в список
+/// недоставленных, откуда их можно вернуть в очередь вручную.
+pub trait WebhookStore: HealthCheck + Send + Sync {
+    fn subscriptions(&self) -> Result<Vec<Subscription>, AppError>;
+    fn save_subscription(&self, subscription: Subscription) -> Result<(), AppError>;
+    fn delete_subscription(&self, id: &str) -> Result<(), AppError>;
+
+    fn enqueue(&self, deliveries: Vec<Delivery>) -> Result<(), AppError>;
+    /// доставки, время попытки которых наступило, в порядке времени
+    fn due(&self, now: u64, limit: usize) -> Result<Vec<Delivery>, AppError>;
+    /// Наступившие доставки, которые до `until` не выдаются никому другому:
+    /// их время попытки переносится на `until` одной записью с выдачей.
+    /// Если отправивший не успел записать итог, доставка снова выдаётся после `until`.
+    fn lease(&self, now: u64, until: u64, limit: usize) -> Result<Vec<Delivery>, AppError>;
+    /// удаление успешно доставленного события из очереди
+    fn complete(&self, delivery_id: &str) -> Result<(), AppError>;
+    /// сохранение доставки с новым временем попытки
+    fn reschedule(&self, delivery: Delivery) -> Result<(), AppError>;
+    /// перенос доставки из очереди в список недоставленных
+    fn dead_letter(&self, delivery: Delivery) -> Result<(), AppError>;
+    fn dead_letters(&self) -> Result<Vec<Delivery>, AppError>;
+    /// возврат недоставленного события в очередь
+    fn requeue(&self, delivery_id: &str, now: u64) -> Result<(), AppError>;
+}
+
+/// повторы неудачных доставок с экспоненциальной задержкой
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// после стольких неудач доставка уходит в недоставленные
+    pub max_attempts: u32,
+    /// задержка после первой неудачи, дальше удваивается
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// задержка перед следующей попыткой после `attempts` неудач
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// Управление подписками и постановка событий в очередь доставки.
+///
+/// Сама отправка выполняется отдельным фоновым диспетчером. Повторная
+/// публикация уже поставленного события отбрасывается по его id.
+#[derive(Clone)]
+pub struct Webhooks {
+    store: Arc<dyn WebhookStore>,
+    /// id последних поставленных в очередь событий
+    seen: Arc<Mutex<RecentIds>>,
+}
+
+impl Webhooks {
+    /// больше, чем событий в одной пачке ретранслятора очереди
+    const REMEMBERED: usize = 1024;
+
+    pub fn new(store: Arc<dyn WebhookStore>) -> Self {
+        Self {
+            store,
+            seen: Arc::new(Mutex::new(RecentIds::new(Self::REMEMBERED))),
+        }
+    }
+
+    /// Новая подписка. Без секрета он генерируется и возвращается
+    /// в ответе, чтобы получатель мог проверять подпись.
+    pub fn subscribe(
+        &self,
+        url: String,
+        events: Vec<String>,
+        secret: Option<String>,
+    ) -> Result<Subscription, AppError> {
+        let subscription = Self::subscription(url, events, secret)?;
+        self.save(subscription.clone())?;
+        Ok(subscription)
+    }
+
+    /// Проверенная подписка с выданным id, ещё не сохранённая
+    pub fn subscription(
+        url: String,
+        events: Vec<String>,
+        secret: Option<String>,
+    ) -> Result<Subscription, AppError> {
+        let parsed = Url::parse(&url).map_err(|e| AppError::InvalidUrl(e.to_string()))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(AppError::InvalidUrl(
+                "only http and https are supported".to_owned(),
+            ));
+        }
+        if let Some(unknown) = events
+            .iter()
+            .find(|e| !LinkEventKind::TYPES.contains(&e.as_str()))
+        {
+            return Err(AppError::InvalidInput(format!(
+                "unknown event type {unknown}"
+            )));
+        }
+        let secret = match secret {
+            Some(secret) if secret.is_empty() => {
+                return Err(AppError::InvalidInput(
+                    "secret must not be empty".to_owned(),
+                ));
+            }
+            Some(secret) => secret,
+            None => nanoid::nanoid!(32),
+        };
+
+        let subscription = Subscription {
+            id: nanoid::nanoid!(12),
+            url,
+            secret,
+            events,
+        };
+        Ok(subscription)
+    }
+
+    /// Сохраняет подписку, собранную через [`Webhooks::subscription`]
+    pub fn save(&self, subscription: Subscription) -> Result<(), AppError> {
+        self.store.save_subscription(subscription)
+    }
+
+    pub fn subscriptions(&self) -> Result<Vec<Subscription>, AppError> {
+        self.store.subscriptions()
+    }
+
+    pub fn unsubscribe(&self, id: &str) -> Result<(), AppError> {
+        self.store.delete_subscription(id)
+    }
+
+    pub fn dead_letters(&self) -> Result<Vec<Delivery>, AppError> {
+        self.store.dead_letters()
+    }
+
+    pub fn retry_dead_letter(&self, id: &str) -> Result<(), AppError> {
+        self.store.requeue(id, now_millis())
+    }
+}
+
+/// подписчик шины событий, ставящий доставки в очередь
+impl EventSubscriber for Webhooks {
+    fn handle(&self, event: &LinkEvent) -> Result<(), AppError> {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.contains(&event.id) {
+            return Ok(());
+        }
+        // запись аудита с ip и ключом автора получателям не отдаётся
+        let event = LinkEvent {
+            audit: None,
+            ..event.clone()
+        };
+        let deliveries: Vec<Delivery> = self
+            .store
+            .subscriptions()?
+            .into_iter()
+            .filter(|s| s.wants(&event))
+            .map(|s| Delivery {
+                id: nanoid::nanoid!(),
+                subscription_id: s.id,
+                url: s.url,
+                event: event.clone(),
+                attempts: 0,
+                next_attempt_at: event.occurred_at,
+                last_error: None,
+            })
+            .collect();
+        if !deliveries.is_empty() {
+            self.store.enqueue(deliveries)?;
+        }
+        seen.insert(event.id.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::adapters::in_memory_webhook_store::InMemoryWebhookStore;
+
+    use super::*;
+
+    #[test]
+    fn delay_doubles_up_to_limit() {
+        // given
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+        };
+
+        // then
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(4), Duration::from_secs(8));
+        assert_eq!(policy.delay(5), Duration::from_secs(10));
+        assert_eq!(policy.delay(100), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn events_are_queued_for_matching_subscriptions() {
+        // given
+        let store = Arc::new(InMemoryWebhookStore::default());
+        let webhooks = Webhooks::new(store.clone());
+        let all = webhooks
+            .subscribe("http://a.example/hook".to_owned(), vec![], None)
+            .unwrap();
+        webhooks
+            .subscribe(
+                "http://b.example/hook".to_owned(),
+                vec!["link.deleted".to_owned()],
+                Some("secret".to_owned()),
+            )
+            .unwrap();
+
+        // when
+        webhooks.handle(&LinkEvent::clicked("abc")).unwrap();
+
+        // then
+        let due = store.due(u64::MAX, 10).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].subscription_id, all.id);
+    }
+
+    #[test]
+    fn republished_event_is_queued_once() {
+        // given
+        let store = Arc::new(InMemoryWebhookStore::default());
+        let webhooks = Webhooks::new(store.clone());
+        webhooks
+            .subscribe("http://a.example/hook".to_owned(), vec![], None)
+            .unwrap();
+        let event = LinkEvent::clicked("abc");
+
+        // when
+        webhooks.handle(&event).unwrap();
+        webhooks.handle(&event).unwrap();
+
+        // then
+        assert_eq!(store.due(u64::MAX, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn unknown_event_type_is_rejected() {
+        // given
+        let webhooks = Webhooks::new(Arc::new(InMemoryWebhookStore::default()));
+
+        // when
+        let result = webhooks.subscribe(
+            "http://a.example/hook".to_owned(),
+            vec!["link.exploded".to_owned()],
+            None,
+        );
+
+        // then
+        assert_eq!(
+            result,
+            Err(AppError::InvalidInput(
+                "unknown event type link.exploded".to_owned()
+            ))
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::{io, process::ExitCode, sync::Arc};
+
+use clap::Parser;
+use rust_url_shortener::{
+    adapters::{
+        file_audit_trail::FileAuditTrail, file_policy::FilePolicy, file_repository::FileRepository,
+        file_tenant_repository::FileTenantRepository,
+        in_memory_tenant_repository::InMemoryTenantRepository,
+    },
+    app::{audit::Actor, error::AppError, tenant::TenantRepository},
+    config::{Config, Storage},
+    di::tenants::Tenants,
+    ports::cli::{
+        args::{Cli, CliCommand},
+        runner::{CliRunner, verify_audit},
+    },
+};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let command = match cli.command {
+        CliCommand::Links(command) => command,
+        CliCommand::VerifyAudit { file } => {
+            let Some(path) = file.or(config.audit_file) else {
+                eprintln!("error: set SHORTENER_AUDIT_FILE or pass the audit file");
+                return ExitCode::FAILURE;
+            };
+            let trail = FileAuditTrail::new(path);
+            return match verify_audit(&trail, cli.format, &mut io::stdout().lock()) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+    };
+
+    let configured = match config.storage {
+        Storage::File(path) => Some(path),
+        Storage::InMemory | Storage::Compact => None,
+    };
+    let Some(path) = cli.data_file.or(configured) else {
+        eprintln!("error: cli needs persistent storage, set SHORTENER_STORAGE=file or --data-file");
+        return ExitCode::FAILURE;
+    };
+
+    // арендатор берётся из того же файла, что и у сервера: его стратегия id,
+    // квота и списки доменов действуют и для cli
+    let tenant_repo: Arc<dyn TenantRepository> = match &config.tenants_file {
+        Some(path) => Arc::new(FileTenantRepository::new(path)),
+        None => Arc::new(InMemoryTenantRepository::default()),
+    };
+    let policy = FilePolicy::new(
+        config.policy.blocklist_file,
+        config.policy.allowlist_file,
+        &config.policy.short_domains,
+    );
+    let container = Tenants::new(FileRepository::new(path), tenant_repo, Arc::new(policy))
+        .and_then(|tenants| tenants.for_tenant(&cli.tenant));
+    let container = match container {
+        Ok(container) => container,
+        Err(AppError::NotFound) => {
+            eprintln!("error: unknown tenant {}", cli.tenant);
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // записи аудита лежат в очереди файла вместе с изменениями,
+    // в журнал их переносит сервер, работающий с тем же файлом
+    let user = std::env::var("USER").unwrap_or_else(|_| "unknown".to_owned());
+    let runner =
+        CliRunner::new(container).with_actor(Actor::new(format!("cli:{user}"), None, "local"));
+
+    match runner
+        .run(command, cli.format, &mut io::stdout().lock())
+        .await
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
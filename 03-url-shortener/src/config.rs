@@ -0,0 +1,666 @@
+use std::{
+    fmt::Display, net::IpAddr, num::NonZeroUsize, path::PathBuf, str::FromStr, time::Duration,
+};
+
+use crate::{
+    adapters::{
+        cached_repository::CacheConfig,
+        durable_repository::{DurabilityConfig, FsyncPolicy},
+        http_prober::LinkCheckConfig,
+    },
+    app::webhook::RetryPolicy,
+    ports::httpimpl::tls::TlsConfig,
+};
+
+/// вид хранилища ссылок
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Storage {
+    /// ссылки живут только в памяти процесса
+    InMemory,
+    /// ссылки живут в памяти процесса в плотном представлении
+    Compact,
+    /// ссылки хранятся в json файле
+    File(PathBuf),
+}
+
+/// настройки политики целей ссылок
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolicyConfig {
+    /// файл с запрещёнными хостами
+    pub blocklist_file: Option<PathBuf>,
+    /// файл с разрешёнными хостами, включает режим allow-листа
+    pub allowlist_file: Option<PathBuf>,
+    /// собственные короткие домены, на которые нельзя ссылаться
+    pub short_domains: Vec<String>,
+}
+
+/// настройки вебхуков
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WebhookConfig {
+    /// файл с подписками и очередью доставок, без него очередь живёт в памяти
+    pub file: Option<PathBuf>,
+    pub retry: RetryPolicy,
+}
+
+/// настройки узла кластера
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterConfig {
+    /// адрес, на котором узел принимает запросы других узлов
+    pub node: String,
+    /// адреса остальных узлов кластера
+    pub peers: Vec<String>,
+    /// на скольких узлах хранится каждая ссылка
+    pub replicas: usize,
+    /// общий секрет, которым узлы подтверждают друг другу, что они из кластера
+    pub secret: String,
+}
+
+/// настройки приложения
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub http_port: u16,
+    pub grpc_port: u16,
+    pub storage: Storage,
+    /// кэш перед постоянным хранилищем, `None` если выключен
+    pub cache: Option<CacheConfig>,
+    /// журнал и снимки хранилища в памяти, `None` если выключены
+    pub durability: Option<DurabilityConfig>,
+    pub policy: PolicyConfig,
+    /// файл с арендаторами, без него арендаторы живут только в памяти
+    pub tenants_file: Option<PathBuf>,
+    /// файл журнала аудита, без него журнал живёт только в памяти
+    pub audit_file: Option<PathBuf>,
+    /// токен админского api, без него api выключено
+    pub admin_token: Option<String>,
+    /// адреса прокси, которым верится заголовок с автором запроса
+    pub trusted_proxies: Vec<IpAddr>,
+    pub webhooks: WebhookConfig,
+    /// сколько после сигнала завершения отвечать «не готов», прежде чем остановиться
+    pub shutdown_grace: Duration,
+    /// источники, которым разрешены запросы из браузера, `*` разрешает все
+    pub cors_origins: Vec<String>,
+    /// режим кластера, `None` для одиночного узла
+    pub cluster: Option<ClusterConfig>,
+    /// https на порту http, `None` для обычного http
+    pub tls: Option<TlsConfig>,
+    /// сколько помнить ключи идемпотентности запросов создания
+    pub idempotency_ttl: Duration,
+    /// сколько ключей идемпотентности помнить, самые старые вытесняются
+    pub idempotency_capacity: NonZeroUsize,
+    /// сколько ссылка лежит в корзине до окончательного удаления
+    pub trash_retention: Duration,
+    /// фоновая проверка целей ссылок, `None` если выключена
+    pub link_check: Option<LinkCheckConfig>,
+}
+
+impl Config {
+    /// Чтение настроек из переменных окружения:
+    /// `SHORTENER_HTTP_PORT`, `SHORTENER_GRPC_PORT`,
+    /// `SHORTENER_STORAGE` (`memory`, `compact` или `file`), `SHORTENER_DATA_FILE`,
+    /// `SHORTENER_WAL_DIR` (журнал для `memory`), `SHORTENER_WAL_FSYNC` (`always`, `never`
+    /// или интервал в мс), `SHORTENER_SNAPSHOT_INTERVAL_SECS`,
+    /// `SHORTENER_CACHE_CAPACITY` (0 выключает кэш), `SHORTENER_CACHE_TTL_SECS`
+    /// `SHORTENER_CACHE_NEGATIVE_TTL_SECS`, `SHORTENER_BLOCKLIST_FILE`,
+    /// `SHORTENER_ALLOWLIST_FILE`, `SHORTENER_SHORT_DOMAINS` (через запятую),
+    /// `SHORTENER_TENANTS_FILE`, `SHORTENER_AUDIT_FILE`, `SHORTENER_ADMIN_TOKEN`,
+    /// `SHORTENER_TRUSTED_PROXIES` (ip через запятую),
+    /// `SHORTENER_WEBHOOKS_FILE`,
+    /// `SHORTENER_WEBHOOK_MAX_ATTEMPTS`, `SHORTENER_WEBHOOK_BASE_DELAY_SECS`
+    /// `SHORTENER_WEBHOOK_MAX_DELAY_SECS`, `SHORTENER_SHUTDOWN_GRACE_SECS`
+    /// `SHORTENER_CORS_ORIGINS` (через запятую), `SHORTENER_CLUSTER_NODE`,
+    /// `SHORTENER_CLUSTER_PEERS` (через запятую), `SHORTENER_CLUSTER_REPLICAS`,
+    /// `SHORTENER_CLUSTER_SECRET` (обязателен для узла кластера),
+    /// `SHORTENER_TLS_CERT` и `SHORTENER_TLS_KEY` (pem, включают https),
+    /// `SHORTENER_TLS_REDIRECT_PORT` (http порт с переадресацией на https)
+    /// `SHORTENER_TLS_RELOAD_SECS`, `SHORTENER_IDEMPOTENCY_TTL_SECS`,
+    /// `SHORTENER_IDEMPOTENCY_CAPACITY`,
+    /// `SHORTENER_TRASH_RETENTION_DAYS`, `SHORTENER_LINK_CHECK_INTERVAL_SECS`
+    /// (включает проверку целей), `SHORTENER_LINK_CHECK_CONCURRENCY`,
+    /// `SHORTENER_LINK_CHECK_HOST_DELAY_MS`, `SHORTENER_LINK_CHECK_TIMEOUT_SECS`
+    /// `SHORTENER_LINK_CHECK_DISABLE_AFTER_DAYS` (без неё ссылки не отключаются)
+    /// и `SHORTENER_LINK_CHECK_ALLOW_PRIVATE` (`true` разрешает цели во внутренних сетях)
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    fn from_lookup(get: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let data_file = get("SHORTENER_DATA_FILE").unwrap_or_else(|| "links.json".to_owned());
+        let storage = match get("SHORTENER_STORAGE").as_deref() {
+            None | Some("memory") => Storage::InMemory,
+            Some("compact") => Storage::Compact,
+            Some("file") => Storage::File(PathBuf::from(data_file)),
+            Some(other) => return Err(format!("SHORTENER_STORAGE: unknown storage {other}")),
+        };
+
+        let durability = match get("SHORTENER_WAL_DIR").filter(|dir| !dir.is_empty()) {
+            Some(_) if storage != Storage::InMemory => {
+                return Err(
+                    "SHORTENER_WAL_DIR: only memory storage has a write-ahead log".to_owned(),
+                );
+            }
+            Some(dir) => Some(DurabilityConfig {
+                dir: PathBuf::from(dir),
+                fsync: parse(&get, "SHORTENER_WAL_FSYNC", FsyncPolicy::Always)?,
+                snapshot_interval: Duration::from_secs(parse(
+                    &get,
+                    "SHORTENER_SNAPSHOT_INTERVAL_SECS",
+                    300,
+                )?),
+            }),
+            None => None,
+        };
+
+        let capacity: usize = parse(&get, "SHORTENER_CACHE_CAPACITY", 10_000)?;
+        let cache = match NonZeroUsize::new(capacity) {
+            Some(capacity) => Some(CacheConfig {
+                capacity,
+                ttl: Duration::from_secs(parse(&get, "SHORTENER_CACHE_TTL_SECS", 60)?),
+                negative_ttl: Duration::from_secs(parse(
+                    &get,
+                    "SHORTENER_CACHE_NEGATIVE_TTL_SECS",
+                    5,
+                )?),
+            }),
+            None => None,
+        };
+
+        let policy = PolicyConfig {
+            blocklist_file: get("SHORTENER_BLOCKLIST_FILE").map(PathBuf::from),
+            allowlist_file: get("SHORTENER_ALLOWLIST_FILE").map(PathBuf::from),
+            short_domains: list(&get, "SHORTENER_SHORT_DOMAINS"),
+        };
+
+        let default_retry = RetryPolicy::default();
+        let webhooks = WebhookConfig {
+            file: get("SHORTENER_WEBHOOKS_FILE").map(PathBuf::from),
+            retry: RetryPolicy {
+                max_attempts: parse(
+                    &get,
+                    "SHORTENER_WEBHOOK_MAX_ATTEMPTS",
+                    default_retry.max_attempts,
+                )?,
+                base_delay: Duration::from_secs(parse(
+                    &get,
+                    "SHORTENER_WEBHOOK_BASE_DELAY_SECS",
+                    default_retry.base_delay.as_secs(),
+                )?),
+                max_delay: Duration::from_secs(parse(
+                    &get,
+                    "SHORTENER_WEBHOOK_MAX_DELAY_SECS",
+                    default_retry.max_delay.as_secs(),
+                )?),
+            },
+        };
+
+        let cluster = match get("SHORTENER_CLUSTER_NODE").filter(|node| !node.is_empty()) {
+            Some(node) => {
+                let replicas = parse(&get, "SHORTENER_CLUSTER_REPLICAS", 2)?;
+                if replicas == 0 {
+                    return Err("SHORTENER_CLUSTER_REPLICAS: must be positive".to_owned());
+                }
+                let secret = get("SHORTENER_CLUSTER_SECRET")
+                    .filter(|secret| !secret.is_empty())
+                    .ok_or("SHORTENER_CLUSTER_SECRET: required for a cluster node")?;
+                Some(ClusterConfig {
+                    peers: list(&get, "SHORTENER_CLUSTER_PEERS")
+                        .into_iter()
+                        .filter(|peer| *peer != node)
+                        .collect(),
+                    node,
+                    replicas,
+                    secret,
+                })
+            }
+            None => None,
+        };
+
+        let tls = match (
+            get("SHORTENER_TLS_CERT").filter(|cert| !cert.is_empty()),
+            get("SHORTENER_TLS_KEY").filter(|key| !key.is_empty()),
+        ) {
+            (Some(cert), Some(key)) => Some(TlsConfig {
+                reload_interval: match parse(&get, "SHORTENER_TLS_RELOAD_SECS", 10)? {
+                    0 => return Err("SHORTENER_TLS_RELOAD_SECS: must be positive".to_owned()),
+                    secs => Duration::from_secs(secs),
+                },
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+                redirect_port: get("SHORTENER_TLS_REDIRECT_PORT")
+                    .map(|port| {
+                        port.parse()
+                            .map_err(|e| format!("SHORTENER_TLS_REDIRECT_PORT: {e}"))
+                    })
+                    .transpose()?,
+            }),
+            (None, None) => None,
+            _ => {
+                return Err(
+                    "SHORTENER_TLS_CERT and SHORTENER_TLS_KEY must be set together".to_owned(),
+                );
+            }
+        };
+
+        let link_check = match parse(&get, "SHORTENER_LINK_CHECK_INTERVAL_SECS", 0)? {
+            0 => None,
+            interval => Some(LinkCheckConfig {
+                interval: Duration::from_secs(interval),
+                concurrency: parse(&get, "SHORTENER_LINK_CHECK_CONCURRENCY", 8)?,
+                host_delay: Duration::from_millis(parse(
+                    &get,
+                    "SHORTENER_LINK_CHECK_HOST_DELAY_MS",
+                    1000,
+                )?),
+                timeout: Duration::from_secs(parse(&get, "SHORTENER_LINK_CHECK_TIMEOUT_SECS", 10)?),
+                disable_after: get("SHORTENER_LINK_CHECK_DISABLE_AFTER_DAYS")
+                    .map(|value| {
+                        let key = "SHORTENER_LINK_CHECK_DISABLE_AFTER_DAYS";
+                        value
+                            .parse::<u64>()
+                            .map_err(|e| format!("{key}: {e}"))
+                            .and_then(|count| days(key, count))
+                    })
+                    .transpose()?,
+                allow_private: parse(&get, "SHORTENER_LINK_CHECK_ALLOW_PRIVATE", false)?,
+            }),
+        };
+
+        Ok(Config {
+            http_port: parse(&get, "SHORTENER_HTTP_PORT", 3001)?,
+            grpc_port: parse(&get, "SHORTENER_GRPC_PORT", 50051)?,
+            storage,
+            cache,
+            durability,
+            policy,
+            tenants_file: get("SHORTENER_TENANTS_FILE").map(PathBuf::from),
+            audit_file: get("SHORTENER_AUDIT_FILE").map(PathBuf::from),
+            admin_token: get("SHORTENER_ADMIN_TOKEN").filter(|t| !t.is_empty()),
+            trusted_proxies: list(&get, "SHORTENER_TRUSTED_PROXIES")
+                .iter()
+                .map(|ip| ip.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("SHORTENER_TRUSTED_PROXIES: {e}"))?,
+            webhooks,
+            shutdown_grace: Duration::from_secs(parse(&get, "SHORTENER_SHUTDOWN_GRACE_SECS", 5)?),
+            cors_origins: cors_origins(list(&get, "SHORTENER_CORS_ORIGINS"))?,
+            cluster,
+            tls,
+            idempotency_ttl: Duration::from_secs(parse(
+                &get,
+                "SHORTENER_IDEMPOTENCY_TTL_SECS",
+                24 * 60 * 60,
+            )?),
+            idempotency_capacity: NonZeroUsize::new(parse(
+                &get,
+                "SHORTENER_IDEMPOTENCY_CAPACITY",
+                10_000,
+            )?)
+            .ok_or("SHORTENER_IDEMPOTENCY_CAPACITY: must be positive")?,
+            trash_retention: days(
+                "SHORTENER_TRASH_RETENTION_DAYS",
+                parse(&get, "SHORTENER_TRASH_RETENTION_DAYS", 30)?,
+            )?,
+            link_check,
+        })
+    }
+}
+
+/// срок в днях, который ещё помещается в миллисекунды u64
+fn days(key: &str, count: u64) -> Result<Duration, String> {
+    count
+        .checked_mul(24 * 60 * 60 * 1000)
+        .map(Duration::from_millis)
+        .ok_or_else(|| format!("{key}: {count} days is too long"))
+}
+
+/// разбор переменной окружения со значением по умолчанию
+fn parse<T>(get: &impl Fn(&str) -> Option<String>, key: &str, default: T) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match get(key) {
+        Some(value) => value.parse().map_err(|e| format!("{key}: {e}")),
+        None => Ok(default),
+    }
+}
+
+/// проверка источников CORS: `*` или `scheme://host[:port]` без пути
+fn cors_origins(origins: Vec<String>) -> Result<Vec<String>, String> {
+    for origin in &origins {
+        let valid = origin == "*"
+            || url::Url::parse(origin)
+                .is_ok_and(|url| url.origin().ascii_serialization() == *origin);
+        if !valid {
+            return Err(format!("SHORTENER_CORS_ORIGINS: invalid origin {origin}"));
+        }
+    }
+    Ok(origins)
+}
+
+/// список через запятую, пустые элементы пропускаются
+fn list(get: &impl Fn(&str) -> Option<String>, key: &str) -> Vec<String> {
+    get(key)
+        .map(|items| {
+            items
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn defaults() {
+        // when
+        let config = Config::from_lookup(lookup(&[])).unwrap();
+
+        // then
+        assert_eq!(
+            config,
+            Config {
+                http_port: 3001,
+                grpc_port: 50051,
+                storage: Storage::InMemory,
+                cache: Some(CacheConfig {
+                    capacity: NonZeroUsize::new(10_000).unwrap(),
+                    ttl: Duration::from_secs(60),
+                    negative_ttl: Duration::from_secs(5),
+                }),
+                durability: None,
+                policy: PolicyConfig::default(),
+                tenants_file: None,
+                audit_file: None,
+                admin_token: None,
+                trusted_proxies: vec![],
+                webhooks: WebhookConfig::default(),
+                shutdown_grace: Duration::from_secs(5),
+                cors_origins: vec![],
+                cluster: None,
+                tls: None,
+                idempotency_ttl: Duration::from_secs(86400),
+                idempotency_capacity: NonZeroUsize::new(10_000).unwrap(),
+                trash_retention: Duration::from_secs(30 * 86400),
+                link_check: None,
+            }
+        );
+    }
+
+    #[test]
+    fn file_storage() {
+        // when
+        let config = Config::from_lookup(lookup(&[
+            ("SHORTENER_STORAGE", "file"),
+            ("SHORTENER_DATA_FILE", "/tmp/links.json"),
+        ]))
+        .unwrap();
+
+        // then
+        assert_eq!(config.storage, Storage::File("/tmp/links.json".into()));
+    }
+
+    #[test]
+    fn compact_storage() {
+        // when
+        let config = Config::from_lookup(lookup(&[("SHORTENER_STORAGE", "compact")])).unwrap();
+
+        // then
+        assert_eq!(config.storage, Storage::Compact);
+    }
+
+    #[test]
+    fn write_ahead_log_settings() {
+        // when
+        let config = Config::from_lookup(lookup(&[
+            ("SHORTENER_WAL_DIR", "/var/lib/shortener"),
+            ("SHORTENER_WAL_FSYNC", "100"),
+        ]))
+        .unwrap();
+        let with_file = Config::from_lookup(lookup(&[
+            ("SHORTENER_STORAGE", "file"),
+            ("SHORTENER_WAL_DIR", "/var/lib/shortener"),
+        ]));
+
+        // then
+        assert_eq!(
+            config.durability,
+            Some(DurabilityConfig {
+                dir: "/var/lib/shortener".into(),
+                fsync: FsyncPolicy::Interval(Duration::from_millis(100)),
+                snapshot_interval: Duration::from_secs(300),
+            })
+        );
+        assert!(with_file.is_err());
+    }
+
+    #[test]
+    fn policy_settings() {
+        // when
+        let config = Config::from_lookup(lookup(&[
+            ("SHORTENER_BLOCKLIST_FILE", "/etc/shortener/blocklist.txt"),
+            ("SHORTENER_SHORT_DOMAINS", "sho.rt, go.example"),
+        ]))
+        .unwrap();
+
+        // then
+        assert_eq!(
+            config.policy,
+            PolicyConfig {
+                blocklist_file: Some("/etc/shortener/blocklist.txt".into()),
+                allowlist_file: None,
+                short_domains: vec!["sho.rt".to_owned(), "go.example".to_owned()],
+            }
+        );
+    }
+
+    #[test]
+    fn webhook_settings() {
+        // when
+        let config = Config::from_lookup(lookup(&[
+            (
+                "SHORTENER_WEBHOOKS_FILE",
+                "/var/lib/shortener/webhooks.json",
+            ),
+            ("SHORTENER_WEBHOOK_MAX_ATTEMPTS", "3"),
+        ]))
+        .unwrap();
+
+        // then
+        assert_eq!(
+            config.webhooks,
+            WebhookConfig {
+                file: Some("/var/lib/shortener/webhooks.json".into()),
+                retry: RetryPolicy {
+                    max_attempts: 3,
+                    ..Default::default()
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn zero_capacity_disables_cache() {
+        // when
+        let config = Config::from_lookup(lookup(&[("SHORTENER_CACHE_CAPACITY", "0")])).unwrap();
+
+        // then
+        assert_eq!(config.cache, None);
+    }
+
+    #[test]
+    fn bad_port() {
+        // when
+        let result = Config::from_lookup(lookup(&[("SHORTENER_HTTP_PORT", "http")]));
+
+        // then
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn trusted_proxies_are_ip_addresses() {
+        // when
+        let config =
+            Config::from_lookup(lookup(&[("SHORTENER_TRUSTED_PROXIES", "10.0.0.5, ::1")])).unwrap();
+        let invalid = Config::from_lookup(lookup(&[("SHORTENER_TRUSTED_PROXIES", "proxy")]));
+
+        // then
+        assert_eq!(
+            config.trusted_proxies,
+            [
+                "10.0.0.5".parse::<IpAddr>().unwrap(),
+                "::1".parse().unwrap()
+            ]
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn cors_origins_are_validated() {
+        // when
+        let config = Config::from_lookup(lookup(&[(
+            "SHORTENER_CORS_ORIGINS",
+            "https://ui.example, http://localhost:8080",
+        )]))
+        .unwrap();
+        let invalid = Config::from_lookup(lookup(&[(
+            "SHORTENER_CORS_ORIGINS",
+            "https://ui.example/app",
+        )]));
+
+        // then
+        assert_eq!(
+            config.cors_origins,
+            vec!["https://ui.example", "http://localhost:8080"]
+        );
+        assert_eq!(
+            invalid,
+            Err("SHORTENER_CORS_ORIGINS: invalid origin https://ui.example/app".to_owned())
+        );
+    }
+
+    #[test]
+    fn cluster_settings() {
+        // when
+        let config = Config::from_lookup(lookup(&[
+            ("SHORTENER_CLUSTER_NODE", "10.0.0.1:7000"),
+            (
+                "SHORTENER_CLUSTER_PEERS",
+                "10.0.0.1:7000, 10.0.0.2:7000, 10.0.0.3:7000",
+            ),
+            ("SHORTENER_CLUSTER_SECRET", "s3cret"),
+        ]))
+        .unwrap();
+
+        // then
+        assert_eq!(
+            config.cluster,
+            Some(ClusterConfig {
+                node: "10.0.0.1:7000".to_owned(),
+                peers: vec!["10.0.0.2:7000".to_owned(), "10.0.0.3:7000".to_owned()],
+                replicas: 2,
+                secret: "s3cret".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn cluster_node_needs_secret() {
+        // when
+        let config = Config::from_lookup(lookup(&[("SHORTENER_CLUSTER_NODE", "10.0.0.1:7000")]));
+
+        // then
+        assert_eq!(
+            config,
+            Err("SHORTENER_CLUSTER_SECRET: required for a cluster node".to_owned())
+        );
+    }
+
+    #[test]
+    fn tls_settings() {
+        // when
+        let config = Config::from_lookup(lookup(&[
+            ("SHORTENER_TLS_CERT", "/etc/shortener/cert.pem"),
+            ("SHORTENER_TLS_KEY", "/etc/shortener/key.pem"),
+            ("SHORTENER_TLS_REDIRECT_PORT", "80"),
+        ]))
+        .unwrap();
+        let half = Config::from_lookup(lookup(&[("SHORTENER_TLS_CERT", "/cert.pem")]));
+        let busy = Config::from_lookup(lookup(&[
+            ("SHORTENER_TLS_CERT", "/cert.pem"),
+            ("SHORTENER_TLS_KEY", "/key.pem"),
+            ("SHORTENER_TLS_RELOAD_SECS", "0"),
+        ]));
+
+        // then
+        assert_eq!(
+            config.tls,
+            Some(TlsConfig {
+                cert: "/etc/shortener/cert.pem".into(),
+                key: "/etc/shortener/key.pem".into(),
+                redirect_port: Some(80),
+                reload_interval: Duration::from_secs(10),
+            })
+        );
+        assert!(half.is_err());
+        assert_eq!(
+            busy,
+            Err("SHORTENER_TLS_RELOAD_SECS: must be positive".to_owned())
+        );
+    }
+
+    #[test]
+    fn link_check_settings() {
+        // when
+        let config = Config::from_lookup(lookup(&[
+            ("SHORTENER_LINK_CHECK_INTERVAL_SECS", "3600"),
+            ("SHORTENER_LINK_CHECK_DISABLE_AFTER_DAYS", "14"),
+        ]))
+        .unwrap();
+        let invalid = Config::from_lookup(lookup(&[
+            ("SHORTENER_LINK_CHECK_INTERVAL_SECS", "3600"),
+            ("SHORTENER_LINK_CHECK_DISABLE_AFTER_DAYS", "two weeks"),
+        ]));
+        let overflow = Config::from_lookup(lookup(&[
+            ("SHORTENER_LINK_CHECK_INTERVAL_SECS", "3600"),
+            (
+                "SHORTENER_LINK_CHECK_DISABLE_AFTER_DAYS",
+                &u64::MAX.to_string(),
+            ),
+        ]));
+        let trash_overflow = Config::from_lookup(lookup(&[(
+            "SHORTENER_TRASH_RETENTION_DAYS",
+            "300000000000000",
+        )]));
+
+        // then
+        assert_eq!(
+            config.link_check,
+            Some(LinkCheckConfig {
+                interval: Duration::from_secs(3600),
+                concurrency: 8,
+                host_delay: Duration::from_secs(1),
+                timeout: Duration::from_secs(10),
+                disable_after: Some(Duration::from_secs(14 * 86400)),
+                allow_private: false,
+            })
+        );
+        assert!(invalid.is_err());
+        assert!(overflow.is_err());
+        assert!(trash_overflow.is_err());
+    }
+}
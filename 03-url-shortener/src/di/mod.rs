@@ -1,35 +1,141 @@
+pub mod tenants;
+
+use std::sync::Arc;
+
 use crate::{
+    adapters::in_memory_link_health_store::InMemoryLinkHealthStore,
     app::{
-        command::create_short_url::{CreateShortUrlCommand, CreateShortUrlRepository},
-        query::get_full_url::{GetFullUrlQuery, GetFullUrlRepository},
+        command::{
+            create_short_url::{CreateShortUrlCommand, CreateShortUrlRepository},
+            delete_short_url::{DeleteShortUrlCommand, DeleteShortUrlRepository},
+            record_click::{RecordClickCommand, RecordClickRepository},
+            record_probe::RecordProbeCommand,
+            update_short_url::{UpdateShortUrlCommand, UpdateShortUrlRepository},
+        },
+        link_health::LinkHealthStore,
+        policy::TargetPolicy,
+        query::{
+            get_full_url::{GetFullUrlQuery, GetFullUrlRepository},
+            get_link_health::GetLinkHealthQuery,
+            get_stats::{GetStatsQuery, GetStatsRepository},
+            list_short_urls::{ListShortUrlsQuery, ListShortUrlsRepository},
+        },
     },
     id_provider::IDProvider,
 };
 
+/// репозиторий для всех команд контейнера, чтение нужно для записей аудита
+pub trait CommandRepository:
+    CreateShortUrlRepository
+    + UpdateShortUrlRepository
+    + DeleteShortUrlRepository
+    + RecordClickRepository
+    + GetFullUrlRepository
+    + Clone
+{
+}
+
+impl<T> CommandRepository for T where
+    T: CreateShortUrlRepository
+        + UpdateShortUrlRepository
+        + DeleteShortUrlRepository
+        + RecordClickRepository
+        + GetFullUrlRepository
+        + Clone
+{
+}
+
+/// репозиторий для всех запросов контейнера
+pub trait QueryRepository:
+    GetFullUrlRepository + GetStatsRepository + ListShortUrlsRepository + Clone
+{
+}
+
+impl<T> QueryRepository for T where
+    T: GetFullUrlRepository + GetStatsRepository + ListShortUrlsRepository + Clone
+{
+}
+
 /// DI контейнер приложения
 pub struct Container<I, R, Q>
 where
     I: IDProvider,
-    R: CreateShortUrlRepository,
-    Q: GetFullUrlRepository,
+    R: CommandRepository,
+    Q: QueryRepository,
 {
     pub shorten_command: CreateShortUrlCommand<I, R>,
+    pub update_command: UpdateShortUrlCommand<R>,
+    pub delete_command: DeleteShortUrlCommand<R>,
+    pub record_click_command: RecordClickCommand<R>,
+    pub probe_command: RecordProbeCommand<R>,
     pub get_full_url_query: GetFullUrlQuery<Q>,
+    pub get_stats_query: GetStatsQuery<Q>,
+    pub list_query: ListShortUrlsQuery<Q>,
+    pub link_health_query: GetLinkHealthQuery,
 }
 
 impl<I, R, Q> Container<I, R, Q>
 where
     I: IDProvider,
-    R: CreateShortUrlRepository,
-    Q: GetFullUrlRepository,
+    R: CommandRepository,
+    Q: QueryRepository,
 {
     pub fn new(id_provider: I, repository: R, querier: Q) -> Self {
-        let shorten_command = CreateShortUrlCommand::new(id_provider, repository);
-        let get_full_url_query = GetFullUrlQuery::new(querier);
+        let shorten_command = CreateShortUrlCommand::new(id_provider, repository.clone());
+        let update_command = UpdateShortUrlCommand::new(repository.clone());
+        let delete_command = DeleteShortUrlCommand::new(repository.clone());
+        let health: Arc<dyn LinkHealthStore> = Arc::new(InMemoryLinkHealthStore::default());
+        let probe_command = RecordProbeCommand::new(repository.clone(), health.clone());
+        let record_click_command = RecordClickCommand::new(repository);
+        let get_full_url_query = GetFullUrlQuery::new(querier.clone());
+        let get_stats_query = GetStatsQuery::new(querier.clone());
+        let list_query = ListShortUrlsQuery::new(querier);
+        let link_health_query = GetLinkHealthQuery::new(health);
 
         Container {
             shorten_command,
+            update_command,
+            delete_command,
+            record_click_command,
+            probe_command,
             get_full_url_query,
+            get_stats_query,
+            list_query,
+            link_health_query,
+        }
+    }
+
+    /// проверять цели новых и изменённых ссылок заданной политикой
+    pub fn with_target_policy(self, policy: Arc<dyn TargetPolicy>) -> Self {
+        Container {
+            shorten_command: self.shorten_command.with_policy(policy.clone()),
+            update_command: self.update_command.with_policy(policy),
+            ..self
+        }
+    }
+
+    /// приводить коды при переходе к формату, в котором их выдаёт `codes`
+    pub fn with_codes(self, codes: Arc<dyn IDProvider + Send + Sync>) -> Self {
+        Container {
+            get_full_url_query: self.get_full_url_query.with_codes(codes),
+            ..self
+        }
+    }
+
+    /// хранить итоги проверок целей в `store`
+    pub fn with_link_health(self, store: Arc<dyn LinkHealthStore>) -> Self {
+        Container {
+            probe_command: self.probe_command.with_store(store.clone()),
+            link_health_query: GetLinkHealthQuery::new(store),
+            ..self
+        }
+    }
+
+    /// ограничить число ссылок, которые можно создать
+    pub fn with_quota(self, max_links: Option<usize>) -> Self {
+        Container {
+            shorten_command: self.shorten_command.with_quota(max_links),
+            ..self
         }
     }
 }
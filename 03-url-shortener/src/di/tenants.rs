@@ -0,0 +1,681 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use url::Url;
+
+use crate::{
+    adapters::{
+        in_memory_link_health_store::InMemoryLinkHealthStore,
+        namespaced_repository::NamespacedRepository,
+    },
+    app::{
+        error::AppError,
+        event::now_millis,
+        link_health::{AllLinks, CheckScope, TargetProber},
+        policy::{AllOf, TargetPolicy},
+        query::list_short_urls::{LinkFilter, TrashFilter},
+        tenant::{Tenant, TenantRepository},
+    },
+    di::{CommandRepository, Container, QueryRepository},
+    id_provider::IdStrategy,
+};
+
+/// контейнер с хранилищем, ограниченным пространством имён арендатора
+pub type TenantContainer<R> =
+    Container<IdStrategy, NamespacedRepository<R>, NamespacedRepository<R>>;
+
+/// контейнер вместе с пространством имён его ссылок, пустым для ссылок без арендатора
+type Scoped<R> = (String, Arc<TenantContainer<R>>);
+
+/// Реестр арендаторов.
+///
+/// Для каждого арендатора лениво собирается свой контейнер с его стратегией id,
+/// политикой целей и квотой поверх общего хранилища. Запросы с неизвестным
+/// доменом обслуживает контейнер по умолчанию без пространства имён.
+pub struct Tenants<R>
+where
+    R: CommandRepository + QueryRepository,
+{
+    store: R,
+    repo: Arc<dyn TenantRepository>,
+    policy: Arc<dyn TargetPolicy>,
+    default: Arc<TenantContainer<R>>,
+    /// домен -> id арендатора
+    domains: Arc<DashMap<String, String>>,
+    /// итоги проверок целей, у каждого арендатора своё пространство имён
+    health: InMemoryLinkHealthStore,
+    /// какие ссылки проверяет этот узел
+    check_scope: Arc<dyn CheckScope>,
+    containers: DashMap<String, Arc<TenantContainer<R>>>,
+}
+
+impl<R> Tenants<R>
+where
+    R: CommandRepository + QueryRepository,
+{
+    /// как часто искать ссылки с истёкшим сроком хранения в корзине
+    const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    pub fn new(
+        store: R,
+        repo: Arc<dyn TenantRepository>,
+        policy: Arc<dyn TargetPolicy>,
+    ) -> Result<Self, AppError> {
+        let domains = Arc::new(DashMap::new());
+        for tenant in repo.list()? {
+            for domain in &tenant.domains {
+                domains.insert(domain.to_lowercase(), tenant.id.clone());
+            }
+        }
+        // домены всех арендаторов тоже наши короткие домены
+        let policy: Arc<dyn TargetPolicy> = Arc::new(AllOf(vec![
+            policy,
+            Arc::new(TenantDomains(domains.clone())),
+        ]));
+
+        let health = InMemoryLinkHealthStore::default();
+        let scoped = NamespacedRepository::new(store.clone(), "");
+        let default = Container::new(IdStrategy::default(), scoped.clone(), scoped)
+            .with_target_policy(policy.clone())
+            .with_link_health(Arc::new(health.clone()));
+
+        Ok(Self {
+            store,
+            repo,
+            policy,
+            default: Arc::new(default),
+            domains,
+            health,
+            check_scope: Arc::new(AllLinks),
+            containers: DashMap::new(),
+        })
+    }
+
+    /// проверять цели только ссылок из `scope`
+    pub fn with_check_scope(self, scope: Arc<dyn CheckScope>) -> Self {
+        Self {
+            check_scope: scope,
+            ..self
+        }
+    }
+
+    /// контейнер для ссылок без арендатора
+    pub fn default_container(&self) -> Arc<TenantContainer<R>> {
+        self.default.clone()
+    }
+
+    /// контейнер арендатора по значению заголовка Host
+    pub fn for_host(&self, host: &str) -> Result<Arc<TenantContainer<R>>, AppError> {
+        let host = strip_port(host).to_lowercase();
+        let Some(id) = self.domains.get(&host).map(|id| id.clone()) else {
+            return Ok(self.default_container());
+        };
+        self.for_tenant(&id)
+    }
+
+    /// контейнер арендатора по id, пустой id - ссылки без арендатора
+    pub fn for_tenant(&self, id: &str) -> Result<Arc<TenantContainer<R>>, AppError> {
+        if id.is_empty() {
+            return Ok(self.default_container());
+        }
+        if let Some(container) = self.containers.get(id) {
+            return Ok(container.clone());
+        }
+
+        let tenant = self.repo.get(id)?;
+        let container = Arc::new(self.build(&tenant));
+        self.containers.insert(tenant.id.clone(), container.clone());
+        Ok(container)
+    }
+
+    pub fn list(&self) -> Result<Vec<Tenant>, AppError> {
+        self.repo.list()
+    }
+
+    pub fn get(&self, id: &str) -> Result<Tenant, AppError> {
+        self.repo.get(id)
+    }
+
+    /// создание или изменение арендатора
+    pub fn save(&self, mut tenant: Tenant) -> Result<(), AppError> {
+        tenant.validate()?;
+        tenant.domains = tenant.domains.iter().map(|d| d.to_lowercase()).collect();
+        for domain in &tenant.domains {
+            if let Some(owner) = self.domains.get(domain)
+                && *owner != tenant.id
+            {
+                return Err(AppError::InvalidInput(format!(
+                    "domain {domain} already belongs to tenant {}",
+                    *owner
+                )));
+            }
+        }
+
+        self.repo.save(tenant.clone())?;
+        self.forget(&tenant.id);
+        for domain in tenant.domains {
+            self.domains.insert(domain, tenant.id.clone());
+        }
+        Ok(())
+    }
+
+    /// Удаление арендатора. Его ссылки остаются в хранилище и
+    /// снова станут доступны, если создать арендатора с тем же id.
+    pub fn delete(&self, id: &str) -> Result<(), AppError> {
+        self.repo.delete(id)?;
+        self.forget(id);
+        Ok(())
+    }
+
+    /// Окончательное удаление ссылок, пролежавших в корзине дольше `retention`,
+    /// возвращает число удалённых. Ссылки удалённых арендаторов не трогаются:
+    /// они вернутся, если арендатора создадут снова.
+    pub async fn purge_trash(&self, retention: Duration) -> Result<usize, AppError> {
+        let before = now_millis().saturating_sub(retention.as_millis() as u64);
+        let filter = LinkFilter {
+            trash: TrashFilter::Only,
+            ..Default::default()
+        };
+        let mut purged = 0;
+        for (_, container) in self.containers()? {
+            for entry in container.list_query.search(&filter).await? {
+                if entry.trashed_at.is_none_or(|at| at > before) {
+                    continue;
+                }
+                match container
+                    .delete_command
+                    .purge_trashed_before(&entry.id, before)
+                    .await
+                {
+                    Ok(()) => purged += 1,
+                    // ссылку восстановили или удалили, пока шёл проход
+                    Err(AppError::NotFound | AppError::InvalidInput(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(purged)
+    }
+
+    /// периодическая очистка корзины
+    pub async fn run_trash_purge(self: Arc<Self>, retention: Duration) {
+        loop {
+            if let Err(e) = self.purge_trash(retention).await {
+                eprintln!("trash purge failed: {e}");
+            }
+            tokio::time::sleep(Self::PURGE_INTERVAL).await;
+        }
+    }
+
+    /// Проверка всех целей ссылок вне корзины, включая цели сплит-ссылок и правил,
+    /// возвращает число проверенных ссылок. Каждый адрес проверяется один раз,
+    /// даже если на него ведут несколько ссылок. Ссылки вне области проверки
+    /// узла пропускаются. Ссылки, все цели которых не отвечают дольше
+    /// `disable_after`, отключаются.
+    pub async fn check_links(
+        &self,
+        prober: &impl TargetProber,
+        disable_after: Option<Duration>,
+    ) -> Result<usize, AppError> {
+        let mut links = Vec::new();
+        for (namespace, container) in self.containers()? {
+            let mut ids = HashSet::new();
+            for entry in container.list_query.execute().await? {
+                if !self.check_scope.covers(&key(&namespace, &entry.id)) {
+                    continue;
+                }
+                let link = match container.get_full_url_query.details(&entry.id).await {
+                    Ok(link) => link,
+                    Err(AppError::NotFound) => continue,
+                    Err(e) => return Err(e),
+                };
+                let targets: Vec<String> =
+                    link.target_urls().into_iter().map(str::to_owned).collect();
+                ids.insert(entry.id.clone());
+                links.push((container.clone(), entry.id, targets));
+            }
+            container.probe_command.forget_others(&ids)?;
+        }
+        let urls: HashSet<String> = links
+            .iter()
+            .flat_map(|(_, _, targets)| targets.iter().cloned())
+            .collect();
+        let probes = prober.probe_all(urls.into_iter().collect()).await;
+
+        let disable_before =
+            disable_after.map(|after| now_millis().saturating_sub(after.as_millis() as u64));
+        let mut recorded = 0;
+        for (container, id, targets) in links {
+            let probes: HashMap<String, _> = targets
+                .into_iter()
+                .filter_map(|url| probes.get(&url).map(|probe| (url, probe.clone())))
+                .collect();
+            match container
+                .probe_command
+                .execute(&id, &probes, disable_before)
+                .await
+            {
+                Ok(()) => recorded += 1,
+                // ссылку удалили или изменили, пока шла проверка
+                Err(AppError::NotFound | AppError::InvalidInput(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(recorded)
+    }
+
+    /// периодическая проверка целей
+    pub async fn run_link_checks(
+        self: Arc<Self>,
+        prober: impl TargetProber,
+        interval: Duration,
+        disable_after: Option<Duration>,
+    ) {
+        loop {
+            if let Err(e) = self.check_links(&prober, disable_after).await {
+                eprintln!("link check failed: {e}");
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// контейнер без арендатора и контейнеры всех арендаторов с их пространствами имён
+    fn containers(&self) -> Result<Vec<Scoped<R>>, AppError> {
+        let mut containers = vec![(String::new(), self.default_container())];
+        for tenant in self.repo.list()? {
+            let container = Arc::new(self.build(&tenant));
+            containers.push((tenant.id, container));
+        }
+        Ok(containers)
+    }
+
+    fn forget(&self, id: &str) {
+        self.domains.retain(|_, owner| owner != id);
+        self.containers.remove(id);
+    }
+
+    fn build(&self, tenant: &Tenant) -> TenantContainer<R> {
+        let scoped = NamespacedRepository::new(self.store.clone(), &tenant.id);
+        let policy = AllOf(vec![self.policy.clone(), Arc::new(tenant.policy())]);
+
+        Container::new(tenant.id_strategy, scoped.clone(), scoped)
+            .with_target_policy(Arc::new(policy))
+            .with_quota(tenant.max_links)
+            .with_codes(Arc::new(tenant.id_strategy))
+            .with_link_health(Arc::new(self.health.scoped(&tenant.id)))
+    }
+}
+
+/// запрет ссылок на домены арендаторов, чтобы не было циклов редиректов
+struct TenantDomains(Arc<DashMap<String, String>>);
+
+impl TargetPolicy for TenantDomains {
+    fn check(&self, full_url: &str) -> Result<(), AppError> {
+        let host = Url::parse(full_url)
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.trim_end_matches('.').to_owned()));
+        match host {
+            Some(host) if self.0.contains_key(&host) => Err(AppError::TargetRejected(format!(
+                "{host} is our own short domain"
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// ключ ссылки в общем хранилище, как у [`NamespacedRepository`]
+fn key(namespace: &str, code: &str) -> String {
+    match namespace {
+        "" => code.to_owned(),
+        ns => format!("{ns}/{code}"),
+    }
+}
+
+/// хост без порта, в том числе для ipv6 вида `[::1]:3001`
+fn strip_port(host: &str) -> &str {
+    if let Some(rest) = host.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    host.split(':').next().unwrap_or(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use dashmap::DashMap;
+
+    use crate::{
+        adapters::{
+            in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+            in_memory_webhook_store::InMemoryWebhookStore,
+        },
+        app::{
+            audit::Actor,
+            event::{EventBus, OutboxRelay},
+            link::Link,
+            link_health::Probe,
+            policy::AllowAll,
+            webhook::{WebhookStore, Webhooks},
+        },
+    };
+
+    use super::*;
+
+    fn tenant(id: &str, domain: &str) -> Tenant {
+        Tenant {
+            id: id.to_owned(),
+            domains: vec![domain.to_owned()],
+            id_strategy: IdStrategy::Alphanumeric { length: 6 },
+            blocklist: vec![],
+            allowlist: None,
+            max_links: Some(1),
+        }
+    }
+
+    fn tenants(store: Arc<DashMap<String, Link>>) -> Tenants<InMemoryRepository> {
+        Tenants::new(
+            InMemoryRepository::new(store),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn host_selects_tenant_namespace() {
+        // given
+        let store = Arc::new(DashMap::new());
+        let tenants = tenants(store.clone());
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+
+        // when
+        let id = tenants
+            .for_host("GO.team-a.example:3001")
+            .unwrap()
+            .shorten_command
+            .execute("https://a.example".to_owned(), &Actor::system())
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(id.len(), 6);
+        assert!(store.contains_key(&format!("team-a/{id}")));
+        let other = tenants.for_host("localhost").unwrap();
+        assert_eq!(
+            other.get_full_url_query.execute(&id).await,
+            Err(AppError::NotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn tenant_quota_is_applied() {
+        // given
+        let tenants = tenants(Arc::new(DashMap::new()));
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+        let container = tenants.for_host("go.team-a.example").unwrap();
+
+        // when
+        container
+            .shorten_command
+            .execute("https://a.example".to_owned(), &Actor::system())
+            .await
+            .unwrap();
+        let second = container
+            .shorten_command
+            .execute("https://b.example".to_owned(), &Actor::system())
+            .await;
+
+        // then
+        assert_eq!(second, Err(AppError::QuotaExceeded));
+    }
+
+    #[tokio::test]
+    async fn tenant_domains_are_rejected_as_targets() {
+        // given
+        let tenants = tenants(Arc::new(DashMap::new()));
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+
+        // when
+        let result = tenants
+            .default_container()
+            .shorten_command
+            .execute(
+                "https://go.team-a.example/docs".to_owned(),
+                &Actor::system(),
+            )
+            .await;
+
+        // then
+        assert!(matches!(result, Err(AppError::TargetRejected(_))));
+    }
+
+    #[tokio::test]
+    async fn expired_trash_is_purged_for_every_tenant() {
+        // given
+        let store = Arc::new(DashMap::new());
+        let tenants = tenants(store.clone());
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+        let trashed = |at: u64| Link {
+            trashed_at: Some(at),
+            ..Link::new("https://a.example")
+        };
+        let week_ago = now_millis() - 7 * 24 * 60 * 60 * 1000;
+        store.insert("old".to_owned(), trashed(week_ago));
+        store.insert("team-a/old".to_owned(), trashed(week_ago));
+        store.insert("recent".to_owned(), trashed(now_millis()));
+        store.insert("active".to_owned(), Link::new("https://a.example"));
+
+        // when
+        let purged = tenants
+            .purge_trash(Duration::from_secs(24 * 60 * 60))
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(purged, 2);
+        let mut left: Vec<String> = store.iter().map(|item| item.key().clone()).collect();
+        left.sort();
+        assert_eq!(left, vec!["active", "recent"]);
+    }
+
+    /// заранее заданные итоги проверок
+    struct ScriptedProber(Vec<(&'static str, Probe)>);
+
+    impl TargetProber for ScriptedProber {
+        async fn probe_all(&self, urls: Vec<String>) -> HashMap<String, Probe> {
+            self.0
+                .iter()
+                .filter(|(url, _)| urls.iter().any(|u| u == url))
+                .map(|(url, probe)| (url.to_string(), probe.clone()))
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn links_of_every_tenant_are_checked() {
+        // given
+        let store = Arc::new(DashMap::new());
+        let tenants = tenants(store.clone());
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+        store.insert("ok".to_owned(), Link::new("https://ok.example"));
+        store.insert("team-a/dead".to_owned(), Link::new("https://dead.example"));
+        store.insert(
+            "trashed".to_owned(),
+            Link {
+                trashed_at: Some(1),
+                ..Link::new("https://dead.example")
+            },
+        );
+        let prober = ScriptedProber(vec![
+            ("https://ok.example", Probe::Status(200)),
+            ("https://dead.example", Probe::Status(404)),
+        ]);
+
+        // when
+        let first = tenants.check_links(&prober, Some(Duration::ZERO)).await;
+        let second = tenants.check_links(&prober, Some(Duration::ZERO)).await;
+
+        // then
+        assert_eq!(first, Ok(2));
+        assert_eq!(second, Ok(2));
+        let dead = store.get("team-a/dead").unwrap();
+        assert!(dead.failing_since.is_some());
+        assert!(dead.disabled_at.is_some());
+        let team_a = tenants.for_host("go.team-a.example").unwrap();
+        let dead_health = team_a.link_health_query.execute("dead").await.unwrap();
+        assert_eq!(dead_health["https://dead.example"].consecutive_failures, 2);
+        let ok = store.get("ok").unwrap();
+        assert_eq!(ok.failing_since, None);
+        assert_eq!(ok.disabled_at, None);
+        let default = tenants.default_container();
+        let ok_health = default.link_health_query.execute("ok").await.unwrap();
+        assert_eq!(ok_health["https://ok.example"].last_status, Some(200));
+        assert!(
+            default
+                .link_health_query
+                .execute("trashed")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    /// узел, которому достались только ссылки без арендатора
+    struct DefaultOnly;
+
+    impl CheckScope for DefaultOnly {
+        fn covers(&self, key: &str) -> bool {
+            !key.contains('/')
+        }
+    }
+
+    #[tokio::test]
+    async fn only_links_in_scope_are_checked() {
+        // given
+        let store = Arc::new(DashMap::new());
+        let tenants = tenants(store.clone()).with_check_scope(Arc::new(DefaultOnly));
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+        store.insert("dead".to_owned(), Link::new("https://dead.example"));
+        store.insert("team-a/dead".to_owned(), Link::new("https://dead.example"));
+        let prober = ScriptedProber(vec![("https://dead.example", Probe::Status(404))]);
+
+        // when
+        let checked = tenants.check_links(&prober, None).await;
+
+        // then
+        assert_eq!(checked, Ok(1));
+        assert!(store.get("dead").unwrap().failing_since.is_some());
+        assert_eq!(store.get("team-a/dead").unwrap().failing_since, None);
+    }
+
+    #[test]
+    fn domain_belongs_to_one_tenant() {
+        // given
+        let tenants = tenants(Arc::new(DashMap::new()));
+        tenants.save(tenant("team-a", "go.example")).unwrap();
+
+        // when
+        let result = tenants.save(tenant("team-b", "go.example"));
+
+        // then
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn tenant_is_selected_by_id() {
+        // given
+        let tenants = tenants(Arc::new(DashMap::new()));
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+
+        // when
+        let by_id = tenants.for_tenant("team-a").unwrap();
+
+        // then
+        let by_host = tenants.for_host("go.team-a.example").unwrap();
+        assert!(Arc::ptr_eq(&by_id, &by_host));
+        assert!(Arc::ptr_eq(
+            &tenants.for_tenant("").unwrap(),
+            &tenants.default_container()
+        ));
+        assert!(matches!(
+            tenants.for_tenant("team-b"),
+            Err(AppError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn deleted_tenant_domain_falls_back_to_default() {
+        // given
+        let tenants = tenants(Arc::new(DashMap::new()));
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+        let before = tenants.for_host("go.team-a.example").unwrap();
+
+        // when
+        tenants.delete("team-a").unwrap();
+        let after = tenants.for_host("go.team-a.example").unwrap();
+
+        // then
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert!(Arc::ptr_eq(&after, &tenants.default_container()));
+    }
+
+    #[tokio::test]
+    async fn tenant_events_carry_tenant_id() {
+        // given
+        let repo = InMemoryRepository::new(Arc::new(DashMap::new()));
+        let webhooks_store = Arc::new(InMemoryWebhookStore::default());
+        let webhooks = Webhooks::new(webhooks_store.clone());
+        webhooks
+            .subscribe("http://hooks.example".to_owned(), vec![], None)
+            .unwrap();
+        let bus = EventBus::default();
+        bus.subscribe(Arc::new(webhooks));
+        let relay = OutboxRelay::new(Arc::new(repo.clone()), Arc::new(bus));
+        let tenants = Tenants::new(
+            repo,
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        tenants.save(tenant("team-a", "go.team-a.example")).unwrap();
+
+        // when
+        tenants
+            .for_host("go.team-a.example")
+            .unwrap()
+            .shorten_command
+            .execute("https://a.example".to_owned(), &Actor::system())
+            .await
+            .unwrap();
+        tenants
+            .default_container()
+            .shorten_command
+            .execute("https://b.example".to_owned(), &Actor::system())
+            .await
+            .unwrap();
+        relay.relay_pending().unwrap();
+
+        // then
+        let mut tenant_ids: Vec<Option<String>> = webhooks_store
+            .due(u64::MAX, 10)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.event.tenant)
+            .collect();
+        tenant_ids.sort();
+        assert_eq!(tenant_ids, vec![None, Some("team-a".to_owned())]);
+    }
+
+    #[test]
+    fn strips_port_from_host() {
+        assert_eq!(strip_port("example.com:3001"), "example.com");
+        assert_eq!(strip_port("[::1]:3001"), "::1");
+        assert_eq!(strip_port("example.com"), "example.com");
+    }
+}
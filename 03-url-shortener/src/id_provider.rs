@@ -1,7 +1,24 @@
+use crate::app::error::AppError;
+
+/// коды, занятые страницами самого сервиса: ссылка с таким кодом не откроется
+pub const RESERVED_CODES: [&str; 3] = ["ui", "healthz", "readyz"];
+
+pub fn is_reserved(code: &str) -> bool {
+    RESERVED_CODES.contains(&code)
+}
+
 /// провайдер для генерации id
 pub trait IDProvider {
-    /// получить новый id
+    /// получить новый id, не входящий в [`RESERVED_CODES`]
     fn provide(&self) -> String;
+
+    /// Код в том виде, в каком его выдал провайдер, из введённого пользователем.
+    /// `None` значит, что код не похож на коды провайдера (например, выдан до
+    /// смены формата) и ищется как есть. Ошибка значит, что код похож на коды
+    /// провайдера, но с опечаткой, которую нельзя исправить.
+    fn normalize(&self, _code: &str) -> Result<Option<String>, AppError> {
+        Ok(None)
+    }
 }
 
 /// боевая реализация провайдера для генерации id
@@ -33,3 +50,178 @@ impl IDProvider for FakeIDProvider {
         self.id.clone()
     }
 }
+
+/// стратегия генерации id, настраиваемая для каждого арендатора
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IdStrategy {
+    /// nanoid из url-безопасного алфавита
+    NanoId { length: usize },
+    /// только цифры и латинские буквы в нижнем регистре
+    Alphanumeric { length: usize },
+    /// Crockford base32 и символ проверки в конце, всего `length + 1` символов.
+    /// Код можно вводить в любом регистре, путая `0/O` и `1/I/L` и с дефисами.
+    /// Коды, выданные до перехода на эту стратегию, открываются как есть,
+    /// если не похожи на коды Crockford этой длины.
+    Crockford { length: usize },
+}
+
+impl IdStrategy {
+    /// длина случайной части кода
+    pub fn length(&self) -> usize {
+        match *self {
+            IdStrategy::NanoId { length }
+            | IdStrategy::Alphanumeric { length }
+            | IdStrategy::Crockford { length } => length,
+        }
+    }
+}
+
+impl Default for IdStrategy {
+    fn default() -> Self {
+        IdStrategy::NanoId { length: 12 }
+    }
+}
+
+impl IdStrategy {
+    fn generate(&self) -> String {
+        const ALPHANUMERIC: [char; 36] = [
+            '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'g',
+            'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x',
+            'y', 'z',
+        ];
+
+        match *self {
+            IdStrategy::NanoId { length } => nanoid::nanoid!(length),
+            IdStrategy::Alphanumeric { length } => nanoid::nanoid!(length, &ALPHANUMERIC),
+            IdStrategy::Crockford { length } => {
+                let alphabet: Vec<char> = crockford::ALPHABET.chars().collect();
+                let mut code = nanoid::nanoid!(length, &alphabet);
+                code.push(crockford::check_symbol(&code));
+                code
+            }
+        }
+    }
+}
+
+impl IDProvider for IdStrategy {
+    fn provide(&self) -> String {
+        loop {
+            let code = self.generate();
+            if !is_reserved(&code) {
+                return code;
+            }
+        }
+    }
+
+    fn normalize(&self, code: &str) -> Result<Option<String>, AppError> {
+        match *self {
+            IdStrategy::Crockford { length } => crockford::normalize(code, length),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Коды Crockford base32: <https://www.crockford.com/base32.html>
+mod crockford {
+    use crate::app::error::AppError;
+
+    pub const ALPHABET: &str = "0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+    /// символы проверки: алфавит и ещё пять для остатков 32..=36
+    const CHECK_SYMBOLS: &str = "0123456789ABCDEFGHJKMNPQRSTVWXYZ*~$=U";
+
+    /// символ проверки: значение кода как числа по основанию 32 по модулю 37
+    pub fn check_symbol(code: &str) -> char {
+        let rem = code
+            .bytes()
+            .filter_map(|c| ALPHABET.bytes().position(|a| a == c))
+            .fold(0, |rem, digit| (rem * 32 + digit) % 37);
+        CHECK_SYMBOLS.as_bytes()[rem] as char
+    }
+
+    /// Код в верхнем регистре без дефисов, `O` читается как `0`, `I` и `L` как `1`.
+    /// Коды этой длины из алфавита Crockford с неверным символом проверки
+    /// отклоняются, не доходя до хранилища. Коды с чужими символами или
+    /// другой длины не считаются кодами Crockford: `None`.
+    pub fn normalize(code: &str, length: usize) -> Result<Option<String>, AppError> {
+        let mut normalized: String = code
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| match c.to_ascii_uppercase() {
+                'O' => '0',
+                'I' | 'L' => '1',
+                c => c,
+            })
+            .collect();
+        if normalized.chars().count() != length + 1 {
+            return Ok(None);
+        }
+        let Some(check) = normalized.pop() else {
+            return Ok(None);
+        };
+        if !normalized.chars().all(|c| ALPHABET.contains(c)) || !CHECK_SYMBOLS.contains(check) {
+            return Ok(None);
+        }
+        if check_symbol(&normalized) != check {
+            return Err(AppError::InvalidInput(format!(
+                "{code} is not a valid short code"
+            )));
+        }
+        normalized.push(check);
+        Ok(Some(normalized))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_codes_are_never_provided() {
+        // given
+        let strategy = IdStrategy::Alphanumeric { length: 2 };
+
+        // when
+        let codes: Vec<String> = (0..5000).map(|_| strategy.provide()).collect();
+
+        // then
+        assert!(codes.iter().all(|code| !is_reserved(code)));
+    }
+
+    #[test]
+    fn crockford_code_survives_typos() {
+        // given
+        let strategy = IdStrategy::Crockford { length: 6 };
+        let provided = strategy.provide();
+
+        // when
+        let normalized = strategy.normalize("7k-Om-lpt");
+
+        // then
+        assert_eq!(normalized, Ok(Some("7K0M1PT".to_owned())));
+        assert_eq!(strategy.normalize(&provided), Ok(Some(provided)));
+    }
+
+    #[test]
+    fn crockford_rejects_mistyped_codes() {
+        // given
+        let strategy = IdStrategy::Crockford { length: 4 };
+        // символ проверки "ZZZZ" по модулю 37 выходит за алфавит
+        let valid = ["00000", "00011", "ZZZZ*"];
+        let mistyped = ["00001", "0000U"];
+        let foreign = ["0001", "000001", "0U001", "ab!c1"];
+
+        // when
+        let normalize = |codes: &[&str]| -> Vec<Result<bool, AppError>> {
+            codes
+                .iter()
+                .map(|code| strategy.normalize(code).map(|code| code.is_some()))
+                .collect()
+        };
+
+        // then
+        assert_eq!(normalize(&valid), vec![Ok(true); 3]);
+        assert!(normalize(&mistyped).iter().all(Result::is_err));
+        assert_eq!(normalize(&foreign), vec![Ok(false); 4]);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod adapters;
+pub mod app;
+pub mod config;
+pub mod di;
+pub mod id_provider;
+pub mod ports;
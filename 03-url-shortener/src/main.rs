@@ -1,21 +1,239 @@
 use dashmap::DashMap;
-use std::sync::Arc;
+use std::{process::ExitCode, sync::Arc};
+use tokio::sync::watch;
 
-use crate::ports::httpimpl::server::Server;
-
-pub mod adapters;
-pub mod app;
-pub mod di;
-pub mod id_provider;
-pub mod ports;
+use rust_url_shortener::{
+    adapters::{
+        cached_repository::CachedRepository,
+        cluster_repository::{ClusterRepository, ShardStore},
+        compact_repository::CompactRepository,
+        durable_repository::{DurableRepository, FsyncPolicy},
+        file_audit_trail::FileAuditTrail,
+        file_policy::FilePolicy,
+        file_repository::FileRepository,
+        file_tenant_repository::FileTenantRepository,
+        file_webhook_store::FileWebhookStore,
+        http_prober::HttpProber,
+        in_memory_audit_trail::InMemoryAuditTrail,
+        in_memory_idempotency_store::InMemoryIdempotencyStore,
+        in_memory_repository::InMemoryRepository,
+        in_memory_tenant_repository::InMemoryTenantRepository,
+        in_memory_webhook_store::InMemoryWebhookStore,
+        webhook_dispatcher::WebhookDispatcher,
+    },
+    app::{
+        audit::{AuditRecorder, AuditTrail},
+        event::{EventBus, EventOutbox, OutboxRelay},
+        health::{HealthCheck, Readiness},
+        idempotency::Idempotency,
+        link_health::{AllLinks, CheckScope},
+        tenant::TenantRepository,
+        webhook::{WebhookStore, Webhooks},
+    },
+    config::{ClusterConfig, Config, Storage},
+    di::{CommandRepository, QueryRepository, tenants::Tenants},
+    ports::{grpc::server::GrpcServer, httpimpl::server::Server},
+};
 
 #[tokio::main]
-async fn main() {
-    let store = Arc::new(DashMap::new());
-    let in_mem = adapters::in_memory_repository::InMemoryRepository::new(store);
-    let idp = id_provider::NanoIdProvider;
-    let container = Arc::new(di::Container::new(idp, in_mem.clone(), in_mem));
-
-    let server = Server::new(3001, container);
-    server.run().await;
+async fn main() -> ExitCode {
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match config.storage.clone() {
+        Storage::InMemory => match &config.durability {
+            Some(durability) => {
+                let store = match DurableRepository::open(durability) {
+                    Ok(store) => store,
+                    Err(e) => {
+                        eprintln!("error: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                };
+                tokio::spawn(store.clone().run_snapshots(durability.snapshot_interval));
+                if let FsyncPolicy::Interval(interval) = durability.fsync {
+                    tokio::spawn(store.clone().run_flusher(interval));
+                }
+                run_node(&config, store).await
+            }
+            None => run_node(&config, InMemoryRepository::new(Arc::new(DashMap::new()))).await,
+        },
+        Storage::Compact => run_node(&config, CompactRepository::new()).await,
+        Storage::File(path) => {
+            let file = FileRepository::new(path);
+            // кэш узла не узнаёт об изменениях на других узлах, поэтому в кластере выключен
+            match (&config.cluster, config.cache) {
+                (None, Some(cache_config)) => {
+                    run(
+                        &config,
+                        CachedRepository::new(file, cache_config),
+                        Arc::new(AllLinks),
+                    )
+                    .await
+                }
+                _ => run_node(&config, file).await,
+            }
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// одиночный узел или узел кластера поверх локального хранилища
+async fn run_node<S>(config: &Config, store: S) -> Result<(), String>
+where
+    S: ShardStore + EventOutbox + HealthCheck + Clone + Send + Sync + 'static,
+{
+    match &config.cluster {
+        Some(cluster) => {
+            let cluster = join_cluster(cluster, store)?;
+            run(config, cluster.clone(), Arc::new(cluster)).await
+        }
+        None => run(config, store, Arc::new(AllLinks)).await,
+    }
+}
+
+/// открытие порта для других узлов и хранилище, распределённое по кластеру
+fn join_cluster<S>(config: &ClusterConfig, local: S) -> Result<ClusterRepository<S>, String>
+where
+    S: ShardStore + Send + Sync + 'static,
+{
+    let listener = std::net::TcpListener::bind(&config.node)
+        .map_err(|e| format!("cluster node {}: {e}", config.node))?;
+    let cluster = ClusterRepository::new(
+        config.node.clone(),
+        config.peers.clone(),
+        config.replicas,
+        &config.secret,
+        local,
+    );
+    cluster.serve(listener);
+    Ok(cluster)
+}
+
+/// Запуск http и grpc серверов поверх общего хранилища ссылок,
+/// `check_scope` — какие ссылки проверяет этот узел.
+async fn run<R>(config: &Config, store: R, check_scope: Arc<dyn CheckScope>) -> Result<(), String>
+where
+    R: CommandRepository + QueryRepository + EventOutbox + HealthCheck + Send + Sync + 'static,
+{
+    let tenant_repo: Arc<dyn TenantRepository> = match &config.tenants_file {
+        Some(path) => Arc::new(FileTenantRepository::new(path)),
+        None => Arc::new(InMemoryTenantRepository::default()),
+    };
+    let policy = FilePolicy::new(
+        config.policy.blocklist_file.clone(),
+        config.policy.allowlist_file.clone(),
+        &config.policy.short_domains,
+    );
+    let webhook_store: Arc<dyn WebhookStore> = match &config.webhooks.file {
+        Some(path) => Arc::new(FileWebhookStore::new(path)),
+        None => Arc::new(InMemoryWebhookStore::default()),
+    };
+    let audit: Arc<dyn AuditTrail> = match &config.audit_file {
+        Some(path) => Arc::new(FileAuditTrail::new(path)),
+        None => Arc::new(InMemoryAuditTrail::default()),
+    };
+    let webhooks = Webhooks::new(webhook_store.clone());
+    let events = Arc::new(EventBus::default());
+    events.subscribe(Arc::new(webhooks.clone()));
+    events.subscribe(Arc::new(AuditRecorder::new(audit.clone())));
+    let relay = OutboxRelay::new(Arc::new(store.clone()), events);
+    let readiness = Readiness::default()
+        .with_component("links", Arc::new(store.clone()))
+        .with_component("tenants", tenant_repo.clone())
+        .with_component("webhooks", webhook_store.clone())
+        .with_component("audit", audit.clone());
+    let readiness = Arc::new(readiness);
+    let tenants = Tenants::new(store, tenant_repo, Arc::new(policy))
+        .map_err(|e| format!("tenants: {e}"))?
+        .with_check_scope(check_scope);
+    let tenants = Arc::new(tenants);
+
+    tokio::spawn(tenants.clone().run_trash_purge(config.trash_retention));
+    if let Some(check) = &config.link_check {
+        // в кластере каждый узел проверяет только ссылки, первым владельцем которых он является
+        tokio::spawn(tenants.clone().run_link_checks(
+            HttpProber::new(check),
+            check.interval,
+            check.disable_after,
+        ));
+    }
+
+    let server = Server::new(config.http_port, tenants.clone())
+        .with_admin_token(config.admin_token.clone())
+        .with_webhooks(webhooks)
+        .with_readiness(readiness.clone())
+        .with_cors_origins(config.cors_origins.clone())
+        .with_audit(audit)
+        .with_trusted_proxies(config.trusted_proxies.clone())
+        .with_tls(config.tls.clone())
+        // ключи живут в памяти узла: повтор через другой узел кластера не узнается
+        .with_idempotency(Arc::new(Idempotency::new(
+            Arc::new(InMemoryIdempotencyStore::new(config.idempotency_capacity)),
+            config.idempotency_ttl,
+        )));
+    let grpc_server = GrpcServer::new(config.grpc_port, tenants.default_container());
+    let dispatcher = WebhookDispatcher::new(webhook_store, config.webhooks.retry);
+
+    // после сигнала сервер сначала перестаёт быть готовым, чтобы балансировщик
+    // успел убрать его, и только потом закрывает соединения
+    let (stop, stopped) = watch::channel(false);
+    let grace = config.shutdown_grace;
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        readiness.begin_shutdown();
+        tokio::time::sleep(grace).await;
+        let _ = stop.send(true);
+    });
+    let until_stopped = |mut stopped: watch::Receiver<bool>| async move {
+        let _ = stopped.wait_for(|stopped| *stopped).await;
+    };
+
+    // ошибка http сервера, например занятый порт, останавливает узел
+    let servers = async {
+        tokio::try_join!(server.run_until(until_stopped(stopped.clone())), async {
+            grpc_server.run_until(until_stopped(stopped)).await;
+            Ok(())
+        })
+    };
+    tokio::select! {
+        result = servers => {
+            result.map_err(|e| format!("http server: {e}"))?;
+        }
+        _ = relay.run() => {}
+        _ = dispatcher.run() => {}
+    }
+    Ok(())
+}
+
+/// Ctrl+C или SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.unwrap();
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .unwrap()
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
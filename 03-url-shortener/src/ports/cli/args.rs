@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// администрирование сокращателя ссылок
+#[derive(Debug, Parser)]
+#[command(name = "shortener-cli")]
+pub struct Cli {
+    /// формат вывода
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    pub format: OutputFormat,
+
+    /// файл хранилища, по умолчанию берётся из SHORTENER_DATA_FILE
+    #[arg(long, global = true)]
+    pub data_file: Option<PathBuf>,
+
+    /// арендатор из SHORTENER_TENANTS_FILE, с чьими ссылками работать,
+    /// по умолчанию ссылки без арендатора
+    #[arg(long, global = true, default_value = "")]
+    pub tenant: String,
+
+    #[command(subcommand)]
+    pub command: CliCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum CliCommand {
+    #[command(flatten)]
+    Links(Command),
+    /// проверить цепочку хэшей журнала аудита, по умолчанию файл из SHORTENER_AUDIT_FILE
+    VerifyAudit { file: Option<PathBuf> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// создать короткую ссылку
+    Shorten { url: String },
+    /// показать полный url по короткому коду
+    Resolve { id: String },
+    /// сменить полный url короткой ссылки
+    Update { id: String, url: String },
+    /// удалить короткую ссылку в корзину
+    Delete { id: String },
+    /// вернуть короткую ссылку из корзины
+    Restore { id: String },
+    /// список всех ссылок
+    List,
+    /// создать короткие ссылки для всех url из файла, по одному на строку
+    Import { file: PathBuf },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
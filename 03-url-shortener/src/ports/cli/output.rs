@@ -0,0 +1,145 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::ports::cli::args::OutputFormat;
+
+/// короткая ссылка в выводе cli
+#[derive(Debug, Serialize)]
+pub struct ShortUrlRecord {
+    pub id: String,
+    pub url: String,
+}
+
+/// строка файла импорта: созданная ссылка или причина отказа
+#[derive(Debug, Serialize)]
+pub struct ImportRecord {
+    pub line: usize,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// результат удаления в выводе cli
+#[derive(Debug, Serialize)]
+pub struct DeletedRecord {
+    pub id: String,
+    pub deleted: bool,
+}
+
+/// Вывод записи или списка записей в выбранном формате.
+///
+/// Для таблицы поля записи становятся колонками в порядке объявления.
+pub fn render<T: Serialize>(
+    format: OutputFormat,
+    value: &T,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let value = serde_json::to_value(value)?;
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, &value)?;
+            writeln!(out)
+        }
+        OutputFormat::Table => render_table(&value, out),
+    }
+}
+
+fn render_table(value: &Value, out: &mut impl Write) -> io::Result<()> {
+    let rows = match value {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    };
+    // необязательные поля есть не во всех записях, колонки собираются по всем
+    let mut keys: Vec<&String> = Vec::new();
+    for row in &rows {
+        let Value::Object(fields) = row else {
+            return Ok(());
+        };
+        for key in fields.keys() {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    let headers: Vec<String> = keys.iter().map(|k| k.to_uppercase()).collect();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            keys.iter()
+                .map(|k| match &row[k.as_str()] {
+                    Value::Null => String::new(),
+                    Value::String(s) => s.clone(),
+                    Value::Array(items) => items
+                        .iter()
+                        .map(|item| match item {
+                            Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    other => other.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|i| {
+            cells
+                .iter()
+                .map(|row| row[i].len())
+                .chain([headers[i].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    for row in [headers].iter().chain(cells.iter()) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_columns_are_aligned() {
+        // given
+        let records = vec![
+            ShortUrlRecord {
+                id: "1".to_owned(),
+                url: "https://google.com".to_owned(),
+            },
+            ShortUrlRecord {
+                id: "12345".to_owned(),
+                url: "https://github.com".to_owned(),
+            },
+        ];
+        let mut out = Vec::new();
+
+        // when
+        render(OutputFormat::Table, &records, &mut out).unwrap();
+
+        // then
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "ID     URL\n1      https://google.com\n12345  https://github.com\n"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::{io::Write, sync::Arc};
+
+use crate::{
+    app::{
+        audit::{Actor, AuditTrail},
+        error::AppError,
+    },
+    di::{CommandRepository, Container, QueryRepository},
+    id_provider::IDProvider,
+    ports::cli::{
+        args::{Command, OutputFormat},
+        output::{DeletedRecord, ImportRecord, ShortUrlRecord, render},
+    },
+};
+
+/// выполнение команд cli поверх DI контейнера
+pub struct CliRunner<I, R, Q>
+where
+    I: IDProvider,
+    R: CommandRepository,
+    Q: QueryRepository,
+{
+    container: Arc<Container<I, R, Q>>,
+    /// от чьего имени изменения попадают в журнал аудита
+    actor: Actor,
+}
+
+impl<I, R, Q> CliRunner<I, R, Q>
+where
+    I: IDProvider,
+    R: CommandRepository,
+    Q: QueryRepository,
+{
+    pub fn new(container: impl Into<Arc<Container<I, R, Q>>>) -> Self {
+        Self {
+            container: container.into(),
+            actor: Actor::new("cli", None, "local"),
+        }
+    }
+
+    /// записывать изменения в журнал аудита от имени `actor`
+    pub fn with_actor(self, actor: Actor) -> Self {
+        Self { actor, ..self }
+    }
+
+    /// Выполнить команду и записать результат в `out`
+    pub async fn run(
+        &self,
+        command: Command,
+        format: OutputFormat,
+        out: &mut impl Write,
+    ) -> Result<(), AppError> {
+        let written = match command {
+            Command::Shorten { url } => {
+                let id = self
+                    .container
+                    .shorten_command
+                    .execute(url.clone(), &self.actor)
+                    .await?;
+                render(format, &ShortUrlRecord { id, url }, out)
+            }
+            Command::Resolve { id } => {
+                let url = self.container.get_full_url_query.execute(&id).await?;
+                render(format, &ShortUrlRecord { id, url }, out)
+            }
+            Command::Update { id, url } => {
+                self.container
+                    .update_command
+                    .execute(&id, url.clone(), &self.actor)
+                    .await?;
+                render(format, &ShortUrlRecord { id, url }, out)
+            }
+            Command::Delete { id } => {
+                self.container
+                    .delete_command
+                    .execute(&id, &self.actor)
+                    .await?;
+                render(format, &DeletedRecord { id, deleted: true }, out)
+            }
+            Command::Restore { id } => {
+                self.container
+                    .delete_command
+                    .restore(&id, &self.actor)
+                    .await?;
+                // без перехода: защищённую или исчерпанную ссылку тоже можно восстановить
+                let link = self.container.get_full_url_query.details(&id).await?;
+                render(format, &ShortUrlRecord { id, url: link.url }, out)
+            }
+            Command::List => {
+                let entries = self.container.list_query.execute().await?;
+                render(format, &entries, out)
+            }
+            Command::Import { file } => {
+                let content = tokio::fs::read_to_string(&file)
+                    .await
+                    .map_err(|e| AppError::Internal(format!("{}: {e}", file.display())))?;
+
+                // ошибка в одной строке не прерывает импорт: выводятся итоги всех строк
+                let mut records = Vec::new();
+                for (index, url) in content.lines().map(str::trim).enumerate() {
+                    if url.is_empty() || url.starts_with('#') {
+                        continue;
+                    }
+                    let result = self
+                        .container
+                        .shorten_command
+                        .execute(url.to_owned(), &self.actor)
+                        .await;
+                    records.push(ImportRecord {
+                        line: index + 1,
+                        url: url.to_owned(),
+                        error: result.as_ref().err().map(ToString::to_string),
+                        id: result.ok(),
+                    });
+                }
+                render(format, &records, out).map_err(|e| AppError::Internal(e.to_string()))?;
+
+                let failed = records.iter().filter(|r| r.error.is_some()).count();
+                if failed > 0 {
+                    return Err(AppError::InvalidInput(format!(
+                        "{failed} of {} urls were not imported",
+                        records.len()
+                    )));
+                }
+                Ok(())
+            }
+        };
+
+        written.map_err(|e| AppError::Internal(e.to_string()))
+    }
+}
+
+/// проверка цепочки журнала аудита, при нарушении возвращается ошибка с номером записи
+pub fn verify_audit(
+    trail: &dyn AuditTrail,
+    format: OutputFormat,
+    out: &mut impl Write,
+) -> Result<(), AppError> {
+    let report = trail.verify()?;
+    render(format, &report, out).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use dashmap::DashMap;
+
+    use crate::{
+        adapters::{
+            in_memory_audit_trail::InMemoryAuditTrail, in_memory_repository::InMemoryRepository,
+        },
+        app::{audit::AuditRecord, link::Link},
+        id_provider::FakeIDProvider,
+    };
+
+    use super::*;
+
+    fn runner(
+        store: Arc<DashMap<String, Link>>,
+    ) -> CliRunner<FakeIDProvider, InMemoryRepository, InMemoryRepository> {
+        let repo = InMemoryRepository::new(store);
+        let idp = FakeIDProvider::new("abc".to_owned());
+        CliRunner::new(Container::new(idp, repo.clone(), repo))
+    }
+
+    async fn run(
+        runner: &CliRunner<FakeIDProvider, InMemoryRepository, InMemoryRepository>,
+        command: Command,
+        format: OutputFormat,
+    ) -> Result<String, AppError> {
+        let mut out = Vec::new();
+        runner.run(command, format, &mut out).await?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[tokio::test]
+    async fn shorten_prints_table() {
+        // given
+        let store = Arc::new(DashMap::new());
+        let runner = runner(store.clone());
+
+        // when
+        let result = run(
+            &runner,
+            Command::Shorten {
+                url: "https://google.com".to_owned(),
+            },
+            OutputFormat::Table,
+        )
+        .await;
+
+        // then
+        assert_eq!(result, Ok("ID   URL\nabc  https://google.com\n".to_owned()));
+        assert_eq!(store.get("abc").unwrap().url, "https://google.com");
+    }
+
+    #[tokio::test]
+    async fn list_prints_json() {
+        // given
+        let store = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+        let runner = runner(store);
+
+        // when
+        let result = run(&runner, Command::List, OutputFormat::Json)
+            .await
+            .unwrap();
+
+        // then
+        let json: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{"id": "123", "url": "https://google.com", "clicks": 0}])
+        );
+    }
+
+    #[tokio::test]
+    async fn delete_unknown_id() {
+        // given
+        let runner = runner(Arc::new(DashMap::new()));
+
+        // when
+        let result = run(
+            &runner,
+            Command::Delete {
+                id: "123".to_owned(),
+            },
+            OutputFormat::Table,
+        )
+        .await;
+
+        // then
+        assert_eq!(result, Err(AppError::NotFound));
+    }
+
+    #[tokio::test]
+    async fn deleted_link_is_restored_from_trash() {
+        // given
+        let store = Arc::new(DashMap::new());
+        store.insert(
+            "123".to_owned(),
+            Link::protected("https://google.com", "secret").unwrap(),
+        );
+        let runner = runner(store.clone());
+        let id = || "123".to_owned();
+
+        // when
+        run(&runner, Command::Delete { id: id() }, OutputFormat::Table)
+            .await
+            .unwrap();
+        let listed = run(&runner, Command::List, OutputFormat::Json).await;
+        let restored = run(&runner, Command::Restore { id: id() }, OutputFormat::Table).await;
+
+        // then
+        assert_eq!(listed, Ok("[]\n".to_owned()));
+        assert_eq!(
+            restored,
+            Ok("ID   URL\n123  https://google.com\n".to_owned())
+        );
+        assert!(!store.get("123").unwrap().is_trashed());
+    }
+
+    #[tokio::test]
+    async fn import_skips_blank_lines_and_comments() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("urls.txt");
+        std::fs::write(&file, "# links\nhttps://google.com\n\n").unwrap();
+        let store = Arc::new(DashMap::new());
+        let runner = runner(store.clone());
+
+        // when
+        let result = run(&runner, Command::Import { file }, OutputFormat::Json).await;
+
+        // then
+        assert!(result.is_ok());
+        assert_eq!(store.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn import_reports_every_line_and_fails_on_bad_ones() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("urls.txt");
+        std::fs::write(&file, "https://google.com\nhttps://github.com\n").unwrap();
+        let store = Arc::new(DashMap::new());
+        let repo = InMemoryRepository::new(store.clone());
+        let idp = FakeIDProvider::new("abc".to_owned());
+        let runner = CliRunner::new(Container::new(idp, repo.clone(), repo).with_quota(Some(1)));
+        let mut out = Vec::new();
+
+        // when
+        let result = runner
+            .run(Command::Import { file }, OutputFormat::Json, &mut out)
+            .await;
+
+        // then
+        assert_eq!(
+            result,
+            Err(AppError::InvalidInput(
+                "1 of 2 urls were not imported".to_owned()
+            ))
+        );
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"line": 1, "url": "https://google.com", "id": "abc"},
+                {"line": 2, "url": "https://github.com", "error": AppError::QuotaExceeded.to_string()},
+            ])
+        );
+        assert_eq!(store.get("abc").unwrap().url, "https://google.com");
+    }
+
+    #[test]
+    fn verify_audit_prints_chain_head() {
+        // given
+        let trail = InMemoryAuditTrail::default();
+        let entry = trail
+            .append(AuditRecord::new(
+                "admin",
+                None,
+                "127.0.0.1",
+                "link.create",
+                "abc",
+            ))
+            .unwrap();
+        let mut out = Vec::new();
+
+        // when
+        let result = verify_audit(&trail, OutputFormat::Json, &mut out);
+
+        // then
+        assert_eq!(result, Ok(()));
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"entries": 1, "last_hash": entry.hash})
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use tonic::Status;
+
+use crate::app::error::AppError;
+
+/// отображение ошибок приложения в grpc статусы
+impl From<AppError> for Status {
+    fn from(err: AppError) -> Self {
+        match err {
+            AppError::NotFound => Status::not_found(err.to_string()),
+            AppError::PasswordRequired | AppError::InvalidPassword | AppError::Unauthorized => {
+                Status::unauthenticated(err.to_string())
+            }
+            AppError::TooManyAttempts | AppError::QuotaExceeded => {
+                Status::resource_exhausted(err.to_string())
+            }
+            AppError::InvalidUrl(_) | AppError::InvalidInput(_) => {
+                Status::invalid_argument(err.to_string())
+            }
+            AppError::TargetRejected(_) => Status::permission_denied(err.to_string()),
+            AppError::Gone(_) | AppError::IdempotencyKeyReused => {
+                Status::failed_precondition(err.to_string())
+            }
+            AppError::RequestInProgress | AppError::Conflict => Status::aborted(err.to_string()),
+            AppError::Internal(_) => Status::internal(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,8 @@
+pub mod error;
+pub mod server;
+pub mod service;
+
+/// код, сгенерированный из proto/shortener.proto
+pub mod proto {
+    tonic::include_proto!("shortener.v1");
+}
@@ -0,0 +1,203 @@
+use std::{future::Future, sync::Arc};
+
+use tokio::net::TcpListener;
+use tonic::transport::server::TcpIncoming;
+
+use crate::{
+    di::{CommandRepository, Container, QueryRepository},
+    id_provider::IDProvider,
+    ports::grpc::{proto::shortener_server::ShortenerServer, service::ShortenerService},
+};
+
+/// grpc сервер приложения
+pub struct GrpcServer<I, R, Q>
+where
+    I: IDProvider + Send + Sync + 'static,
+    R: CommandRepository + Send + Sync + 'static,
+    Q: QueryRepository + Send + Sync + 'static,
+{
+    port: u16,
+    container: Arc<Container<I, R, Q>>,
+}
+
+impl<I, R, Q> GrpcServer<I, R, Q>
+where
+    I: IDProvider + Send + Sync + 'static,
+    R: CommandRepository + Send + Sync + 'static,
+    Q: QueryRepository + Send + Sync + 'static,
+{
+    pub fn new(port: u16, container: Arc<Container<I, R, Q>>) -> Self {
+        GrpcServer { port, container }
+    }
+
+    /// Запуск сервера
+    pub async fn run(self) {
+        self.run_until(std::future::pending()).await;
+    }
+
+    /// запуск сервера до завершения `shutdown`
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) {
+        let addr = format!("0.0.0.0:{}", self.port);
+        let listener = TcpListener::bind(addr).await.unwrap();
+
+        self.serve_until(listener, shutdown).await;
+    }
+
+    /// Запуск сервера на уже открытом сокете
+    pub async fn serve(self, listener: TcpListener) {
+        self.serve_until(listener, std::future::pending()).await;
+    }
+
+    async fn serve_until(self, listener: TcpListener, shutdown: impl Future<Output = ()>) {
+        let service = ShortenerService::new(self.container);
+
+        tonic::transport::Server::builder()
+            .add_service(ShortenerServer::new(service))
+            .serve_with_incoming_shutdown(TcpIncoming::from(listener), shutdown)
+            .await
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dashmap::DashMap;
+    use tonic::{Code, transport::Channel};
+
+    use crate::{
+        adapters::in_memory_repository::InMemoryRepository,
+        id_provider::NanoIdProvider,
+        ports::grpc::proto::{
+            GetStatsRequest, ResolveRequest, ShortenRequest, shortener_client::ShortenerClient,
+        },
+    };
+
+    use super::*;
+
+    async fn start_server() -> ShortenerClient<Channel> {
+        let store = Arc::new(DashMap::new());
+        let repo = InMemoryRepository::new(store);
+        let container = Arc::new(Container::new(NanoIdProvider, repo.clone(), repo));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(GrpcServer::new(addr.port(), container).serve(listener));
+
+        ShortenerClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn shorten_and_resolve() {
+        // given
+        let mut client = start_server().await;
+
+        // when
+        let id = client
+            .shorten(ShortenRequest {
+                url: "https://google.com".to_owned(),
+                password: None,
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+        let resolved = client
+            .resolve(ResolveRequest { id, password: None })
+            .await
+            .unwrap()
+            .into_inner();
+
+        // then
+        assert_eq!(resolved.url, "https://google.com");
+    }
+
+    #[tokio::test]
+    async fn resolve_unknown_id() {
+        // given
+        let mut client = start_server().await;
+
+        // when
+        let result = client
+            .resolve(ResolveRequest {
+                id: "unknown".to_owned(),
+                password: None,
+            })
+            .await;
+
+        // then
+        assert_eq!(result.unwrap_err().code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn stats_count_resolves() {
+        // given
+        let mut client = start_server().await;
+        let id = client
+            .shorten(ShortenRequest {
+                url: "https://google.com".to_owned(),
+                password: None,
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+
+        // when
+        for _ in 0..3 {
+            client
+                .resolve(ResolveRequest {
+                    id: id.clone(),
+                    password: None,
+                })
+                .await
+                .unwrap();
+        }
+        let stats = client
+            .get_stats(GetStatsRequest { id: id.clone() })
+            .await
+            .unwrap()
+            .into_inner();
+
+        // then
+        assert_eq!(stats.id, id);
+        assert_eq!(stats.clicks, 3);
+    }
+
+    #[tokio::test]
+    async fn protected_link_needs_password() {
+        // given
+        let mut client = start_server().await;
+        let id = client
+            .shorten(ShortenRequest {
+                url: "https://google.com".to_owned(),
+                password: Some("secret".to_owned()),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .id;
+
+        // when
+        let without_password = client
+            .resolve(ResolveRequest {
+                id: id.clone(),
+                password: None,
+            })
+            .await;
+        let with_password = client
+            .resolve(ResolveRequest {
+                id,
+                password: Some("secret".to_owned()),
+            })
+            .await;
+
+        // then
+        assert_eq!(without_password.unwrap_err().code(), Code::Unauthenticated);
+        assert_eq!(
+            with_password.unwrap().into_inner().url,
+            "https://google.com"
+        );
+    }
+}
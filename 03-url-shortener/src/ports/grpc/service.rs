@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use tonic::{Request, Response, Status};
+
+use crate::{
+    app::{audit::Actor, query::get_full_url::Visit},
+    di::{CommandRepository, Container, QueryRepository},
+    id_provider::IDProvider,
+    ports::grpc::proto::{
+        GetStatsRequest, GetStatsResponse, ResolveRequest, ResolveResponse, ShortenRequest,
+        ShortenResponse, shortener_server::Shortener,
+    },
+};
+
+/// реализация grpc сервиса поверх DI контейнера
+pub struct ShortenerService<I, R, Q>
+where
+    I: IDProvider + Send + Sync + 'static,
+    R: CommandRepository + Send + Sync + 'static,
+    Q: QueryRepository + Send + Sync + 'static,
+{
+    container: Arc<Container<I, R, Q>>,
+}
+
+impl<I, R, Q> ShortenerService<I, R, Q>
+where
+    I: IDProvider + Send + Sync + 'static,
+    R: CommandRepository + Send + Sync + 'static,
+    Q: QueryRepository + Send + Sync + 'static,
+{
+    pub fn new(container: Arc<Container<I, R, Q>>) -> Self {
+        Self { container }
+    }
+}
+
+#[tonic::async_trait]
+impl<I, R, Q> Shortener for ShortenerService<I, R, Q>
+where
+    I: IDProvider + Send + Sync + 'static,
+    R: CommandRepository + Send + Sync + 'static,
+    Q: QueryRepository + Send + Sync + 'static,
+{
+    async fn shorten(
+        &self,
+        request: Request<ShortenRequest>,
+    ) -> Result<Response<ShortenResponse>, Status> {
+        let ip = request
+            .remote_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_owned());
+        let actor = Actor::new("anonymous", None, ip);
+        let ShortenRequest { url, password } = request.into_inner();
+        let id = match password {
+            Some(password) => {
+                self.container
+                    .shorten_command
+                    .execute_protected(url, password, &actor)
+                    .await?
+            }
+            None => self.container.shorten_command.execute(url, &actor).await?,
+        };
+
+        Ok(Response::new(ShortenResponse { id }))
+    }
+
+    async fn resolve(
+        &self,
+        request: Request<ResolveRequest>,
+    ) -> Result<Response<ResolveResponse>, Status> {
+        let client = request
+            .remote_addr()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_owned());
+        let ResolveRequest { id, password } = request.into_inner();
+        let visit = Visit {
+            visitor: client.clone(),
+            ..Default::default()
+        };
+        let resolved = match password {
+            Some(password) => {
+                self.container
+                    .get_full_url_query
+                    .execute_with_password(&id, password, &client, &visit)
+                    .await?
+            }
+            None => {
+                self.container
+                    .get_full_url_query
+                    .resolve(&id, &visit)
+                    .await?
+            }
+        };
+        let target = resolved.variant.map(|v| v.url);
+        self.container
+            .record_click_command
+            .execute_for_target(&resolved.short_url, target.as_deref())
+            .await?;
+
+        Ok(Response::new(ResolveResponse { url: resolved.url }))
+    }
+
+    async fn get_stats(
+        &self,
+        request: Request<GetStatsRequest>,
+    ) -> Result<Response<GetStatsResponse>, Status> {
+        let id = request.into_inner().id;
+        let stats = self.container.get_stats_query.execute(&id).await?;
+
+        Ok(Response::new(GetStatsResponse {
+            id,
+            clicks: stats.clicks,
+            target_clicks: stats.targets.into_iter().collect(),
+        }))
+    }
+}
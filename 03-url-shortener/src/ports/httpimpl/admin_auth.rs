@@ -0,0 +1,34 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use subtle::ConstantTimeEq;
+
+use crate::app::error::AppError;
+
+/// пропускает только запросы с заголовком `Authorization: Bearer <токен>`
+pub async fn require_admin_token(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !has_token(request.headers(), &token) {
+        return AppError::Unauthorized.into_response();
+    }
+    next.run(request).await
+}
+
+/// в заголовке `Authorization` именно этот bearer токен
+pub fn has_token(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        // сравнение за постоянное время, чтобы токен нельзя было подобрать по задержке
+        .is_some_and(|given| given.as_bytes().ct_eq(token.as_bytes()).into())
+}
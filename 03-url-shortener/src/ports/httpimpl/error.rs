@@ -0,0 +1,27 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::app::error::AppError;
+
+/// отображение ошибок приложения в http ответы
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::PasswordRequired | AppError::InvalidPassword | AppError::Unauthorized => {
+                StatusCode::UNAUTHORIZED
+            }
+            AppError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::InvalidUrl(_) | AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            AppError::TargetRejected(_) | AppError::QuotaExceeded => StatusCode::FORBIDDEN,
+            AppError::IdempotencyKeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::RequestInProgress | AppError::Conflict => StatusCode::CONFLICT,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status, self.to_string()).into_response()
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app::{
+        audit::{Actor, AuditRecord, AuditTrail},
+        error::AppError,
+        query::get_full_url::Visit,
+    },
+    di::{
+        CommandRepository, QueryRepository,
+        tenants::{TenantContainer, Tenants},
+    },
+    ports::httpimpl::admin_auth::has_token,
+};
+
+/// Адрес клиента, если сервер запущен с `ConnectInfo`.
+///
+/// Без него (например, в тестах роутера) клиент считается `unknown`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientAddr(pub String);
+
+impl<S> FromRequestParts<S> for ClientAddr
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let addr = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_owned());
+
+        Ok(ClientAddr(addr))
+    }
+}
+
+/// заголовок с именем того, кто выполняет запрос, например от прокси с авторизацией
+pub const ACTOR_HEADER: &str = "x-actor";
+
+/// Кому верить, называя автора запроса: владельцу админского токена
+/// и прокси с этими адресами. Лежит в расширениях запроса.
+#[derive(Debug, Clone, Default)]
+pub struct ActorTrust {
+    pub admin_token: Option<Arc<str>>,
+    pub proxies: Vec<IpAddr>,
+}
+
+/// Автор запроса для записей журнала аудита.
+///
+/// Автор берётся из заголовка [`ACTOR_HEADER`], если запрос пришёл с
+/// админским токеном или от доверенного прокси, иначе это `admin` для
+/// запросов с админским токеном и `anonymous` для остальных.
+///
+/// Изменения ссылок несут запись в своём событии, а арендаторы и вебхуки
+/// пишутся в журнал через [`Auditor::log`] до изменения: у них нет очереди,
+/// которая сохранила бы запись вместе с изменением. Без журнала в
+/// расширениях запроса такие записи никуда не пишутся.
+pub struct Auditor {
+    trail: Option<Arc<dyn AuditTrail>>,
+    pub actor: Actor,
+}
+
+impl Auditor {
+    /// заготовка записи о действии `action` над объектом `id`
+    pub fn record(&self, action: &str, id: &str) -> AuditRecord {
+        self.actor.record(action, id)
+    }
+
+    /// Записать действие, которое сейчас будет выполнено. Изменение без записи
+    /// не пройдёт, а запись о попытке, которая не удалась, останется в журнале.
+    pub async fn log(&self, record: AuditRecord) -> Result<(), AppError> {
+        let Some(trail) = self.trail.clone() else {
+            return Ok(());
+        };
+        tokio::task::spawn_blocking(move || trail.append(record))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?
+            .map(|_| ())
+    }
+}
+
+impl<S> FromRequestParts<S> for Auditor
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ClientAddr(ip) = ClientAddr::from_request_parts(parts, state).await?;
+        let trust = parts
+            .extensions
+            .get::<ActorTrust>()
+            .cloned()
+            .unwrap_or_default();
+        let api_key = trust
+            .admin_token
+            .filter(|token| has_token(&parts.headers, token))
+            .map(|token| hex::encode(&Sha256::digest(token.as_bytes())[..8]));
+        let from_proxy = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .is_some_and(|ConnectInfo(addr)| trust.proxies.contains(&addr.ip()));
+        let named = parts
+            .headers
+            .get(ACTOR_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|actor| !actor.is_empty() && (api_key.is_some() || from_proxy));
+        let name = match (named, &api_key) {
+            (Some(actor), _) => actor.to_owned(),
+            (None, Some(_)) => "admin".to_owned(),
+            (None, None) => "anonymous".to_owned(),
+        };
+
+        Ok(Auditor {
+            trail: parts.extensions.get::<Arc<dyn AuditTrail>>().cloned(),
+            actor: Actor::new(name, api_key, ip),
+        })
+    }
+}
+
+/// cookie с ключом цели сплит-ссылки, выданной посетителю
+pub const SPLIT_COOKIE: &str = "split";
+
+/// Сведения о посетителе для выбора адреса перехода: query string,
+/// ip, user agent, `Accept-Language` и цель из cookie [`SPLIT_COOKIE`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Visitor(pub Visit);
+
+impl<S> FromRequestParts<S> for Visitor
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ClientAddr(client) = ClientAddr::from_request_parts(parts, state).await?;
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_owned()
+        };
+        let user_agent = header(header::USER_AGENT);
+        let variant = parts
+            .headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == SPLIT_COOKIE)
+            .map(|(_, value)| value.to_owned());
+
+        Ok(Visitor(Visit {
+            query: parts.uri.query().unwrap_or_default().to_owned(),
+            visitor: format!("{client} {user_agent}"),
+            variant,
+            accept_language: header(header::ACCEPT_LANGUAGE),
+            user_agent,
+            at: None,
+        }))
+    }
+}
+
+/// Контейнер арендатора, выбранного по заголовку Host.
+///
+/// Без заголовка используется контейнер по умолчанию.
+pub struct TenantScope<R>(pub Arc<TenantContainer<R>>)
+where
+    R: CommandRepository + QueryRepository;
+
+impl<R> FromRequestParts<Arc<Tenants<R>>> for TenantScope<R>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        tenants: &Arc<Tenants<R>>,
+    ) -> Result<Self, Self::Rejection> {
+        let host = parts
+            .headers
+            .get(header::HOST)
+            .and_then(|value| value.to_str().ok());
+        let container = match host {
+            Some(host) => tenants.for_host(host)?,
+            None => tenants.default_container(),
+        };
+
+        Ok(TenantScope(container))
+    }
+}
@@ -1,31 +1,162 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 use axum::{
-    Router,
-    routing::{get, post},
+    Extension, Router,
+    http::{HeaderName, HeaderValue, Method, header},
+    middleware,
+    routing::{delete, get, post},
 };
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
 use crate::{
-    app::{
-        command::create_short_url::CreateShortUrlRepository,
-        query::get_full_url::GetFullUrlRepository,
+    app::{audit::AuditTrail, health::Readiness, idempotency::Idempotency, webhook::Webhooks},
+    di::{CommandRepository, QueryRepository, tenants::Tenants},
+    ports::httpimpl::{
+        extractors::{ACTOR_HEADER, ActorTrust},
+        idempotency::IDEMPOTENCY_KEY_HEADER,
     },
-    di::Container,
-    id_provider::IDProvider,
 };
 
+/// необязательные части api
+#[derive(Clone, Default)]
+pub struct RouterOptions {
+    /// токен админского api, без него админские ручки не подключаются
+    pub admin_token: Option<String>,
+    /// управление подписками на вебхуки в админском api
+    pub webhooks: Option<Webhooks>,
+    /// компоненты, проверяемые ручкой готовности
+    pub readiness: Arc<Readiness>,
+    /// источники, которым разрешены запросы из браузера, `*` разрешает все
+    pub cors_origins: Vec<String>,
+    /// журнал изменений, без него изменения не записываются
+    pub audit: Option<Arc<dyn AuditTrail>>,
+    /// прокси, которым верится заголовок с автором запроса
+    pub trusted_proxies: Vec<IpAddr>,
+    /// повторы создания с заголовком `Idempotency-Key`, без него заголовок игнорируется
+    pub idempotency: Option<Arc<Idempotency>>,
+}
+
 /// маппинг урлов
-pub fn get_router<I, R, Q>(contaiter: Arc<Container<I, R, Q>>) -> Router
+pub fn get_router<R>(tenants: Arc<Tenants<R>>, options: RouterOptions) -> Router
 where
-    I: IDProvider + Send + Sync + 'static,
-    R: CreateShortUrlRepository + Send + Sync + 'static,
-    Q: GetFullUrlRepository + Send + Sync + 'static,
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
 {
+    use crate::ports::httpimpl::admin_auth::require_admin_token;
+    use crate::ports::httpimpl::handlers::audit::list_audit;
     use crate::ports::httpimpl::handlers::get_full_url::get_full_url;
+    use crate::ports::httpimpl::handlers::health::{healthz, readyz};
+    use crate::ports::httpimpl::handlers::links::{
+        delete_link, dry_run, get_link, list_tags, purge_link, restore_link, search_links,
+        update_link,
+    };
+    use crate::ports::httpimpl::handlers::redirect::{redirect, unlock};
     use crate::ports::httpimpl::handlers::shorten_url::shorten_url;
+    use crate::ports::httpimpl::handlers::tenants::{
+        delete_tenant, get_tenant, list_tenants, put_tenant,
+    };
+    use crate::ports::httpimpl::handlers::ui::{index, root, ui_asset};
+    use crate::ports::httpimpl::handlers::webhooks::{
+        create_webhook, delete_webhook, list_dead_letters, list_webhooks, retry_dead_letter,
+    };
+    use crate::ports::httpimpl::idempotency::idempotent;
 
-    Router::new()
+    let mut create = post(shorten_url);
+    if let Some(idempotency) = options.idempotency {
+        create = create.layer(middleware::from_fn_with_state(idempotency, idempotent));
+    }
+    let mut router = Router::new()
         .route("/{id}", get(get_full_url))
-        .route("/", post(shorten_url))
-        .with_state(contaiter)
+        .route("/r/{id}", get(redirect).post(unlock))
+        .route("/", create.get(root))
+        .route("/ui", get(root))
+        .route("/ui/", get(index))
+        .route("/ui/{file}", get(ui_asset));
+
+    let health = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(options.readiness);
+    router = router.merge(health);
+
+    let trust = ActorTrust {
+        admin_token: options.admin_token.as_deref().map(Arc::from),
+        proxies: options.trusted_proxies,
+    };
+    if let Some(token) = options.admin_token {
+        let mut admin = Router::new()
+            .route("/admin/tenants", get(list_tenants))
+            .route(
+                "/admin/tenants/{id}",
+                get(get_tenant).put(put_tenant).delete(delete_tenant),
+            )
+            .route(
+                "/admin/links/{id}",
+                get(get_link).put(update_link).delete(delete_link),
+            )
+            .route("/admin/links/{id}/dry-run", post(dry_run))
+            .route("/admin/links/{id}/restore", post(restore_link))
+            .route("/admin/links/{id}/purge", post(purge_link))
+            .route("/api/links", get(search_links))
+            .route("/api/tags", get(list_tags));
+        if let Some(webhooks) = options.webhooks {
+            let hooks = Router::new()
+                .route("/admin/webhooks", get(list_webhooks).post(create_webhook))
+                .route("/admin/webhooks/{id}", delete(delete_webhook))
+                .route("/admin/webhooks/dead-letters", get(list_dead_letters))
+                .route(
+                    "/admin/webhooks/dead-letters/{id}/retry",
+                    post(retry_dead_letter),
+                )
+                .with_state(webhooks);
+            admin = admin.merge(hooks);
+        }
+        if let Some(trail) = options.audit.clone() {
+            let audit = Router::new()
+                .route("/admin/audit", get(list_audit))
+                .with_state(trail);
+            admin = admin.merge(audit);
+        }
+        let admin = admin.route_layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            require_admin_token,
+        ));
+        router = router.merge(admin);
+    }
+
+    let mut router = router.with_state(tenants).layer(Extension(trust));
+    if let Some(trail) = options.audit {
+        router = router.layer(Extension(trail));
+    }
+    match cors(&options.cors_origins) {
+        Some(layer) => router.layer(layer),
+        None => router,
+    }
+}
+
+/// cors-слой для веб-интерфейса, размещённого на другом домене
+fn cors(origins: &[String]) -> Option<CorsLayer> {
+    if origins.is_empty() {
+        return None;
+    }
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+            .allow_headers([
+                header::CONTENT_TYPE,
+                header::AUTHORIZATION,
+                HeaderName::from_static("x-link-password"),
+                HeaderName::from_static(ACTOR_HEADER),
+                HeaderName::from_static(IDEMPOTENCY_KEY_HEADER),
+            ]),
+    )
 }
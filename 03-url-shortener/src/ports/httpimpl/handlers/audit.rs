@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+
+use crate::app::{
+    audit::{AuditEntry, AuditFilter, AuditTrail},
+    error::AppError,
+};
+
+/// ручка чтения журнала аудита по ссылке или автору
+pub async fn list_audit(
+    Query(filter): Query<AuditFilter>,
+    State(trail): State<Arc<dyn AuditTrail>>,
+) -> Result<Json<Vec<AuditEntry>>, AppError> {
+    let entries = tokio::task::spawn_blocking(move || trail.query(&filter))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))??;
+    Ok(Json(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, SocketAddr};
+
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        extract::ConnectInfo,
+        http::{Request, header},
+    };
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            in_memory_audit_trail::InMemoryAuditTrail, in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+        },
+        app::{
+            audit::AuditRecorder,
+            event::{EventBus, OutboxRelay},
+            policy::AllowAll,
+        },
+        di::tenants::Tenants,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    use super::*;
+
+    fn request(method: &str, uri: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, "Bearer token")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-actor", "alice")
+            .body(Body::from(body.to_owned()))
+            .unwrap()
+    }
+
+    async fn send(router: &Router, request: Request<Body>) -> serde_json::Value {
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert!(response.status().is_success(), "{}", response.status());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap_or_default()
+    }
+
+    /// роутер и реле, переносящее записи аудита ссылок из outbox в журнал
+    fn router(
+        trail: &Arc<InMemoryAuditTrail>,
+        trusted_proxies: Vec<IpAddr>,
+    ) -> (Router, OutboxRelay) {
+        let repo = InMemoryRepository::new(Arc::new(DashMap::new()));
+        let bus = EventBus::default();
+        bus.subscribe(Arc::new(AuditRecorder::new(trail.clone())));
+        let relay = OutboxRelay::new(Arc::new(repo.clone()), Arc::new(bus));
+        let tenants = Tenants::new(
+            repo,
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        let router = get_router(
+            Arc::new(tenants),
+            RouterOptions {
+                admin_token: Some("token".to_owned()),
+                audit: Some(trail.clone()),
+                trusted_proxies,
+                ..Default::default()
+            },
+        );
+        (router, relay)
+    }
+
+    #[tokio::test]
+    async fn link_changes_are_audited() {
+        // given
+        let trail = Arc::new(InMemoryAuditTrail::default());
+        let (router, relay) = router(&trail, vec![]);
+        let created = send(
+            &router,
+            request("POST", "/", r#"{"url": "https://a.example"}"#),
+        )
+        .await;
+        let id = created["url"].as_str().unwrap().to_owned();
+        let anonymous = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, "Bearer guess")
+            .header("x-actor", "mallory")
+            .body(Body::from(r#"{"url": "https://b.example"}"#))
+            .unwrap();
+        send(&router, anonymous).await;
+
+        // when
+        send(
+            &router,
+            request(
+                "PUT",
+                &format!("/admin/links/{id}"),
+                r#"{"url": "https://a.example/v2"}"#,
+            ),
+        )
+        .await;
+        send(
+            &router,
+            request("DELETE", &format!("/admin/links/{id}"), ""),
+        )
+        .await;
+        relay.relay_pending().unwrap();
+        let by_link = send(
+            &router,
+            request("GET", &format!("/admin/audit?link={id}"), ""),
+        )
+        .await;
+        let by_anonymous = send(&router, request("GET", "/admin/audit?actor=anonymous", "")).await;
+
+        // then
+        let actions: Vec<&str> = by_link
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, ["link.create", "link.update", "link.delete"]);
+        assert_eq!(by_link[1]["actor"], "alice");
+        assert_eq!(by_link[1]["ip"], "unknown");
+        assert_eq!(by_link[1]["api_key"].as_str().unwrap().len(), 16);
+        assert_eq!(by_link[1]["before"]["url"], "https://a.example");
+        assert_eq!(by_link[1]["after"]["url"], "https://a.example/v2");
+        assert!(by_link[2]["after"]["trashed_at"].is_u64());
+        assert_eq!(by_anonymous.as_array().unwrap().len(), 1);
+        assert_eq!(by_anonymous[0]["api_key"], serde_json::Value::Null);
+        assert_eq!(trail.verify().unwrap().entries, 4);
+    }
+
+    #[tokio::test]
+    async fn actor_header_is_trusted_only_from_proxy() {
+        // given
+        let trail = Arc::new(InMemoryAuditTrail::default());
+        let proxy: SocketAddr = "10.0.0.5:40000".parse().unwrap();
+        let (router, relay) = router(&trail, vec![proxy.ip()]);
+        let from = |addr: &str| {
+            let mut request = Request::post("/")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-actor", "bob")
+                .body(Body::from(r#"{"url": "https://a.example"}"#))
+                .unwrap();
+            request
+                .extensions_mut()
+                .insert(ConnectInfo(addr.parse::<SocketAddr>().unwrap()));
+            request
+        };
+
+        // when
+        send(&router, from("10.0.0.5:40000")).await;
+        send(&router, from("10.0.0.9:40000")).await;
+        relay.relay_pending().unwrap();
+
+        // then
+        let actors: Vec<String> = trail
+            .entries()
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.record.actor)
+            .collect();
+        assert_eq!(actors, ["bob", "anonymous"]);
+    }
+}
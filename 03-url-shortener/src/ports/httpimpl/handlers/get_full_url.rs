@@ -1,43 +1,69 @@
-use std::sync::Arc;
-
-use axum::{
-    Json,
-    extract::{Path, State},
-};
+use axum::{extract::Path, http::HeaderMap};
 
 use crate::{
-    app::{
-        command::create_short_url::CreateShortUrlRepository,
-        query::get_full_url::GetFullUrlRepository,
+    app::error::AppError,
+    di::{CommandRepository, QueryRepository},
+    ports::httpimpl::{
+        extractors::{ClientAddr, TenantScope, Visitor},
+        negotiation::{Negotiated, Represent, ResponseFormat, escape_html},
     },
-    di::Container,
-    id_provider::IDProvider,
 };
 
+/// заголовок с паролем для защищённых ссылок
+pub const PASSWORD_HEADER: &str = "x-link-password";
+
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct FullUrlResponse {
     url: String,
 }
 
+impl Represent for FullUrlResponse {
+    fn text(&self) -> String {
+        self.url.clone()
+    }
+
+    fn html(&self) -> String {
+        let url = escape_html(&self.url);
+        format!("<p>Full URL: <a href=\"{url}\">{url}</a></p>")
+    }
+}
+
 impl From<String> for FullUrlResponse {
     fn from(url: String) -> Self {
         FullUrlResponse { url }
     }
 }
 
-/// ручка для получения полного url
-pub async fn get_full_url<I, R, Q>(
+/// Ручка для получения полного url, пароль передаётся в заголовке `X-Link-Password`.
+/// Ответ в формате из `Accept`.
+pub async fn get_full_url<R>(
     Path(id): Path<String>,
-    State(container): State<Arc<Container<I, R, Q>>>,
-) -> Result<Json<FullUrlResponse>, String>
+    TenantScope(container): TenantScope<R>,
+    ClientAddr(client): ClientAddr,
+    Visitor(visit): Visitor,
+    format: ResponseFormat,
+    headers: HeaderMap,
+) -> Result<Negotiated<FullUrlResponse>, AppError>
 where
-    I: IDProvider + Send + Sync + 'static,
-    R: CreateShortUrlRepository + Send + Sync + 'static,
-    Q: GetFullUrlRepository + Send + Sync + 'static,
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
 {
+    let password = headers
+        .get(PASSWORD_HEADER)
+        .and_then(|value| value.to_str().ok());
+    let resolved = match password {
+        Some(password) => {
+            container
+                .get_full_url_query
+                .execute_with_password(&id, password.to_owned(), &client, &visit)
+                .await?
+        }
+        None => container.get_full_url_query.resolve(&id, &visit).await?,
+    };
+    let target = resolved.variant.map(|v| v.url);
     container
-        .get_full_url_query
-        .execute(&id)
-        .await
-        .map(|url| Json(FullUrlResponse::from(url)))
+        .record_click_command
+        .execute_for_target(&resolved.short_url, target.as_deref())
+        .await?;
+
+    Ok(Negotiated(format, FullUrlResponse::from(resolved.url)))
 }
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, http::StatusCode};
+
+use crate::app::{
+    error::AppError,
+    health::{HealthReport, HealthStatus, Readiness},
+};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct LivenessResponse {
+    status: String,
+}
+
+/// ручка живости: процесс отвечает на запросы
+pub async fn healthz() -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: "up".to_owned(),
+    })
+}
+
+/// ручка готовности: 503, если какой-то компонент недоступен или идёт завершение работы
+pub async fn readyz(
+    State(readiness): State<Arc<Readiness>>,
+) -> Result<(StatusCode, Json<HealthReport>), AppError> {
+    let report = tokio::task::spawn_blocking(move || readiness.report())
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let status = match report.status {
+        HealthStatus::Up => StatusCode::OK,
+        HealthStatus::Down => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    Ok((status, Json(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::Request,
+    };
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            file_repository::FileRepository, in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+        },
+        app::policy::AllowAll,
+        di::tenants::Tenants,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    use super::*;
+
+    fn router(readiness: Arc<Readiness>) -> Router {
+        let tenants = Tenants::new(
+            InMemoryRepository::new(Arc::new(DashMap::new())),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        let options = RouterOptions {
+            readiness,
+            ..Default::default()
+        };
+        get_router(Arc::new(tenants), options)
+    }
+
+    async fn get(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let response = router
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn ready_lists_components() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let readiness = Readiness::default()
+            .with_component(
+                "memory",
+                Arc::new(InMemoryRepository::new(Arc::new(DashMap::new()))),
+            )
+            .with_component(
+                "file",
+                Arc::new(FileRepository::new(dir.path().join("missing/links.json"))),
+            );
+
+        // when
+        let (status, report) = get(router(Arc::new(readiness)), "/readyz").await;
+
+        // then
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["status"], "down");
+        assert_eq!(report["components"][0]["name"], "memory");
+        assert_eq!(report["components"][0]["status"], "up");
+        assert!(report["components"][0]["latency_ms"].is_number());
+        assert_eq!(report["components"][1]["status"], "down");
+    }
+
+    #[tokio::test]
+    async fn liveness_survives_shutdown() {
+        // given
+        let readiness = Arc::new(Readiness::default());
+        let router = router(readiness.clone());
+
+        // when
+        readiness.begin_shutdown();
+        let (ready, report) = get(router.clone(), "/readyz").await;
+        let (live, _) = get(router, "/healthz").await;
+
+        // then
+        assert_eq!(ready, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["reason"], "shutting down");
+        assert_eq!(live, StatusCode::OK);
+    }
+}
@@ -0,0 +1,730 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    Json,
+    extract::{Path, Query},
+    http::StatusCode,
+};
+
+use crate::{
+    app::{
+        error::AppError,
+        link::{ClickLimit, Link, LinkMeta, LinkOptions, MetaChange},
+        link_health::LinkHealth,
+        query::{
+            get_full_url::Visit,
+            list_short_urls::{LinkFilter, ShortUrlEntry, TagCount},
+        },
+        redirect::{Passthrough, RedirectRules, UtmTemplate},
+        routing::RouteRule,
+        split::WeightedTarget,
+    },
+    di::{CommandRepository, QueryRepository},
+    ports::httpimpl::extractors::{Auditor, TenantScope},
+};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct UpdateShortUrlRequest {
+    /// новая цель, без неё цель остаётся прежней
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(flatten)]
+    options: LinkOptionsRequest,
+}
+
+/// необязательные настройки ссылки в запросах создания и изменения
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+pub struct LinkOptionsRequest {
+    /// При изменении ссылки отсутствующее поле оставляет настройку как есть,
+    /// а `null` стирает её. Так же ведут себя все поля ниже.
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    title: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    note: Option<Option<String>>,
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    tags: Option<Option<Vec<String>>>,
+    /// слияние query string перехода с параметрами цели
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    passthrough: Option<Option<Passthrough>>,
+    /// utm метки, добавляемые при переходе
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    utm: Option<Option<UtmTemplate>>,
+    /// цели с весами, между которыми делится трафик
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    targets: Option<Option<Vec<WeightedTarget>>>,
+    /// правила выбора цели по устройству, языку, времени и параметрам
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    rules: Option<Option<Vec<RouteRule>>>,
+    /// после стольких переходов ссылка отвечает 410
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    max_clicks: Option<Option<u64>>,
+    /// текст ответа 410
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    exhausted_message: Option<Option<String>>,
+}
+
+impl LinkOptionsRequest {
+    /// настройки из html формы, где есть только описание ссылки
+    pub fn described(title: Option<String>, note: Option<String>, tags: Vec<String>) -> Self {
+        Self {
+            title: Some(title),
+            note: Some(note),
+            tags: Some(Some(tags)),
+            ..Default::default()
+        }
+    }
+
+    pub fn into_options(self) -> Result<LinkOptions, AppError> {
+        let utm = match self.utm {
+            Some(Some(utm)) => Some(Some(UtmTemplate::new(
+                utm.source,
+                utm.medium,
+                utm.campaign,
+            )?)),
+            utm => utm,
+        };
+        Ok(LinkOptions {
+            meta: MetaChange {
+                title: self.title,
+                note: self.note,
+                tags: self.tags.map(Option::unwrap_or_default),
+            },
+            passthrough: self.passthrough.map(Option::unwrap_or_default),
+            utm,
+            targets: self.targets.map(Option::unwrap_or_default),
+            rules: self.rules.map(Option::unwrap_or_default),
+            max_clicks: match self.max_clicks {
+                None => ClickLimit::Keep,
+                Some(None) => ClickLimit::Remove,
+                Some(Some(max)) => ClickLimit::Set(max),
+            },
+            exhausted_message: self.exhausted_message,
+        })
+    }
+}
+
+/// `Some(None)` для поля со значением `null`, отсутствующее поле даёт `None` через `default`
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    serde::Deserialize::deserialize(deserializer).map(Some)
+}
+
+/// Ссылка со всеми настройками в том же виде, что и тело запроса изменения,
+/// чтобы клиент мог поправить часть полей и отправить остальные как есть.
+#[derive(serde::Serialize)]
+pub struct LinkResponse {
+    id: String,
+    url: String,
+    /// защищена ли ссылка паролем, сам хэш не отдаётся
+    protected: bool,
+    #[serde(flatten)]
+    meta: LinkMeta,
+    #[serde(flatten)]
+    redirect: RedirectRules,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    targets: Vec<WeightedTarget>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rules: Vec<RouteRule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_clicks: Option<u64>,
+    /// сколько переходов уже списано в счёт `max_clicks`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clicks_used: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exhausted_message: Option<String>,
+    /// когда ссылку удалили в корзину, мс unix
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trashed_at: Option<u64>,
+    /// с какого момента не отвечает хотя бы одна цель, при изменении ссылки сбрасывается
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failing_since: Option<u64>,
+    /// итоги последних проверок по каждой цели
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    health: BTreeMap<String, LinkHealth>,
+    /// когда ссылку отключили из-за неотвечающей цели, изменение ссылки включает её
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disabled_at: Option<u64>,
+}
+
+impl LinkResponse {
+    pub(crate) fn new(id: String, link: Link) -> Self {
+        Self {
+            id,
+            protected: link.is_protected(),
+            url: link.url,
+            meta: link.meta,
+            redirect: link.redirect,
+            targets: link.targets,
+            rules: link.rules,
+            max_clicks: link.max_clicks,
+            clicks_used: link.max_clicks.map(|_| link.clicks_used),
+            exhausted_message: link.exhausted_message,
+            trashed_at: link.trashed_at,
+            failing_since: link.failing_since,
+            health: BTreeMap::new(),
+            disabled_at: link.disabled_at,
+        }
+    }
+
+    /// ответ с итогами проверок целей
+    pub(crate) fn with_health(self, health: BTreeMap<String, LinkHealth>) -> Self {
+        Self { health, ..self }
+    }
+}
+
+/// переход с придуманными заголовками для проверки правил ссылки
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+pub struct DryRunRequest {
+    /// заголовки запроса, учитываются `User-Agent` и `Accept-Language`
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    query: String,
+    /// момент перехода в секундах unix, по умолчанию текущий
+    #[serde(default)]
+    at: Option<u64>,
+    /// признак посетителя для выбора цели сплит-ссылки
+    #[serde(default)]
+    visitor: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct DryRunResponse {
+    url: String,
+    /// номер сработавшего правила
+    rule: Option<usize>,
+    /// номер выбранной цели сплит-ссылки
+    variant: Option<usize>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TagsParams {
+    #[serde(default)]
+    prefix: String,
+}
+
+/// ручка получения ссылки со всеми настройками
+pub async fn get_link<R>(
+    Path(id): Path<String>,
+    TenantScope(container): TenantScope<R>,
+) -> Result<Json<LinkResponse>, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    let link = container.get_full_url_query.details(&id).await?;
+    let health = container.link_health_query.execute(&id).await?;
+    Ok(Json(LinkResponse::new(id, link).with_health(health)))
+}
+
+/// ручка смены цели и настроек короткой ссылки, меняются только заданные поля
+pub async fn update_link<R>(
+    Path(id): Path<String>,
+    TenantScope(container): TenantScope<R>,
+    auditor: Auditor,
+    Json(input): Json<UpdateShortUrlRequest>,
+) -> Result<StatusCode, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    let options = input.options.into_options()?;
+    container
+        .update_command
+        .execute_with_options(&id, input.url, options, &auditor.actor)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// ручка удаления короткой ссылки в корзину
+pub async fn delete_link<R>(
+    Path(id): Path<String>,
+    TenantScope(container): TenantScope<R>,
+    auditor: Auditor,
+) -> Result<StatusCode, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    container
+        .delete_command
+        .execute(&id, &auditor.actor)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// ручка возврата ссылки из корзины
+pub async fn restore_link<R>(
+    Path(id): Path<String>,
+    TenantScope(container): TenantScope<R>,
+    auditor: Auditor,
+) -> Result<StatusCode, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    container
+        .delete_command
+        .restore(&id, &auditor.actor)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// ручка окончательного удаления ссылки из корзины, не дожидаясь срока хранения
+pub async fn purge_link<R>(
+    Path(id): Path<String>,
+    TenantScope(container): TenantScope<R>,
+    auditor: Auditor,
+) -> Result<StatusCode, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    container.delete_command.purge(&id, &auditor.actor).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// ручка пробного перехода: адрес, куда ушёл бы посетитель, без учёта перехода
+pub async fn dry_run<R>(
+    Path(id): Path<String>,
+    TenantScope(container): TenantScope<R>,
+    Json(input): Json<DryRunRequest>,
+) -> Result<Json<DryRunResponse>, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    let header = |name: &str| {
+        input
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default()
+    };
+    let visit = Visit {
+        query: input.query.trim_start_matches('?').to_owned(),
+        visitor: input.visitor.clone(),
+        variant: None,
+        user_agent: header("user-agent"),
+        accept_language: header("accept-language"),
+        at: input.at,
+    };
+    let resolved = container.get_full_url_query.preview(&id, &visit).await?;
+
+    Ok(Json(DryRunResponse {
+        url: resolved.url,
+        rule: resolved.rule,
+        variant: resolved.variant.map(|v| v.index),
+    }))
+}
+
+/// ручка поиска ссылок по тегу и тексту в заголовке или заметке
+pub async fn search_links<R>(
+    Query(filter): Query<LinkFilter>,
+    TenantScope(container): TenantScope<R>,
+) -> Result<Json<Vec<ShortUrlEntry>>, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    Ok(Json(container.list_query.search(&filter).await?))
+}
+
+/// ручка числа ссылок по тегам для подсказок
+pub async fn list_tags<R>(
+    Query(params): Query<TagsParams>,
+    TenantScope(container): TenantScope<R>,
+) -> Result<Json<Vec<TagCount>>, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    Ok(Json(container.list_query.tags(&params.prefix).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Request, StatusCode, header},
+    };
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+        },
+        app::policy::AllowAll,
+        di::tenants::Tenants,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    fn router() -> Router {
+        let tenants = Tenants::new(
+            InMemoryRepository::new(Arc::new(DashMap::new())),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        get_router(
+            Arc::new(tenants),
+            RouterOptions {
+                admin_token: Some("token".to_owned()),
+                ..Default::default()
+            },
+        )
+    }
+
+    async fn send(router: &Router, request: Request<Body>) -> serde_json::Value {
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert!(response.status().is_success(), "{}", response.status());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap_or_default()
+    }
+
+    fn json(method: &str, uri: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, "Bearer token")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_owned()))
+            .unwrap()
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri)
+            .header(header::AUTHORIZATION, "Bearer token")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn links_are_found_by_tag_and_text() {
+        // given
+        let router = router();
+        let created = send(
+            &router,
+            json(
+                "POST",
+                "/",
+                r#"{"url": "https://a.example", "title": "Quarterly report", "tags": ["Finance"]}"#,
+            ),
+        )
+        .await;
+        let report = created["url"].as_str().unwrap().to_owned();
+        send(
+            &router,
+            json(
+                "POST",
+                "/",
+                r#"{"url": "https://b.example", "tags": ["hr"]}"#,
+            ),
+        )
+        .await;
+
+        // when
+        send(
+            &router,
+            json(
+                "PUT",
+                &format!("/admin/links/{report}"),
+                r#"{"url": "https://a.example/v2", "note": "Board copy", "tags": ["finance", "board"]}"#,
+            ),
+        )
+        .await;
+        let anonymous = router
+            .clone()
+            .oneshot(
+                Request::get("/api/links?tag=finance")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let by_tag = send(&router, get("/api/links?tag=finance&q=board")).await;
+        let tags = send(&router, get("/api/tags?prefix=b")).await;
+
+        // then
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(by_tag.as_array().unwrap().len(), 1);
+        assert_eq!(by_tag[0]["id"], report.as_str());
+        assert_eq!(by_tag[0]["url"], "https://a.example/v2");
+        assert_eq!(by_tag[0]["title"], "Quarterly report");
+        assert_eq!(by_tag[0]["tags"], serde_json::json!(["board", "finance"]));
+        assert_eq!(tags, serde_json::json!([{"tag": "board", "count": 1}]));
+    }
+
+    #[tokio::test]
+    async fn update_keeps_description_fields_it_does_not_mention() {
+        // given
+        let router = router();
+        let created = send(
+            &router,
+            json(
+                "POST",
+                "/",
+                r#"{"url": "https://a.example", "title": "Report", "note": "Draft", "tags": ["finance"]}"#,
+            ),
+        )
+        .await;
+        let id = created["url"].as_str().unwrap().to_owned();
+        let uri = format!("/admin/links/{id}");
+
+        // when
+        send(
+            &router,
+            json("PUT", &uri, r#"{"url": "https://a.example/v2"}"#),
+        )
+        .await;
+        let kept = send(&router, get(&uri)).await;
+        send(
+            &router,
+            json(
+                "PUT",
+                &uri,
+                r#"{"url": "https://a.example/v2", "note": null, "tags": []}"#,
+            ),
+        )
+        .await;
+        let cleared = send(&router, get(&uri)).await;
+
+        // then
+        assert_eq!(kept["title"], "Report");
+        assert_eq!(kept["note"], "Draft");
+        assert_eq!(kept["tags"], serde_json::json!(["finance"]));
+        assert_eq!(cleared["title"], "Report");
+        assert_eq!(cleared["note"], serde_json::Value::Null);
+        assert_eq!(cleared["tags"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn update_of_title_keeps_targets_and_rules() {
+        // given
+        let router = router();
+        let created = send(
+            &router,
+            json(
+                "POST",
+                "/",
+                r#"{
+                    "url": "https://a.example",
+                    "passthrough": "merge",
+                    "utm": {"source": "news"},
+                    "targets": [
+                        {"url": "https://a.example", "weight": 1},
+                        {"url": "https://b.example", "weight": 1}
+                    ],
+                    "rules": [{"when": {"device": ["ios"]}, "target": "https://ios.example"}],
+                    "max_clicks": 10,
+                    "exhausted_message": "Over"
+                }"#,
+            ),
+        )
+        .await;
+        let id = created["url"].as_str().unwrap().to_owned();
+        let uri = format!("/admin/links/{id}");
+        let before = send(&router, get(&uri)).await;
+
+        // when
+        send(&router, json("PUT", &uri, r#"{"title": "Launch"}"#)).await;
+        let after = send(&router, get(&uri)).await;
+
+        // then
+        assert_eq!(after["title"], "Launch");
+        for field in [
+            "url",
+            "passthrough",
+            "utm",
+            "targets",
+            "rules",
+            "max_clicks",
+            "exhausted_message",
+        ] {
+            assert_eq!(after[field], before[field], "{field}");
+            assert_ne!(after[field], serde_json::Value::Null, "{field}");
+        }
+    }
+
+    #[tokio::test]
+    async fn rules_are_validated_and_dry_run_shows_target() {
+        // given
+        let router = router();
+        let rules = r#"[
+            {"when": {"device": ["ios"]}, "target": "https://apps.apple.com/app"},
+            {"when": {"device": ["android"]}, "target": "https://play.google.com/app"}
+        ]"#;
+        let created = send(
+            &router,
+            json(
+                "POST",
+                "/",
+                &format!(r#"{{"url": "https://app.example", "rules": {rules}}}"#),
+            ),
+        )
+        .await;
+        let id = created["url"].as_str().unwrap().to_owned();
+
+        // when
+        let invalid = router
+            .clone()
+            .oneshot(json(
+                "POST",
+                "/",
+                r#"{"url": "https://app.example", "rules": [{"when": {}, "target": "https://a.example"}]}"#,
+            ))
+            .await
+            .unwrap();
+        let iphone = send(
+            &router,
+            json(
+                "POST",
+                &format!("/admin/links/{id}/dry-run"),
+                r#"{"headers": {"User-Agent": "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0)"}}"#,
+            ),
+        )
+        .await;
+        let desktop = send(
+            &router,
+            json("POST", &format!("/admin/links/{id}/dry-run"), "{}"),
+        )
+        .await;
+
+        // then
+        assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            iphone,
+            serde_json::json!({"url": "https://apps.apple.com/app", "rule": 0, "variant": null})
+        );
+        assert_eq!(desktop["url"], "https://app.example");
+        assert_eq!(desktop["rule"], serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn deleted_link_stays_in_trash_until_restored_or_purged() {
+        // given
+        let router = router();
+        let created = send(
+            &router,
+            json("POST", "/", r#"{"url": "https://a.example"}"#),
+        )
+        .await;
+        let id = created["url"].as_str().unwrap().to_owned();
+        let status = |request: Request<Body>| {
+            let router = router.clone();
+            async move { router.oneshot(request).await.unwrap().status() }
+        };
+
+        // when
+        let deleted = status(json("DELETE", &format!("/admin/links/{id}"), "")).await;
+        let resolved_in_trash = status(get(&format!("/{id}"))).await;
+        let listed = send(&router, get("/api/links")).await;
+        let trash = send(&router, get("/api/links?trash=only")).await;
+        let restored = status(json("POST", &format!("/admin/links/{id}/restore"), "")).await;
+        let resolved_after_restore = status(get(&format!("/{id}"))).await;
+        let purged_active = status(json("POST", &format!("/admin/links/{id}/purge"), "")).await;
+        status(json("DELETE", &format!("/admin/links/{id}"), "")).await;
+        let purged = status(json("POST", &format!("/admin/links/{id}/purge"), "")).await;
+        let after_purge = status(get(&format!("/admin/links/{id}"))).await;
+
+        // then
+        assert_eq!(deleted, StatusCode::NO_CONTENT);
+        assert_eq!(resolved_in_trash, StatusCode::GONE);
+        assert_eq!(listed, serde_json::json!([]));
+        assert_eq!(trash[0]["id"], id.as_str());
+        assert!(trash[0]["trashed_at"].is_u64());
+        assert_eq!(restored, StatusCode::NO_CONTENT);
+        assert_eq!(resolved_after_restore, StatusCode::OK);
+        assert_eq!(purged_active, StatusCode::BAD_REQUEST);
+        assert_eq!(purged, StatusCode::NO_CONTENT);
+        assert_eq!(after_purge, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn edit_keeps_used_clicks_of_limited_link() {
+        // given
+        let router = router();
+        let created = send(
+            &router,
+            json(
+                "POST",
+                "/",
+                r#"{"url": "https://a.example", "max_clicks": 1}"#,
+            ),
+        )
+        .await;
+        let id = created["url"].as_str().unwrap().to_owned();
+        let visit = || async {
+            let request = Request::get(format!("/r/{id}"))
+                .body(Body::empty())
+                .unwrap();
+            router.clone().oneshot(request).await.unwrap().status()
+        };
+        let edit = |body: &'static str| json("PUT", &format!("/admin/links/{id}"), body);
+        assert_eq!(visit().await, StatusCode::TEMPORARY_REDIRECT);
+
+        // when
+        send(&router, edit(r#"{"url": "https://a.example/v2"}"#)).await;
+        let after_edit = visit().await;
+        let details = send(&router, get(&format!("/admin/links/{id}"))).await;
+        send(
+            &router,
+            edit(r#"{"url": "https://a.example/v2", "max_clicks": 2}"#),
+        )
+        .await;
+        let after_raise = [visit().await, visit().await];
+        send(
+            &router,
+            edit(r#"{"url": "https://a.example/v2", "max_clicks": null}"#),
+        )
+        .await;
+        let after_removal = visit().await;
+
+        // then
+        assert_eq!(after_edit, StatusCode::GONE);
+        assert_eq!(details["max_clicks"], 1);
+        assert_eq!(details["clicks_used"], 1);
+        assert_eq!(
+            after_raise,
+            [StatusCode::TEMPORARY_REDIRECT, StatusCode::GONE]
+        );
+        assert_eq!(after_removal, StatusCode::TEMPORARY_REDIRECT);
+    }
+}
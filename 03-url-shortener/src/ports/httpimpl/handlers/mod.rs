@@ -1,2 +1,9 @@
+pub mod audit;
 pub mod get_full_url;
+pub mod health;
+pub mod links;
+pub mod redirect;
 pub mod shorten_url;
+pub mod tenants;
+pub mod ui;
+pub mod webhooks;
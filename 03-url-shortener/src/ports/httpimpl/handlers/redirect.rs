@@ -0,0 +1,367 @@
+use axum::{
+    Form,
+    extract::Path,
+    http::{StatusCode, header},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+
+use crate::{
+    app::{error::AppError, query::get_full_url::Resolved},
+    di::{CommandRepository, QueryRepository},
+    ports::httpimpl::{
+        extractors::{ClientAddr, SPLIT_COOKIE, TenantScope, Visitor},
+        negotiation::escape_html,
+    },
+};
+
+/// сколько посетитель остаётся на выбранной цели сплит-ссылки
+const SPLIT_COOKIE_MAX_AGE_SECS: u64 = 30 * 24 * 3600;
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct PasswordForm {
+    password: String,
+}
+
+/// ручка перехода по короткой ссылке, для защищённой ссылки отдаёт форму пароля
+pub async fn redirect<R>(
+    Path(id): Path<String>,
+    TenantScope(container): TenantScope<R>,
+    Visitor(visit): Visitor,
+) -> Result<Response, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    let res = container.get_full_url_query.resolve(&id, &visit).await;
+    match res {
+        Ok(resolved) => {
+            let target = resolved.variant.as_ref().map(|v| v.url.as_str());
+            container
+                .record_click_command
+                .execute_for_target(&resolved.short_url, target)
+                .await?;
+            Ok(with_split_cookie(
+                &resolved.short_url,
+                &resolved,
+                Redirect::temporary(&resolved.url),
+            ))
+        }
+        Err(AppError::PasswordRequired) => {
+            Ok(Html(password_form(&id, &visit.query, None)).into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// ручка проверки пароля из формы
+pub async fn unlock<R>(
+    Path(id): Path<String>,
+    TenantScope(container): TenantScope<R>,
+    ClientAddr(client): ClientAddr,
+    Visitor(visit): Visitor,
+    Form(input): Form<PasswordForm>,
+) -> Result<Response, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    let res = container
+        .get_full_url_query
+        .execute_with_password(&id, input.password, &client, &visit)
+        .await;
+
+    match res {
+        Ok(resolved) => {
+            let target = resolved.variant.as_ref().map(|v| v.url.as_str());
+            container
+                .record_click_command
+                .execute_for_target(&resolved.short_url, target)
+                .await?;
+            Ok(with_split_cookie(
+                &resolved.short_url,
+                &resolved,
+                Redirect::to(&resolved.url),
+            ))
+        }
+        Err(e @ (AppError::InvalidPassword | AppError::TooManyAttempts)) => {
+            let status = match e {
+                AppError::TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+                _ => StatusCode::UNAUTHORIZED,
+            };
+            Ok((
+                status,
+                Html(password_form(&id, &visit.query, Some(&e.to_string()))),
+            )
+                .into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// запоминание выбранной цели сплит-ссылки, cookie видна только переходам по этой ссылке
+fn with_split_cookie(id: &str, resolved: &Resolved, redirect: Redirect) -> Response {
+    let Some(variant) = &resolved.variant else {
+        return redirect.into_response();
+    };
+    let cookie = format!(
+        "{SPLIT_COOKIE}={}; Path=/r/{id}; Max-Age={SPLIT_COOKIE_MAX_AGE_SECS}; HttpOnly; SameSite=Lax",
+        variant.key
+    );
+    ([(header::SET_COOKIE, cookie)], redirect).into_response()
+}
+
+/// форма отправляется с той же query string, чтобы после пароля она попала в переход
+fn password_form(id: &str, query: &str, error: Option<&str>) -> String {
+    let action = match query {
+        "" => format!("/r/{id}"),
+        query => format!("/r/{id}?{query}"),
+    };
+    let action = escape_html(&action);
+    let error = error
+        .map(|e| format!("<p class=\"error\">{}</p>", escape_html(e)))
+        .unwrap_or_default();
+
+    format!(
+        r#"<!doctype html>
+<html>
+<head><meta charset="utf-8"><title>Protected link</title></head>
+<body>
+<h1>This link is password protected</h1>
+{error}
+<form method="post" action="{action}">
+<input type="password" name="password" autofocus required>
+<button type="submit">Open</button>
+</form>
+</body>
+</html>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::{Body, to_bytes},
+        http::{Request, header},
+    };
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+        },
+        app::{
+            link::{ClickLimit, Link, LinkOptions},
+            policy::AllowAll,
+            redirect::{Passthrough, RedirectRules, UtmTemplate},
+            split::{self, WeightedTarget},
+        },
+        di::tenants::Tenants,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    use super::*;
+
+    fn router(store: Arc<DashMap<String, Link>>) -> axum::Router {
+        let tenants = Tenants::new(
+            InMemoryRepository::new(store),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        get_router(Arc::new(tenants), RouterOptions::default())
+    }
+
+    fn unlock_request(password: &str) -> Request<Body> {
+        Request::post("/r/123")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(format!("password={password}")))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn redirects_to_full_url() {
+        // given
+        let store = Arc::new(DashMap::new());
+        store.insert("123".to_owned(), Link::new("https://google.com"));
+
+        // when
+        let response = router(store)
+            .oneshot(Request::get("/r/123").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "https://google.com");
+    }
+
+    #[tokio::test]
+    async fn protected_link_serves_password_form() {
+        // given
+        let store = Arc::new(DashMap::new());
+        store.insert(
+            "123".to_owned(),
+            Link::protected("https://google.com", "secret").unwrap(),
+        );
+
+        // when
+        let response = router(store)
+            .oneshot(Request::get("/r/123").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains(r#"action="/r/123""#));
+    }
+
+    #[tokio::test]
+    async fn correct_password_redirects() {
+        // given
+        let store = Arc::new(DashMap::new());
+        store.insert(
+            "123".to_owned(),
+            Link::protected("https://google.com", "secret").unwrap(),
+        );
+
+        // when
+        let response = router(store)
+            .oneshot(unlock_request("secret"))
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "https://google.com");
+    }
+
+    #[tokio::test]
+    async fn wrong_password_shows_form_again() {
+        // given
+        let store = Arc::new(DashMap::new());
+        store.insert(
+            "123".to_owned(),
+            Link::protected("https://google.com", "secret").unwrap(),
+        );
+
+        // when
+        let response = router(store)
+            .oneshot(unlock_request("wrong"))
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("Invalid password"));
+    }
+
+    #[test]
+    fn form_escapes_id_and_query() {
+        // when
+        let html = password_form("<script>", "a=\"><b>&c=1", None);
+
+        // then
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("action=\"/r/&lt;script&gt;?a=&quot;&gt;&lt;b&gt;&amp;c=1\""));
+    }
+
+    #[tokio::test]
+    async fn redirect_applies_utm_and_incoming_params() {
+        // given
+        let store = Arc::new(DashMap::new());
+        let mut link = Link::new("https://a.example/sale?id=1");
+        link.redirect = RedirectRules {
+            passthrough: Passthrough::Merge,
+            utm: Some(UtmTemplate::new(Some("partner".to_owned()), None, None).unwrap()),
+        };
+        store.insert("123".to_owned(), link);
+
+        // when
+        let response = router(store)
+            .oneshot(
+                Request::get("/r/123?id=2&ref=blog")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://a.example/sale?id=1&utm_source=partner&ref=blog"
+        );
+    }
+
+    #[tokio::test]
+    async fn split_target_is_remembered_in_cookie() {
+        // given
+        let store = Arc::new(DashMap::new());
+        let mut link = Link::new("https://a.example");
+        link.targets = ["https://a.example", "https://b.example"]
+            .map(|url| WeightedTarget {
+                url: url.to_owned(),
+                weight: 50,
+            })
+            .to_vec();
+        store.insert("123".to_owned(), link);
+        let router = router(store);
+        let remembered = format!("theme=dark; split={}", split::key("https://b.example"));
+
+        // when
+        let first = router
+            .clone()
+            .oneshot(Request::get("/r/123").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let returning = router
+            .oneshot(
+                Request::get("/r/123")
+                    .header(header::COOKIE, remembered)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // then
+        let cookie = first.headers()[header::SET_COOKIE].to_str().unwrap();
+        let chosen = first.headers()[header::LOCATION].to_str().unwrap();
+        assert!(
+            cookie.starts_with(&format!("split={};", split::key(chosen))),
+            "{cookie}"
+        );
+        assert!(cookie.contains("Path=/r/123"), "{cookie}");
+        assert_eq!(returning.headers()[header::LOCATION], "https://b.example");
+    }
+
+    #[tokio::test]
+    async fn exhausted_link_answers_gone() {
+        // given
+        let store = Arc::new(DashMap::new());
+        let link = Link::new("https://a.example").with_options(LinkOptions {
+            max_clicks: ClickLimit::Set(1),
+            exhausted_message: Some(Some("This invite was already used".to_owned())),
+            ..Default::default()
+        });
+        store.insert("123".to_owned(), link);
+        let router = router(store);
+        let request = || Request::get("/r/123").body(Body::empty()).unwrap();
+
+        // when
+        let first = router.clone().oneshot(request()).await.unwrap();
+        let second = router.oneshot(request()).await.unwrap();
+
+        // then
+        assert_eq!(first.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(second.status(), StatusCode::GONE);
+        let body = to_bytes(second.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "This invite was already used");
+    }
+}
@@ -1,39 +1,344 @@
-use std::sync::Arc;
-
-use axum::{Json, extract::State};
+use axum::{
+    Form, Json,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Response},
+};
 
 use crate::{
-    app::{
-        command::create_short_url::CreateShortUrlRepository,
-        query::get_full_url::GetFullUrlRepository,
+    app::error::AppError,
+    di::{CommandRepository, QueryRepository},
+    ports::httpimpl::{
+        extractors::{Auditor, TenantScope},
+        handlers::links::LinkOptionsRequest,
+        negotiation::{Negotiated, Represent, ResponseFormat, escape_html},
     },
-    di::Container,
-    id_provider::IDProvider,
 };
 
-#[derive(serde::Deserialize, serde::Serialize)]
+#[derive(Default, serde::Deserialize, serde::Serialize)]
 pub struct CreateShortUrlRequest {
     url: String,
+    /// необязательный пароль для открытия ссылки
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(flatten)]
+    options: LinkOptionsRequest,
+}
+
+/// html форма создания ссылки, теги перечисляются через запятую
+#[derive(serde::Deserialize)]
+pub struct CreateShortUrlForm {
+    url: String,
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    tags: String,
+}
+
+impl From<CreateShortUrlForm> for CreateShortUrlRequest {
+    fn from(form: CreateShortUrlForm) -> Self {
+        // незаполненные поля формы приходят пустыми строками
+        let filled = |value: String| Some(value).filter(|value| !value.trim().is_empty());
+        let tags = form
+            .tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect();
+        CreateShortUrlRequest {
+            url: form.url.trim().to_owned(),
+            password: filled(form.password),
+            options: LinkOptionsRequest::described(filled(form.title), filled(form.note), tags),
+        }
+    }
+}
+
+/// Тело запроса создания по `Content-Type`: html форма, `text/plain`
+/// с одним адресом, остальное разбирается как json. Адрес из скрипта
+/// отправляется как `curl -H 'Content-Type: text/plain' -d https://...`.
+pub struct CreateShortUrlInput {
+    pub request: CreateShortUrlRequest,
+    /// тело пришло не в json, такому клиенту по умолчанию отвечаем текстом
+    pub plain: bool,
+}
+
+impl<S> FromRequest<S> for CreateShortUrlInput
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(|media| media.trim().to_ascii_lowercase());
+        let input = match content_type.as_deref() {
+            Some("application/x-www-form-urlencoded") => {
+                let Form(form) = Form::<CreateShortUrlForm>::from_request(request, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                form.into()
+            }
+            Some("text/plain") => {
+                let body = Bytes::from_request(request, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                raw_url(&body).map_err(IntoResponse::into_response)?
+            }
+            _ => {
+                let Json(input) = Json::<CreateShortUrlRequest>::from_request(request, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                return Ok(CreateShortUrlInput {
+                    request: input,
+                    plain: false,
+                });
+            }
+        };
+        Ok(CreateShortUrlInput {
+            request: input,
+            plain: true,
+        })
+    }
+}
+
+/// тело целиком — один адрес
+fn raw_url(body: &[u8]) -> Result<CreateShortUrlRequest, AppError> {
+    let url = std::str::from_utf8(body).map_err(|e| AppError::InvalidInput(e.to_string()))?;
+    Ok(CreateShortUrlRequest {
+        url: url.trim().to_owned(),
+        ..Default::default()
+    })
 }
 
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct ShortUrlResponse {
     url: String,
+    /// адрес перехода по ссылке для текстового и html ответа
+    #[serde(skip)]
+    link: String,
+}
+
+impl Represent for ShortUrlResponse {
+    fn text(&self) -> String {
+        self.link.clone()
+    }
+
+    fn html(&self) -> String {
+        let link = escape_html(&self.link);
+        format!("<p>Short link: <a href=\"{link}\">{link}</a></p>")
+    }
+}
+
+/// Абсолютный адрес перехода по короткой ссылке на том хосте, куда пришёл запрос.
+/// Схема берётся из `X-Forwarded-Proto`, если сервер стоит за прокси.
+fn short_link(headers: &HeaderMap, id: &str) -> String {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    match header(header::HOST) {
+        Some(host) => {
+            let scheme =
+                header(header::HeaderName::from_static("x-forwarded-proto")).unwrap_or("http");
+            format!("{scheme}://{host}/r/{id}")
+        }
+        None => format!("/r/{id}"),
+    }
 }
 
-/// ручка для получения короткой ссылки
-pub async fn shorten_url<I, R, Q>(
-    State(container): State<Arc<Container<I, R, Q>>>,
-    Json(input): Json<CreateShortUrlRequest>,
-) -> Result<Json<ShortUrlResponse>, String>
+/// Ручка для получения короткой ссылки, ответ в формате из `Accept`.
+/// Если подходит любой формат, на тело не в json отвечаем текстом.
+pub async fn shorten_url<R>(
+    TenantScope(container): TenantScope<R>,
+    auditor: Auditor,
+    headers: HeaderMap,
+    CreateShortUrlInput {
+        request: input,
+        plain,
+    }: CreateShortUrlInput,
+) -> Result<Negotiated<ShortUrlResponse>, AppError>
 where
-    I: IDProvider + Send + Sync + 'static,
-    R: CreateShortUrlRepository + Send + Sync + 'static,
-    Q: GetFullUrlRepository + Send + Sync + 'static,
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
 {
-    container
+    let fallback = match plain {
+        true => ResponseFormat::Text,
+        false => ResponseFormat::Json,
+    };
+    let format = ResponseFormat::from_headers_or(&headers, fallback);
+    let options = input.options.into_options()?;
+    let id = container
         .shorten_command
-        .execute(input.url)
-        .await
-        .map(|id| Json(ShortUrlResponse { url: id }))
+        .execute_with_options(input.url, input.password, options, &auditor.actor)
+        .await?;
+
+    Ok(Negotiated(
+        format,
+        ShortUrlResponse {
+            link: short_link(&headers, &id),
+            url: id,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Request, StatusCode},
+    };
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+        },
+        app::policy::AllowAll,
+        di::tenants::Tenants,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    use super::*;
+
+    fn router() -> Router {
+        let tenants = Tenants::new(
+            InMemoryRepository::new(Arc::new(DashMap::new())),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        get_router(Arc::new(tenants), RouterOptions::default())
+    }
+
+    async fn send(router: &Router, request: Request<Body>) -> (StatusCode, String, String) {
+        let response = router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_owned())
+            .unwrap_or_default();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    fn shorten(content_type: &str, accept: &str, body: &str) -> Request<Body> {
+        Request::post("/")
+            .header(header::HOST, "s.example")
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::ACCEPT, accept)
+            .body(Body::from(body.to_owned()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn plain_text_in_and_out() {
+        // given
+        let router = router();
+
+        // when
+        let (status, content_type, link) = send(
+            &router,
+            shorten("text/plain", "text/plain", "https://a.example/docs\n"),
+        )
+        .await;
+        let id = link.trim().rsplit('/').next().unwrap();
+        let resolve = Request::get(format!("/{id}"))
+            .header(header::ACCEPT, "text/plain")
+            .body(Body::empty())
+            .unwrap();
+        let (_, _, full) = send(&router, resolve).await;
+
+        // then
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/plain"), "{content_type}");
+        assert_eq!(link, format!("http://s.example/r/{id}\n"));
+        assert_eq!(full, "https://a.example/docs\n");
+    }
+
+    #[tokio::test]
+    async fn html_form_gets_html_page() {
+        // given
+        let router = router();
+        let form = "url=https%3A%2F%2Fa.example%2F%3Fa%3D1%26b%3D2&title=&tags=docs%2C+team";
+
+        // when
+        let (status, content_type, page) = send(
+            &router,
+            shorten("application/x-www-form-urlencoded", "text/html", form),
+        )
+        .await;
+        let (_, _, json) = send(
+            &router,
+            shorten(
+                "application/json",
+                "application/json",
+                r#"{"url": "https://b.example"}"#,
+            ),
+        )
+        .await;
+
+        // then
+        assert_eq!(status, StatusCode::OK);
+        assert!(content_type.starts_with("text/html"), "{content_type}");
+        assert!(page.contains(r#"<a href="http://s.example/r/"#), "{page}");
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), 1);
+        assert!(json["url"].is_string());
+    }
+
+    #[tokio::test]
+    async fn plain_text_url_with_url_param_is_not_a_form() {
+        // given
+        let router = router();
+        let url = "https://a.example/?a=1&url=https://b.example";
+
+        // when
+        let (status, _, link) = send(&router, shorten("text/plain", "*/*", url)).await;
+        let id = link.trim().rsplit('/').next().unwrap();
+        let resolve = Request::get(format!("/{id}"))
+            .header(header::ACCEPT, "text/plain")
+            .body(Body::empty())
+            .unwrap();
+        let (_, _, full) = send(&router, resolve).await;
+
+        // then
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(full, format!("{url}\n"));
+    }
+
+    #[tokio::test]
+    async fn form_without_url_field_is_rejected() {
+        // given
+        let router = router();
+
+        // when
+        let (status, _, _) = send(
+            &router,
+            shorten(
+                "application/x-www-form-urlencoded",
+                "*/*",
+                "https://a.example/?a=1&b=2",
+            ),
+        )
+        .await;
+
+        // then
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
 }
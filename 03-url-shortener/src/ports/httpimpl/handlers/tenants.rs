@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+
+use crate::{
+    app::{error::AppError, tenant::Tenant},
+    di::{CommandRepository, QueryRepository, tenants::Tenants},
+    ports::httpimpl::extractors::Auditor,
+};
+
+/// ручка списка арендаторов
+pub async fn list_tenants<R>(
+    State(tenants): State<Arc<Tenants<R>>>,
+) -> Result<Json<Vec<Tenant>>, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    tenants.list().map(Json)
+}
+
+/// ручка получения арендатора
+pub async fn get_tenant<R>(
+    Path(id): Path<String>,
+    State(tenants): State<Arc<Tenants<R>>>,
+) -> Result<Json<Tenant>, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    tenants.get(&id).map(Json)
+}
+
+/// ручка создания или изменения арендатора
+pub async fn put_tenant<R>(
+    Path(id): Path<String>,
+    State(tenants): State<Arc<Tenants<R>>>,
+    auditor: Auditor,
+    Json(tenant): Json<Tenant>,
+) -> Result<Json<Tenant>, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    if tenant.id != id {
+        return Err(AppError::InvalidInput(
+            "tenant id does not match path".to_owned(),
+        ));
+    }
+    tenant.validate()?;
+    let record = match tenants.get(&id).ok() {
+        Some(before) => auditor.record("tenant.update", &id).with_before(&before),
+        None => auditor.record("tenant.create", &id),
+    };
+    auditor.log(record.with_after(&tenant)).await?;
+    tenants.save(tenant)?;
+    tenants.get(&id).map(Json)
+}
+
+/// ручка удаления арендатора
+pub async fn delete_tenant<R>(
+    Path(id): Path<String>,
+    State(tenants): State<Arc<Tenants<R>>>,
+    auditor: Auditor,
+) -> Result<StatusCode, AppError>
+where
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
+{
+    let before = tenants.get(&id)?;
+    auditor
+        .log(auditor.record("tenant.delete", &id).with_before(&before))
+        .await?;
+    tenants.delete(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Request, header},
+    };
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+        },
+        app::policy::AllowAll,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    use super::*;
+
+    fn router() -> Router {
+        let tenants = Tenants::new(
+            InMemoryRepository::new(Arc::new(DashMap::new())),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        get_router(
+            Arc::new(tenants),
+            RouterOptions {
+                admin_token: Some("token".to_owned()),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn put_team_a() -> Request<Body> {
+        Request::put("/admin/tenants/team-a")
+            .header(header::AUTHORIZATION, "Bearer token")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"{"id": "team-a", "domains": ["go.team-a.example"]}"#,
+            ))
+            .unwrap()
+    }
+
+    async fn shorten(router: &Router, host: &str) -> String {
+        let response = router
+            .clone()
+            .oneshot(
+                Request::post("/")
+                    .header(header::HOST, host)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"url": "https://a.example/docs"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        json["url"].as_str().unwrap().to_owned()
+    }
+
+    async fn resolve(router: &Router, host: &str, id: &str) -> StatusCode {
+        router
+            .clone()
+            .oneshot(
+                Request::get(format!("/{id}"))
+                    .header(header::HOST, host)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn admin_api_requires_token() {
+        // given
+        let router = router();
+
+        // when
+        let response = router
+            .oneshot(Request::get("/admin/tenants").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn admin_api_is_disabled_without_token() {
+        // given
+        let tenants = Tenants::new(
+            InMemoryRepository::new(Arc::new(DashMap::new())),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        let router = get_router(Arc::new(tenants), RouterOptions::default());
+
+        // when
+        let response = router
+            .oneshot(Request::get("/admin/tenants").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // then
+        assert_ne!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn codes_are_isolated_by_host() {
+        // given
+        let router = router();
+        let response = router.clone().oneshot(put_team_a()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // when
+        let id = shorten(&router, "go.team-a.example").await;
+
+        // then
+        assert_eq!(
+            resolve(&router, "go.team-a.example", &id).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            resolve(&router, "localhost:3001", &id).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            resolve(&router, "localhost:3001", &format!("team-a%2F{id}")).await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn put_rejects_mismatched_id() {
+        // given
+        let router = router();
+
+        // when
+        let response = router
+            .oneshot(
+                Request::put("/admin/tenants/team-b")
+                    .header(header::AUTHORIZATION, "Bearer token")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        r#"{"id": "team-a", "domains": ["go.team-a.example"]}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
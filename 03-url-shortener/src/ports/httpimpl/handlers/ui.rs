@@ -0,0 +1,170 @@
+use axum::{
+    extract::Path,
+    http::{StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+
+/// файлы веб-интерфейса, вшитые в бинарник
+const ASSETS: &[(&str, &str, &str)] = &[
+    (
+        "index.html",
+        "text/html; charset=utf-8",
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/ui/index.html")),
+    ),
+    (
+        "app.js",
+        "text/javascript; charset=utf-8",
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/ui/app.js")),
+    ),
+    (
+        "style.css",
+        "text/css; charset=utf-8",
+        include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/ui/style.css")),
+    ),
+];
+
+/// корень сайта ведёт в веб-интерфейс
+pub async fn root() -> Redirect {
+    Redirect::to("/ui/")
+}
+
+/// ручка страницы веб-интерфейса
+pub async fn index() -> Response {
+    asset("index.html")
+}
+
+/// ручка файлов веб-интерфейса
+pub async fn ui_asset(Path(name): Path<String>) -> Response {
+    asset(&name)
+}
+
+fn asset(name: &str) -> Response {
+    match ASSETS.iter().find(|(file, _, _)| *file == name) {
+        Some((_, content_type, body)) => (
+            [
+                (header::CONTENT_TYPE, *content_type),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            *body,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Method, Request},
+    };
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+        },
+        app::policy::AllowAll,
+        di::tenants::Tenants,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    use super::*;
+
+    fn router(cors_origins: Vec<String>) -> Router {
+        let tenants = Tenants::new(
+            InMemoryRepository::new(Arc::new(DashMap::new())),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        get_router(
+            Arc::new(tenants),
+            RouterOptions {
+                admin_token: Some("token".to_owned()),
+                cors_origins,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn ui_is_served_from_binary() {
+        // given
+        let router = router(vec![]);
+
+        // when
+        let page = router
+            .clone()
+            .oneshot(Request::get("/ui/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let script = router
+            .clone()
+            .oneshot(Request::get("/ui/app.js").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let missing = router
+            .oneshot(Request::get("/ui/secret.txt").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(page.status(), StatusCode::OK);
+        assert_eq!(
+            page.headers()[header::CONTENT_TYPE],
+            "text/html; charset=utf-8"
+        );
+        let body = to_bytes(page.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains(r#"<script src="app.js">"#));
+        assert_eq!(
+            script.headers()[header::CONTENT_TYPE],
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn cors_allows_only_configured_origins() {
+        // given
+        let router = router(vec!["https://ui.example".to_owned()]);
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri("/admin/links/abc")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+                .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // when
+        let allowed = router
+            .clone()
+            .oneshot(preflight("https://ui.example"))
+            .await
+            .unwrap();
+        let other = router
+            .oneshot(preflight("https://evil.example"))
+            .await
+            .unwrap();
+
+        // then
+        assert_eq!(allowed.status(), StatusCode::OK);
+        assert_eq!(
+            allowed.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://ui.example"
+        );
+        assert!(
+            !other
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+        );
+    }
+}
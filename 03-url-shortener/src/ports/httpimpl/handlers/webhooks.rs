@@ -0,0 +1,284 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+
+use crate::{
+    app::{
+        error::AppError,
+        event::LinkEvent,
+        webhook::{Delivery, Subscription, Webhooks},
+    },
+    ports::httpimpl::extractors::Auditor,
+};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct CreateWebhookRequest {
+    url: String,
+    /// типы событий, по умолчанию все
+    #[serde(default)]
+    events: Vec<String>,
+    /// ключ подписи, по умолчанию генерируется
+    #[serde(default)]
+    secret: Option<String>,
+}
+
+/// подписка без секрета, секрет показывается только при создании
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct WebhookResponse {
+    id: String,
+    url: String,
+    events: Vec<String>,
+}
+
+impl From<Subscription> for WebhookResponse {
+    fn from(s: Subscription) -> Self {
+        WebhookResponse {
+            id: s.id,
+            url: s.url,
+            events: s.events,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct DeadLetterResponse {
+    id: String,
+    subscription_id: String,
+    url: String,
+    event: LinkEvent,
+    attempts: u32,
+    last_error: Option<String>,
+}
+
+impl From<Delivery> for DeadLetterResponse {
+    fn from(d: Delivery) -> Self {
+        DeadLetterResponse {
+            id: d.id,
+            subscription_id: d.subscription_id,
+            url: d.url,
+            event: d.event,
+            attempts: d.attempts,
+            last_error: d.last_error,
+        }
+    }
+}
+
+/// ручка списка подписок
+pub async fn list_webhooks(
+    State(webhooks): State<Webhooks>,
+) -> Result<Json<Vec<WebhookResponse>>, AppError> {
+    let subscriptions = webhooks.subscriptions()?;
+    Ok(Json(subscriptions.into_iter().map(Into::into).collect()))
+}
+
+/// ручка создания подписки, в ответе есть секрет для проверки подписи
+pub async fn create_webhook(
+    State(webhooks): State<Webhooks>,
+    auditor: Auditor,
+    Json(input): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<Subscription>), AppError> {
+    let subscription = Webhooks::subscription(input.url, input.events, input.secret)?;
+    auditor
+        .log(
+            auditor
+                .record("webhook.create", &subscription.id)
+                .with_after(&WebhookResponse::from(subscription.clone())),
+        )
+        .await?;
+    webhooks.save(subscription.clone())?;
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+/// ручка удаления подписки
+pub async fn delete_webhook(
+    Path(id): Path<String>,
+    State(webhooks): State<Webhooks>,
+    auditor: Auditor,
+) -> Result<StatusCode, AppError> {
+    let before = webhooks
+        .subscriptions()?
+        .into_iter()
+        .find(|s| s.id == id)
+        .map(WebhookResponse::from);
+    let record = auditor.record("webhook.delete", &id);
+    auditor
+        .log(match before {
+            Some(before) => record.with_before(&before),
+            None => record,
+        })
+        .await?;
+    webhooks.unsubscribe(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// ручка списка недоставленных событий
+pub async fn list_dead_letters(
+    State(webhooks): State<Webhooks>,
+) -> Result<Json<Vec<DeadLetterResponse>>, AppError> {
+    let dead_letters = webhooks.dead_letters()?;
+    Ok(Json(dead_letters.into_iter().map(Into::into).collect()))
+}
+
+/// ручка повторной отправки недоставленного события
+pub async fn retry_dead_letter(
+    Path(id): Path<String>,
+    State(webhooks): State<Webhooks>,
+    auditor: Auditor,
+) -> Result<StatusCode, AppError> {
+    auditor.log(auditor.record("webhook.retry", &id)).await?;
+    webhooks.retry_dead_letter(&id)?;
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Router,
+        body::{Body, to_bytes},
+        http::{Request, header},
+    };
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+            in_memory_webhook_store::InMemoryWebhookStore,
+        },
+        app::{
+            event::{EventBus, OutboxRelay},
+            policy::AllowAll,
+            webhook::WebhookStore,
+        },
+        di::tenants::Tenants,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    use super::*;
+
+    fn router(store: Arc<InMemoryWebhookStore>) -> (Router, OutboxRelay) {
+        let webhooks = Webhooks::new(store);
+        let bus = EventBus::default();
+        bus.subscribe(Arc::new(webhooks.clone()));
+        let repo = InMemoryRepository::new(Arc::new(DashMap::new()));
+        let relay = OutboxRelay::new(Arc::new(repo.clone()), Arc::new(bus));
+        let tenants = Tenants::new(
+            repo,
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        let router = get_router(
+            Arc::new(tenants),
+            RouterOptions {
+                admin_token: Some("token".to_owned()),
+                webhooks: Some(webhooks),
+                ..Default::default()
+            },
+        );
+        (router, relay)
+    }
+
+    fn admin(method: &str, uri: &str, body: &str) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, "Bearer token")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_owned()))
+            .unwrap()
+    }
+
+    async fn json(response: axum::response::Response) -> serde_json::Value {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn link_lifecycle_is_queued_for_subscriber() {
+        // given
+        let store = Arc::new(InMemoryWebhookStore::default());
+        let (router, relay) = router(store.clone());
+        let response = router
+            .clone()
+            .oneshot(admin(
+                "POST",
+                "/admin/webhooks",
+                r#"{"url": "http://hooks.example/in", "events": ["link.created", "link.updated", "link.trashed"]}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(json(response).await["secret"].is_string());
+
+        // when
+        let response = router
+            .clone()
+            .oneshot(
+                Request::post("/")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(r#"{"url": "https://a.example"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let id = json(response).await["url"].as_str().unwrap().to_owned();
+        let link = format!("/admin/links/{id}");
+        let updated = router
+            .clone()
+            .oneshot(admin("PUT", &link, r#"{"url": "https://b.example"}"#))
+            .await
+            .unwrap();
+        router
+            .clone()
+            .oneshot(Request::get(format!("/{id}")).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let deleted = router.oneshot(admin("DELETE", &link, "")).await.unwrap();
+        let relayed = relay.relay_pending().unwrap();
+
+        // then
+        assert_eq!(updated.status(), StatusCode::NO_CONTENT);
+        assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+        assert_eq!(relayed, 4);
+        let mut types: Vec<&str> = store
+            .due(u64::MAX, 10)
+            .unwrap()
+            .iter()
+            .map(|d| d.event.kind.name())
+            .collect();
+        types.sort();
+        assert_eq!(types, vec!["link.created", "link.trashed", "link.updated"]);
+    }
+
+    #[tokio::test]
+    async fn listing_hides_secrets() {
+        // given
+        let (router, _) = router(Arc::new(InMemoryWebhookStore::default()));
+        router
+            .clone()
+            .oneshot(admin(
+                "POST",
+                "/admin/webhooks",
+                r#"{"url": "http://hooks.example/in", "secret": "s3cret"}"#,
+            ))
+            .await
+            .unwrap();
+
+        // when
+        let response = router
+            .oneshot(admin("GET", "/admin/webhooks", ""))
+            .await
+            .unwrap();
+
+        // then
+        let list = json(response).await;
+        assert_eq!(list[0]["url"], "http://hooks.example/in");
+        assert!(list[0].get("secret").is_none());
+    }
+}
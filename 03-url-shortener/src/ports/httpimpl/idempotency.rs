@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, to_bytes},
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app::{
+        audit::Actor,
+        error::AppError,
+        idempotency::{Idempotency, Outcome, StoredResponse},
+    },
+    ports::httpimpl::extractors::Auditor,
+};
+
+/// заголовок с ключом идемпотентности от клиента
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// заголовок, которым помечен ответ, взятый из хранилища
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// самое большое тело, которое запоминается для сравнения с повтором
+const MAX_BODY: usize = 1 << 20;
+
+/// Выполняет запрос с заголовком `Idempotency-Key` не больше одного раза.
+///
+/// Ключ действует в пределах хоста и клиента, а повтором считается запрос
+/// с тем же типом содержимого и телом. Запросы без заголовка проходят как есть.
+pub async fn idempotent(
+    State(idempotency): State<Arc<Idempotency>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= 255 => key.to_owned(),
+        _ => {
+            return AppError::InvalidInput("idempotency key must be 1-255 characters".to_owned())
+                .into_response();
+        }
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let Ok(Auditor { actor, .. }) = Auditor::from_request_parts(&mut parts, &()).await;
+    let Ok(body) = to_bytes(body, MAX_BODY).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let header = |name| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let scope = format!("{} {} {key}", header(header::HOST), client(&actor));
+    let mut hasher = Sha256::new();
+    hasher.update(media_type(header(header::CONTENT_TYPE)).as_bytes());
+    hasher.update([0]);
+    hasher.update(&body);
+    let fingerprint = hex::encode(hasher.finalize());
+
+    let request = Request::from_parts(parts, Body::from(body));
+    let outcome = idempotency
+        .run(&scope, &fingerprint, async {
+            stored(next.run(request).await).await
+        })
+        .await;
+    match outcome {
+        Ok(Outcome::Executed(response)) => restore(response),
+        Ok(Outcome::Replayed(response)) => {
+            let mut response = restore(response);
+            response
+                .headers_mut()
+                .insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+            response
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// чьи ключи: автор запроса, его админский токен и адрес
+fn client(actor: &Actor) -> String {
+    let api_key = actor.api_key.as_deref().unwrap_or("-");
+    format!("{} {api_key} {}", actor.name, actor.ip)
+}
+
+/// тип содержимого без параметров, чтобы `charset` не отличал повтор от исходного запроса
+fn media_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+async fn stored(response: Response) -> StoredResponse {
+    let (parts, body) = response.into_parts();
+    match to_bytes(body, usize::MAX).await {
+        Ok(body) => StoredResponse {
+            status: parts.status.as_u16(),
+            content_type: parts
+                .headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned),
+            body: body.to_vec(),
+        },
+        Err(e) => StoredResponse {
+            status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+            content_type: None,
+            body: e.to_string().into_bytes(),
+        },
+    }
+}
+
+fn restore(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = (status, stored.body).into_response();
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|value| HeaderValue::from_str(&value).ok())
+    {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+
+    use axum::{Router, extract::ConnectInfo};
+    use dashmap::DashMap;
+    use tower::ServiceExt;
+
+    use crate::{
+        adapters::{
+            in_memory_idempotency_store::InMemoryIdempotencyStore,
+            in_memory_repository::InMemoryRepository,
+            in_memory_tenant_repository::InMemoryTenantRepository,
+        },
+        app::policy::AllowAll,
+        di::tenants::Tenants,
+        ports::httpimpl::get_router::{RouterOptions, get_router},
+    };
+
+    use super::*;
+
+    fn router() -> Router {
+        let tenants = Tenants::new(
+            InMemoryRepository::new(Arc::new(DashMap::new())),
+            Arc::new(InMemoryTenantRepository::default()),
+            Arc::new(AllowAll),
+        )
+        .unwrap();
+        get_router(
+            Arc::new(tenants),
+            RouterOptions {
+                idempotency: Some(Arc::new(Idempotency::new(
+                    Arc::new(InMemoryIdempotencyStore::default()),
+                    Duration::from_secs(60),
+                ))),
+                ..Default::default()
+            },
+        )
+    }
+
+    async fn shorten(router: &Router, key: Option<&str>, url: &str) -> (StatusCode, bool, String) {
+        let mut request = Request::post("/").header(header::CONTENT_TYPE, "application/json");
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        let body = format!(r#"{{"url": "{url}"}}"#);
+        let response = router
+            .clone()
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let replayed = response.headers().contains_key(REPLAYED_HEADER);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, replayed, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn retry_with_same_key_returns_original_link() {
+        // given
+        let router = router();
+        let (_, _, original) = shorten(&router, Some("retry-1"), "https://a.example").await;
+
+        // when
+        let retry = shorten(&router, Some("retry-1"), "https://a.example").await;
+        let changed = shorten(&router, Some("retry-1"), "https://b.example").await;
+        let (_, _, without_key) = shorten(&router, None, "https://a.example").await;
+
+        // then
+        assert_eq!(retry, (StatusCode::OK, true, original.clone()));
+        assert_eq!(changed.0, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_ne!(without_key, original);
+    }
+
+    async fn shorten_from(router: &Router, client: &str, content_type: &str) -> (bool, String) {
+        let mut request = Request::post("/")
+            .header(header::CONTENT_TYPE, content_type)
+            .header(IDEMPOTENCY_KEY_HEADER, "shared")
+            .body(Body::from(r#"{"url": "https://a.example"}"#))
+            .unwrap();
+        let addr: SocketAddr = format!("{client}:4000").parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        let response = router.clone().oneshot(request).await.unwrap();
+        let replayed = response.headers().contains_key(REPLAYED_HEADER);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (replayed, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_by_client_and_ignore_charset() {
+        // given
+        let router = router();
+        let (_, original) = shorten_from(&router, "10.0.0.1", "application/json").await;
+
+        // when
+        let retry = shorten_from(&router, "10.0.0.1", "application/json; charset=utf-8").await;
+        let (other_replayed, other) = shorten_from(&router, "10.0.0.2", "application/json").await;
+
+        // then
+        assert_eq!(retry, (true, original.clone()));
+        assert!(!other_replayed);
+        assert_ne!(other, original);
+    }
+}
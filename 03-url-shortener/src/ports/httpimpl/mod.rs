@@ -1,3 +1,9 @@
+pub mod admin_auth;
+pub mod error;
+pub mod extractors;
 pub mod get_router;
 pub mod handlers;
+pub mod idempotency;
+pub mod negotiation;
 pub mod server;
+pub mod tls;
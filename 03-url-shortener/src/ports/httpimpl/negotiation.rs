@@ -0,0 +1,176 @@
+use std::convert::Infallible;
+
+use axum::{
+    Json,
+    extract::FromRequestParts,
+    http::{HeaderMap, header, request::Parts},
+    response::{Html, IntoResponse, Response},
+};
+use serde::Serialize;
+
+/// Формат ответа, выбранный по заголовку `Accept`.
+///
+/// Учитываются веса `q`, при равных весах побеждает тип, указанный раньше.
+/// Без заголовка, с одним `*/*` или без знакомых типов ответ отдаётся в json.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    Text,
+    Html,
+}
+
+impl ResponseFormat {
+    pub fn from_accept(accept: &str) -> Self {
+        Self::from_accept_or(accept, ResponseFormat::Json)
+    }
+
+    /// формат по `Accept`, а если клиенту подходит любой, то `fallback`
+    pub fn from_accept_or(accept: &str, fallback: ResponseFormat) -> Self {
+        if accept.trim().is_empty() {
+            return fallback;
+        }
+        let mut best: Option<(f32, ResponseFormat)> = None;
+        for item in accept.split(',') {
+            let mut parts = item.split(';').map(str::trim);
+            let media = parts.next().unwrap_or_default().to_ascii_lowercase();
+            let format = match media.as_str() {
+                "application/json" | "application/*" => ResponseFormat::Json,
+                "*/*" => fallback,
+                "text/plain" | "text/*" => ResponseFormat::Text,
+                "text/html" => ResponseFormat::Html,
+                _ => continue,
+            };
+            let q = parts
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if q > 0.0 && best.is_none_or(|(best_q, _)| q > best_q) {
+                best = Some((q, format));
+            }
+        }
+        best.map(|(_, format)| format).unwrap_or_default()
+    }
+
+    /// формат по заголовку `Accept` запроса, см. [`Self::from_accept_or`]
+    pub fn from_headers_or(headers: &HeaderMap, fallback: ResponseFormat) -> Self {
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        Self::from_accept_or(accept, fallback)
+    }
+}
+
+impl<S> FromRequestParts<S> for ResponseFormat
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_headers_or(&parts.headers, ResponseFormat::Json))
+    }
+}
+
+/// ответ, у которого кроме json есть текстовое и html представления
+pub trait Represent: Serialize {
+    /// одна строка без разметки
+    fn text(&self) -> String;
+    /// содержимое `<body>`, уже экранированное
+    fn html(&self) -> String;
+}
+
+/// ответ в формате, который попросил клиент
+pub struct Negotiated<T>(pub ResponseFormat, pub T);
+
+impl<T: Represent> IntoResponse for Negotiated<T> {
+    fn into_response(self) -> Response {
+        let Negotiated(format, value) = self;
+        match format {
+            ResponseFormat::Json => Json(value).into_response(),
+            ResponseFormat::Text => format!("{}\n", value.text()).into_response(),
+            ResponseFormat::Html => Html(format!(
+                "<!doctype html>\n<html><head><meta charset=\"utf-8\"><title>URL shortener</title></head>\n<body>{}</body></html>\n",
+                value.html()
+            ))
+            .into_response(),
+        }
+    }
+}
+
+/// экранирование текста для вставки в html
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_follows_accept_weights() {
+        // given
+        let cases = [
+            ("", ResponseFormat::Json),
+            ("*/*", ResponseFormat::Json),
+            ("text/plain", ResponseFormat::Text),
+            ("image/png, text/*", ResponseFormat::Text),
+            (
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                ResponseFormat::Html,
+            ),
+            ("application/json;q=0.5, text/plain", ResponseFormat::Text),
+            ("text/plain;q=0, text/html;q=0.1", ResponseFormat::Html),
+            ("image/png", ResponseFormat::Json),
+        ];
+
+        // when
+        let formats: Vec<ResponseFormat> = cases
+            .iter()
+            .map(|(accept, _)| ResponseFormat::from_accept(accept))
+            .collect();
+
+        // then
+        let expected: Vec<ResponseFormat> = cases.iter().map(|(_, format)| *format).collect();
+        assert_eq!(formats, expected);
+    }
+
+    #[test]
+    fn any_format_falls_back() {
+        // when
+        let fallback = |accept| ResponseFormat::from_accept_or(accept, ResponseFormat::Text);
+
+        // then
+        assert_eq!(fallback(""), ResponseFormat::Text);
+        assert_eq!(fallback("*/*"), ResponseFormat::Text);
+        assert_eq!(
+            fallback("application/json, */*;q=0.5"),
+            ResponseFormat::Json
+        );
+        assert_eq!(fallback("text/html, */*"), ResponseFormat::Html);
+    }
+
+    #[test]
+    fn html_is_escaped() {
+        // when
+        let escaped = escape_html(r#"https://a.example/?q="<b>"&x='1'"#);
+
+        // then
+        assert_eq!(
+            escaped,
+            "https://a.example/?q=&quot;&lt;b&gt;&quot;&amp;x=&#39;1&#39;"
+        );
+    }
+}
@@ -1,44 +1,132 @@
-use std::sync::Arc;
-
-use crate::ports::httpimpl::get_router::get_router;
-use crate::{
-    app::{
-        command::create_short_url::CreateShortUrlRepository,
-        query::get_full_url::GetFullUrlRepository,
-    },
-    di::Container,
-    id_provider::IDProvider,
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
 };
-use tokio::net::TcpListener;
+
+use crate::app::{
+    audit::AuditTrail, health::Readiness, idempotency::Idempotency, webhook::Webhooks,
+};
+use crate::di::{CommandRepository, QueryRepository, tenants::Tenants};
+use crate::ports::httpimpl::get_router::{RouterOptions, get_router};
+use crate::ports::httpimpl::tls::{TlsConfig, redirect_router, serve_tls};
+use tokio::{net::TcpListener, sync::watch};
 
 /// сервер приложения
-pub struct Server<I, R, Q>
+pub struct Server<R>
 where
-    I: IDProvider + Send + Sync + 'static,
-    R: CreateShortUrlRepository + Send + Sync + 'static,
-    Q: GetFullUrlRepository + Send + Sync + 'static,
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
 {
     port: u16,
-    container: Arc<Container<I, R, Q>>,
+    tenants: Arc<Tenants<R>>,
+    options: RouterOptions,
+    tls: Option<TlsConfig>,
 }
 
-impl<I, R, Q> Server<I, R, Q>
+impl<R> Server<R>
 where
-    I: IDProvider + Send + Sync + 'static,
-    R: CreateShortUrlRepository + Send + Sync + 'static,
-    Q: GetFullUrlRepository + Send + Sync + 'static,
+    R: CommandRepository + QueryRepository + Send + Sync + 'static,
 {
-    pub fn new(port: u16, container: Arc<Container<I, R, Q>>) -> Self {
-        Server { port, container }
+    pub fn new(port: u16, tenants: Arc<Tenants<R>>) -> Self {
+        Server {
+            port,
+            tenants,
+            options: RouterOptions::default(),
+            tls: None,
+        }
+    }
+
+    /// включить админское api, доступное с этим bearer токеном
+    pub fn with_admin_token(mut self, admin_token: Option<String>) -> Self {
+        self.options.admin_token = admin_token;
+        self
+    }
+
+    /// управлять подписками на вебхуки через админское api
+    pub fn with_webhooks(mut self, webhooks: Webhooks) -> Self {
+        self.options.webhooks = Some(webhooks);
+        self
+    }
+
+    /// проверять эти компоненты в ручке готовности
+    pub fn with_readiness(mut self, readiness: Arc<Readiness>) -> Self {
+        self.options.readiness = readiness;
+        self
+    }
+
+    /// разрешить запросы из браузера с этих источников
+    pub fn with_cors_origins(mut self, origins: Vec<String>) -> Self {
+        self.options.cors_origins = origins;
+        self
+    }
+
+    /// записывать изменения в журнал аудита
+    pub fn with_audit(mut self, trail: Arc<dyn AuditTrail>) -> Self {
+        self.options.audit = Some(trail);
+        self
+    }
+
+    /// верить заголовку с автором запроса от прокси с этими адресами
+    pub fn with_trusted_proxies(mut self, proxies: Vec<IpAddr>) -> Self {
+        self.options.trusted_proxies = proxies;
+        self
+    }
+
+    /// не создавать ссылку повторно на запрос с тем же `Idempotency-Key`
+    pub fn with_idempotency(mut self, idempotency: Arc<Idempotency>) -> Self {
+        self.options.idempotency = Some(idempotency);
+        self
+    }
+
+    /// отвечать по https с этим сертификатом вместо http
+    pub fn with_tls(mut self, tls: Option<TlsConfig>) -> Self {
+        self.tls = tls;
+        self
     }
 
     /// Запуск сервера
-    pub async fn run(self) {
-        let container = self.container;
-        let router = get_router(container);
+    pub async fn run(self) -> io::Result<()> {
+        self.run_until(std::future::pending()).await
+    }
+
+    /// Запуск сервера до завершения `shutdown`, после чего сервер
+    /// перестаёт принимать соединения и дожидается начатых запросов.
+    /// Ошибка возвращается, если не удалось открыть порт или поднять tls.
+    pub async fn run_until(
+        self,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> io::Result<()> {
+        let router = get_router(self.tenants, self.options);
         let addr = format!("0.0.0.0:{}", self.port);
-        let listener = TcpListener::bind(addr).await.unwrap();
+        if let Some(tls) = self.tls {
+            let listener = std::net::TcpListener::bind(addr)?;
+            let Some(port) = tls.redirect_port else {
+                return serve_tls(listener, router, &tls, shutdown).await;
+            };
+            // переадресация останавливается вместе с https по тому же сигналу
+            let (stop, stopped) = watch::channel(false);
+            tokio::spawn(async move {
+                shutdown.await;
+                let _ = stop.send(true);
+            });
+            let signal = |mut stopped: watch::Receiver<bool>| async move {
+                let _ = stopped.wait_for(|stopped| *stopped).await;
+            };
+            let https = serve_tls(listener, router, &tls, signal(stopped.clone()));
+            let redirect_listener = TcpListener::bind(format!("0.0.0.0:{port}")).await?;
+            let redirect = axum::serve(redirect_listener, redirect_router(self.port))
+                .with_graceful_shutdown(signal(stopped));
+            tokio::try_join!(https, async { redirect.await })?;
+            return Ok(());
+        }
+        let listener = TcpListener::bind(addr).await?;
 
-        axum::serve(listener, router).await.unwrap();
+        axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown)
+        .await
     }
 }
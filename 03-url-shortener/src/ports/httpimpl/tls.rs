@@ -0,0 +1,234 @@
+use std::{future::Future, io, net::SocketAddr, path::PathBuf, time::Duration};
+
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Uri, header},
+    middleware,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_server::{Handle, tls_rustls::RustlsConfig};
+
+/// настройки https
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// цепочка сертификатов в pem
+    pub cert: PathBuf,
+    /// закрытый ключ в pem
+    pub key: PathBuf,
+    /// порт http, с которого клиентов отправляют на https, `None` если не нужен
+    pub redirect_port: Option<u16>,
+    /// как часто проверять, не поменялись ли файлы сертификата
+    pub reload_interval: Duration,
+}
+
+/// сколько после `shutdown` ждать начатые запросы, прежде чем закрыть соединения
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Https поверх готового listener до завершения `shutdown`.
+///
+/// Сертификат перечитывается, когда меняется содержимое файлов, уже открытые
+/// соединения продолжают работать со старым. Если новые файлы не читаются
+/// (например, ключ ещё не дописан), остаётся прежний сертификат.
+/// Запросы получают `X-Forwarded-Proto: https`, как от прокси, снимающего tls,
+/// и заголовок Host, если клиент по http/2 прислал только `:authority`.
+pub async fn serve_tls(
+    listener: std::net::TcpListener,
+    router: Router,
+    tls: &TlsConfig,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> io::Result<()> {
+    let (cert, key) = read_pem(tls).await?;
+    let config = RustlsConfig::from_pem(cert.clone(), key.clone()).await?;
+    let reload = tokio::spawn(watch_certificates(config.clone(), tls.clone(), (cert, key)));
+
+    let handle = Handle::new();
+    let stop = handle.clone();
+    tokio::spawn(async move {
+        shutdown.await;
+        stop.graceful_shutdown(Some(SHUTDOWN_TIMEOUT));
+    });
+    listener.set_nonblocking(true)?;
+    let router = router.layer(middleware::map_request(mark_https));
+    let served = axum_server::from_tcp_rustls(listener, config)?
+        .handle(handle)
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+        .await;
+    reload.abort();
+    served
+}
+
+async fn mark_https(mut request: Request) -> Request {
+    // в http/2 хост приходит в `:authority`, а ручки смотрят на Host
+    let authority = request
+        .uri()
+        .authority()
+        .and_then(|authority| HeaderValue::from_str(authority.as_str()).ok());
+    let headers = request.headers_mut();
+    if let Some(authority) = authority
+        && !headers.contains_key(header::HOST)
+    {
+        headers.insert(header::HOST, authority);
+    }
+    headers.insert(
+        HeaderName::from_static("x-forwarded-proto"),
+        HeaderValue::from_static("https"),
+    );
+    request
+}
+
+async fn read_pem(tls: &TlsConfig) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let cert = tokio::fs::read(&tls.cert).await?;
+    let key = tokio::fs::read(&tls.key).await?;
+    Ok((cert, key))
+}
+
+async fn watch_certificates(config: RustlsConfig, tls: TlsConfig, mut loaded: (Vec<u8>, Vec<u8>)) {
+    loop {
+        tokio::time::sleep(tls.reload_interval).await;
+        let current = match read_pem(&tls).await {
+            Ok(current) => current,
+            Err(e) => {
+                eprintln!("tls certificate read failed: {e}");
+                continue;
+            }
+        };
+        if current == loaded {
+            continue;
+        }
+        match config
+            .reload_from_pem(current.0.clone(), current.1.clone())
+            .await
+        {
+            Ok(()) => loaded = current,
+            Err(e) => eprintln!("tls certificate reload failed: {e}"),
+        }
+    }
+}
+
+/// http сервер, отправляющий все запросы на тот же адрес по https
+pub fn redirect_router(https_port: u16) -> Router {
+    Router::new().fallback(to_https).with_state(https_port)
+}
+
+async fn to_https(State(https_port): State<u16>, headers: HeaderMap, uri: Uri) -> Response {
+    let Some(host) = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return (StatusCode::BAD_REQUEST, "Host header is required").into_response();
+    };
+    // у ipv6 адреса двоеточия внутри скобок, порт только после `]`
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let location = match https_port {
+        443 => format!("https://{host}{path}"),
+        port => format!("https://{host}:{port}{path}"),
+    };
+    Redirect::permanent(&location).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, routing::get};
+    use rcgen::{CertifiedKey, generate_simple_self_signed};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn write_certificate(dir: &std::path::Path) -> reqwest::Certificate {
+        let CertifiedKey { cert, signing_key } =
+            generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        // между записями ключ не подходит к сертификату, сервер должен это пережить
+        std::fs::write(dir.join("key.pem"), signing_key.serialize_pem()).unwrap();
+        std::fs::write(dir.join("cert.pem"), cert.pem()).unwrap();
+        reqwest::Certificate::from_pem(cert.pem().as_bytes()).unwrap()
+    }
+
+    fn client(addr: SocketAddr, root: reqwest::Certificate) -> reqwest::Client {
+        reqwest::Client::builder()
+            .tls_certs_only([root])
+            .resolve("localhost", addr)
+            .pool_max_idle_per_host(0)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn certificate_is_reloaded_without_restart() {
+        // given
+        let dir = tempfile::tempdir().unwrap();
+        let first = write_certificate(dir.path());
+        let tls = TlsConfig {
+            cert: dir.path().join("cert.pem"),
+            key: dir.path().join("key.pem"),
+            redirect_port: None,
+            reload_interval: Duration::from_millis(20),
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = Router::new().route(
+            "/ping",
+            get(|headers: HeaderMap| async move {
+                format!(
+                    "{:?} {:?}",
+                    headers["x-forwarded-proto"],
+                    headers[header::HOST]
+                )
+            }),
+        );
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve_tls(listener, router, &tls, async {
+                let _ = stopped.await;
+            })
+            .await
+        });
+        let url = format!("https://localhost:{}/ping", addr.port());
+        let host = format!(r#""https" "localhost:{}""#, addr.port());
+        let before = client(addr, first.clone()).get(&url).send().await.unwrap();
+
+        // when
+        let second = write_certificate(dir.path());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let with_new = client(addr, second).get(&url).send().await;
+        let with_old = client(addr, first).get(&url).send().await;
+        stop.send(()).unwrap();
+
+        // then
+        assert_eq!(before.text().await.unwrap(), host);
+        assert_eq!(with_new.unwrap().text().await.unwrap(), host);
+        assert!(with_old.is_err());
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn plain_http_is_redirected_to_https() {
+        // given
+        let cases = [
+            (8443, "s.example:8080", "https://s.example:8443/abc?x=1"),
+            (443, "s.example", "https://s.example/abc?x=1"),
+            (443, "[::1]:8080", "https://[::1]/abc?x=1"),
+        ];
+
+        for (port, host, expected) in cases {
+            // when
+            let response = redirect_router(port)
+                .oneshot(
+                    Request::get("/abc?x=1")
+                        .header(header::HOST, host)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            // then
+            assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+            assert_eq!(response.headers()[header::LOCATION], expected);
+        }
+    }
+}
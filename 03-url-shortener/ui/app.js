@@ -0,0 +1,203 @@
+"use strict";
+
+// Настройки хранятся в localStorage: адрес api для интерфейса, размещённого
+// отдельно от сервиса, и админский токен для списка, изменения и удаления ссылок.
+const settings = {
+  get api() {
+    return (localStorage.getItem("api") || location.origin).replace(/\/+$/, "");
+  },
+  get token() {
+    return localStorage.getItem("token") || "";
+  },
+  save(api, token) {
+    localStorage.setItem("api", api);
+    localStorage.setItem("token", token);
+  },
+};
+
+const $ = (selector) => document.querySelector(selector);
+
+async function request(method, path, body) {
+  const headers = {};
+  if (body !== undefined) {
+    headers["Content-Type"] = "application/json";
+  }
+  if (settings.token) {
+    headers["Authorization"] = `Bearer ${settings.token}`;
+  }
+  const response = await fetch(settings.api + path, {
+    method,
+    headers,
+    body: body === undefined ? undefined : JSON.stringify(body),
+  });
+  if (!response.ok) {
+    throw new Error((await response.text()) || `${response.status} ${response.statusText}`);
+  }
+  return response.status === 204 ? null : response.json();
+}
+
+function showError(error) {
+  const box = $("#error");
+  box.textContent = error ? error.message : "";
+  box.hidden = !error;
+}
+
+function shortLink(id) {
+  return `${settings.api}/r/${encodeURIComponent(id)}`;
+}
+
+function parseTags(value) {
+  return value
+    .split(",")
+    .map((tag) => tag.trim())
+    .filter((tag) => tag);
+}
+
+function cell(row, text) {
+  const td = row.insertCell();
+  td.textContent = text;
+  return td;
+}
+
+function button(label, className, onClick) {
+  const el = document.createElement("button");
+  el.type = "button";
+  el.textContent = label;
+  el.className = className;
+  el.addEventListener("click", onClick);
+  return el;
+}
+
+async function loadLinks() {
+  const hint = $("#list-hint");
+  const table = $("#links");
+  if (!settings.token) {
+    hint.hidden = false;
+    table.hidden = true;
+    return;
+  }
+  hint.hidden = true;
+
+  const form = new FormData($("#search-form"));
+  const params = new URLSearchParams();
+  for (const key of ["q", "tag"]) {
+    if (form.get(key)) {
+      params.set(key, form.get(key));
+    }
+  }
+  const links = await request("GET", `/api/links?${params}`);
+
+  const body = table.tBodies[0];
+  body.replaceChildren();
+  for (const link of links) {
+    const row = body.insertRow();
+    const anchor = document.createElement("a");
+    anchor.href = shortLink(link.id);
+    anchor.textContent = link.id;
+    anchor.target = "_blank";
+    anchor.rel = "noopener";
+    row.insertCell().append(anchor);
+    cell(row, link.url);
+    cell(row, link.title || "");
+    cell(row, (link.tags || []).join(", "));
+    cell(row, link.clicks);
+    const actions = row.insertCell();
+    actions.className = "actions";
+    actions.append(
+      button("Edit", "secondary", () => editLink(link.id).catch(showError)),
+      " ",
+      button("Delete", "danger", () => deleteLink(link.id).catch(showError)),
+    );
+  }
+  table.hidden = false;
+}
+
+async function editLink(id) {
+  const link = await request("GET", `/admin/links/${encodeURIComponent(id)}`);
+  const dialog = $("#edit-dialog");
+  const form = $("#edit-form");
+  $("#edit-id").textContent = id;
+  form.elements.url.value = link.url;
+  form.elements.title.value = link.title || "";
+  form.elements.note.value = link.note || "";
+  form.elements.tags.value = (link.tags || []).join(", ");
+
+  dialog.onclose = async () => {
+    if (dialog.returnValue !== "save") {
+      return;
+    }
+    // PUT оставляет как есть настройки, которых нет в запросе
+    const update = {
+      url: form.elements.url.value,
+      title: form.elements.title.value || null,
+      note: form.elements.note.value || null,
+      tags: parseTags(form.elements.tags.value),
+    };
+    try {
+      await request("PUT", `/admin/links/${encodeURIComponent(id)}`, update);
+      showError(null);
+      await loadLinks();
+    } catch (error) {
+      showError(error);
+    }
+  };
+  dialog.returnValue = "";
+  dialog.showModal();
+}
+
+async function deleteLink(id) {
+  if (!confirm(`Delete ${id}?`)) {
+    return;
+  }
+  await request("DELETE", `/admin/links/${encodeURIComponent(id)}`);
+  showError(null);
+  await loadLinks();
+}
+
+$("#shorten-form").addEventListener("submit", async (event) => {
+  event.preventDefault();
+  const form = event.target;
+  try {
+    const created = await request("POST", "/", {
+      url: form.elements.url.value,
+      title: form.elements.title.value || null,
+      tags: parseTags(form.elements.tags.value),
+    });
+    const link = $("#result-link");
+    link.href = shortLink(created.url);
+    link.textContent = shortLink(created.url);
+    $("#result").hidden = false;
+    form.reset();
+    showError(null);
+    await loadLinks();
+  } catch (error) {
+    showError(error);
+  }
+});
+
+$("#copy").addEventListener("click", async () => {
+  try {
+    await navigator.clipboard.writeText($("#result-link").textContent);
+    $("#copy").textContent = "Copied";
+    setTimeout(() => ($("#copy").textContent = "Copy"), 1500);
+  } catch (error) {
+    showError(error);
+  }
+});
+
+$("#search-form").addEventListener("submit", (event) => {
+  event.preventDefault();
+  loadLinks().catch(showError);
+});
+
+$("#settings-form").addEventListener("submit", (event) => {
+  event.preventDefault();
+  const form = event.target;
+  settings.save(form.elements.api.value, form.elements.token.value);
+  $("#settings").open = false;
+  loadLinks().catch(showError);
+});
+
+$("#settings-form").elements.api.value = localStorage.getItem("api") || "";
+$("#settings-form").elements.token.value = settings.token;
+loadLinks().catch(showError);